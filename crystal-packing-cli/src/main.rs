@@ -5,26 +5,62 @@
 //
 //
 
+use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::convert::TryInto;
+use std::ffi::OsString;
+use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
-use std::path;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, bail, Error};
-use clap::arg_enum;
-use log::{debug, info, LevelFilter};
+use clap::{arg_enum, ErrorKind};
+use log::{debug, info, warn, LevelFilter};
 use rand::prelude::*;
 use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
 use crystal_packing::traits::*;
-use crystal_packing::wallpaper::{WallpaperGroup, WallpaperGroups};
+use crystal_packing::wallpaper::{WallpaperGroup, WallpaperGroups, WyckoffSite};
 use crystal_packing::{
-    LJShape2, LineShape, MCOptimiser, MolecularShape2, PackedState2, PotentialState2,
+    brillouin_zone_path, check_stability, normal_sizes, BandPoint, BasinHopping, CapsuleShape,
+    CellMove, Control, CoolingSchedule, CsvTrace, ExponentialSchedule, FlexibleTrimer, FreeEnergy,
+    GaussianMove, Generation, GeneticOptimiser, GeometricPressure, ImportedShape,
+    IsobaricOptimiser, LJShape2, LamSchedule, LineShape, LinearPressure, LinearSchedule,
+    MCCheckpoint, MCOptimiser, MinimaArchive, MixedMove, MixedShape, MolecularShape2, MoveSet,
+    Observer, Observers, PackedState2, ParametricShape, Phonons, PointGroup, PolydisperseState,
+    PotentialState2, PressureSchedule, ReheatSchedule, Relaxation, RotationMove, RoundedShape,
+    SVGTrajectory, ShapeDefinition, SingleBasisMove, StabilityCriteria, StatePoint, StaticStrain,
+    StrainFluctuation, XYZTrajectory,
 };
 
-#[derive(StructOpt, Debug, Clone, Copy)]
+arg_enum! {
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+    pub enum Schedule {
+        Exponential,
+        Linear,
+        Lam,
+        Reheat,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+    pub enum Moves {
+        Single,
+        Gaussian,
+        Cell,
+        Rotation,
+    }
+}
+
+#[derive(StructOpt, Debug, Clone, Serialize, Deserialize)]
 pub struct BuildOptimiser {
     /// The number of steps to run the Monte-Carlo Optimisation.
     #[structopt(short, long, default_value = "100")]
@@ -61,6 +97,35 @@ pub struct BuildOptimiser {
     /// which allows for an early exit.
     #[structopt(long)]
     convergence: Option<f64>,
+
+    /// The schedule used to reduce the temperature over the optimisation.
+    #[structopt(
+        long,
+        possible_values = &Schedule::variants(),
+        case_insensitive = true,
+        default_value = "Exponential"
+    )]
+    schedule: Schedule,
+
+    /// The fraction the temperature changes each inner loop in the Lam schedule.
+    #[structopt(long, default_value = "0.05")]
+    adapt_rate: f64,
+
+    /// The number of inner loops without an improvement before the Reheat schedule returns to
+    /// the initial temperature.
+    #[structopt(long, default_value = "10")]
+    reheat_after: u64,
+
+    /// The moves used to modify the state. When more than one is given, each step chooses one of
+    /// the moves at random.
+    #[structopt(
+        long,
+        possible_values = &Moves::variants(),
+        case_insensitive = true,
+        use_delimiter = true,
+        default_value = "Single"
+    )]
+    moves: Vec<Moves>,
 }
 
 impl Default for BuildOptimiser {
@@ -74,6 +139,10 @@ impl Default for BuildOptimiser {
             inner_steps: 1000,
             seed: None,
             convergence: None,
+            schedule: Schedule::Exponential,
+            adapt_rate: 0.05,
+            reheat_after: 10,
+            moves: vec![Moves::Single],
         }
     }
 }
@@ -119,6 +188,69 @@ impl BuildOptimiser {
         self
     }
 
+    pub fn schedule(&mut self, schedule: Schedule) -> &mut Self {
+        self.schedule = schedule;
+        self
+    }
+
+    pub fn moves(&mut self, moves: Vec<Moves>) -> &mut Self {
+        self.moves = moves;
+        self
+    }
+
+    fn build_schedule(&self, kt_ratio: f64) -> Box<dyn CoolingSchedule> {
+        match self.schedule {
+            Schedule::Exponential => Box::new(ExponentialSchedule::new(self.kt_start, kt_ratio)),
+            Schedule::Linear => Box::new(LinearSchedule::new(
+                self.kt_start,
+                self.kt_finish.unwrap_or(0.),
+            )),
+            Schedule::Lam => Box::new(LamSchedule::new(self.kt_start, self.adapt_rate)),
+            Schedule::Reheat => Box::new(ReheatSchedule::new(
+                self.kt_start,
+                kt_ratio,
+                self.reheat_after,
+            )),
+        }
+    }
+
+    fn build_moves(&self) -> Box<dyn MoveSet> {
+        let mut moves: Vec<Box<dyn MoveSet>> = self
+            .moves
+            .iter()
+            .map(|m| -> Box<dyn MoveSet> {
+                match m {
+                    Moves::Single => Box::new(SingleBasisMove),
+                    Moves::Gaussian => Box::new(GaussianMove),
+                    Moves::Cell => Box::new(CellMove),
+                    Moves::Rotation => Box::new(RotationMove),
+                }
+            })
+            .collect();
+        match moves.len() {
+            0 => Box::new(SingleBasisMove),
+            1 => moves.remove(0),
+            _ => Box::new(MixedMove::new(moves)),
+        }
+    }
+
+    fn build_seed(&self) -> u64 {
+        match self.seed {
+            None => rand_pcg::Pcg64Mcg::from_entropy().gen(),
+            Some(x) => x,
+        }
+    }
+
+    /// Relax each of the structures found by the genetic and basin hopping optimisers, using the
+    /// steps of the Monte Carlo optimisation at zero temperature
+    pub fn build_relaxation(&self) -> Relaxation {
+        Relaxation::MonteCarlo {
+            steps: self.steps,
+            inner_steps: u64::min(self.inner_steps, self.steps),
+            max_step_size: self.max_step_size,
+        }
+    }
+
     pub fn build(&self) -> MCOptimiser {
         let kt_ratio = match (self.kt_ratio, self.kt_finish) {
             (Some(ratio), _) => 1. - ratio,
@@ -126,10 +258,6 @@ impl BuildOptimiser {
             (None, None) => 0.1,
         };
         debug!("Setting kt_ratio to: {}", kt_ratio);
-        let seed = match self.seed {
-            None => rand_pcg::Pcg64Mcg::from_entropy().gen(),
-            Some(x) => x,
-        };
 
         MCOptimiser::new(
             self.kt_start,
@@ -137,21 +265,248 @@ impl BuildOptimiser {
             self.max_step_size,
             self.steps,
             u64::min(self.inner_steps, self.steps),
-            seed,
+            self.build_seed(),
             self.convergence,
         )
+        .with_schedule(self.build_schedule(kt_ratio))
+        .with_moves(self.build_moves())
+    }
+}
+
+/// Options for monitoring the progress of the Monte Carlo optimisation
+///
+/// Each of the replications writes to a separate file, with the index of the replication
+/// appended to the name of the file.
+#[derive(StructOpt, Debug, Clone, Serialize, Deserialize)]
+pub struct Monitor {
+    /// Write the score, temperature, step ratio and acceptance of every inner loop to a CSV file.
+    #[structopt(long, parse(from_os_str))]
+    trace: Option<PathBuf>,
+
+    /// Write the configuration over the optimisation to a trajectory. With the extension
+    /// `.xyz` this is a single extended XYZ file, while `.svg` writes a series of SVG files.
+    #[structopt(long, parse(from_os_str))]
+    trajectory: Option<PathBuf>,
+
+    /// The number of inner loops between frames of the trajectory
+    #[structopt(long, default_value = "1")]
+    trajectory_every: u64,
+}
+
+impl Monitor {
+    /// The path of a file for an individual replication
+    fn replication_path(path: &Path, index: u64) -> PathBuf {
+        let mut name = path.file_stem().unwrap_or_default().to_os_string();
+        name.push(format!("-{}", index));
+        if let Some(extension) = path.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        path.with_file_name(name)
+    }
+
+    /// The observers for a replication, appending to existing files when `resume` is true
+    fn observers<S: State>(
+        &self,
+        index: u64,
+        resume: bool,
+    ) -> Result<Observers<'static, S>, Error> {
+        let mut observers = Observers::default();
+        if let Some(trace) = &self.trace {
+            let path = Self::replication_path(trace, index);
+            if resume {
+                observers.push(CsvTrace::append(&path)?);
+            } else {
+                observers.push(CsvTrace::create(&path)?);
+            }
+        }
+        if let Some(trajectory) = &self.trajectory {
+            let path = Self::replication_path(trajectory, index);
+            match path.extension().and_then(|e| e.to_str()) {
+                Some("xyz") if resume => {
+                    observers.push(XYZTrajectory::append(&path, self.trajectory_every)?)
+                }
+                Some("xyz") => observers.push(XYZTrajectory::create(&path, self.trajectory_every)?),
+                Some("svg") => observers.push(SVGTrajectory::new(&path, self.trajectory_every)),
+                _ => bail!("The trajectory requires either an xyz or svg extension"),
+            }
+        }
+        Ok(observers)
     }
 }
 
 arg_enum! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
     enum Force {
         LJ,
         Hard,
     }
 }
 
-#[derive(Debug, StructOpt)]
+arg_enum! {
+    /// How the structures found by each of the replications are ranked
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    enum Ranking {
+        Score,
+        FreeEnergy,
+    }
+}
+
+// The variants of an arg_enum can't have the attribute needed to derive Default
+#[allow(clippy::derivable_impls)]
+impl Default for Ranking {
+    fn default() -> Self {
+        Ranking::Score
+    }
+}
+
+arg_enum! {
+    /// The algorithm searching for the best structure
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    enum Search {
+        MonteCarlo,
+        Genetic,
+        BasinHopping,
+    }
+}
+
+// The variants of an arg_enum can't have the attribute needed to derive Default
+#[allow(clippy::derivable_impls)]
+impl Default for Search {
+    fn default() -> Self {
+        Search::MonteCarlo
+    }
+}
+
+arg_enum! {
+    /// How the mirror images of a chiral shape are treated
+    ///
+    /// Any allows every wallpaper group, with the mirror images created by the group, while
+    /// Enantiopure only allows the groups without reflections, so all the shapes have the same
+    /// handedness, and Racemic only allows the groups with reflections, so both enantiomers are
+    /// within the packing.
+    ///
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    enum Chirality {
+        Any,
+        Enantiopure,
+        Racemic,
+    }
+}
+
+// The variants of an arg_enum can't have the attribute needed to derive Default
+#[allow(clippy::derivable_impls)]
+impl Default for Chirality {
+    fn default() -> Self {
+        Chirality::Any
+    }
+}
+
+/// Check the wallpaper group is consistent with the chirality of the packing
+fn check_chirality(
+    chirality: Chirality,
+    group: &WallpaperGroup,
+    point_group: PointGroup,
+) -> Result<(), Error> {
+    let improper = WyckoffSite::new(group)?.has_improper();
+    match (chirality, improper) {
+        (Chirality::Enantiopure, true) => bail!(
+            "The group {} has operations which reflect the shape, which isn't enantiopure",
+            group.name
+        ),
+        // The enantiomer is only placed by the improper operations of the group, so without
+        // them the packing would silently be enantiopure
+        (Chirality::Racemic, false) => bail!(
+            "The group {} has no operations which reflect the shape, so can't form a racemic \
+             packing, which requires a group like p1g1, p2mg or p2gg",
+            group.name
+        ),
+        (Chirality::Racemic, true) if !point_group.is_chiral() => {
+            warn!("The shape is the same as its mirror image, so both enantiomers are identical")
+        }
+        (Chirality::Any, true) if point_group.is_chiral() => warn!(
+            "The group {} reflects the chiral shape, so the packing is racemic",
+            group.name
+        ),
+        _ => (),
+    }
+    Ok(())
+}
+
+/// The free energy of a state, which is only available for shapes with a potential
+trait Ranked: State {
+    /// The point group of the shape being packed
+    fn point_group(&self) -> PointGroup;
+
+    fn free_energy(&self, _temperature: f64) -> Result<Option<f64>, Error> {
+        bail!("Ranking by the free energy requires a potential")
+    }
+}
+
+impl<S: Shape + Intersect> Ranked for PackedState2<S> {
+    fn point_group(&self) -> PointGroup {
+        self.shape.point_group()
+    }
+}
+
+impl<S: Shape + Intersect + Scale> Ranked for PolydisperseState<S> {
+    fn point_group(&self) -> PointGroup {
+        self.shape.point_group()
+    }
+}
+
+impl Ranked for PotentialState2<LJShape2> {
+    fn point_group(&self) -> PointGroup {
+        self.shape.point_group()
+    }
+
+    fn free_energy(&self, temperature: f64) -> Result<Option<f64>, Error> {
+        Ok(FreeEnergy::new(temperature).free_energy(self))
+    }
+}
+
+/// Rank the unique structures by their free energy, returning the one with the lowest
+///
+/// Structures with imaginary modes have no harmonic free energy, so are ranked last.
+fn rank_by_free_energy<S: Ranked>(states: Vec<S>, temperature: f64) -> Result<S, Error> {
+    let mut unique = MinimaArchive::new(states.len(), 1e-6);
+    for state in states.iter() {
+        unique.insert(state);
+    }
+    let mut ranked = unique
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|state| Ok((state.free_energy(temperature)?, state)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    // The sort is stable, so structures without a free energy remain ordered by their score
+    ranked.sort_by(|(a, _), (b, _)| match (a, b) {
+        (Some(a), Some(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+    for (rank, (free_energy, state)) in ranked.iter().enumerate() {
+        match (free_energy, state.score()) {
+            (Some(free_energy), Some(score)) => info!(
+                "Rank {}: Free energy: {}, Score: {}",
+                rank + 1,
+                free_energy,
+                score
+            ),
+            (None, Some(score)) => info!("Rank {}: Imaginary modes, Score: {}", rank + 1, score),
+            _ => {}
+        }
+    }
+    ranked
+        .into_iter()
+        .next()
+        .map(|(_, state)| state)
+        .ok_or_else(|| anyhow!("Error in running optimisation."))
+}
+
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
 #[structopt(name = "packing")]
 struct Args {
     /// Pass many times for more log output
@@ -180,155 +535,2164 @@ struct Args {
     #[structopt(long, parse(from_os_str))]
     start_config: Option<PathBuf>,
 
-    /// The number of independent starting configurations to optimise
+    /// The number of independent starting configurations to optimise, which with basin hopping
+    /// is the number of distinct minima kept for ranking
     #[structopt(long, default_value = "100")]
     replications: u64,
 
+    /// Stop the optimisation after this many seconds, saving the best structure found so far
+    /// along with a checkpoint from which the optimisation can be resumed.
+    #[structopt(long)]
+    time_limit: Option<f64>,
+
+    /// Save a checkpoint of every replication at this interval in seconds. The checkpoint is
+    /// saved alongside the outfile with the extension `.checkpoint.json`, and is removed once the
+    /// optimisation finishes.
+    #[structopt(long)]
+    checkpoint_every: Option<f64>,
+
+    /// How the best of the structures found by the replications is chosen, either the
+    /// `Score`, or the `FreeEnergy` at the temperature, which requires a potential
+    #[structopt(
+        long,
+        possible_values = &Ranking::variants(),
+        case_insensitive = true,
+        default_value = "Score"
+    )]
+    #[serde(default)]
+    rank_by: Ranking,
+
+    /// The temperature at which the free energy is calculated when ranking by the free energy
+    #[structopt(long)]
+    #[serde(default)]
+    temperature: Option<f64>,
+
+    /// How the mirror images of a chiral shape are treated, either `Any`, `Enantiopure` which
+    /// forbids groups which reflect the shape, or `Racemic` which requires them
+    #[structopt(
+        long,
+        possible_values = &Chirality::variants(),
+        case_insensitive = true,
+        default_value = "Any"
+    )]
+    #[serde(default)]
+    chirality: Chirality,
+
+    /// The standard deviation of the sizes of the shapes relative to the mean size, with a
+    /// differently sized copy of the shape on each of the occupied sites
+    #[structopt(long)]
+    #[serde(default)]
+    polydispersity: Option<f64>,
+
+    /// The number of independent occupied sites when drawing the sizes from a distribution
+    #[structopt(long)]
+    #[serde(default)]
+    sites: Option<usize>,
+
+    /// The comma separated size of the shape on each of the occupied sites, with one site for
+    /// each size
+    #[structopt(
+        long,
+        use_delimiter = true,
+        conflicts_with_all = &["polydispersity", "sites"]
+    )]
+    #[serde(default)]
+    sizes: Vec<f64>,
+
+    /// The algorithm searching for the best structure, either `MonteCarlo` which anneals each of
+    /// the replications, `Genetic` which evolves a population of structures, or `BasinHopping`
+    /// which hops between the local minima
+    #[structopt(
+        long,
+        possible_values = &Search::variants(),
+        case_insensitive = true,
+        default_value = "MonteCarlo"
+    )]
+    #[serde(default)]
+    optimiser: Search,
+
+    /// The number of structures in each generation of the genetic optimiser
+    #[structopt(long, default_value = "20")]
+    #[serde(default)]
+    population: usize,
+
+    /// The number of generations of the genetic optimiser
+    #[structopt(long, default_value = "10")]
+    #[serde(default)]
+    generations: u64,
+
+    /// The number of perturbations of the basin hopping optimiser, which accepts a worse
+    /// minimum at the temperature kt-start
+    #[structopt(long, default_value = "100")]
+    #[serde(default)]
+    cycles: u64,
+
+    /// The largest perturbation of each value as a fraction of its range in basin hopping
+    #[structopt(long, default_value = "0.1")]
+    #[serde(default)]
+    perturbation: f64,
+
     #[structopt(flatten)]
     optimisation: BuildOptimiser,
+
+    #[structopt(flatten)]
+    monitor: Monitor,
 }
 
+impl Args {
+    fn checkpoint_path(&self) -> PathBuf {
+        self.outfile.with_extension("checkpoint.json")
+    }
+
+    /// The report of the best and median score of each generation of the genetic optimiser
+    fn generations_path(&self) -> PathBuf {
+        self.outfile.with_extension("generations.csv")
+    }
+
+    /// The size of the shape on each occupied site, which is None for a single site of the shape
+    fn site_sizes(&self) -> Result<Option<Vec<f64>>, Error> {
+        match (self.sizes.is_empty(), self.polydispersity, self.sites) {
+            (true, None, None) => Ok(None),
+            (true, polydispersity, sites) => Ok(Some(normal_sizes(
+                sites.unwrap_or(1),
+                polydispersity.unwrap_or(0.),
+                &mut thread_rng(),
+            )?)),
+            (false, None, None) => Ok(Some(self.sizes.clone())),
+            (false, _, _) => bail!("The sizes are either given explicitly or drawn, not both"),
+        }
+    }
+}
+
+/// Continue an optimisation from a checkpoint
+///
+/// The resumed optimisation gives the same result as if it had never been interrupted.
 #[derive(Debug, StructOpt)]
-enum Shapes {
-    #[structopt(name = "polygon")]
-    Polygon {
-        /// The number of equally spaced sides
-        #[structopt(long, default_value = "4")]
-        sides: usize,
-    },
-    #[structopt(name = "trimer")]
-    Trimer {
-        /// The distance from the central particle to the outer particles
-        #[structopt(short, long, default_value = "1.")]
-        distance: f64,
-        /// The angle between the two outer particles in degrees
-        #[structopt(short, long, default_value = "120")]
-        angle: f64,
-        /// The radius of the outer small particles
-        #[structopt(short, long, default_value = "0.637556")]
-        radius: f64,
-    },
-    #[structopt(name = "circle")]
-    Circle {},
+struct Resume {
+    /// Pass many times for more log output
+    #[structopt(long, short, parse(from_occurrences))]
+    verbosity: u8,
+
+    /// The checkpoint file created by the interrupted optimisation
+    #[structopt(parse(from_os_str))]
+    checkpoint: PathBuf,
+
+    /// Stop the optimisation after this many seconds, saving a new checkpoint.
+    #[structopt(long)]
+    time_limit: Option<f64>,
 }
 
-fn analyse_state(
-    outfile: path::PathBuf,
-    start_configs: u64,
-    state: impl State,
-    optimiser: &BuildOptimiser,
-) -> Result<(), Error> {
-    let final_state = (0..start_configs)
-        .into_par_iter()
-        // Create collection of quickly optimised initial states
-        .map(|index| {
-            let result = optimiser
-                .clone()
-                .steps(100)
-                .kt_start(0.)
-                .seed(index)
-                .convergence(None)
-                .build()
-                .optimise_state(state.clone());
-            (index, result)
-        })
-        // Perform Monte carlo optimisation
-        .map(|(index, opt_state)| {
-            let result = optimiser
-                .clone()
-                .seed(index)
-                .build()
-                .optimise_state(opt_state);
-            (index, result)
-        })
-        // Final optimsation to help find the minimum
-        .map(|(index, opt_state)| {
-            optimiser
-                .clone()
-                .kt_start(0.)
-                .seed(index)
-                .build()
-                .optimise_state(opt_state)
+/// The values taken by one of the parameters of the shape over a sweep
+#[derive(Debug, Clone)]
+struct SweepParameter {
+    name: String,
+    values: Vec<f64>,
+}
+
+impl FromStr for SweepParameter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, values) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected a parameter as name=values, got {}", s))?;
+        let values = match values.split(':').collect::<Vec<_>>()[..] {
+            [start, stop, step] => {
+                let (start, stop, step): (f64, f64, f64) =
+                    (start.parse()?, stop.parse()?, step.parse()?);
+                if !(step > 0. && stop >= start) {
+                    bail!(
+                        "Expected an increasing range start:stop:step, got {}",
+                        values
+                    );
+                }
+                // The tolerance ensures the stop value is included despite rounding errors
+                let count = ((stop - start) / step + 1e-8).floor() as usize;
+                (0..=count)
+                    .map(|i| {
+                        // Removing the rounding errors gives tidy values in the names of files
+                        let value = start + i as f64 * step;
+                        (value * 1e10).round() / 1e10
+                    })
+                    .collect()
+            }
+            [_] => values
+                .split(',')
+                .map(f64::from_str)
+                .collect::<Result<_, _>>()?,
+            _ => bail!(
+                "Expected either a range start:stop:step or a list, got {}",
+                values
+            ),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            values,
         })
-        .max()
-        .ok_or_else(|| anyhow!("Error in running optimisation."))?;
+    }
+}
 
-    info!(
-        "Final score: {}",
-        final_state
-            .score()
-            .ok_or_else(|| anyhow!("State has become corrupted"))?
-    );
+/// Optimise every combination of the values of the shape parameters and wallpaper groups
+///
+/// Each point of the sweep is an independent optimisation, with the points run in parallel.
+/// Once a point is finished, the best score and the names of the saved structures are written
+/// as a row of the CSV results. Points already in the results are skipped, and points stopped
+/// by a `--time-limit` continue from their checkpoint, so running the same sweep again
+/// continues from where it was interrupted.
+#[derive(Debug, StructOpt)]
+struct Sweep {
+    /// Pass many times for more log output
+    #[structopt(long, short, parse(from_occurrences))]
+    verbosity: u8,
 
-    let serialised = serde_json::to_string(&final_state)?;
+    /// The shape being packed, which is any of the shapes of the optimisation
+    shape: String,
 
-    File::create(outfile.with_extension("json"))?.write_all(&serialised.as_bytes())?;
-    svg::save(outfile.with_extension("svg"), &final_state.as_svg())?;
+    /// The values of a parameter of the shape, either as a range `name=start:stop:step`
+    /// including the stop value, or a list `name=a,b,c`. Pass many times to sweep over many
+    /// parameters.
+    #[structopt(short, long = "param")]
+    params: Vec<SweepParameter>,
 
-    Ok(())
+    /// The comma separated wallpaper groups of the sweep
+    #[structopt(
+        long,
+        possible_values = &WallpaperGroups::variants(),
+        use_delimiter = true,
+        default_value = "p2"
+    )]
+    groups: Vec<WallpaperGroups>,
+
+    /// The directory where the structures are saved
+    #[structopt(long, parse(from_os_str))]
+    outdir: PathBuf,
+
+    /// The CSV file of results, which defaults to `sweep.csv` in the outdir
+    #[structopt(long, parse(from_os_str))]
+    results: Option<PathBuf>,
+
+    /// Options of the optimisation of every point, given after `--`
+    #[structopt(last = true)]
+    options: Vec<String>,
 }
 
-#[paw::main]
-fn main(args: Args) -> Result<(), Error> {
-    let log_level = match args.verbosity {
-        0 => LevelFilter::Info,
-        1 => LevelFilter::Debug,
-        2 => LevelFilter::Trace,
-        _ => LevelFilter::Trace,
-    };
-    env_logger::Builder::new().filter_level(log_level).init();
+impl Sweep {
+    fn results_path(&self) -> PathBuf {
+        self.results
+            .clone()
+            .unwrap_or_else(|| self.outdir.join("sweep.csv"))
+    }
 
-    debug!("Logging Level: {}", log_level);
+    fn header(&self) -> Vec<&str> {
+        self.params
+            .iter()
+            .map(|p| p.name.as_str())
+            .chain(vec!["wallpaper", "score", "structure", "image"])
+            .collect()
+    }
 
-    let wg: WallpaperGroup = args.wallpaper.try_into()?;
+    /// Every combination of the values of the parameters and the wallpaper groups
+    fn points(&self) -> Vec<(Vec<f64>, WallpaperGroups)> {
+        let mut combinations = vec![vec![]];
+        for param in self.params.iter() {
+            combinations = combinations
+                .iter()
+                .flat_map(|values: &Vec<f64>| {
+                    param.values.iter().map(move |&v| {
+                        let mut values = values.clone();
+                        values.push(v);
+                        values
+                    })
+                })
+                .collect();
+        }
+        self.groups
+            .iter()
+            .flat_map(|&group| combinations.iter().map(move |v| (v.clone(), group)))
+            .collect()
+    }
 
-    match (args.shape, args.potential) {
-        (
-            Shapes::Trimer {
-                distance,
-                angle,
-                radius,
-            },
-            Force::LJ,
-        ) => analyse_state(
-            args.outfile,
-            args.replications,
-            PotentialState2::from_group(LJShape2::from_trimer(radius, angle, distance), &wg)?,
-            &args.optimisation,
-        ),
-        (
-            Shapes::Trimer {
-                distance,
-                angle,
-                radius,
-            },
-            Force::Hard,
-        ) => analyse_state(
-            args.outfile,
-            args.replications,
-            PackedState2::from_group(MolecularShape2::from_trimer(radius, angle, distance), &wg)?,
-            &args.optimisation,
-        ),
-        (Shapes::Circle {}, Force::LJ) => analyse_state(
-            args.outfile,
-            args.replications,
-            PotentialState2::from_group(LJShape2::circle(), &wg)?,
-            &args.optimisation,
-        ),
-        (Shapes::Circle {}, Force::Hard) => analyse_state(
-            args.outfile,
-            args.replications,
-            PackedState2::from_group(MolecularShape2::circle(), &wg)?,
-            &args.optimisation,
-        ),
-        (Shapes::Polygon { sides }, Force::Hard) => analyse_state(
-            args.outfile,
-            args.replications,
-            PackedState2::from_group(LineShape::polygon(sides)?, &wg)?,
-            &args.optimisation,
-        ),
-        (Shapes::Polygon { .. }, Force::LJ) => {
-            bail!("Polygon with a LJ potential is not yet implemented")
+    /// The identifying columns of a point, which are the values of the parameters and the group
+    fn key(values: &[f64], group: WallpaperGroups) -> Vec<String> {
+        values
+            .iter()
+            .map(f64::to_string)
+            .chain(std::iter::once(group.to_string()))
+            .collect()
+    }
+
+    /// The arguments of the optimisation of a single point
+    fn point_args(&self, values: &[f64], group: WallpaperGroups) -> Result<Args, Error> {
+        let mut name = format!("{}-{}", self.shape, group);
+        for (param, value) in self.params.iter().zip(values) {
+            name.push_str(&format!("-{}{}", param.name, value));
+        }
+        // The extension is given explicitly so values with a decimal point are kept
+        let outfile = self.outdir.join(format!("{}.json", name));
+
+        let mut argv: Vec<OsString> = vec!["packing".into(), group.to_string().into()];
+        argv.push("--outfile".into());
+        argv.push(outfile.into());
+        argv.extend(self.options.iter().map(OsString::from));
+        argv.push(self.shape.clone().into());
+        for (param, value) in self.params.iter().zip(values) {
+            argv.push(format!("--{}={}", param.name, value).into());
+        }
+        Ok(Args::from_iter_safe(argv)?)
+    }
+
+    /// The points which are already in the results of a previous sweep
+    fn finished(&self) -> Result<HashSet<Vec<String>>, Error> {
+        let path = self.results_path();
+        if !path.exists() {
+            return Ok(HashSet::new());
+        }
+        let mut reader = csv::Reader::from_path(&path)?;
+        let header = reader.headers()?;
+        if header.is_empty() {
+            return Ok(HashSet::new());
+        }
+        if header.iter().ne(self.header()) {
+            bail!(
+                "The results in {} have the columns {}, which are different to this sweep",
+                path.display(),
+                header.iter().collect::<Vec<_>>().join(",")
+            );
+        }
+        let columns = self.params.len() + 1;
+        reader
+            .records()
+            .map(|record| Ok(record?.iter().take(columns).map(String::from).collect()))
+            .collect()
+    }
+
+    /// Optimise a single point, resuming from the checkpoint of a point which was stopped by
+    /// the time limit of a previous sweep
+    fn run_point(args: &Args) -> Result<Option<f64>, Error> {
+        let checkpoint = args.checkpoint_path();
+        let resume = if checkpoint.exists() {
+            info!("Resuming the optimisation from {}", checkpoint.display());
+            Some(fs::read_to_string(&checkpoint)?)
+        } else {
+            None
+        };
+        run(args.clone(), resume)
+    }
+
+    fn run(&self) -> Result<(), Error> {
+        fs::create_dir_all(&self.outdir)?;
+        let finished = self.finished()?;
+        // Creating the arguments first finds any mistakes before starting the optimisations
+        let points = self
+            .points()
+            .into_iter()
+            .map(|(values, group)| (Self::key(&values, group), values, group))
+            .filter(|(key, _, _)| !finished.contains(key))
+            .map(|(key, values, group)| Ok((key, self.point_args(&values, group)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        info!(
+            "Running {} points of the sweep, with {} already finished",
+            points.len(),
+            finished.len()
+        );
+
+        let path = self.results_path();
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        let empty = file.metadata()?.len() == 0;
+        // The values are quoted where needed, like the path of a structure containing a comma
+        let mut writer = csv::Writer::from_writer(file);
+        if empty {
+            writer.write_record(self.header())?;
+            writer.flush()?;
+        }
+        let results = Mutex::new(writer);
+
+        let failed = points
+            .into_par_iter()
+            .filter(|(key, args)| {
+                let name = key.join(",");
+                let score = match Self::run_point(args) {
+                    Ok(Some(score)) => score,
+                    Ok(None) => {
+                        info!(
+                            "The point {} reached the time limit, running the sweep again \
+                             continues it from {}",
+                            name,
+                            args.checkpoint_path().display()
+                        );
+                        return false;
+                    }
+                    Err(e) => {
+                        warn!("The point {} failed: {}", name, e);
+                        return true;
+                    }
+                };
+                let mut row = key.clone();
+                row.push(score.to_string());
+                for extension in &["json", "svg"] {
+                    let path = args.outfile.with_extension(extension);
+                    row.push(path.display().to_string());
+                }
+                // Each row is written as soon as the point is finished, so no work is lost
+                let mut writer = results.lock().unwrap();
+                let written = writer.write_record(&row).and_then(|_| Ok(writer.flush()?));
+                if let Err(e) = written {
+                    warn!("Unable to write the results of {}: {}", name, e);
+                }
+                false
+            })
+            .count();
+        if failed > 0 {
+            bail!("{} points of the sweep failed", failed);
+        }
+        info!("Saved the results to {}", path.display());
+        Ok(())
+    }
+}
+
+/// A structure saved by an optimisation
+///
+/// The type of the state and the shape are read from the `kind` of each, which determines the
+/// type the structure is read as. Shapes like the [`CapsuleShape`] and [`RoundedShape`] are
+/// saved with the same fields, so can only be told apart by their kind.
+#[derive(Debug, Clone)]
+struct Structure {
+    /// The kind of state, like `PackedState`
+    state: String,
+    /// The kind of shape, like `LineShape`
+    shape: String,
+    value: serde_json::Value,
+}
+
+impl Structure {
+    fn read(path: &Path) -> Result<Self, Error> {
+        let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let kind = |value: &serde_json::Value| {
+            value
+                .get("kind")
+                .and_then(serde_json::Value::as_str)
+                .map(String::from)
+        };
+        match (kind(&value), value.get("shape").and_then(kind)) {
+            (Some(state), Some(shape)) => Ok(Self {
+                state,
+                shape,
+                value,
+            }),
+            _ => bail!(
+                "Unable to find the kind of state and shape in {}",
+                path.display()
+            ),
+        }
+    }
+
+    fn parse<S: DeserializeOwned>(&self) -> Result<S, Error> {
+        Ok(S::deserialize(&self.value)?)
+    }
+
+    fn kinds(&self) -> (&str, &str) {
+        (self.state.as_str(), self.shape.as_str())
+    }
+}
+
+/// Test whether a saved structure is stable without the constraints of its wallpaper group
+///
+/// The structure is expanded into a supercell where every shape is able to move independently,
+/// which is then simulated using Monte Carlo. Packings of hard shapes are simulated at a
+/// constant pressure, while shapes with a potential are simulated at a constant temperature.
+#[derive(Debug, StructOpt)]
+struct Stability {
+    /// Pass many times for more log output
+    #[structopt(long, short, parse(from_occurrences))]
+    verbosity: u8,
+
+    /// The structure saved by a previous optimisation
+    #[structopt(parse(from_os_str))]
+    structure: PathBuf,
+
+    /// The number of unit cells along the a axis of the supercell
+    #[structopt(long, default_value = "3")]
+    na: usize,
+
+    /// The number of unit cells along the b axis of the supercell
+    #[structopt(long, default_value = "3")]
+    nb: usize,
+
+    /// The number of steps in the simulation of the supercell
+    #[structopt(short, long, default_value = "10000")]
+    steps: u64,
+
+    /// The number of steps between each adjustment of the step size
+    #[structopt(long, default_value = "1000")]
+    inner_steps: u64,
+
+    /// The largest change to any value in a single step
+    #[structopt(long, default_value = "0.01")]
+    max_step_size: f64,
+
+    /// The reduced pressure βPa at which packings of hard shapes are simulated
+    #[structopt(long, default_value = "50")]
+    pressure: f64,
+
+    /// The temperature at which shapes with a potential are simulated
+    #[structopt(long, default_value = "0.1")]
+    kt: f64,
+
+    /// The seed of the random number generator
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+
+    /// The largest RMSD relative to the spacing between shapes of a stable structure
+    #[structopt(long, default_value = "0.1")]
+    max_relative_rmsd: f64,
+
+    /// The smallest fraction of the initial density retained by a stable structure
+    #[structopt(long, default_value = "0.95")]
+    min_retained_density: f64,
+
+    /// Where to save the supercell at the end of the simulation
+    #[structopt(long, parse(from_os_str))]
+    outfile: Option<PathBuf>,
+}
+
+impl Stability {
+    fn criteria(&self) -> StabilityCriteria {
+        StabilityCriteria {
+            max_relative_rmsd: self.max_relative_rmsd,
+            min_retained_density: self.min_retained_density,
+        }
+    }
+
+    /// Simulate packings of hard shapes at a constant pressure
+    fn isobaric<S: Packed>(&self, state: S) -> S {
+        IsobaricOptimiser::new(
+            Box::new(LinearPressure::new(self.pressure, self.pressure)),
+            self.max_step_size,
+            self.steps,
+            self.inner_steps,
+            self.seed,
+        )
+        .optimise_equation_of_state(state, &mut ())
+        .0
+    }
+
+    /// Simulate shapes with a potential at a constant temperature, where the cell is free to
+    /// expand
+    fn isothermal<S: State>(&self, state: S) -> S {
+        MCOptimiser::new(
+            self.kt,
+            1.,
+            self.max_step_size,
+            self.steps,
+            self.inner_steps,
+            self.seed,
+            None,
+        )
+        .with_expanding_cell(true)
+        .optimise_state(state)
+    }
+
+    /// Run the test on the structure, which is read as the type of state it was saved as
+    fn run(&self) -> Result<(), Error> {
+        let structure = Structure::read(&self.structure)?;
+        match structure.kinds() {
+            ("PotentialState", "LJShape2") => self
+                .report(&structure.parse::<PotentialState2<LJShape2>>()?, |s| {
+                    self.isothermal(s)
+                }),
+            ("PackedState", "FlexibleTrimer") => self
+                .report(&structure.parse::<PackedState2<FlexibleTrimer>>()?, |s| {
+                    self.isobaric(s)
+                }),
+            ("PackedState", "MolecularShape2") => self
+                .report(&structure.parse::<PackedState2<MolecularShape2>>()?, |s| {
+                    self.isobaric(s)
+                }),
+            ("PackedState", "LineShape") => self
+                .report(&structure.parse::<PackedState2<LineShape>>()?, |s| {
+                    self.isobaric(s)
+                }),
+            ("PackedState", "CapsuleShape") => self
+                .report(&structure.parse::<PackedState2<CapsuleShape>>()?, |s| {
+                    self.isobaric(s)
+                }),
+            ("PackedState", "RoundedShape") => self
+                .report(&structure.parse::<PackedState2<RoundedShape>>()?, |s| {
+                    self.isobaric(s)
+                }),
+            ("PackedState", "ParametricShape") => self
+                .report(&structure.parse::<PackedState2<ParametricShape>>()?, |s| {
+                    self.isobaric(s)
+                }),
+            ("PackedState", "MixedShape") => self
+                .report(&structure.parse::<PackedState2<MixedShape>>()?, |s| {
+                    self.isobaric(s)
+                }),
+            (state, shape) => bail!(
+                "Unable to test the stability of a {} of the shape {}",
+                state,
+                shape
+            ),
+        }
+    }
+
+    fn report<S: Supercell>(&self, state: &S, simulate: impl FnOnce(S) -> S) -> Result<(), Error> {
+        let (report, supercell) =
+            check_stability(state, self.na, self.nb, simulate, self.criteria());
+        info!(
+            "Relative RMSD: {:.4}, Retained density: {:.4}",
+            report.relative_rmsd, report.retained_density
+        );
+        if report.stable {
+            info!("The structure is stable");
+        } else {
+            warn!("The structure is unstable");
+        }
+        println!("{}", serde_json::to_string_pretty(&report)?);
+
+        if let Some(outfile) = &self.outfile {
+            fs::write(
+                outfile.with_extension("json"),
+                serde_json::to_string(&supercell)?,
+            )?;
+            svg::save(outfile.with_extension("svg"), &supercell.as_svg())?;
+        }
+        Ok(())
+    }
+}
+
+/// Calculate the vibrational modes of a saved structure of shapes interacting with a potential
+///
+/// The eigenvalues of the Hessian of the lattice energy are saved with the extension
+/// `.hessian.csv`, while the phonon dispersion along a path through the Brillouin zone is saved
+/// with the extension `.bands.csv`. Modes with imaginary frequencies, which indicate the
+/// structure is not a minimum of the lattice energy, are given negative frequencies.
+#[derive(Debug, StructOpt)]
+struct PhononAnalysis {
+    /// Pass many times for more log output
+    #[structopt(long, short, parse(from_occurrences))]
+    verbosity: u8,
+
+    /// The structure saved by a previous optimisation
+    #[structopt(parse(from_os_str))]
+    structure: PathBuf,
+
+    /// Where to save the results, which defaults to alongside the structure
+    #[structopt(long, parse(from_os_str))]
+    outfile: Option<PathBuf>,
+
+    /// The number of periodic images in each direction included in the force constants
+    #[structopt(long, default_value = "3")]
+    shells: i64,
+
+    /// The number of points along each segment of the path through the Brillouin zone
+    #[structopt(long, default_value = "50")]
+    points: usize,
+
+    /// The largest imaginary frequency which is treated as numerical error
+    #[structopt(long, default_value = "1e-3")]
+    tolerance: f64,
+}
+
+impl PhononAnalysis {
+    fn run(&self) -> Result<(), Error> {
+        let structure = Structure::read(&self.structure)?;
+        let state: PotentialState2<LJShape2> = match structure.kinds() {
+            ("PotentialState", "LJShape2") => structure.parse()?,
+            (state, shape) => bail!(
+                "Phonons require shapes interacting with a potential, got a {} of the shape {}",
+                state,
+                shape
+            ),
+        };
+        let outfile = self.outfile.as_ref().unwrap_or(&self.structure);
+
+        let phonons = Phonons::from_state(&state, self.shells).with_tolerance(self.tolerance);
+        let mut hessian = String::from("mode,eigenvalue\n");
+        for (mode, eigenvalue) in phonons.hessian_eigenvalues().iter().enumerate() {
+            hessian.push_str(&format!("{},{}\n", mode, eigenvalue));
+        }
+        fs::write(outfile.with_extension("hessian.csv"), hessian)?;
+
+        let bands = phonons.dispersion(&brillouin_zone_path(&state.cell), self.points);
+        let mut contents = String::from(BandPoint::CSV_HEADER);
+        for point in bands.iter() {
+            contents.push('\n');
+            contents.push_str(&point.as_csv());
+        }
+        contents.push('\n');
+        fs::write(outfile.with_extension("bands.csv"), contents)?;
+
+        let imaginary: Vec<_> = bands.iter().filter(|b| b.imaginary > 0).collect();
+        match imaginary
+            .iter()
+            .min_by(|a, b| a.frequencies[0].partial_cmp(&b.frequencies[0]).unwrap())
+        {
+            Some(softest) => warn!(
+                "Imaginary modes at {} of {} wavevectors, the largest being {} at k = ({}, {})",
+                imaginary.len(),
+                bands.len(),
+                -softest.frequencies[0],
+                softest.k.x,
+                softest.k.y
+            ),
+            None => info!("No imaginary modes, the structure is a minimum of the lattice energy"),
+        }
+        Ok(())
+    }
+}
+
+/// Calculate the elastic constants of a saved structure
+///
+/// For shapes interacting with a potential, small strains are applied to the unit cell with the
+/// shapes relaxed at each strain. For hard shapes, the elastic constants are found from the
+/// fluctuations of the cell in an isobaric simulation of a supercell.
+#[derive(Debug, StructOpt)]
+struct Elastic {
+    /// Pass many times for more log output
+    #[structopt(long, short, parse(from_occurrences))]
+    verbosity: u8,
+
+    /// The structure saved by a previous optimisation
+    #[structopt(parse(from_os_str))]
+    structure: PathBuf,
+
+    /// The strain applied to shapes with a potential, with the largest strain being twice this
+    #[structopt(long, default_value = "0.005")]
+    strain: f64,
+
+    /// The reduced pressure βPa at which packings of hard shapes are simulated
+    #[structopt(long, default_value = "50")]
+    pressure: f64,
+
+    /// The number of steps in the simulation of hard shapes, where the fluctuations of the cell
+    /// are slow to converge
+    #[structopt(short, long, default_value = "1000000")]
+    steps: u64,
+
+    /// The number of steps between each sample of the cell
+    #[structopt(long, default_value = "100")]
+    inner_steps: u64,
+
+    /// The largest change to any value in a single step
+    #[structopt(long, default_value = "0.01")]
+    max_step_size: f64,
+
+    /// The number of unit cells along the a axis of the supercell of hard shapes
+    #[structopt(long, default_value = "2")]
+    na: usize,
+
+    /// The number of unit cells along the b axis of the supercell of hard shapes
+    #[structopt(long, default_value = "2")]
+    nb: usize,
+
+    /// The seed of the random number generator
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+}
+
+impl Elastic {
+    fn fluctuation(&self) -> StrainFluctuation {
+        StrainFluctuation::new(
+            self.pressure,
+            self.max_step_size,
+            self.steps,
+            self.inner_steps,
+            self.seed,
+        )
+        .with_supercell(self.na, self.nb)
+    }
+
+    fn run(&self) -> Result<(), Error> {
+        let structure = Structure::read(&self.structure)?;
+        let constants = match structure.kinds() {
+            ("PotentialState", "LJShape2") => StaticStrain::new(self.strain)
+                .elastic_constants(&structure.parse::<PotentialState2<LJShape2>>()?),
+            ("PackedState", "FlexibleTrimer") => self
+                .fluctuation()
+                .elastic_constants(&structure.parse::<PackedState2<FlexibleTrimer>>()?)?,
+            ("PackedState", "MolecularShape2") => self
+                .fluctuation()
+                .elastic_constants(&structure.parse::<PackedState2<MolecularShape2>>()?)?,
+            ("PackedState", "LineShape") => self
+                .fluctuation()
+                .elastic_constants(&structure.parse::<PackedState2<LineShape>>()?)?,
+            ("PackedState", "CapsuleShape") => self
+                .fluctuation()
+                .elastic_constants(&structure.parse::<PackedState2<CapsuleShape>>()?)?,
+            ("PackedState", "RoundedShape") => self
+                .fluctuation()
+                .elastic_constants(&structure.parse::<PackedState2<RoundedShape>>()?)?,
+            ("PackedState", "ParametricShape") => self
+                .fluctuation()
+                .elastic_constants(&structure.parse::<PackedState2<ParametricShape>>()?)?,
+            ("PackedState", "MixedShape") => self
+                .fluctuation()
+                .elastic_constants(&structure.parse::<PackedState2<MixedShape>>()?)?,
+            (state, shape) => bail!(
+                "Unable to find the elastic constants of a {} of the shape {}",
+                state,
+                shape
+            ),
+        };
+        info!(
+            "Bulk modulus: {:.4}, Poisson ratio: {:.4}",
+            constants.bulk_modulus, constants.poisson_ratio
+        );
+        println!("{}", serde_json::to_string_pretty(&constants)?);
+        Ok(())
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy)]
+    enum Ramp {
+        Linear,
+        Geometric,
+    }
+}
+
+/// Compress a saved structure of hard shapes using isobaric Monte Carlo
+///
+/// The pressure is ramped from the start to the finish, with the cell able to expand as well as
+/// contract. The equation of state, being the mean packing fraction at each pressure, is saved
+/// with the extension `.eos.csv`, while the compressed structure is saved with the extension
+/// `.compressed.json`.
+#[derive(Debug, StructOpt)]
+struct Compress {
+    /// Pass many times for more log output
+    #[structopt(long, short, parse(from_occurrences))]
+    verbosity: u8,
+
+    /// The structure saved by a previous optimisation
+    #[structopt(parse(from_os_str))]
+    structure: PathBuf,
+
+    /// The reduced pressure βPa at the start of the compression
+    #[structopt(long, default_value = "1")]
+    pressure_start: f64,
+
+    /// The reduced pressure βPa at the end of the compression
+    #[structopt(long, default_value = "100")]
+    pressure_finish: f64,
+
+    /// How the pressure changes from the start to the finish
+    #[structopt(
+        long,
+        possible_values = &Ramp::variants(),
+        case_insensitive = true,
+        default_value = "Geometric"
+    )]
+    ramp: Ramp,
+
+    /// The number of steps in the compression
+    #[structopt(short, long, default_value = "100000")]
+    steps: u64,
+
+    /// The number of steps at each pressure, which are averaged for the equation of state
+    #[structopt(long, default_value = "1000")]
+    inner_steps: u64,
+
+    /// The largest change to any value in a single step
+    #[structopt(long, default_value = "0.01")]
+    max_step_size: f64,
+
+    /// The seed of the random number generator
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+
+    /// Where to save the results, which defaults to alongside the structure
+    #[structopt(long, parse(from_os_str))]
+    outfile: Option<PathBuf>,
+}
+
+impl Compress {
+    fn optimiser(&self) -> IsobaricOptimiser {
+        let pressure: Box<dyn PressureSchedule> = match self.ramp {
+            Ramp::Linear => Box::new(LinearPressure::new(
+                self.pressure_start,
+                self.pressure_finish,
+            )),
+            Ramp::Geometric => Box::new(GeometricPressure::new(
+                self.pressure_start,
+                self.pressure_finish,
+            )),
+        };
+        IsobaricOptimiser::new(
+            pressure,
+            self.max_step_size,
+            self.steps,
+            self.inner_steps,
+            self.seed,
+        )
+    }
+
+    fn run(&self) -> Result<(), Error> {
+        let structure = Structure::read(&self.structure)?;
+        match structure.kinds() {
+            ("PackedState", "FlexibleTrimer") => {
+                self.compress(structure.parse::<PackedState2<FlexibleTrimer>>()?)
+            }
+            ("PackedState", "MolecularShape2") => {
+                self.compress(structure.parse::<PackedState2<MolecularShape2>>()?)
+            }
+            ("PackedState", "LineShape") => {
+                self.compress(structure.parse::<PackedState2<LineShape>>()?)
+            }
+            ("PackedState", "CapsuleShape") => {
+                self.compress(structure.parse::<PackedState2<CapsuleShape>>()?)
+            }
+            ("PackedState", "RoundedShape") => {
+                self.compress(structure.parse::<PackedState2<RoundedShape>>()?)
+            }
+            ("PackedState", "ParametricShape") => {
+                self.compress(structure.parse::<PackedState2<ParametricShape>>()?)
+            }
+            ("PackedState", "MixedShape") => {
+                self.compress(structure.parse::<PackedState2<MixedShape>>()?)
+            }
+            ("PolydisperseState", "MolecularShape2") => {
+                self.compress(structure.parse::<PolydisperseState<MolecularShape2>>()?)
+            }
+            ("PolydisperseState", "LineShape") => {
+                self.compress(structure.parse::<PolydisperseState<LineShape>>()?)
+            }
+            (state, shape) => bail!(
+                "Only packings of hard shapes can be compressed, got a {} of the shape {}",
+                state,
+                shape
+            ),
+        }
+    }
+
+    fn compress<S: Packed>(&self, state: S) -> Result<(), Error> {
+        let outfile = self.outfile.as_ref().unwrap_or(&self.structure);
+        let (state, equation_of_state) =
+            self.optimiser().optimise_equation_of_state(state, &mut ());
+
+        let mut contents = String::from(StatePoint::CSV_HEADER);
+        for point in equation_of_state.iter() {
+            contents.push('\n');
+            contents.push_str(&point.as_csv());
+        }
+        contents.push('\n');
+        fs::write(outfile.with_extension("eos.csv"), contents)?;
+
+        if let Some(last) = equation_of_state.last() {
+            info!(
+                "Packing fraction at a pressure of {}: {:.4}",
+                last.pressure, last.packing_fraction
+            );
+        }
+        fs::write(
+            outfile.with_extension("compressed.json"),
+            serde_json::to_string(&state)?,
+        )?;
+        svg::save(outfile.with_extension("compressed.svg"), &state.as_svg())?;
+        Ok(())
+    }
+}
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+enum Shapes {
+    #[structopt(name = "polygon")]
+    Polygon {
+        /// The number of equally spaced sides
+        #[structopt(long, default_value = "4")]
+        sides: usize,
+    },
+    #[structopt(name = "trimer")]
+    Trimer {
+        /// The distance from the central particle to the outer particles
+        #[structopt(short, long, default_value = "1.")]
+        distance: f64,
+        /// The angle between the two outer particles in degrees
+        #[structopt(short, long, default_value = "120")]
+        angle: f64,
+        /// The radius of the outer small particles
+        #[structopt(short, long, default_value = "0.637556")]
+        radius: f64,
+        /// The smallest angle in degrees the molecule can bend to, which makes the angle a degree
+        /// of freedom of the optimisation
+        #[structopt(long)]
+        #[serde(default)]
+        min_angle: Option<f64>,
+        /// The largest angle in degrees the molecule can bend to, which makes the angle a degree
+        /// of freedom of the optimisation
+        #[structopt(long)]
+        #[serde(default)]
+        max_angle: Option<f64>,
+        /// The energy of bending a flexible molecule away from the angle, per square radian
+        #[structopt(long, default_value = "0")]
+        #[serde(default)]
+        stiffness: f64,
+    },
+    #[structopt(name = "circle")]
+    Circle {},
+    #[structopt(name = "rounded-polygon")]
+    RoundedPolygon {
+        /// The number of equally spaced sides
+        #[structopt(long, default_value = "4")]
+        sides: usize,
+        /// The radius of the disc rounding the corners of the polygon
+        #[structopt(short, long, default_value = "0.1")]
+        radius: f64,
+    },
+    #[structopt(name = "superellipse")]
+    Superellipse {
+        /// The exponent n of the curve |x|^n + |y/b|^n = 1, going from a diamond at 1, through an
+        /// ellipse at 2, to a rectangle as it becomes large
+        #[structopt(short, long, default_value = "4")]
+        exponent: f64,
+        /// The ratio of the length of the long axis to the short axis
+        #[structopt(long, default_value = "1")]
+        aspect_ratio: f64,
+        /// The distance between the curve and the polygon approximating it, which is checked at
+        /// points sampled along each edge
+        #[structopt(long, default_value = "1e-3")]
+        tolerance: f64,
+    },
+    #[structopt(name = "star")]
+    Star {
+        /// The number of arms of the star
+        #[structopt(long, default_value = "5")]
+        points: usize,
+        /// How far the arms extend, the radius being 1 + amplitude cos(points θ)
+        #[structopt(short, long, default_value = "0.3")]
+        amplitude: f64,
+        /// The distance between the curve and the polygon approximating it, which is checked at
+        /// points sampled along each edge
+        #[structopt(long, default_value = "1e-3")]
+        tolerance: f64,
+    },
+    /// A shape read from a file
+    ///
+    /// The format is determined by the extension, being a JSON or TOML file with either the
+    /// `vertices` of a polygon or the x, y, and radius of `discs`, a `.wkt` polygon, or an
+    /// SVG with a single `<path>` or a collection of `<circle>` elements.
+    #[structopt(name = "file")]
+    File {
+        /// The file containing the definition of the shape
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// The largest distance between a curve in an SVG path and the polygon approximating it
+        #[structopt(long, default_value = "1e-3")]
+        tolerance: f64,
+    },
+    #[structopt(name = "capsule")]
+    Capsule {
+        /// The ratio of the total length of the capsule to its width
+        #[structopt(long, default_value = "2")]
+        aspect_ratio: f64,
+        /// Join two capsules at an end to form a bent-core molecule, with this angle in degrees
+        /// between the arms
+        #[structopt(long)]
+        angle: Option<f64>,
+    },
+}
+
+/// The stages of the optimisation of each replication
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Stage {
+    /// Quickly optimise the initial state
+    Initial,
+    /// Perform the Monte Carlo optimisation
+    Anneal,
+    /// Final optimisation to help find the minimum
+    Minimise,
+    Done,
+}
+
+impl Stage {
+    fn next(self) -> Self {
+        match self {
+            Stage::Initial => Stage::Anneal,
+            Stage::Anneal => Stage::Minimise,
+            Stage::Minimise | Stage::Done => Stage::Done,
+        }
+    }
+
+    fn optimiser(self, optimiser: &BuildOptimiser, index: u64) -> Option<MCOptimiser> {
+        match self {
+            Stage::Initial => Some(
+                optimiser
+                    .clone()
+                    .steps(100)
+                    .kt_start(0.)
+                    .seed(index)
+                    .convergence(None)
+                    .build(),
+            ),
+            Stage::Anneal => Some(optimiser.clone().seed(index).build()),
+            Stage::Minimise => Some(optimiser.clone().kt_start(0.).seed(index).build()),
+            Stage::Done => None,
+        }
+    }
+}
+
+/// The progress of a single replication
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Replica<S> {
+    index: u64,
+    stage: Stage,
+    state: S,
+    /// The state with the best score found so far, which is the result when the optimisation
+    /// is stopped part way through
+    best: S,
+    /// The progress within the current stage, which is None before the stage has started
+    progress: Option<MCCheckpoint>,
+}
+
+/// Everything required to resume an interrupted optimisation
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint<S> {
+    args: Args,
+    replicas: Vec<Replica<S>>,
+}
+
+impl<S: Serialize> Checkpoint<S> {
+    /// Write the checkpoint, replacing the previous checkpoint only once complete
+    fn save(&self, path: &Path) -> Result<(), Error> {
+        let partial = path.with_extension("partial");
+        serde_json::to_writer(File::create(&partial)?, self)?;
+        fs::rename(partial, path)?;
+        Ok(())
+    }
+}
+
+/// Keeps the latest progress of every replication, periodically saving it as a checkpoint
+struct Checkpointer<S> {
+    args: Args,
+    path: PathBuf,
+    every: Option<Duration>,
+    deadline: Option<Instant>,
+    latest: Mutex<(Checkpoint<S>, Instant)>,
+}
+
+impl<S: State> Checkpointer<S> {
+    fn new(checkpoint: Checkpoint<S>) -> Self {
+        let start = Instant::now();
+        Self {
+            args: checkpoint.args.clone(),
+            path: checkpoint.args.checkpoint_path(),
+            every: checkpoint
+                .args
+                .checkpoint_every
+                .map(Duration::from_secs_f64),
+            deadline: checkpoint
+                .args
+                .time_limit
+                .map(|limit| start + Duration::from_secs_f64(limit)),
+            latest: Mutex::new((checkpoint, start)),
+        }
+    }
+
+    /// Record the progress of a replication, returning whether it should continue
+    fn update(
+        &self,
+        position: usize,
+        stage: Stage,
+        state: &S,
+        best: &S,
+        progress: &MCCheckpoint,
+    ) -> Control {
+        if let Some(every) = self.every {
+            let mut latest = self.latest.lock().unwrap();
+            let (checkpoint, saved) = &mut *latest;
+            let replica = &mut checkpoint.replicas[position];
+            replica.stage = stage;
+            replica.state = state.clone();
+            replica.best = best.clone();
+            replica.progress = Some(progress.clone());
+
+            if saved.elapsed() >= every {
+                match checkpoint.save(&self.path) {
+                    Ok(_) => debug!("Saved checkpoint to {}", self.path.display()),
+                    Err(e) => warn!("Unable to save checkpoint: {}", e),
+                }
+                *saved = Instant::now();
+            }
+        }
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Control::Stop,
+            _ => Control::Continue,
+        }
+    }
+
+    /// Record a replication between stages, where it isn't passed to [`Checkpointer::update`]
+    fn record(&self, position: usize, replica: &Replica<S>) {
+        if self.every.is_some() {
+            self.latest.lock().unwrap().0.replicas[position] = replica.clone();
+        }
+    }
+
+    /// Run the remaining stages of a replication
+    fn run(&self, position: usize, mut replica: Replica<S>) -> Result<Replica<S>, Error> {
+        let args = &self.args;
+        while let Some(optimiser) = replica.stage.optimiser(&args.optimisation, replica.index) {
+            let progress = match replica.progress.take() {
+                Some(progress) => progress,
+                None => optimiser.checkpoint(&replica.state),
+            };
+            let mut observers = match replica.stage {
+                Stage::Anneal => args
+                    .monitor
+                    .observers(replica.index, progress.loop_index() > 0)?,
+                _ => Observers::default(),
+            };
+            let stage = replica.stage;
+            let mut best = replica.best.clone();
+            let (state, progress) = optimiser.optimise_checkpointed(
+                replica.state,
+                progress,
+                &mut observers,
+                &mut |state, progress| {
+                    if state.score() > best.score() {
+                        best = state.clone();
+                    }
+                    self.update(position, stage, state, &best, progress)
+                },
+            );
+            // The final loop of a stage isn't passed to the control
+            if state.score() > best.score() {
+                best = state.clone();
+            }
+            replica.state = state;
+            replica.best = best;
+            let finished = progress.is_finished();
+            if finished {
+                replica.stage = stage.next();
+            } else {
+                replica.progress = Some(progress);
+            }
+            self.record(position, &replica);
+            if !finished {
+                break;
+            }
+        }
+        Ok(replica)
+    }
+}
+
+/// Optimise every replication of the state, returning the final score once finished
+///
+/// The score is None when the time limit stopped the optimisation before it finished.
+fn analyse_state<S: Ranked + DeserializeOwned>(
+    args: Args,
+    state: S,
+    resume: Option<String>,
+) -> Result<Option<f64>, Error> {
+    let point_group = state.point_group();
+    info!("Shape point group: {}", point_group);
+    check_chirality(args.chirality, &args.wallpaper.try_into()?, point_group)?;
+    if args.optimiser != Search::MonteCarlo {
+        return search_state(args, state);
+    }
+    let checkpoint = match resume {
+        Some(checkpoint) => Checkpoint {
+            args: args.clone(),
+            ..serde_json::from_str(&checkpoint)?
+        },
+        None => Checkpoint {
+            replicas: (0..args.replications)
+                .map(|index| Replica {
+                    index,
+                    stage: Stage::Initial,
+                    state: state.clone(),
+                    best: state.clone(),
+                    progress: None,
+                })
+                .collect(),
+            args: args.clone(),
+        },
+    };
+    let checkpointer = Checkpointer::new(checkpoint);
+    let replicas = checkpointer.latest.lock().unwrap().0.replicas.clone();
+
+    let replicas = replicas
+        .into_par_iter()
+        .enumerate()
+        .map(|(position, replica)| checkpointer.run(position, replica))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let finished = replicas.iter().all(|r| r.stage == Stage::Done);
+    // An unfinished optimisation may be part way through annealing, where the current state is
+    // worse than the best found so far
+    let states = replicas
+        .iter()
+        .map(|r| {
+            if finished {
+                r.state.clone()
+            } else {
+                r.best.clone()
+            }
+        })
+        .collect();
+
+    let checkpoint = args.checkpoint_path();
+    if !finished {
+        Checkpoint {
+            args: args.clone(),
+            replicas,
+        }
+        .save(&checkpoint)?;
+        info!(
+            "Time limit reached, continue the optimisation using: packing resume {}",
+            checkpoint.display()
+        );
+    }
+
+    let score = save_best(&args, states)?;
+
+    // A finished optimisation has nothing left to resume, so the periodic checkpoints are
+    // removed once the results are saved
+    if finished && checkpoint.exists() {
+        fs::remove_file(&checkpoint)?;
+    }
+
+    Ok(Some(score).filter(|_| finished))
+}
+
+/// Optimise the state with a single run of the genetic or basin hopping optimiser
+///
+/// For the genetic optimiser the population takes the place of the replications, with the best
+/// and median score of every generation written to a CSV report alongside the outfile. Basin
+/// hopping keeps an archive of the distinct minima, which are ranked in place of the structures
+/// of the replications. Neither is checkpointed, so the optimisation always runs to completion.
+fn search_state<S: Ranked>(args: Args, state: S) -> Result<Option<f64>, Error> {
+    if args.time_limit.is_some() || args.checkpoint_every.is_some() {
+        bail!(
+            "The {} optimiser can't be checkpointed, so doesn't support a time limit",
+            args.optimiser
+        );
+    }
+    let mut observers = args.monitor.observers(0, false)?;
+    let seed = args.optimisation.build_seed();
+    let relaxation = args.optimisation.build_relaxation();
+
+    let states = match args.optimiser {
+        Search::Genetic => {
+            let optimiser = GeneticOptimiser::new(args.population, args.generations, seed)
+                .with_relaxation(relaxation);
+            let path = args.generations_path();
+            let mut report = File::create(&path)?;
+            writeln!(report, "{}", Generation::CSV_HEADER)?;
+            let best = optimiser.optimise_reported(state, &mut |generation, best| {
+                if let Err(e) = writeln!(report, "{}", generation.as_csv()) {
+                    warn!(
+                        "Unable to report generation {}: {}",
+                        generation.generation, e
+                    );
+                }
+                if let Err(e) = observers.observe(&optimiser.snapshot(generation), best) {
+                    warn!(
+                        "Observer failed at generation {}: {}",
+                        generation.generation, e
+                    );
+                }
+            });
+            info!("Saved the scores of each generation to {}", path.display());
+            vec![best]
+        }
+        Search::BasinHopping => {
+            let archive = BasinHopping::new(
+                args.cycles,
+                args.optimisation.kt_start,
+                args.perturbation,
+                seed,
+            )
+            .with_relaxation(relaxation)
+            .with_archive(args.replications as usize, 1e-6)
+            .optimise_archive(state, &mut observers);
+            info!("Found {} distinct minima", archive.len());
+            archive.iter().cloned().collect()
+        }
+        Search::MonteCarlo => bail!("The Monte Carlo optimisation runs each of the replications"),
+    };
+
+    save_best(&args, states).map(Some)
+}
+
+/// Save the best of the structures found by the optimisation, returning its score
+fn save_best<S: Ranked>(args: &Args, states: Vec<S>) -> Result<f64, Error> {
+    let final_state = match (args.rank_by, args.temperature) {
+        (Ranking::FreeEnergy, Some(temperature)) => rank_by_free_energy(states, temperature)?,
+        _ => states
+            .into_iter()
+            .max()
+            .ok_or_else(|| anyhow!("Error in running optimisation."))?,
+    };
+
+    let score = final_state
+        .score()
+        .ok_or_else(|| anyhow!("State has become corrupted"))?;
+    info!("Final score: {}", score);
+
+    let serialised = serde_json::to_string(&final_state)?;
+
+    File::create(args.outfile.with_extension("json"))?.write_all(serialised.as_bytes())?;
+    svg::save(args.outfile.with_extension("svg"), &final_state.as_svg())?;
+
+    Ok(score)
+}
+
+fn init_logging(verbosity: u8) {
+    let log_level = match verbosity {
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        2 => LevelFilter::Trace,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new().filter_level(log_level).init();
+
+    debug!("Logging Level: {}", log_level);
+}
+
+/// Read the definition of a shape, with the format determined by the extension of the file
+fn read_shape(path: &Path, tolerance: f64) -> Result<ShapeDefinition, Error> {
+    let contents = fs::read_to_string(path)?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => Ok(serde_json::from_str(&contents)?),
+        Some("toml") => Ok(toml::from_str(&contents)?),
+        Some("wkt") => ShapeDefinition::from_wkt(&contents),
+        Some("svg") => ShapeDefinition::from_svg(&contents, tolerance),
+        _ => bail!(
+            "Unknown format of {}, expected a json, toml, wkt, or svg file",
+            path.display()
+        ),
+    }
+}
+
+/// Optimise a population of differently sized copies of the shape on independent sites
+fn run_polydisperse(
+    args: Args,
+    wg: &WallpaperGroup,
+    sizes: &[f64],
+    resume: Option<String>,
+) -> Result<Option<f64>, Error> {
+    info!("Size of the shape on each site: {:?}", sizes);
+    match (args.shape.clone(), args.potential) {
+        (
+            Shapes::Trimer {
+                distance,
+                angle,
+                radius,
+                min_angle: None,
+                max_angle: None,
+                ..
+            },
+            Force::Hard,
+        ) => {
+            let shape = MolecularShape2::from_trimer(radius, angle, distance);
+            analyse_state(
+                args,
+                PolydisperseState::from_group(shape, wg, sizes)?,
+                resume,
+            )
+        }
+        (Shapes::Circle {}, Force::Hard) => {
+            let shape = MolecularShape2::circle();
+            analyse_state(
+                args,
+                PolydisperseState::from_group(shape, wg, sizes)?,
+                resume,
+            )
+        }
+        (Shapes::Polygon { sides }, Force::Hard) => {
+            let shape = LineShape::polygon(sides)?;
+            analyse_state(
+                args,
+                PolydisperseState::from_group(shape, wg, sizes)?,
+                resume,
+            )
+        }
+        (_, Force::LJ) => bail!("Polydisperse shapes with a LJ potential are not yet implemented"),
+        (_, Force::Hard) => bail!("Polydisperse populations of this shape are not yet implemented"),
+    }
+}
+
+/// Optimise the state described by the arguments, continuing from the checkpoint when resuming
+fn run(args: Args, resume: Option<String>) -> Result<Option<f64>, Error> {
+    let wg: WallpaperGroup = args.wallpaper.try_into()?;
+    if args.rank_by == Ranking::FreeEnergy {
+        if args.temperature.is_none() {
+            bail!("Ranking by the free energy requires a --temperature");
+        }
+        if let Force::Hard = args.potential {
+            bail!("Ranking by the free energy requires a potential");
+        }
+    }
+    if let Shapes::Trimer {
+        min_angle: None,
+        max_angle: None,
+        stiffness,
+        ..
+    } = args.shape
+    {
+        if stiffness != 0. {
+            bail!("A --stiffness requires the angle to bend, given by --min-angle or --max-angle");
+        }
+    }
+    if let Some(sizes) = args.site_sizes()? {
+        return run_polydisperse(args, &wg, &sizes, resume);
+    }
+
+    match (args.shape.clone(), args.potential) {
+        (
+            Shapes::Trimer {
+                distance,
+                angle,
+                radius,
+                min_angle: None,
+                max_angle: None,
+                ..
+            },
+            Force::LJ,
+        ) => analyse_state(
+            args,
+            PotentialState2::from_group(LJShape2::from_trimer(radius, angle, distance), &wg)?,
+            resume,
+        ),
+        (Shapes::Trimer { .. }, Force::LJ) => {
+            bail!("Flexible Trimer with a LJ potential is not yet implemented")
+        }
+        (
+            Shapes::Trimer {
+                distance,
+                angle,
+                radius,
+                min_angle: None,
+                max_angle: None,
+                ..
+            },
+            Force::Hard,
+        ) => analyse_state(
+            args,
+            PackedState2::from_group(MolecularShape2::from_trimer(radius, angle, distance), &wg)?,
+            resume,
+        ),
+        (
+            Shapes::Trimer {
+                distance,
+                angle,
+                radius,
+                min_angle,
+                max_angle,
+                stiffness,
+            },
+            Force::Hard,
+        ) => {
+            let shape = FlexibleTrimer::new(
+                radius,
+                angle,
+                distance,
+                min_angle.unwrap_or(angle),
+                max_angle.unwrap_or(angle),
+            )?
+            .with_stiffness(stiffness);
+            analyse_state(args, PackedState2::from_group(shape, &wg)?, resume)
+        }
+        (Shapes::Circle {}, Force::LJ) => analyse_state(
+            args,
+            PotentialState2::from_group(LJShape2::circle(), &wg)?,
+            resume,
+        ),
+        (Shapes::Circle {}, Force::Hard) => analyse_state(
+            args,
+            PackedState2::from_group(MolecularShape2::circle(), &wg)?,
+            resume,
+        ),
+        (Shapes::Polygon { sides }, Force::Hard) => analyse_state(
+            args,
+            PackedState2::from_group(LineShape::polygon(sides)?, &wg)?,
+            resume,
+        ),
+        (Shapes::Polygon { .. }, Force::LJ) => {
+            bail!("Polygon with a LJ potential is not yet implemented")
+        }
+        (
+            Shapes::Capsule {
+                aspect_ratio,
+                angle,
+            },
+            Force::Hard,
+        ) => {
+            let shape = match angle {
+                Some(angle) => CapsuleShape::bent_core(aspect_ratio, angle)?,
+                None => CapsuleShape::capsule(aspect_ratio)?,
+            };
+            analyse_state(args, PackedState2::from_group(shape, &wg)?, resume)
+        }
+        (Shapes::RoundedPolygon { sides, radius }, Force::Hard) => analyse_state(
+            args,
+            PackedState2::from_group(RoundedShape::polygon(sides, radius)?, &wg)?,
+            resume,
+        ),
+        (Shapes::RoundedPolygon { .. }, Force::LJ) => {
+            bail!("Rounded polygon with a LJ potential is not yet implemented")
+        }
+        (
+            Shapes::Superellipse {
+                exponent,
+                aspect_ratio,
+                tolerance,
+            },
+            Force::Hard,
+        ) => {
+            let shape = ParametricShape::superellipse(exponent, aspect_ratio, tolerance)?;
+            info!("Shape area: {}", shape.area());
+            analyse_state(args, PackedState2::from_group(shape, &wg)?, resume)
+        }
+        (
+            Shapes::Star {
+                points,
+                amplitude,
+                tolerance,
+            },
+            Force::Hard,
+        ) => {
+            let shape = ParametricShape::star(points, amplitude, tolerance)?;
+            info!("Shape area: {}", shape.area());
+            analyse_state(args, PackedState2::from_group(shape, &wg)?, resume)
+        }
+        (Shapes::Superellipse { .. }, Force::LJ) | (Shapes::Star { .. }, Force::LJ) => {
+            bail!("Parametric shapes with a LJ potential are not yet implemented")
+        }
+        (Shapes::File { path, tolerance }, Force::Hard) => {
+            let shape = read_shape(&path, tolerance)?.build()?;
+            info!("Shape area: {}", shape.area());
+            match shape {
+                ImportedShape::Polygon(shape) => {
+                    analyse_state(args, PackedState2::from_group(shape, &wg)?, resume)
+                }
+                ImportedShape::Molecular(shape) => {
+                    analyse_state(args, PackedState2::from_group(shape, &wg)?, resume)
+                }
+                ImportedShape::Mixed(shape) => {
+                    analyse_state(args, PackedState2::from_group(shape, &wg)?, resume)
+                }
+            }
+        }
+        (Shapes::File { .. }, Force::LJ) => {
+            bail!("Shapes from a file with a LJ potential are not yet implemented")
+        }
+        (Shapes::Capsule { .. }, Force::LJ) => {
+            bail!("Capsule with a LJ potential is not yet implemented")
+        }
+    }
+}
+
+/// Find the densest packing of shapes in each of the wallpaper groups
+#[derive(Debug, StructOpt)]
+#[structopt(name = "packing")]
+#[allow(clippy::large_enum_variant)]
+enum Command {
+    /// Optimise the packing of a shape in a wallpaper group
+    Run(Args),
+    Resume(Resume),
+    Sweep(Sweep),
+    Stability(Stability),
+    Elastic(Elastic),
+    Phonons(PhononAnalysis),
+    Compress(Compress),
+}
+
+impl Command {
+    /// Parse the arguments, running an optimisation when there is no subcommand
+    ///
+    /// An optimisation was run using `packing <wallpaper> <shape>` before there were any
+    /// subcommands, which continues to work as an alias of `packing run <wallpaper> <shape>`.
+    ///
+    fn parse(mut argv: Vec<OsString>) -> Result<Self, clap::Error> {
+        match Self::from_iter_safe(&argv) {
+            // Without a subcommand the first argument, either the wallpaper group or one of the
+            // flags of the optimisation, is the one which isn't recognised
+            Err(clap::Error {
+                kind:
+                    ErrorKind::UnrecognizedSubcommand
+                    | ErrorKind::InvalidSubcommand
+                    | ErrorKind::UnknownArgument,
+                info: Some(ref info),
+                ..
+            }) if argv.get(1).map(|a| a.to_string_lossy()) == info.first().map(Into::into) => {
+                argv.insert(1, "run".into());
+                Self::from_iter_safe(argv)
+            }
+            command => command,
+        }
+    }
+}
+
+fn main() -> Result<(), Error> {
+    match Command::parse(std::env::args_os().collect()).unwrap_or_else(|e| e.exit()) {
+        Command::Run(args) => {
+            init_logging(args.verbosity);
+            run(args, None).map(|_| ())
+        }
+        Command::Resume(resume) => {
+            init_logging(resume.verbosity);
+
+            // The arguments determine the type of state stored in the checkpoint, so are read first
+            #[derive(Deserialize)]
+            struct Header {
+                args: Args,
+            }
+            let checkpoint = fs::read_to_string(&resume.checkpoint)?;
+            let mut args = serde_json::from_str::<Header>(&checkpoint)?.args;
+            args.time_limit = resume.time_limit;
+            run(args, Some(checkpoint)).map(|_| ())
+        }
+        Command::Sweep(sweep) => {
+            init_logging(sweep.verbosity);
+            sweep.run()
+        }
+        Command::Stability(stability) => {
+            init_logging(stability.verbosity);
+            stability.run()
+        }
+        Command::Elastic(elastic) => {
+            init_logging(elastic.verbosity);
+            elastic.run()
+        }
+        Command::Phonons(phonons) => {
+            init_logging(phonons.verbosity);
+            phonons.run()
+        }
+        Command::Compress(compress) => {
+            init_logging(compress.verbosity);
+            compress.run()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn p2() -> WallpaperGroup<'static> {
+        WallpaperGroups::p2.try_into().unwrap()
+    }
+
+    /// Save the state as a structure in the temporary directory
+    fn save_structure<S: Serialize>(name: &str, state: &S) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("packing-{}-{}.json", name, std::process::id()));
+        fs::write(&path, serde_json::to_string(state).unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn stability_of_rounded_structure() {
+        let shape = RoundedShape::polygon(4, 0.1).unwrap();
+        let state = PackedState2::from_group(shape.clone(), &p2()).unwrap();
+        let path = save_structure("rounded", &state);
+
+        // A rounded shape has the same fields as a capsule shape, so is told apart by its kind
+        let structure = Structure::read(&path).unwrap();
+        assert_eq!(structure.kinds(), ("PackedState", "RoundedShape"));
+        let read: PackedState2<RoundedShape> = structure.parse().unwrap();
+        assert_eq!(read.shape, shape);
+
+        let path_str = path.to_str().unwrap();
+        let args = [
+            "stability",
+            path_str,
+            "--steps",
+            "100",
+            "--inner-steps",
+            "10",
+        ];
+        Stability::from_iter_safe(&args).unwrap().run().unwrap();
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn stability_rejects_polydisperse() {
+        let shape = LineShape::polygon(4).unwrap();
+        let state = PolydisperseState::from_group(shape, &p2(), &[1., 0.8]).unwrap();
+        let path = save_structure("polydisperse", &state);
+
+        // The sizes would be lost reading the structure as a packed state
+        let structure = Structure::read(&path).unwrap();
+        assert_eq!(structure.kinds(), ("PolydisperseState", "LineShape"));
+        let args = ["stability", path.to_str().unwrap()];
+        assert!(Stability::from_iter_safe(&args).unwrap().run().is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn compress_equation_of_state() {
+        let state = PackedState2::from_group(LineShape::polygon(4).unwrap(), &p2()).unwrap();
+        let path = save_structure("compress", &state);
+        let path_str = path.to_str().unwrap();
+        let args = [
+            "compress",
+            path_str,
+            "--steps",
+            "1000",
+            "--inner-steps",
+            "100",
+        ];
+        Compress::from_iter_safe(&args).unwrap().run().unwrap();
+
+        let eos = fs::read_to_string(path.with_extension("eos.csv")).unwrap();
+        assert_eq!(eos.lines().next(), Some(StatePoint::CSV_HEADER));
+        assert_eq!(eos.lines().count(), 11);
+        for extension in &["json", "eos.csv", "compressed.json", "compressed.svg"] {
+            fs::remove_file(path.with_extension(extension)).unwrap();
+        }
+    }
+
+    #[test]
+    fn compress_flexible_trimer() {
+        let shape = FlexibleTrimer::new(0.7, 120., 1., 60., 180.)
+            .unwrap()
+            .with_stiffness(100.);
+        let state = PackedState2::from_group(shape, &p2()).unwrap();
+        state.shape.internal_basis()[0]
+            .set_value(std::f64::consts::FRAC_PI_2)
+            .unwrap();
+        // The bending energy is large enough for the score to be negative
+        assert!(state.score().unwrap() < 0.);
+        let path = save_structure("compress-flexible", &state);
+        let args = [
+            "compress",
+            path.to_str().unwrap(),
+            "--steps",
+            "1000",
+            "--inner-steps",
+            "100",
+        ];
+        Compress::from_iter_safe(&args).unwrap().run().unwrap();
+
+        // The equation of state has the packing fraction rather than the score
+        let eos = fs::read_to_string(path.with_extension("eos.csv")).unwrap();
+        for line in eos.lines().skip(1) {
+            let fraction: f64 = line.split(',').nth(2).unwrap().parse().unwrap();
+            assert!(fraction > 0. && fraction <= 1., "{}", line);
+        }
+        for extension in &["json", "eos.csv", "compressed.json", "compressed.svg"] {
+            fs::remove_file(path.with_extension(extension)).unwrap();
+        }
+    }
+
+    #[test]
+    fn compress_rejects_potential() {
+        let state = PotentialState2::from_group(LJShape2::circle(), &p2()).unwrap();
+        let path = save_structure("compress-potential", &state);
+        let args = ["compress", path.to_str().unwrap()];
+        assert!(Compress::from_iter_safe(&args).unwrap().run().is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn read_parametric_structure() {
+        let shape = ParametricShape::superellipse(4., 2., 1e-3).unwrap();
+        let state = PackedState2::from_group(shape.clone(), &p2()).unwrap();
+        let path = save_structure("parametric", &state);
+
+        // The edges of a parametric shape would also be read as a line shape
+        let structure = Structure::read(&path).unwrap();
+        assert_eq!(structure.kinds(), ("PackedState", "ParametricShape"));
+        let read: PackedState2<ParametricShape> = structure.parse().unwrap();
+        assert_eq!(read.shape, shape);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn sweep_parameter_range() {
+        let param: SweepParameter = "angle=100:120:5".parse().unwrap();
+        assert_eq!(param.name, "angle");
+        assert_eq!(param.values, vec![100., 105., 110., 115., 120.]);
+        // The stop value is included despite the rounding of the step
+        let param: SweepParameter = "radius=0.1:0.3:0.1".parse().unwrap();
+        assert_eq!(param.values, vec![0.1, 0.2, 0.3]);
+        let param: SweepParameter = "radius=0.1:0.35:0.1".parse().unwrap();
+        assert_eq!(param.values, vec![0.1, 0.2, 0.3]);
+        assert!("angle=120:100:5".parse::<SweepParameter>().is_err());
+        assert!("angle=100:120:0".parse::<SweepParameter>().is_err());
+        assert!("angle=100:120".parse::<SweepParameter>().is_err());
+    }
+
+    #[test]
+    fn sweep_parameter_list() {
+        let param: SweepParameter = "sides=3,5,4".parse().unwrap();
+        assert_eq!(param.name, "sides");
+        assert_eq!(param.values, vec![3., 5., 4.]);
+        assert!("sides".parse::<SweepParameter>().is_err());
+        assert!("sides=3,five".parse::<SweepParameter>().is_err());
+    }
+
+    /// A sweep of polygons saving the results in a temporary directory
+    fn shape_sweep(shape: &str, name: &str, params: &[&str], options: &[&str]) -> Sweep {
+        let outdir = std::env::temp_dir().join(format!("packing-{}-{}", name, std::process::id()));
+        let mut args = vec!["sweep", shape, "--groups", "p1,p2"];
+        for param in params {
+            args.extend(&["--param", param]);
+        }
+        let outdir = outdir.to_str().unwrap().to_string();
+        args.extend(&["--outdir", &outdir, "--"]);
+        args.extend(options);
+        Sweep::from_iter_safe(&args).unwrap()
+    }
+
+    fn polygon_sweep(name: &str, params: &[&str], options: &[&str]) -> Sweep {
+        shape_sweep("polygon", name, params, options)
+    }
+
+    #[test]
+    fn sweep_points() {
+        let params = ["sides=3,4", "radius=0.1:0.3:0.1"];
+        let sweep = shape_sweep("rounded-polygon", "points", &params, &[]);
+        let points: Vec<_> = sweep
+            .points()
+            .iter()
+            .map(|(values, group)| Sweep::key(values, *group).join(","))
+            .collect();
+        assert_eq!(points.len(), 2 * 2 * 3);
+        assert_eq!(points[0], "3,0.1,p1");
+        assert_eq!(points[1], "3,0.2,p1");
+        assert_eq!(points[3], "4,0.1,p1");
+        assert_eq!(points[6], "3,0.1,p2");
+        let unique: HashSet<_> = points.iter().collect();
+        assert_eq!(unique.len(), points.len());
+
+        // The parameters of each point are passed to the shape
+        let args = sweep.point_args(&[4., 0.3], WallpaperGroups::p2).unwrap();
+        match args.shape {
+            Shapes::RoundedPolygon { sides, radius } => {
+                assert_eq!(sides, 4);
+                assert_eq!(radius, 0.3);
+            }
+            shape => panic!("Expected a rounded polygon, got {:?}", shape),
+        }
+        assert_eq!(args.wallpaper.to_string(), "p2");
+    }
+
+    #[test]
+    fn sweep_skips_finished_points() {
+        let sweep = polygon_sweep("finished", &["sides=3,4"], &[]);
+        fs::create_dir_all(&sweep.outdir).unwrap();
+        let path = sweep.results_path();
+        assert!(sweep.finished().unwrap().is_empty());
+
+        // The path of a structure with a comma is quoted
+        let row = "3,p2,0.5,\"a,b.json\",\"a,b.svg\"";
+        fs::write(&path, format!("{}\n{}\n", sweep.header().join(","), row)).unwrap();
+        let finished = sweep.finished().unwrap();
+        assert_eq!(finished.len(), 1);
+        assert!(finished.contains(&Sweep::key(&[3.], WallpaperGroups::p2)));
+
+        // The results of a sweep over different parameters can't be continued
+        fs::write(&path, "sides,size,wallpaper,score,structure,image\n").unwrap();
+        assert!(sweep.finished().is_err());
+        fs::remove_dir_all(&sweep.outdir).unwrap();
+    }
+
+    #[test]
+    fn sweep_resumes_time_limited_points() {
+        let options = [
+            "--steps",
+            "1000",
+            "--inner-steps",
+            "10",
+            "--replications",
+            "2",
+        ];
+        let limited: Vec<_> = options
+            .iter()
+            .chain(&["--time-limit", "0"])
+            .copied()
+            .collect();
+        // The comma in the outdir is quoted in the paths of the results
+        let sweep = polygon_sweep("resume,quoted", &["sides=4"], &limited);
+        sweep.run().unwrap();
+        // The points stopped by the time limit aren't recorded, leaving a checkpoint
+        assert!(sweep.finished().unwrap().is_empty());
+        let args = sweep.point_args(&[4.], WallpaperGroups::p2).unwrap();
+        assert!(args.checkpoint_path().exists());
+
+        let sweep = polygon_sweep("resume,quoted", &["sides=4"], &options);
+        sweep.run().unwrap();
+        assert_eq!(sweep.finished().unwrap().len(), 2);
+        let mut reader = csv::Reader::from_path(sweep.results_path()).unwrap();
+        for record in reader.records() {
+            let record = record.unwrap();
+            assert_eq!(record.len(), sweep.header().len());
+            assert!(Path::new(&record[3]).exists());
+        }
+        fs::remove_dir_all(&sweep.outdir).unwrap();
+    }
+
+    fn parse_command(argv: &[&str]) -> Result<Command, clap::Error> {
+        Command::parse(argv.iter().map(OsString::from).collect())
+    }
+
+    #[test]
+    fn run_without_subcommand() {
+        for argv in &[
+            vec!["packing", "run", "p2", "--outfile", "out", "trimer"],
+            vec!["packing", "p2", "--outfile", "out", "trimer"],
+            vec!["packing", "-v", "p2", "--outfile", "out", "trimer"],
+        ] {
+            match parse_command(argv) {
+                Ok(Command::Run(args)) => assert_eq!(args.wallpaper.to_string(), "p2"),
+                command => panic!("{:?} parsed as {:?}", argv, command),
+            }
+        }
+        assert!(matches!(
+            parse_command(&["packing", "resume", "checkpoint.json"]),
+            Ok(Command::Resume(_))
+        ));
+        assert!(parse_command(&["packing", "p2", "--outfile", "out", "unknown-shape"]).is_err());
+    }
+
+    #[test]
+    fn finished_run_removes_checkpoint() {
+        let outfile = std::env::temp_dir().join(format!("packing-finished-{}", std::process::id()));
+        let outfile = outfile.to_str().unwrap();
+        let argv = [
+            "packing",
+            "run",
+            "p2",
+            "--replications",
+            "2",
+            "--steps",
+            "100",
+            "--checkpoint-every",
+            "0",
+            "--outfile",
+            outfile,
+            "polygon",
+        ];
+        let args = match parse_command(&argv).unwrap() {
+            Command::Run(args) => args,
+            command => panic!("Expected an optimisation, got {:?}", command),
+        };
+        let path = args.checkpoint_path();
+        assert!(run(args.clone(), None).unwrap().is_some());
+        // A finished optimisation isn't resumed, either by resume or a sweep
+        assert!(!path.exists());
+        for extension in &["json", "svg"] {
+            fs::remove_file(args.outfile.with_extension(extension)).unwrap();
+        }
+    }
+
+    #[test]
+    fn genetic_optimiser_reports_generations() {
+        let outfile = std::env::temp_dir().join(format!("packing-genetic-{}", std::process::id()));
+        let outfile = outfile.to_str().unwrap();
+        let argv = [
+            "packing",
+            "run",
+            "p2",
+            "--optimiser",
+            "genetic",
+            "--population",
+            "4",
+            "--generations",
+            "2",
+            "--steps",
+            "100",
+            "--outfile",
+            outfile,
+            "polygon",
+        ];
+        let args = match parse_command(&argv).unwrap() {
+            Command::Run(args) => args,
+            command => panic!("Expected an optimisation, got {:?}", command),
+        };
+        let path = args.generations_path();
+        assert!(run(args.clone(), None).unwrap().is_some());
+
+        let report = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], Generation::CSV_HEADER);
+        // The initial population along with each of the generations
+        assert_eq!(lines.len(), 4);
+        assert!(lines[3].starts_with("2,"));
+
+        // There is no checkpoint of the population to resume from
+        let mut limited = args.clone();
+        limited.time_limit = Some(1.);
+        assert!(run(limited, None).is_err());
+
+        fs::remove_file(path).unwrap();
+        for extension in &["json", "svg"] {
+            fs::remove_file(args.outfile.with_extension(extension)).unwrap();
+        }
+    }
+
+    #[test]
+    fn basin_hopping_optimiser() {
+        let outfile = std::env::temp_dir().join(format!("packing-basin-{}", std::process::id()));
+        let outfile = outfile.to_str().unwrap();
+        let argv = [
+            "packing",
+            "run",
+            "p2",
+            "--optimiser",
+            "basinhopping",
+            "--cycles",
+            "5",
+            "--steps",
+            "100",
+            "--outfile",
+            outfile,
+            "polygon",
+        ];
+        let args = match parse_command(&argv).unwrap() {
+            Command::Run(args) => args,
+            command => panic!("Expected an optimisation, got {:?}", command),
+        };
+        assert_eq!(args.optimiser, Search::BasinHopping);
+        let initial = PackedState2::from_group(LineShape::polygon(4).unwrap(), &p2())
+            .unwrap()
+            .score()
+            .unwrap();
+        let score = run(args.clone(), None).unwrap().unwrap();
+        assert!(score > initial);
+
+        // The saved structure is the best of the minima
+        let saved: PackedState2<LineShape> = Structure::read(&args.outfile.with_extension("json"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(saved.score(), Some(score));
+        for extension in &["json", "svg"] {
+            fs::remove_file(args.outfile.with_extension(extension)).unwrap();
+        }
+    }
+
+    #[test]
+    fn racemic_requires_reflections() {
+        let p2mg: WallpaperGroup = WallpaperGroups::p2mg.try_into().unwrap();
+        let chiral = PointGroup::Cyclic(1);
+        assert!(check_chirality(Chirality::Racemic, &p2(), chiral).is_err());
+        assert!(check_chirality(Chirality::Racemic, &p2mg, chiral).is_ok());
+        assert!(check_chirality(Chirality::Enantiopure, &p2mg, chiral).is_err());
+        assert!(check_chirality(Chirality::Enantiopure, &p2(), chiral).is_ok());
+
+        // The optimisation stops before it starts, rather than giving an enantiopure packing
+        let outfile = std::env::temp_dir().join(format!("packing-racemic-{}", std::process::id()));
+        let argv = [
+            "packing",
+            "run",
+            "p2",
+            "--chirality",
+            "racemic",
+            "--outfile",
+            outfile.to_str().unwrap(),
+            "trimer",
+            "--angle",
+            "100",
+        ];
+        match parse_command(&argv).unwrap() {
+            Command::Run(args) => assert!(run(args, None).is_err()),
+            command => panic!("Expected an optimisation, got {:?}", command),
         }
+        assert!(!outfile.with_extension("json").exists());
     }
 }
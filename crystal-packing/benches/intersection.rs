@@ -41,7 +41,7 @@ fn create_packed_state(points: usize) -> Result<PackedState<LineShape>, Error> {
         mirror_secondary: false,
     }];
 
-    Ok(PackedState::initialise(shape, wallpaper, isopointal))
+    PackedState::initialise(shape, wallpaper, isopointal)
 }
 
 fn state_check_intersection(c: &mut Criterion) {
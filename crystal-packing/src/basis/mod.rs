@@ -9,41 +9,84 @@ pub mod shared_value;
 
 pub use shared_value::SharedValue;
 
+/// A single degree of freedom which can be modified by an optimiser
+///
+/// Each of the variants is bounded to the range [min, max]. The variants describe what the value
+/// represents, which allows moves to treat the parameters of the unit cell or the orientation of
+/// a site differently from the remaining values.
+///
 #[non_exhaustive]
 pub enum Basis<'a> {
+    /// A parameter of the unit cell
     CellBasis {
         value: &'a SharedValue,
+        min: f64,
+        max: f64,
     },
+    /// A value without any special meaning, like the fractional position of a site
     StandardBasis {
         value: &'a SharedValue,
         min: f64,
         max: f64,
     },
+    /// The orientation of an occupied site
+    AngleBasis {
+        value: &'a SharedValue,
+        min: f64,
+        max: f64,
+    },
 }
 
 impl<'a> Basis<'a> {
     pub fn set_value(&self, new_value: f64) -> Result<(), Error> {
-        match self {
-            Basis::CellBasis { value, .. } => {
-                value.set_value(new_value);
-                Ok(())
-            }
-            Basis::StandardBasis { value, min, max } if min <= &new_value && &new_value <= max => {
-                value.set_value(new_value);
-                Ok(())
-            }
-            Basis::StandardBasis { .. } => Err(anyhow!("Out of Bounds")),
+        let (min, max) = self.bounds();
+        if min <= new_value && new_value <= max {
+            self.value().set_value(new_value);
+            Ok(())
+        } else {
+            Err(anyhow!("Out of Bounds"))
         }
     }
 
     pub fn get_value(&self) -> f64 {
+        self.value().get_value()
+    }
+
+    /// The minimum and maximum values the basis is allowed to take
+    pub fn bounds(&self) -> (f64, f64) {
         match self {
-            Basis::CellBasis { value, .. } => value.get_value(),
-            Basis::StandardBasis { value, .. } => value.get_value(),
+            Basis::CellBasis { min, max, .. } => (*min, *max),
+            Basis::StandardBasis { min, max, .. } => (*min, *max),
+            Basis::AngleBasis { min, max, .. } => (*min, *max),
         }
     }
 
+    /// Replace the bounds of the basis, keeping the value it refers to
+    pub fn with_bounds(self, min: f64, max: f64) -> Self {
+        match self {
+            Basis::CellBasis { value, .. } => Basis::CellBasis { value, min, max },
+            Basis::StandardBasis { value, .. } => Basis::StandardBasis { value, min, max },
+            Basis::AngleBasis { value, .. } => Basis::AngleBasis { value, min, max },
+        }
+    }
+
+    pub fn is_cell(&self) -> bool {
+        matches!(self, Basis::CellBasis { .. })
+    }
+
+    pub fn is_angle(&self) -> bool {
+        matches!(self, Basis::AngleBasis { .. })
+    }
+
     pub fn scale(&self) -> f64 {
         1.
     }
+
+    fn value(&self) -> &SharedValue {
+        match self {
+            Basis::CellBasis { value, .. } => value,
+            Basis::StandardBasis { value, .. } => value,
+            Basis::AngleBasis { value, .. } => value,
+        }
+    }
 }
@@ -7,7 +7,7 @@
 use std::f64::consts::PI;
 
 use itertools::iproduct;
-use nalgebra::{Point2, Translation2};
+use nalgebra::{Matrix2, Point2, Translation2};
 use serde::{Deserialize, Serialize};
 
 use crate::{Basis, SharedValue, Transform2};
@@ -100,6 +100,10 @@ impl Cell2 {
         self.angle.get_value()
     }
 
+    pub fn family(&self) -> CrystalFamily {
+        self.family
+    }
+
     /// Convert a transformation into Cartesian coordinates
     ///
     /// The positions of particles are stored in fractional coordinates, making changes to the
@@ -133,24 +137,38 @@ impl Cell2 {
     /// Each of the different crystal families impose different restrictions on the degrees of
     /// freedom of a unit cell. This compiles these degrees of freedom into a vector of Bases,
     /// which is the data structure used to modify the values.
+    ///
+    /// The lengths of the cell are bounded by their current values, so the cell is only able to
+    /// contract.
     pub fn get_degrees_of_freedom(&self) -> Vec<Basis> {
+        self.degrees_of_freedom(self.length.get_value(), self.ratio.get_value())
+    }
+
+    /// The degrees of freedom of the unit cell, allowing the cell to expand as well as contract
+    ///
+    /// The ratio of the lengths is bounded by 10, being the inverse of the lower bound.
+    pub fn get_expanding_degrees_of_freedom(&self) -> Vec<Basis> {
+        self.degrees_of_freedom(f64::INFINITY, 10.)
+    }
+
+    fn degrees_of_freedom(&self, max_length: f64, max_ratio: f64) -> Vec<Basis> {
         let mut basis: Vec<Basis> = vec![
             // All cells have at least a single variable cell length
-            Basis::StandardBasis {
+            Basis::CellBasis {
                 value: &self.length,
                 min: 0.01,
-                max: self.length.get_value(),
+                max: max_length,
             },
         ];
         match self.family {
             // Monoclinic has both variable angle and varaible ratio of sides
             CrystalFamily::Monoclinic => {
-                basis.push(Basis::StandardBasis {
+                basis.push(Basis::CellBasis {
                     value: &self.ratio,
                     min: 0.1,
-                    max: self.ratio.get_value(),
+                    max: max_ratio,
                 });
-                basis.push(Basis::StandardBasis {
+                basis.push(Basis::CellBasis {
                     value: &self.angle,
                     min: PI / 4.,
                     max: PI / 2.,
@@ -158,10 +176,10 @@ impl Cell2 {
             }
             // The Orthorhombic have a second variable cell length in the ratio
             CrystalFamily::Orthorhombic => {
-                basis.push(Basis::StandardBasis {
+                basis.push(Basis::CellBasis {
                     value: &self.ratio,
                     min: 0.1,
-                    max: self.ratio.get_value(),
+                    max: max_ratio,
                 });
             }
             _ => {}
@@ -182,6 +200,63 @@ impl Cell2 {
         Point2::new(x, y)
     }
 
+    /// The cell made from `n` copies of this cell along a and `m` copies along b
+    ///
+    /// The supercell belongs to the Monoclinic family, so none of the parameters are
+    /// constrained by symmetry.
+    ///
+    /// ```
+    /// use crystal_packing::{Cell2, CrystalFamily};
+    /// let cell = Cell2::from_family(CrystalFamily::Hexagonal, 2.);
+    /// let supercell = cell.supercell(3, 2);
+    /// assert_eq!(supercell.a(), 6.);
+    /// assert!((supercell.b() - 4.).abs() < 1e-12);
+    /// ```
+    ///
+    pub fn supercell(&self, n: usize, m: usize) -> Cell2 {
+        let length = self.a() * n as f64;
+        Cell2 {
+            length: SharedValue::new(length),
+            ratio: SharedValue::new(self.b() * m as f64 / length),
+            angle: SharedValue::new(self.angle()),
+            family: CrystalFamily::Monoclinic,
+        }
+    }
+
+    /// The matrix with the lattice vectors of the cell in Cartesian coordinates as the columns
+    pub fn lattice(&self) -> Matrix2<f64> {
+        let (ax, ay) = self.to_cartesian(1., 0.);
+        let (bx, by) = self.to_cartesian(0., 1.);
+        Matrix2::new(ax, bx, ay, by)
+    }
+
+    /// The cell after applying a homogeneous strain
+    ///
+    /// The strain is the symmetric infinitesimal strain tensor, with the deformation being
+    /// I + strain. Any rotation of the deformed cell is removed, so the strained cell belongs to
+    /// the Monoclinic family.
+    ///
+    /// ```
+    /// use crystal_packing::{Cell2, CrystalFamily};
+    /// use nalgebra::Matrix2;
+    /// let cell = Cell2::from_family(CrystalFamily::Tetragonal, 2.);
+    /// let strained = cell.strained(&Matrix2::new(0.1, 0., 0., 0.));
+    /// assert!((strained.a() - 2.2).abs() < 1e-12);
+    /// assert!((strained.b() - 2.).abs() < 1e-12);
+    /// ```
+    ///
+    pub fn strained(&self, strain: &Matrix2<f64>) -> Cell2 {
+        let lattice = (Matrix2::identity() + strain) * self.lattice();
+        let (a, b) = (lattice.column(0).norm(), lattice.column(1).norm());
+        let angle = f64::acos(lattice.column(0).dot(&lattice.column(1)) / (a * b));
+        Cell2 {
+            length: SharedValue::new(a),
+            ratio: SharedValue::new(b / a),
+            angle: SharedValue::new(angle),
+            family: CrystalFamily::Monoclinic,
+        }
+    }
+
     /// Calculates the area of the cell
     ///
     /// This uses the general formula for the area of a rhombus which is
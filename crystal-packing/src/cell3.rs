@@ -0,0 +1,340 @@
+//
+// cell3.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+
+use itertools::iproduct;
+use nalgebra::{Matrix3, Point3, Translation3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{Basis, SharedValue, Transform3};
+
+/// The crystal systems of three dimensional crystals
+///
+/// Each of the crystal systems imposes a different set of restrictions on the six parameters of
+/// the unit cell. The Monoclinic system uses the convention where b is the unique axis, so the
+/// only angle which can differ from a right angle is beta.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CrystalSystem {
+    Triclinic,
+    Monoclinic,
+    Orthorhombic,
+    Tetragonal,
+    Hexagonal,
+    Cubic,
+}
+
+/// Representing the unit cell of a three dimensional crystal
+///
+/// The cell is described by the lengths a, b, and c of the sides, along with the angles alpha,
+/// beta, and gamma between them. The length of a is stored directly, with b and c stored as
+/// ratios to a, so the whole cell is able to be scaled by a single value. The a axis is along
+/// x, with the b axis in the xy plane.
+///
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cell3 {
+    length: SharedValue,
+    ratio_b: SharedValue,
+    ratio_c: SharedValue,
+    alpha: SharedValue,
+    beta: SharedValue,
+    gamma: SharedValue,
+    system: CrystalSystem,
+}
+
+impl Clone for Cell3 {
+    fn clone(&self) -> Self {
+        Cell3 {
+            length: SharedValue::new(self.length.get_value()),
+            ratio_b: SharedValue::new(self.ratio_b.get_value()),
+            ratio_c: SharedValue::new(self.ratio_c.get_value()),
+            alpha: SharedValue::new(self.alpha.get_value()),
+            beta: SharedValue::new(self.beta.get_value()),
+            gamma: SharedValue::new(self.gamma.get_value()),
+            system: self.system,
+        }
+    }
+}
+
+impl std::fmt::Display for Cell3 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Cell3 {{ a: {}, b: {}, c: {}, alpha: {}, beta: {}, gamma: {} }}",
+            self.a(),
+            self.b(),
+            self.c(),
+            self.alpha(),
+            self.beta(),
+            self.gamma()
+        )
+    }
+}
+
+impl Cell3 {
+    pub fn a(&self) -> f64 {
+        self.length.get_value()
+    }
+
+    pub fn b(&self) -> f64 {
+        self.length.get_value() * self.ratio_b.get_value()
+    }
+
+    pub fn c(&self) -> f64 {
+        self.length.get_value() * self.ratio_c.get_value()
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha.get_value()
+    }
+
+    pub fn beta(&self) -> f64 {
+        self.beta.get_value()
+    }
+
+    pub fn gamma(&self) -> f64 {
+        self.gamma.get_value()
+    }
+
+    pub fn system(&self) -> CrystalSystem {
+        self.system
+    }
+
+    /// Initialise a cell from the crystal system it belongs to
+    ///
+    /// All the sides are initially the same length, with the angles being right angles, apart
+    /// from the Hexagonal system where gamma is 120 degrees.
+    ///
+    pub fn from_system(system: CrystalSystem, length: f64) -> Cell3 {
+        let gamma = match system {
+            CrystalSystem::Hexagonal => 2. * PI / 3.,
+            _ => PI / 2.,
+        };
+        Cell3 {
+            length: SharedValue::new(length),
+            ratio_b: SharedValue::new(1.),
+            ratio_c: SharedValue::new(1.),
+            alpha: SharedValue::new(PI / 2.),
+            beta: SharedValue::new(PI / 2.),
+            gamma: SharedValue::new(gamma),
+            system,
+        }
+    }
+
+    /// The matrix with the lattice vectors of the cell in Cartesian coordinates as the columns
+    ///
+    /// Where the angles don't describe a valid cell the values are NaN.
+    ///
+    pub fn lattice(&self) -> Matrix3<f64> {
+        let (a, b, c) = (self.a(), self.b(), self.c());
+        let (cos_alpha, cos_beta) = (self.alpha().cos(), self.beta().cos());
+        let (cos_gamma, sin_gamma) = (self.gamma().cos(), self.gamma().sin());
+        let cy = (cos_alpha - cos_beta * cos_gamma) / sin_gamma;
+        let cz = f64::sqrt(1. - cos_beta.powi(2) - cy.powi(2));
+        Matrix3::new(
+            a,
+            b * cos_gamma,
+            c * cos_beta,
+            0.,
+            b * sin_gamma,
+            c * cy,
+            0.,
+            0.,
+            c * cz,
+        )
+    }
+
+    /// The volume of the cell, which is NaN when the angles don't describe a valid cell
+    ///
+    /// ```
+    /// use crystal_packing::{Cell3, CrystalSystem};
+    /// let cell = Cell3::from_system(CrystalSystem::Cubic, 2.);
+    /// assert!((cell.volume() - 8.).abs() < 1e-12);
+    /// ```
+    ///
+    pub fn volume(&self) -> f64 {
+        self.lattice().determinant()
+    }
+
+    /// The distance between the planes of lattice points perpendicular to each of the axes
+    ///
+    /// This is the thickness of the cell in each direction, which determines how many periodic
+    /// images need to be considered.
+    ///
+    pub fn plane_spacing(&self) -> Vector3<f64> {
+        let lattice = self.lattice();
+        let volume = lattice.determinant();
+        let (a, b, c) = (lattice.column(0), lattice.column(1), lattice.column(2));
+        Vector3::new(
+            volume / b.cross(&c).norm(),
+            volume / c.cross(&a).norm(),
+            volume / a.cross(&b).norm(),
+        )
+    }
+
+    /// Convert a point in relative coordinates to real coordinates
+    pub fn to_cartesian_point(&self, point: Point3<f64>) -> Point3<f64> {
+        Point3::from(self.lattice() * point.coords)
+    }
+
+    /// Convert a transformation in fractional coordinates into Cartesian coordinates
+    pub fn to_cartesian_isometry(&self, transform: Transform3) -> Transform3 {
+        transform.set_position(self.to_cartesian_point(transform.position()))
+    }
+
+    /// The periodic images of a transform in Cartesian coordinates
+    ///
+    /// The number of shells is given separately for each of the axes.
+    ///
+    pub fn periodic_images(
+        &self,
+        transform: Transform3,
+        shells: [i64; 3],
+        zero: bool,
+    ) -> impl Iterator<Item = Transform3> + '_ {
+        let [x, y, z] = shells;
+        iproduct!(-x..=x, -y..=y, -z..=z)
+            .filter(move |&(x, y, z)| zero || !(x == 0 && y == 0 && z == 0))
+            .map(move |(x, y, z)| {
+                let position =
+                    Translation3::new(x as f64, y as f64, z as f64) * transform.position();
+                transform.set_position(self.to_cartesian_point(position))
+            })
+    }
+
+    /// The corners of the cell, centred on the origin, in Cartesian coordinates
+    pub fn get_corners(&self) -> Vec<Point3<f64>> {
+        iproduct!(&[-0.5, 0.5], &[-0.5, 0.5], &[-0.5, 0.5])
+            .map(|(&x, &y, &z)| self.to_cartesian_point(Point3::new(x, y, z)))
+            .collect()
+    }
+
+    /// The degrees of freedom of the cell, where the cell is only able to contract
+    pub fn get_degrees_of_freedom(&self) -> Vec<Basis> {
+        self.degrees_of_freedom(
+            self.length.get_value(),
+            self.ratio_b.get_value(),
+            self.ratio_c.get_value(),
+            PI / 2.,
+        )
+    }
+
+    /// The degrees of freedom of the cell, allowing the cell to expand as well as contract
+    pub fn get_expanding_degrees_of_freedom(&self) -> Vec<Basis> {
+        self.degrees_of_freedom(f64::INFINITY, 10., 10., 3. * PI / 4.)
+    }
+
+    fn degrees_of_freedom(
+        &self,
+        max_length: f64,
+        max_ratio_b: f64,
+        max_ratio_c: f64,
+        max_angle: f64,
+    ) -> Vec<Basis> {
+        let ratio = |value, max| Basis::CellBasis {
+            value,
+            min: 0.1,
+            max,
+        };
+        let angle = |value| Basis::CellBasis {
+            value,
+            min: PI / 4.,
+            max: max_angle,
+        };
+        // All cells have at least a single variable cell length
+        let mut basis: Vec<Basis> = vec![Basis::CellBasis {
+            value: &self.length,
+            min: 0.01,
+            max: max_length,
+        }];
+        match self.system {
+            CrystalSystem::Triclinic => {
+                basis.push(ratio(&self.ratio_b, max_ratio_b));
+                basis.push(ratio(&self.ratio_c, max_ratio_c));
+                basis.push(angle(&self.alpha));
+                basis.push(angle(&self.beta));
+                basis.push(angle(&self.gamma));
+            }
+            CrystalSystem::Monoclinic => {
+                basis.push(ratio(&self.ratio_b, max_ratio_b));
+                basis.push(ratio(&self.ratio_c, max_ratio_c));
+                basis.push(angle(&self.beta));
+            }
+            CrystalSystem::Orthorhombic => {
+                basis.push(ratio(&self.ratio_b, max_ratio_b));
+                basis.push(ratio(&self.ratio_c, max_ratio_c));
+            }
+            // Both of these have a = b, with c independent
+            CrystalSystem::Tetragonal | CrystalSystem::Hexagonal => {
+                basis.push(ratio(&self.ratio_c, max_ratio_c));
+            }
+            CrystalSystem::Cubic => {}
+        }
+        basis
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn degrees_of_freedom() {
+        let expected = [
+            (CrystalSystem::Triclinic, 6),
+            (CrystalSystem::Monoclinic, 4),
+            (CrystalSystem::Orthorhombic, 3),
+            (CrystalSystem::Tetragonal, 2),
+            (CrystalSystem::Hexagonal, 2),
+            (CrystalSystem::Cubic, 1),
+        ];
+        for (system, dof) in expected.iter() {
+            let cell = Cell3::from_system(*system, 1.);
+            assert_eq!(cell.get_degrees_of_freedom().len(), *dof);
+        }
+    }
+
+    #[test]
+    fn hexagonal_volume() {
+        let cell = Cell3::from_system(CrystalSystem::Hexagonal, 2.);
+        assert_abs_diff_eq!(cell.volume(), 8. * f64::sqrt(3.) / 2., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn lattice_lengths() {
+        let cell = Cell3::from_system(CrystalSystem::Triclinic, 1.);
+        let basis = cell.get_expanding_degrees_of_freedom();
+        basis[1].set_value(2.).unwrap();
+        basis[2].set_value(3.).unwrap();
+        basis[3].set_value(1.2).unwrap();
+        basis[4].set_value(1.7).unwrap();
+        basis[5].set_value(1.4).unwrap();
+        let lattice = cell.lattice();
+        assert_abs_diff_eq!(lattice.column(0).norm(), 1., epsilon = 1e-12);
+        assert_abs_diff_eq!(lattice.column(1).norm(), 2., epsilon = 1e-12);
+        assert_abs_diff_eq!(lattice.column(2).norm(), 3., epsilon = 1e-12);
+        let cos = |i: usize, j: usize| {
+            lattice.column(i).dot(&lattice.column(j))
+                / (lattice.column(i).norm() * lattice.column(j).norm())
+        };
+        assert_abs_diff_eq!(cos(1, 2), f64::cos(1.2), epsilon = 1e-12);
+        assert_abs_diff_eq!(cos(0, 2), f64::cos(1.7), epsilon = 1e-12);
+        assert_abs_diff_eq!(cos(0, 1), f64::cos(1.4), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn plane_spacing_cubic() {
+        let cell = Cell3::from_system(CrystalSystem::Cubic, 3.);
+        assert_abs_diff_eq!(
+            cell.plane_spacing(),
+            Vector3::new(3., 3., 3.),
+            epsilon = 1e-12
+        );
+    }
+}
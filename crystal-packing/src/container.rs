@@ -0,0 +1,271 @@
+//
+// container.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+
+use anyhow::{bail, Error};
+use nalgebra::Point2;
+use serde::{Deserialize, Serialize};
+
+use crate::{Basis, Line2, SharedValue, Transform2};
+
+/// The outline of a container, with a size of 1
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Outline {
+    /// A circle with a radius of 1
+    Circle,
+    /// A polygon with the vertices in order around the boundary
+    Polygon(Vec<Point2<f64>>),
+}
+
+/// The boundary of a finite cluster of shapes
+///
+/// The container is centred on the origin, with the size being a degree of freedom scaling the
+/// outline. Positions are stored in fractional coordinates, in the range [-0.5, 0.5], which
+/// span twice the size of the container, so the outline of a container fits within the range of
+/// the positions.
+///
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Container {
+    outline: Outline,
+    size: SharedValue,
+}
+
+impl Clone for Container {
+    fn clone(&self) -> Self {
+        Container {
+            outline: self.outline.clone(),
+            size: SharedValue::new(self.size.get_value()),
+        }
+    }
+}
+
+impl std::fmt::Display for Container {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.outline {
+            Outline::Circle => write!(f, "Container {{ circle, size: {} }}", self.size()),
+            Outline::Polygon(ref v) => write!(
+                f,
+                "Container {{ polygon of {} sides, size: {} }}",
+                v.len(),
+                self.size()
+            ),
+        }
+    }
+}
+
+impl Container {
+    /// A circular container where the size is the radius
+    pub fn circle(size: f64) -> Container {
+        Container {
+            outline: Outline::Circle,
+            size: SharedValue::new(size),
+        }
+    }
+
+    /// A square container where the size is half the length of a side
+    pub fn square(size: f64) -> Container {
+        let vertices = vec![(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)];
+        Container::polygon(&vertices, size).expect("A square is a valid container")
+    }
+
+    /// A polygonal container from the vertices in order around the boundary
+    ///
+    /// The polygon is centred on its centroid, which has to be within the polygon, and scaled so
+    /// the vertices lie within the square of half length `size`. The polygon doesn't need to be
+    /// convex.
+    ///
+    /// ```
+    /// use crystal_packing::Container;
+    /// let triangle = Container::polygon(&[(0., 0.), (2., 0.), (0., 2.)], 1.).unwrap();
+    /// assert!(triangle.area() > 0.);
+    /// ```
+    ///
+    pub fn polygon(vertices: &[(f64, f64)], size: f64) -> Result<Container, Error> {
+        if vertices.len() < 3 {
+            bail!("A polygon requires at least 3 vertices");
+        }
+        let points: Vec<Point2<f64>> = vertices.iter().map(|&(x, y)| Point2::new(x, y)).collect();
+        let area = signed_area(&points);
+        if area.abs() < 1e-12 {
+            bail!("The polygon has no area");
+        }
+        // The centroid of the polygon from the sum over the triangles of each edge
+        let centroid = edges(&points).fold(Point2::origin(), |acc, (a, b)| {
+            acc + (a.coords + b.coords) * (a.x * b.y - b.x * a.y) / (6. * area)
+        });
+        if !contains(&points, &centroid) {
+            bail!("The centroid of the polygon is outside of it");
+        }
+        let centred: Vec<Point2<f64>> = points.iter().map(|p| Point2::from(p - centroid)).collect();
+        let scale = centred
+            .iter()
+            .map(|p| f64::max(p.x.abs(), p.y.abs()))
+            .fold(0., f64::max);
+        Ok(Container {
+            outline: Outline::Polygon(centred.into_iter().map(|p| p / scale).collect()),
+            size: SharedValue::new(size),
+        })
+    }
+
+    /// A regular polygon with `sides` sides and a vertex along the x axis
+    pub fn regular(sides: usize, size: f64) -> Result<Container, Error> {
+        let vertices: Vec<(f64, f64)> = (0..sides)
+            .map(|i| {
+                let angle = 2. * PI * i as f64 / sides as f64;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+        Container::polygon(&vertices, size)
+    }
+
+    /// Replace the size of the container, keeping the outline
+    pub fn with_size(self, size: f64) -> Container {
+        Container {
+            outline: self.outline,
+            size: SharedValue::new(size),
+        }
+    }
+
+    pub fn outline(&self) -> &Outline {
+        &self.outline
+    }
+
+    pub fn size(&self) -> f64 {
+        self.size.get_value()
+    }
+
+    /// The area within the container
+    pub fn area(&self) -> f64 {
+        match self.outline {
+            Outline::Circle => PI * self.size().powi(2),
+            Outline::Polygon(ref v) => signed_area(v).abs() * self.size().powi(2),
+        }
+    }
+
+    /// The largest distance from the centre at which every point is within the container
+    ///
+    /// This is a fraction of the size of the container.
+    ///
+    pub fn inradius(&self) -> f64 {
+        match self.outline {
+            Outline::Circle => 1.,
+            Outline::Polygon(ref v) => edges(v)
+                .map(|(a, b)| Line2 { start: a, end: b }.distance_to(&Point2::origin()))
+                .fold(f64::INFINITY, f64::min),
+        }
+    }
+
+    /// The edges of a polygonal container in Cartesian coordinates
+    pub fn edges(&self) -> Vec<Line2> {
+        match self.outline {
+            Outline::Circle => vec![],
+            Outline::Polygon(ref v) => edges(v)
+                .map(|(a, b)| Line2 {
+                    start: a * self.size(),
+                    end: b * self.size(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether a point in Cartesian coordinates is within the container
+    pub fn contains(&self, point: &Point2<f64>) -> bool {
+        let point = point / self.size();
+        match self.outline {
+            Outline::Circle => point.coords.norm() <= 1.,
+            Outline::Polygon(ref v) => contains(v, &point),
+        }
+    }
+
+    /// Convert a transformation in relative coordinates into Cartesian coordinates
+    pub fn to_cartesian_isometry(&self, transform: Transform2) -> Transform2 {
+        transform.set_position(transform.position() * 2. * self.size())
+    }
+
+    /// The size of the container is bounded by the current value, so it is only able to contract
+    pub fn get_degrees_of_freedom(&self) -> Vec<Basis> {
+        self.degrees_of_freedom(self.size())
+    }
+
+    /// The size of the container is able to expand as well as contract
+    pub fn get_expanding_degrees_of_freedom(&self) -> Vec<Basis> {
+        self.degrees_of_freedom(f64::INFINITY)
+    }
+
+    fn degrees_of_freedom(&self, max_size: f64) -> Vec<Basis> {
+        vec![Basis::CellBasis {
+            value: &self.size,
+            min: 0.01,
+            max: max_size,
+        }]
+    }
+}
+
+/// Each pair of consecutive vertices of a polygon, including the edge closing the polygon
+fn edges(vertices: &[Point2<f64>]) -> impl Iterator<Item = (Point2<f64>, Point2<f64>)> + '_ {
+    vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(&a, &b)| (a, b))
+}
+
+/// The area of a polygon, which is positive when the vertices are anticlockwise
+fn signed_area(vertices: &[Point2<f64>]) -> f64 {
+    edges(vertices)
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum::<f64>()
+        / 2.
+}
+
+/// Whether a point is within a polygon, using the number of edges crossed by a ray along x
+fn contains(vertices: &[Point2<f64>], point: &Point2<f64>) -> bool {
+    edges(vertices)
+        .filter(|(a, b)| {
+            (a.y > point.y) != (b.y > point.y)
+                && point.x < a.x + (point.y - a.y) * (b.x - a.x) / (b.y - a.y)
+        })
+        .count()
+        % 2
+        == 1
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn square_area() {
+        let container = Container::square(2.);
+        assert_abs_diff_eq!(container.area(), 16.);
+        assert_abs_diff_eq!(container.inradius(), 1.);
+    }
+
+    #[test]
+    fn circle_area() {
+        let container = Container::circle(2.);
+        assert_abs_diff_eq!(container.area(), 4. * PI);
+    }
+
+    #[test]
+    fn polygon_centred() {
+        let container = Container::polygon(&[(1., 1.), (3., 1.), (3., 3.), (1., 3.)], 1.).unwrap();
+        assert!(container.contains(&Point2::new(0.9, -0.9)));
+        assert!(!container.contains(&Point2::new(1.1, 0.)));
+    }
+
+    #[test]
+    fn concave_polygon() {
+        // An L shaped container with the centroid within the polygon
+        let vertices = [(0., 0.), (4., 0.), (4., 1.), (1., 1.), (1., 4.), (0., 4.)];
+        assert!(Container::polygon(&vertices, 1.).is_err());
+        let vertices = [(0., 0.), (4., 0.), (4., 2.), (2., 2.), (2., 4.), (0., 4.)];
+        let container = Container::polygon(&vertices, 1.).unwrap();
+        assert!(!container.contains(&Point2::new(0.9, 0.9)));
+    }
+}
@@ -0,0 +1,354 @@
+//
+// elastic.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use anyhow::{anyhow, bail, Error};
+use nalgebra::{Matrix2, Matrix3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::optimisation::basin_hopping::ascend;
+use crate::traits::*;
+use crate::{IsobaricOptimiser, LinearPressure, Observer, PotentialState, Snapshot};
+
+/// The elastic constants of a two dimensional crystal in Voigt notation
+///
+/// The indices 1 and 2 are the normal strains along the x and y axes, with the x axis along
+/// the a vector of the unit cell, while 6 is the shear strain in the xy plane. Components
+/// coupling normal and shear strains are not included.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ElasticConstants {
+    pub c11: f64,
+    pub c22: f64,
+    pub c12: f64,
+    pub c66: f64,
+    /// The two dimensional bulk modulus, (C11 + C22 + 2 C12) / 4
+    pub bulk_modulus: f64,
+    /// The contraction along y for a stress along x, C12 / C22
+    pub poisson_ratio: f64,
+}
+
+impl ElasticConstants {
+    /// Calculate the bulk modulus and Poisson ratio from the elastic constants
+    ///
+    /// ```
+    /// use crystal_packing::ElasticConstants;
+    /// let constants = ElasticConstants::new(3., 3., 1., 1.);
+    /// assert_eq!(constants.bulk_modulus, 2.);
+    /// assert_eq!(constants.poisson_ratio, 1. / 3.);
+    /// ```
+    ///
+    pub fn new(c11: f64, c22: f64, c12: f64, c66: f64) -> Self {
+        Self {
+            c11,
+            c22,
+            c12,
+            c66,
+            bulk_modulus: (c11 + c22 + 2. * c12) / 4.,
+            poisson_ratio: c12 / c22,
+        }
+    }
+}
+
+/// Strain the cell then relax the shapes to minimise the lattice energy
+///
+/// The cell is held fixed while the shapes are relaxed using gradient descent, with the relaxation
+/// finishing after `steps` steps or once the step size is smaller than the `tolerance`.
+///
+pub(crate) fn relax_strained<S>(
+    state: &PotentialState<S>,
+    strain: &Matrix2<f64>,
+    steps: u64,
+    tolerance: f64,
+) -> PotentialState<S>
+where
+    S: Shape + Potential,
+{
+    let mut strained = state.clone();
+    strained.cell = state.cell.strained(strain);
+    let basis = strained.generate_basis();
+    let internal = &basis[strained.basis_blocks()[0]..];
+    ascend(
+        internal,
+        &|| Some(-strained.lattice_energy()),
+        steps,
+        0.01,
+        tolerance,
+    );
+    strained
+}
+
+/// The elastic constants of a structure with a potential from the energy of strained cells
+///
+/// Small homogeneous strains of the unit cell are applied along each of the directions
+/// required for the elastic constants. At each strain the positions and orientations of the
+/// shapes are relaxed to minimise the lattice energy, with the curvature of the energy with
+/// respect to the strain giving the elastic constants. Every shape is relaxed independently of
+/// the symmetry of the original structure, which is broken by the strain.
+///
+/// The elastic constants are in units of energy per area, as given by the potential.
+///
+/// ```
+/// # use std::convert::TryInto;
+/// # use crystal_packing::wallpaper::{WallpaperGroup, WallpaperGroups};
+/// use crystal_packing::{LJShape2, PotentialState, StaticStrain};
+/// # let group: WallpaperGroup = WallpaperGroups::p1.try_into().unwrap();
+/// let state = PotentialState::from_group(LJShape2::circle(), &group).unwrap();
+/// let constants = StaticStrain::new(0.005).elastic_constants(&state);
+/// assert!(constants.c11 > 0.);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StaticStrain {
+    amplitude: f64,
+    steps: u64,
+    tolerance: f64,
+}
+
+impl StaticStrain {
+    /// Use strains of up to twice the amplitude
+    pub fn new(amplitude: f64) -> Self {
+        Self {
+            amplitude,
+            steps: 10_000,
+            tolerance: 1e-10,
+        }
+    }
+
+    /// The number of gradient descent steps and the smallest step size when relaxing the shapes
+    pub fn with_relaxation(mut self, steps: u64, tolerance: f64) -> Self {
+        self.steps = steps;
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// The lattice energy after straining the cell and relaxing the shapes
+    fn relaxed_energy<S>(&self, state: &PotentialState<S>, strain: &Matrix2<f64>) -> f64
+    where
+        S: Shape + Potential,
+    {
+        relax_strained(state, strain, self.steps, self.tolerance).lattice_energy()
+    }
+
+    pub fn elastic_constants<S>(&self, state: &PotentialState<S>) -> ElasticConstants
+    where
+        S: Shape + Potential,
+    {
+        let state = state.supercell(1, 1);
+        let area = state.cell.area();
+        // Fitting a quadratic to the energies at strains of -2, -1, 0, 1 and 2 times the
+        // amplitude, the least squares estimate of the quadratic coefficient weights each
+        // energy by k^2 - 2.
+        let curvature = |direction: Matrix2<f64>| {
+            let quadratic = (-2..=2)
+                .map(|k: i32| {
+                    let strain = direction * (k as f64 * self.amplitude);
+                    f64::from(k * k - 2) * self.relaxed_energy(&state, &strain)
+                })
+                .sum::<f64>()
+                / (14. * self.amplitude.powi(2));
+            2. * quadratic / area
+        };
+        let c11 = curvature(Matrix2::new(1., 0., 0., 0.));
+        let c22 = curvature(Matrix2::new(0., 0., 0., 1.));
+        // A biaxial strain has the curvature C11 + C22 + 2 C12
+        let biaxial = curvature(Matrix2::identity());
+        // The engineering shear strain is twice the tensor component
+        let c66 = curvature(Matrix2::new(0., 0.5, 0.5, 0.));
+        ElasticConstants::new(c11, c22, (biaxial - c11 - c22) / 2., c66)
+    }
+}
+
+/// Record the lattice of the unit cell throughout an optimisation
+struct CellSamples(Vec<Matrix2<f64>>);
+
+impl<S: Supercell> Observer<S> for CellSamples {
+    fn observe(&mut self, _snapshot: &Snapshot, state: &S) -> Result<(), Error> {
+        self.0.push(state.cell().lattice());
+        Ok(())
+    }
+}
+
+/// The elastic constants of a packing of hard shapes from the fluctuations of the cell
+///
+/// Hard shapes have no potential energy, so rather than the curvature of the energy, the
+/// elastic constants come from the free energy. The structure is expanded into a supercell,
+/// which is simulated using isobaric Monte Carlo at a constant pressure, where the shape of the
+/// cell is able to fluctuate. The covariance of the strain of the cell is the compliance
+/// scaled by kT / A, with the inverse of the compliance giving the elastic constants. The
+/// first half of the simulation is treated as equilibration.
+///
+/// The elastic constants are in the same reduced units as the pressure, βCa, where a is the
+/// area of a single shape.
+///
+/// ```
+/// # use std::convert::TryInto;
+/// # use crystal_packing::wallpaper::{WallpaperGroup, WallpaperGroups};
+/// use crystal_packing::{LineShape, MCOptimiser, Optimiser, PackedState, StrainFluctuation};
+/// # let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+/// let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+/// let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+///
+/// let fluctuation = StrainFluctuation::new(50., 0.01, 2000, 10, 0).with_supercell(1, 1);
+/// let constants = fluctuation.elastic_constants(&state).unwrap();
+/// assert!(constants.bulk_modulus > 0.);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrainFluctuation {
+    pressure: f64,
+    max_step_size: f64,
+    steps: u64,
+    inner_steps: u64,
+    seed: u64,
+    supercell: (usize, usize),
+}
+
+impl StrainFluctuation {
+    /// Sample the cell after every `inner_steps` steps of the simulation at the reduced
+    /// pressure βPa
+    pub fn new(pressure: f64, max_step_size: f64, steps: u64, inner_steps: u64, seed: u64) -> Self {
+        Self {
+            pressure,
+            max_step_size,
+            steps,
+            inner_steps,
+            seed,
+            supercell: (2, 2),
+        }
+    }
+
+    /// The number of unit cells along each axis of the simulated supercell
+    pub fn with_supercell(mut self, n: usize, m: usize) -> Self {
+        self.supercell = (n, m);
+        self
+    }
+
+    pub fn elastic_constants<S: Supercell + Packed>(
+        &self,
+        state: &S,
+    ) -> Result<ElasticConstants, Error> {
+        let (n, m) = self.supercell;
+        let supercell = state.supercell(n, m);
+        let packing_fraction = supercell
+            .packing_fraction()
+            .ok_or_else(|| anyhow!("The structure has intersecting shapes"))?;
+        let shape_area =
+            packing_fraction * supercell.cell().area() / supercell.total_shapes() as f64;
+
+        let optimiser = IsobaricOptimiser::new(
+            Box::new(LinearPressure::new(self.pressure, self.pressure)),
+            self.max_step_size,
+            self.steps,
+            self.inner_steps,
+            self.seed,
+        );
+        let mut samples = CellSamples(vec![]);
+        optimiser.optimise_equation_of_state(supercell, &mut samples);
+        let samples = &samples.0[samples.0.len() / 2..];
+        if samples.len() < 3 {
+            bail!("Too few samples of the cell to find the elastic constants");
+        }
+        let count = samples.len() as f64;
+
+        let reference = samples.iter().sum::<Matrix2<f64>>() / count;
+        let inverse = reference
+            .try_inverse()
+            .ok_or_else(|| anyhow!("The unit cell has no area"))?;
+        let strains: Vec<Vector3<f64>> = samples
+            .iter()
+            .map(|lattice| {
+                let deformation = lattice * inverse;
+                let strain = (deformation.transpose() * deformation - Matrix2::identity()) / 2.;
+                Vector3::new(strain[(0, 0)], strain[(1, 1)], 2. * strain[(0, 1)])
+            })
+            .collect();
+        let mean = strains.iter().sum::<Vector3<f64>>() / count;
+        let covariance = strains
+            .iter()
+            .map(|s| (s - mean) * (s - mean).transpose())
+            .sum::<Matrix3<f64>>()
+            / count;
+
+        // Working in units where kT is 1, the compliance is the covariance scaled by the area
+        let compliance = covariance * reference.determinant().abs();
+        let stiffness = compliance
+            .try_inverse()
+            .ok_or_else(|| anyhow!("The cell didn't fluctuate, try a larger step size"))?
+            * shape_area;
+        Ok(ElasticConstants::new(
+            stiffness[(0, 0)],
+            stiffness[(1, 1)],
+            stiffness[(0, 1)],
+            stiffness[(2, 2)],
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::wallpaper::{Wallpaper, WallpaperGroup, WallpaperGroups, WyckoffSite};
+    use crate::{Cell2, CrystalFamily, LJShape2, LineShape, MCOptimiser, PackedState, Transform2};
+
+    #[test]
+    fn hexagonal_lattice_isotropic() {
+        let wallpaper = Wallpaper {
+            name: String::from("p1"),
+            family: CrystalFamily::Hexagonal,
+        };
+        let isopointal = vec![WyckoffSite {
+            letter: 'a',
+            symmetries: vec![Transform2::from_operations("x,y").unwrap()],
+            num_rotations: 1,
+            mirror_primary: false,
+            mirror_secondary: false,
+        }];
+        let mut state =
+            PotentialState::initialise(LJShape2::circle(), wallpaper, &isopointal).unwrap();
+        state.cell = Cell2::from_family(CrystalFamily::Hexagonal, 1.11);
+
+        let constants = StaticStrain::new(0.002).elastic_constants(&state);
+        assert!(constants.c11 > 0.);
+        assert_relative_eq!(constants.c11, constants.c22, max_relative = 1e-3);
+        assert_relative_eq!(
+            constants.c66,
+            (constants.c11 - constants.c12) / 2.,
+            max_relative = 1e-2
+        );
+    }
+
+    #[test]
+    fn relaxes_molecules() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state =
+            PotentialState::from_group(LJShape2::from_trimer(0.7, 120., 1.), &group).unwrap();
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+        let strain = StaticStrain::new(0.005).with_relaxation(100, 1e-6);
+        let initial = state.lattice_energy();
+        // Relaxing the shapes can only lower the energy
+        assert!(strain.relaxed_energy(&state.supercell(1, 1), &Matrix2::zeros()) <= initial);
+    }
+
+    #[test]
+    fn hard_shapes_stable() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+        let constants = StrainFluctuation::new(50., 0.01, 2000, 10, 0)
+            .with_supercell(1, 1)
+            .elastic_constants(&state)
+            .unwrap();
+        // The stiffness is positive definite
+        assert!(constants.c11 > 0.);
+        assert!(constants.c22 > 0.);
+        assert!(constants.c66 > 0.);
+        assert!(constants.bulk_modulus > 0.);
+    }
+}
@@ -0,0 +1,163 @@
+//
+// free_energy.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use log::debug;
+use nalgebra::Matrix2;
+
+use crate::elastic::relax_strained;
+use crate::traits::*;
+use crate::{Phonons, PotentialState};
+
+/// The quasi-harmonic free energy of a structure with a potential
+///
+/// The free energy at each of a series of isotropic expansions of the unit cell is the lattice
+/// energy, after relaxing the shapes, along with the classical harmonic vibrational free energy
+/// from the phonon frequencies. The quasi-harmonic free energy is the lowest of these, allowing
+/// the structure to expand with increasing temperature.
+///
+/// The free energy is per shape, so structures with a different number of shapes in the unit
+/// cell can be compared. At a temperature of zero this is the lattice energy per shape.
+///
+/// ```
+/// # use std::convert::TryInto;
+/// # use crystal_packing::wallpaper::{WallpaperGroup, WallpaperGroups};
+/// use crystal_packing::{FreeEnergy, LJShape2, MCOptimiser, Optimiser, PotentialState};
+/// # let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+/// let state = PotentialState::from_group(LJShape2::circle(), &group).unwrap();
+/// let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+///
+/// let free_energy = FreeEnergy::new(0.1).with_grid(4).free_energy(&state).unwrap();
+/// assert!(free_energy < 0.);
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreeEnergy {
+    temperature: f64,
+    grid: usize,
+    shells: i64,
+    expansion: Vec<f64>,
+}
+
+impl FreeEnergy {
+    pub fn new(temperature: f64) -> Self {
+        Self {
+            temperature,
+            grid: 8,
+            shells: 3,
+            expansion: vec![-0.01, 0., 0.01, 0.02, 0.03],
+        }
+    }
+
+    /// The number of wavevectors along each axis of the Brillouin zone
+    pub fn with_grid(mut self, grid: usize) -> Self {
+        self.grid = grid;
+        self
+    }
+
+    /// The number of periodic images in each direction included in the force constants
+    pub fn with_shells(mut self, shells: i64) -> Self {
+        self.shells = shells;
+        self
+    }
+
+    /// The isotropic strains of the unit cell at which the free energy is calculated
+    pub fn with_expansion(mut self, expansion: Vec<f64>) -> Self {
+        self.expansion = expansion;
+        self
+    }
+
+    /// The harmonic free energy per shape at a single expansion of the cell
+    fn harmonic<S>(&self, state: &PotentialState<S>, expansion: f64) -> Option<f64>
+    where
+        S: Shape + Potential + Inertia,
+    {
+        let state = relax_strained(state, &(Matrix2::identity() * expansion), 10_000, 1e-10);
+        let vibrational = if self.temperature > 0. {
+            Phonons::from_state(&state, self.shells)
+                .vibrational_free_energy(self.temperature, self.grid)?
+        } else {
+            0.
+        };
+        let free_energy = (state.lattice_energy() + vibrational) / state.total_shapes() as f64;
+        debug!("Expansion: {}, Free energy: {}", expansion, free_energy);
+        Some(free_energy)
+    }
+
+    /// The lowest free energy per shape over the expansions of the cell
+    ///
+    /// This is None when the structure has imaginary modes at every expansion.
+    ///
+    pub fn free_energy<S>(&self, state: &PotentialState<S>) -> Option<f64>
+    where
+        S: Shape + Potential + Inertia,
+    {
+        // Every shape is able to relax independently
+        let state = state.supercell(1, 1);
+        self.expansion
+            .iter()
+            .filter_map(|&expansion| self.harmonic(&state, expansion))
+            .fold(None, |min, f| Some(min.map_or(f, |m: f64| m.min(f))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::wallpaper::{Wallpaper, WyckoffSite};
+    use crate::{Cell2, CrystalFamily, LJShape2, Transform2};
+
+    fn lattice(family: CrystalFamily, length: f64) -> PotentialState<LJShape2> {
+        let wallpaper = Wallpaper {
+            name: String::from("p1"),
+            family,
+        };
+        let isopointal = vec![WyckoffSite {
+            letter: 'a',
+            symmetries: vec![Transform2::from_operations("x,y").unwrap()],
+            num_rotations: 1,
+            mirror_primary: false,
+            mirror_secondary: false,
+        }];
+        let mut state =
+            PotentialState::initialise(LJShape2::circle(), wallpaper, &isopointal).unwrap();
+        state.cell = Cell2::from_family(family, length);
+        state
+    }
+
+    #[test]
+    fn zero_temperature_lattice_energy() {
+        let state = lattice(CrystalFamily::Hexagonal, 1.11);
+        let free_energy = FreeEnergy::new(0.)
+            .with_expansion(vec![0.])
+            .free_energy(&state)
+            .unwrap();
+        assert_abs_diff_eq!(free_energy, state.lattice_energy(), epsilon = 1e-10);
+    }
+
+    #[test]
+    fn lowest_expansion() {
+        let state = lattice(CrystalFamily::Hexagonal, 1.11);
+        let free_energy = FreeEnergy::new(0.2).with_grid(4);
+        let expanded = free_energy.free_energy(&state).unwrap();
+        let fixed = free_energy
+            .with_expansion(vec![0.])
+            .free_energy(&state)
+            .unwrap();
+        assert!(expanded <= fixed);
+    }
+
+    #[test]
+    fn unstable_has_no_free_energy() {
+        let state = lattice(CrystalFamily::Tetragonal, 1.11);
+        let free_energy = FreeEnergy::new(0.1)
+            .with_grid(4)
+            .with_expansion(vec![0.])
+            .free_energy(&state);
+        assert_eq!(free_energy, None);
+    }
+}
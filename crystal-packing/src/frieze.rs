@@ -0,0 +1,183 @@
+//
+// frieze.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use crate::wallpaper::WyckoffSite;
+use crate::Transform2;
+
+/// The symmetry operations of one of the frieze groups
+///
+/// The frieze groups describe the symmetry of a pattern which is periodic in a single
+/// direction. The periodic direction is along x, with y being across the strip, so the
+/// operation `x,-y` is the mirror through the centre line of the strip.
+///
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FriezeGroup<'a> {
+    pub name: &'a str,
+    pub wyckoff_str: Vec<&'a str>,
+}
+
+/// Defining one of the frieze groups.
+///
+/// This is the highest level description of the symmetry operations of a strip.
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Frieze {
+    pub name: String,
+}
+
+impl Frieze {
+    pub fn new(group: &FriezeGroup) -> Frieze {
+        Frieze {
+            name: String::from(group.name),
+        }
+    }
+}
+
+impl<'a> From<FriezeGroup<'a>> for Frieze {
+    fn from(group: FriezeGroup) -> Frieze {
+        Frieze {
+            name: String::from(group.name),
+        }
+    }
+}
+
+impl WyckoffSite {
+    /// The general position of a frieze group
+    pub fn from_frieze(group: &FriezeGroup) -> Result<WyckoffSite, Error> {
+        let symmetries = group
+            .wyckoff_str
+            .iter()
+            .map(|&a| Transform2::from_operations(a))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(WyckoffSite {
+            letter: 'a',
+            symmetries,
+            num_rotations: 1,
+            mirror_primary: false,
+            mirror_secondary: false,
+        })
+    }
+}
+
+/// The seven frieze groups
+///
+/// The names are the short form of the crystallographic notation, where the first position
+/// is the rotation, the second the mirror perpendicular to the strip, and the final the mirror
+/// or glide along the strip.
+///
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FriezeGroups {
+    p1,
+    p11g,
+    p1m1,
+    p2,
+    p2mg,
+    p11m,
+    p2mm,
+}
+
+impl std::str::FromStr for FriezeGroups {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "p1" => Ok(FriezeGroups::p1),
+            "p11g" => Ok(FriezeGroups::p11g),
+            "p1m1" => Ok(FriezeGroups::p1m1),
+            "p2" => Ok(FriezeGroups::p2),
+            "p211" => Ok(FriezeGroups::p2),
+            "p2mg" => Ok(FriezeGroups::p2mg),
+            "p11m" => Ok(FriezeGroups::p11m),
+            "p2mm" => Ok(FriezeGroups::p2mm),
+            _ => Err(anyhow!("Invalid Value")),
+        }
+    }
+}
+
+impl std::fmt::Display for FriezeGroups {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FriezeGroups::p1 => write!(f, "p1"),
+            FriezeGroups::p11g => write!(f, "p11g"),
+            FriezeGroups::p1m1 => write!(f, "p1m1"),
+            FriezeGroups::p2 => write!(f, "p2"),
+            FriezeGroups::p2mg => write!(f, "p2mg"),
+            FriezeGroups::p11m => write!(f, "p11m"),
+            FriezeGroups::p2mm => write!(f, "p2mm"),
+        }
+    }
+}
+
+impl FriezeGroups {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["p1", "p11g", "p1m1", "p2", "p2mg", "p11m", "p2mm"]
+    }
+}
+
+impl<'a> TryFrom<FriezeGroups> for FriezeGroup<'a> {
+    type Error = Error;
+
+    fn try_from(name: FriezeGroups) -> Result<Self, Self::Error> {
+        match name {
+            FriezeGroups::p1 => Ok(FriezeGroup {
+                name: "p1",
+                wyckoff_str: vec!["x,y"],
+            }),
+            FriezeGroups::p11g => Ok(FriezeGroup {
+                name: "p11g",
+                wyckoff_str: vec!["x,y", "x+1/2,-y"],
+            }),
+            FriezeGroups::p1m1 => Ok(FriezeGroup {
+                name: "p1m1",
+                wyckoff_str: vec!["x,y", "-x,y"],
+            }),
+            FriezeGroups::p2 => Ok(FriezeGroup {
+                name: "p2",
+                wyckoff_str: vec!["x,y", "-x,-y"],
+            }),
+            FriezeGroups::p2mg => Ok(FriezeGroup {
+                name: "p2mg",
+                wyckoff_str: vec!["x,y", "-x,-y", "-x+1/2,y", "x+1/2,-y"],
+            }),
+            FriezeGroups::p11m => Ok(FriezeGroup {
+                name: "p11m",
+                wyckoff_str: vec!["x,y", "x,-y"],
+            }),
+            FriezeGroups::p2mm => Ok(FriezeGroup {
+                name: "p2mm",
+                wyckoff_str: vec!["x,y", "-x,-y", "-x,y", "x,-y"],
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use super::*;
+
+    #[test]
+    fn all_groups_valid() {
+        for name in FriezeGroups::variants() {
+            let group: FriezeGroups = name.parse().unwrap();
+            assert_eq!(group.to_string(), name);
+            let group: FriezeGroup = group.try_into().unwrap();
+            assert!(WyckoffSite::from_frieze(&group).is_ok());
+        }
+    }
+
+    #[test]
+    fn multiplicity() {
+        let group: FriezeGroup = FriezeGroups::p2mg.try_into().unwrap();
+        let wyckoff = WyckoffSite::from_frieze(&group).unwrap();
+        assert_eq!(wyckoff.multiplicity(), 4);
+    }
+}
@@ -0,0 +1,483 @@
+//
+// import.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! Reading the definition of a shape from a file
+//!
+//! A shape is a polygon described by its vertices, a molecule made of discs, or both. These
+//! can be read from a [`ShapeDefinition`] serialised with serde, from a WKT polygon, or from
+//! the `<path>` and `<circle>` elements of an SVG document.
+//!
+
+use anyhow::{anyhow, bail, Error};
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+use svg::node::element::path::{Command, Data, Position};
+use svg::node::element::tag::Type;
+use svg::parser::Event;
+
+use crate::traits::{Intersect, Shape};
+use crate::{Atom2, LineShape, MixedShape, MolecularShape2, Primitive, Transform2};
+
+/// The maximum number of times a curve is divided in half when flattening
+const MAX_DEPTH: usize = 16;
+
+fn default_name() -> String {
+    String::from("Imported")
+}
+
+/// The description of a shape, with the vertices of a polygon and a collection of discs
+///
+/// In JSON, a triangle is described as
+///
+/// ```json
+/// { "name": "Triangle", "vertices": [[0, 0], [1, 0], [0, 1]] }
+/// ```
+///
+/// while a molecule of discs has the x, y position and radius of each disc
+///
+/// ```json
+/// { "name": "Dimer", "discs": [[0, 0, 1], [1.5, 0, 0.5]] }
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShapeDefinition {
+    #[serde(default = "default_name")]
+    pub name: String,
+    #[serde(default)]
+    pub vertices: Vec<[f64; 2]>,
+    #[serde(default)]
+    pub discs: Vec<[f64; 3]>,
+}
+
+/// A shape created from a [`ShapeDefinition`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportedShape {
+    Polygon(LineShape),
+    Molecular(MolecularShape2),
+    Mixed(MixedShape),
+}
+
+impl ImportedShape {
+    pub fn area(&self) -> f64 {
+        match self {
+            ImportedShape::Polygon(shape) => shape.area(),
+            ImportedShape::Molecular(shape) => shape.area(),
+            ImportedShape::Mixed(shape) => shape.area(),
+        }
+    }
+}
+
+impl ShapeDefinition {
+    /// The polygon described by the vertices, where a repeated first vertex is removed
+    fn polygon(&self) -> Result<LineShape, Error> {
+        let mut vertices: Vec<Point2<f64>> = self
+            .vertices
+            .iter()
+            .map(|&[x, y]| Point2::new(x, y))
+            .collect();
+        // Closed rings repeat the first vertex at the end
+        if vertices.len() > 1 && vertices.first() == vertices.last() {
+            vertices.pop();
+        }
+        LineShape::from_vertices(&self.name, &vertices)
+    }
+
+    fn atoms(&self) -> Result<Vec<Atom2>, Error> {
+        self.discs
+            .iter()
+            .map(|&[x, y, radius]| {
+                if !(x.is_finite() && y.is_finite() && radius.is_finite()) || radius <= 0. {
+                    bail!(
+                        "Discs need a finite position and a positive radius, got ({}, {}, {})",
+                        x,
+                        y,
+                        radius
+                    );
+                }
+                Ok(Atom2::new(x, y, radius))
+            })
+            .collect()
+    }
+
+    /// Create the shape, which is validated and centred on the centroid
+    ///
+    /// Having both vertices and discs gives a [`MixedShape`] of the polygon and the discs. The
+    /// centroid is the average of the centroids of the polygon and each disc weighted by their
+    /// area, ignoring any overlap.
+    ///
+    pub fn build(&self) -> Result<ImportedShape, Error> {
+        let polygon = match self.vertices.is_empty() {
+            true => None,
+            false => Some(self.polygon()?),
+        };
+        let atoms = self.atoms()?;
+        let weighted = polygon
+            .iter()
+            .map(|p| (p.centroid(), p.area()))
+            .chain(atoms.iter().map(|a| (a.position, a.area())));
+        let (moment, area) = weighted.fold((Vector2::zeros(), 0.), |(moment, area), (c, a)| {
+            (moment + c.coords * a, area + a)
+        });
+        let centre = Transform2::new(0., (-moment.x / area, -moment.y / area));
+        let polygon = polygon.map(|p| p.transform(&centre));
+        let atoms: Vec<Atom2> = atoms.iter().map(|a| a * centre).collect();
+
+        match (polygon, atoms.is_empty()) {
+            (Some(polygon), true) => Ok(ImportedShape::Polygon(polygon)),
+            (None, false) => Ok(ImportedShape::Molecular(MolecularShape2 {
+                name: self.name.clone(),
+                items: atoms,
+            })),
+            (Some(polygon), false) => {
+                let items = std::iter::once(Primitive::Polygon(polygon))
+                    .chain(atoms.into_iter().map(Primitive::Disc))
+                    .collect();
+                Ok(ImportedShape::Mixed(MixedShape::new(&self.name, items)?))
+            }
+            (None, true) => bail!("The shape needs either vertices or discs"),
+        }
+    }
+
+    /// Read the exterior ring of a polygon in the Well-Known Text format
+    ///
+    /// ```
+    /// use crystal_packing::ShapeDefinition;
+    /// let shape = ShapeDefinition::from_wkt("POLYGON ((0 0, 2 0, 0 2, 0 0))").unwrap();
+    /// assert_eq!(shape.vertices.len(), 4);
+    /// ```
+    ///
+    pub fn from_wkt(input: &str) -> Result<ShapeDefinition, Error> {
+        let input = input.trim();
+        let body = input
+            .get(..7)
+            .filter(|keyword| keyword.eq_ignore_ascii_case("POLYGON"))
+            .map(|_| input[7..].trim())
+            .ok_or_else(|| anyhow!("Only WKT polygons are supported"))?;
+        let rings = body
+            .strip_prefix('(')
+            .and_then(|b| b.strip_suffix(')'))
+            .ok_or_else(|| anyhow!("Invalid WKT polygon {}", input))?
+            .trim();
+        let exterior = rings
+            .strip_prefix('(')
+            .and_then(|r| r.strip_suffix(')'))
+            .ok_or_else(|| anyhow!("Invalid WKT polygon {}", input))?;
+        if exterior.contains('(') || exterior.contains(')') {
+            bail!("Polygons with holes are not supported");
+        }
+        let vertices = exterior
+            .split(',')
+            .map(|point| {
+                let values = point
+                    .split_whitespace()
+                    .map(str::parse)
+                    .collect::<Result<Vec<f64>, _>>()?;
+                match values.as_slice() {
+                    &[x, y] => Ok([x, y]),
+                    _ => bail!(
+                        "Expected a point with two coordinates, got {}",
+                        point.trim()
+                    ),
+                }
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(ShapeDefinition {
+            name: default_name(),
+            vertices,
+            discs: vec![],
+        })
+    }
+
+    /// Read the shape from the `<path>` or `<circle>` elements of an SVG document
+    ///
+    /// A single closed path gives a polygon, with any curves flattened so every point on the
+    /// curve is within `tolerance` of the polygon. Otherwise each of the circles is a disc.
+    /// Elliptical arcs and transforms are not supported.
+    ///
+    /// ```
+    /// use crystal_packing::ShapeDefinition;
+    /// let svg = r#"<svg><path d="M 0 0 L 2 0 Q 1 2 0 0 Z"/></svg>"#;
+    /// let shape = ShapeDefinition::from_svg(svg, 0.01).unwrap();
+    /// assert!(shape.vertices.len() > 3);
+    /// ```
+    ///
+    pub fn from_svg(input: &str, tolerance: f64) -> Result<ShapeDefinition, Error> {
+        if tolerance.is_nan() || tolerance <= 0. {
+            bail!("The tolerance needs to be positive, got {}", tolerance);
+        }
+        let mut paths = vec![];
+        let mut discs = vec![];
+        for event in svg::read(input)? {
+            match event {
+                Event::Tag("path", Type::Empty, attributes)
+                | Event::Tag("path", Type::Start, attributes) => {
+                    let data = attributes
+                        .get("d")
+                        .ok_or_else(|| anyhow!("A path is missing the d attribute"))?;
+                    paths.push(flatten_path(&Data::parse(data)?, tolerance)?);
+                }
+                Event::Tag("circle", Type::Empty, attributes)
+                | Event::Tag("circle", Type::Start, attributes) => {
+                    // The centre of a circle defaults to the origin, while the radius is required
+                    let coordinate = |name: &str| -> Result<f64, Error> {
+                        Ok(attributes.get(name).map_or(Ok(0.), |value| value.parse())?)
+                    };
+                    let radius: f64 = attributes
+                        .get("r")
+                        .ok_or_else(|| anyhow!("A circle is missing the radius"))?
+                        .parse()?;
+                    discs.push([coordinate("cx")?, coordinate("cy")?, radius]);
+                }
+                Event::Error(error) => bail!("Unable to parse SVG: {}", error),
+                _ => {}
+            }
+        }
+        match (paths.len(), discs.is_empty()) {
+            (0, false) => Ok(ShapeDefinition {
+                name: default_name(),
+                vertices: vec![],
+                discs,
+            }),
+            (1, true) => Ok(ShapeDefinition {
+                name: default_name(),
+                vertices: paths.remove(0),
+                discs: vec![],
+            }),
+            (0, true) => bail!("No path or circle elements were found"),
+            _ => bail!("Only a single path, or a collection of circles is supported"),
+        }
+    }
+}
+
+/// The vertices of a closed SVG path, with the curves replaced by line segments
+fn flatten_path(data: &Data, tolerance: f64) -> Result<Vec<[f64; 2]>, Error> {
+    let mut points: Vec<Point2<f64>> = vec![];
+    let mut current = Point2::origin();
+    // The control point of the previous curve, which is reflected by the smooth curves of the
+    // same kind, along with whether it was a cubic curve
+    let mut control: Option<(bool, Point2<f64>)> = None;
+    for command in data.iter() {
+        let (position, parameters, step) = match command {
+            Command::Move(p, params) | Command::Line(p, params) => (p, params, 2),
+            Command::HorizontalLine(p, params) | Command::VerticalLine(p, params) => (p, params, 1),
+            Command::QuadraticCurve(p, params) | Command::SmoothCubicCurve(p, params) => {
+                (p, params, 4)
+            }
+            Command::SmoothQuadraticCurve(p, params) => (p, params, 2),
+            Command::CubicCurve(p, params) => (p, params, 6),
+            Command::EllipticalArc(..) => bail!("Elliptical arcs are not supported"),
+            Command::Close => break,
+        };
+        if parameters.is_empty() || parameters.len() % step != 0 {
+            bail!("Invalid number of parameters in path");
+        }
+        for (index, values) in parameters.chunks(step).enumerate() {
+            let origin = match position {
+                Position::Absolute => Vector2::zeros(),
+                Position::Relative => current.coords,
+            };
+            let point =
+                |i: usize| Point2::new(f64::from(values[i]), f64::from(values[i + 1])) + origin;
+            let reflected = |cubic: bool| match control {
+                Some((kind, c)) if kind == cubic => current + (current - c),
+                _ => current,
+            };
+            let (next, new_control) = match command {
+                // Subsequent pairs of a move are lines
+                Command::Move(..) if index == 0 => {
+                    if !points.is_empty() {
+                        bail!("Only a single closed path is supported");
+                    }
+                    (point(0), None)
+                }
+                Command::Move(..) | Command::Line(..) => (point(0), None),
+                Command::HorizontalLine(..) => {
+                    let x = f64::from(values[0]) + origin.x;
+                    (Point2::new(x, current.y), None)
+                }
+                Command::VerticalLine(..) => {
+                    let y = f64::from(values[0]) + origin.y;
+                    (Point2::new(current.x, y), None)
+                }
+                Command::QuadraticCurve(..) | Command::SmoothQuadraticCurve(..) => {
+                    let (c, end) = match command {
+                        Command::QuadraticCurve(..) => (point(0), point(2)),
+                        _ => (reflected(false), point(0)),
+                    };
+                    // A quadratic curve is a cubic curve with the control points raised
+                    let c1 = current + (c - current) * 2. / 3.;
+                    let c2 = end + (c - end) * 2. / 3.;
+                    flatten_cubic([current, c1, c2, end], tolerance, 0, &mut points);
+                    (end, Some((false, c)))
+                }
+                Command::CubicCurve(..) | Command::SmoothCubicCurve(..) => {
+                    let (c1, c2, end) = match command {
+                        Command::CubicCurve(..) => (point(0), point(2), point(4)),
+                        _ => (reflected(true), point(0), point(2)),
+                    };
+                    flatten_cubic([current, c1, c2, end], tolerance, 0, &mut points);
+                    (end, Some((true, c2)))
+                }
+                _ => unreachable!(),
+            };
+            points.push(next);
+            current = next;
+            control = new_control;
+        }
+    }
+    Ok(points.iter().map(|p| [p.x, p.y]).collect())
+}
+
+/// Add the points along a cubic Bézier curve, excluding the first and last
+///
+/// The curve is within the convex hull of the control points, so once the control points are
+/// within `tolerance` of the line between the ends, so is the curve.
+///
+fn flatten_cubic(
+    curve: [Point2<f64>; 4],
+    tolerance: f64,
+    depth: usize,
+    points: &mut Vec<Point2<f64>>,
+) {
+    let chord = crate::Line2 {
+        start: curve[0],
+        end: curve[3],
+    };
+    if depth >= MAX_DEPTH
+        || (chord.distance_to(&curve[1]) <= tolerance && chord.distance_to(&curve[2]) <= tolerance)
+    {
+        return;
+    }
+    // Split the curve in half using de Casteljau's algorithm
+    let mid = |a: Point2<f64>, b: Point2<f64>| a + (b - a) / 2.;
+    let (p01, p12, p23) = (
+        mid(curve[0], curve[1]),
+        mid(curve[1], curve[2]),
+        mid(curve[2], curve[3]),
+    );
+    let (p012, p123) = (mid(p01, p12), mid(p12, p23));
+    let middle = mid(p012, p123);
+    flatten_cubic([curve[0], p01, p012, middle], tolerance, depth + 1, points);
+    points.push(middle);
+    flatten_cubic([middle, p123, p23, curve[3]], tolerance, depth + 1, points);
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn polygon_centred() {
+        let definition = ShapeDefinition {
+            name: String::from("Square"),
+            vertices: vec![[1., 1.], [3., 1.], [3., 3.], [1., 3.], [1., 1.]],
+            discs: vec![],
+        };
+        match definition.build().unwrap() {
+            ImportedShape::Polygon(shape) => {
+                assert_eq!(shape.items.len(), 4);
+                assert_abs_diff_eq!(shape.area(), 4.);
+                assert_abs_diff_eq!(shape.enclosing_radius(), f64::sqrt(2.));
+            }
+            _ => panic!("Expected a polygon"),
+        }
+    }
+
+    #[test]
+    fn discs_centred() {
+        let definition = ShapeDefinition {
+            name: String::from("Dimer"),
+            vertices: vec![],
+            discs: vec![[0., 0., 1.], [3., 0., 1.]],
+        };
+        match definition.build().unwrap() {
+            ImportedShape::Molecular(shape) => {
+                assert_abs_diff_eq!(shape.items[0].position.x, -1.5);
+                assert_abs_diff_eq!(shape.items[1].position.x, 1.5);
+            }
+            _ => panic!("Expected a molecular shape"),
+        }
+    }
+
+    #[test]
+    fn polygon_and_discs() {
+        let definition = ShapeDefinition {
+            name: String::from("Bumps"),
+            vertices: vec![[-1., -1.], [1., -1.], [1., 1.], [-1., 1.]],
+            discs: vec![[1., 1., 0.5], [-1., -1., 0.5]],
+        };
+        match definition.build().unwrap() {
+            ImportedShape::Mixed(shape) => {
+                assert_eq!(shape.items.len(), 3);
+                let expected = 4. + 2. * 0.75 * std::f64::consts::PI * 0.25;
+                assert_abs_diff_eq!(shape.area(), expected, epsilon = 1e-4);
+            }
+            _ => panic!("Expected a mixed shape"),
+        }
+    }
+
+    #[test]
+    fn invalid_definitions() {
+        let empty = ShapeDefinition {
+            name: default_name(),
+            vertices: vec![],
+            discs: vec![],
+        };
+        assert!(empty.build().is_err());
+        let negative = ShapeDefinition {
+            discs: vec![[0., 0., -1.]],
+            ..empty.clone()
+        };
+        assert!(negative.build().is_err());
+        let crossed = ShapeDefinition {
+            vertices: vec![[0., 0.], [1., 1.], [1., 0.], [0., 1.]],
+            ..empty
+        };
+        assert!(crossed.build().is_err());
+    }
+
+    #[test]
+    fn wkt() {
+        let shape = ShapeDefinition::from_wkt("polygon((0 0, 4 0, 4 2, 0 2, 0 0))").unwrap();
+        assert_abs_diff_eq!(shape.build().unwrap().area(), 8.);
+        assert!(ShapeDefinition::from_wkt("POINT (0 0)").is_err());
+        assert!(ShapeDefinition::from_wkt("POLYGON ((0 0, 1 0, 0 1), (0 0, 1 0, 0 1))").is_err());
+        assert!(ShapeDefinition::from_wkt("POLYGON ((0 0 1, 1 0, 0 1))").is_err());
+    }
+
+    #[test]
+    fn svg_relative_path() {
+        let svg = r#"<svg><path d="m 1 1 h 2 v 2 l -2 0 z"/></svg>"#;
+        let shape = ShapeDefinition::from_svg(svg, 0.01).unwrap();
+        assert_eq!(shape.vertices, vec![[1., 1.], [3., 1.], [3., 3.], [1., 3.]]);
+    }
+
+    #[test]
+    fn svg_curve_flattened() {
+        // A circle of radius 1 approximated by four cubic curves
+        let k = 0.552_284_75;
+        let svg = format!(
+            r#"<svg><path d="M 1 0 C 1 {k} {k} 1 0 1 S -1 {k} -1 0 S -{k} -1 0 -1 S 1 -{k} 1 0 Z"/></svg>"#,
+            k = k
+        );
+        let shape = ShapeDefinition::from_svg(&svg, 1e-4).unwrap();
+        let area = shape.build().unwrap().area();
+        assert_abs_diff_eq!(area, std::f64::consts::PI, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn svg_circles() {
+        let svg = r#"<svg><circle cx="0" cy="0" r="1"/><circle cx="2" cy="0" r="1"/></svg>"#;
+        let shape = ShapeDefinition::from_svg(svg, 0.01).unwrap();
+        assert_eq!(shape.discs, vec![[0., 0., 1.], [2., 0., 1.]]);
+        let mixed = r#"<svg><circle r="1"/><path d="M 0 0 L 1 0 L 0 1 Z"/></svg>"#;
+        assert!(ShapeDefinition::from_svg(mixed, 0.01).is_err());
+        let arc = r#"<svg><path d="M 0 0 A 1 1 0 0 0 1 1 Z"/></svg>"#;
+        assert!(ShapeDefinition::from_svg(arc, 0.01).is_err());
+    }
+}
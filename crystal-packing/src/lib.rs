@@ -10,22 +10,50 @@
 
 pub mod basis;
 pub mod cell;
+pub mod cell3;
+pub mod container;
+pub mod elastic;
+pub mod free_energy;
+pub mod frieze;
+pub mod import;
 pub mod ops_macros;
 pub mod optimisation;
+pub mod phonon;
 pub mod shape;
 pub mod site;
+pub mod space_group;
+pub mod stability;
 pub mod state;
+pub mod strip;
+pub mod symmetry;
+pub mod to_obj;
 pub mod to_svg;
+pub mod to_xyz;
 pub mod traits;
 pub mod transform;
+pub mod transform3;
 pub mod wallpaper;
 
 pub use crate::basis::*;
 pub use crate::cell::*;
+pub use crate::cell3::*;
+pub use crate::container::*;
+pub use crate::elastic::*;
+pub use crate::free_energy::*;
+pub use crate::frieze::FriezeGroup;
+pub use crate::import::{ImportedShape, ShapeDefinition};
 pub use crate::optimisation::*;
+pub use crate::phonon::*;
 pub use crate::shape::*;
 pub use crate::site::*;
+pub use crate::space_group::SpaceGroup;
+pub use crate::stability::*;
 pub use crate::state::*;
-pub use crate::traits::{FromSymmetry, Intersect, Shape};
+pub use crate::strip::*;
+pub use crate::symmetry::*;
+pub use crate::traits::{
+    FromSymmetry, Intersect, Intersect3, Optimiser, Packed, Scale, Shape, Shape3, Supercell,
+};
 pub use crate::transform::Transform2;
+pub use crate::transform3::Transform3;
 pub use crate::wallpaper::WallpaperGroup;
@@ -0,0 +1,464 @@
+//
+// basin_hopping.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use log::{debug, warn};
+use rand::prelude::*;
+use rand_pcg::Pcg64Mcg;
+
+use crate::traits::*;
+use crate::{Basis, MCOptimiser, Observer, Snapshot};
+
+/// How each perturbed state is relaxed into the nearest local minimum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Relaxation {
+    /// A Monte Carlo optimisation at zero temperature, only accepting moves which improve the
+    /// score.
+    MonteCarlo {
+        steps: u64,
+        inner_steps: u64,
+        max_step_size: f64,
+    },
+    /// Follow the gradient of the score, found using finite differences, with a step size which
+    /// grows after a successful step and shrinks after a failed step. The relaxation finishes
+    /// once the step size is smaller than the tolerance.
+    GradientDescent {
+        steps: u64,
+        step_size: f64,
+        tolerance: f64,
+    },
+}
+
+impl Default for Relaxation {
+    fn default() -> Self {
+        Relaxation::MonteCarlo {
+            steps: 1000,
+            inner_steps: 100,
+            max_step_size: 0.01,
+        }
+    }
+}
+
+impl Relaxation {
+    /// Relax the state into the nearest local minimum
+    pub fn relax<S: State>(&self, state: S, seed: u64) -> S {
+        match *self {
+            Relaxation::MonteCarlo {
+                steps,
+                inner_steps,
+                max_step_size,
+            } => MCOptimiser::new(0., 1., max_step_size, steps, inner_steps, seed, None)
+                .optimise_state(state),
+            Relaxation::GradientDescent {
+                steps,
+                step_size,
+                tolerance,
+            } => gradient_descent(state, steps, step_size, tolerance),
+        }
+    }
+}
+
+/// The gradient of the objective with respect to each basis, using a forward finite difference
+fn gradient(basis: &[Basis], objective: &dyn Fn() -> Option<f64>, score: f64) -> Vec<f64> {
+    let delta = 1e-7;
+    basis
+        .iter()
+        .map(|b| {
+            let value = b.get_value();
+            let (_, max) = b.bounds();
+            // Take the difference in the direction which remains within the bounds
+            let step = if value + delta <= max { delta } else { -delta };
+            let gradient = match b.set_value(value + step).ok().and_then(|_| objective()) {
+                Some(new) => (new - score) / step,
+                // Moving into an invalid configuration doesn't help
+                None => 0.,
+            };
+            b.set_value(value).unwrap();
+            gradient
+        })
+        .collect()
+}
+
+fn gradient_descent<S: State>(state: S, steps: u64, step_size: f64, tolerance: f64) -> S {
+    ascend(
+        &state.generate_basis(),
+        &|| state.score(),
+        steps,
+        step_size,
+        tolerance,
+    );
+    state
+}
+
+/// Maximise an objective by following the gradient with respect to the values of a basis
+///
+/// This allows for relaxing a subset of the basis of a state, or an objective other than the
+/// score of the state.
+///
+pub(crate) fn ascend(
+    basis: &[Basis],
+    objective: &dyn Fn() -> Option<f64>,
+    steps: u64,
+    step_size: f64,
+    tolerance: f64,
+) {
+    let mut score = match objective() {
+        Some(score) => score,
+        None => return,
+    };
+    let mut step_size = step_size;
+    for _ in 0..steps {
+        if step_size < tolerance {
+            break;
+        }
+        let gradient = gradient(basis, objective, score);
+        let norm = gradient.iter().map(|g| g * g).sum::<f64>().sqrt();
+        if norm == 0. {
+            break;
+        }
+
+        let previous: Vec<f64> = basis.iter().map(Basis::get_value).collect();
+        for (b, g) in basis.iter().zip(gradient.iter()) {
+            let (min, max) = b.bounds();
+            let value = b.get_value() + step_size * g / norm;
+            b.set_value(value.max(min).min(max)).unwrap();
+        }
+        match objective() {
+            Some(new) if new > score => {
+                score = new;
+                step_size *= 1.2;
+            }
+            _ => {
+                for (b, value) in basis.iter().zip(previous) {
+                    b.set_value(value).unwrap();
+                }
+                step_size *= 0.5;
+            }
+        }
+    }
+}
+
+/// A collection of the distinct local minima found over an optimisation
+///
+/// The minima are ordered from the best score to the worst, with only the best `capacity`
+/// minima being kept. A minimum is the same as one already in the archive when the scores are
+/// within `tolerance` and every value of the basis describing the structure is within the
+/// `structure_tolerance`, so distinct structures which happen to pack equally well are all kept.
+///
+#[derive(Debug, Clone)]
+pub struct MinimaArchive<S> {
+    capacity: usize,
+    tolerance: f64,
+    structure_tolerance: f64,
+    minima: Vec<(f64, Vec<f64>, S)>,
+}
+
+impl<S: State> MinimaArchive<S> {
+    pub fn new(capacity: usize, tolerance: f64) -> Self {
+        Self {
+            capacity,
+            tolerance,
+            structure_tolerance: 1e-3,
+            minima: vec![],
+        }
+    }
+
+    /// Replace the largest difference in the values of the basis within the same minimum
+    pub fn with_structure_tolerance(mut self, tolerance: f64) -> Self {
+        self.structure_tolerance = tolerance;
+        self
+    }
+
+    fn same_structure(&self, a: &[f64], b: &[f64]) -> bool {
+        a.len() == b.len()
+            && a.iter()
+                .zip(b)
+                .all(|(a, b)| f64::abs(a - b) < self.structure_tolerance)
+    }
+
+    /// Add a state to the archive, returning whether it is a new distinct minimum
+    pub fn insert(&mut self, state: &S) -> bool {
+        let score = match state.score() {
+            Some(score) => score,
+            None => return false,
+        };
+        let values: Vec<f64> = state
+            .generate_basis()
+            .iter()
+            .map(Basis::get_value)
+            .collect();
+        if self.minima.iter().any(|(s, v, _)| {
+            f64::abs(s - score) < self.tolerance && self.same_structure(v, &values)
+        }) {
+            return false;
+        }
+        let position = self
+            .minima
+            .iter()
+            .position(|(s, _, _)| *s < score)
+            .unwrap_or(self.minima.len());
+        if position >= self.capacity {
+            return false;
+        }
+        self.minima.insert(position, (score, values, state.clone()));
+        self.minima.truncate(self.capacity);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.minima.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.minima.is_empty()
+    }
+
+    /// The minimum with the best score
+    pub fn best(&self) -> Option<&S> {
+        self.minima.first().map(|(_, _, s)| s)
+    }
+
+    pub fn into_best(self) -> Option<S> {
+        self.minima.into_iter().next().map(|(_, _, s)| s)
+    }
+
+    /// The scores of each of the minima, from best to worst
+    pub fn scores(&self) -> impl Iterator<Item = f64> + '_ {
+        self.minima.iter().map(|(score, _, _)| *score)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &S> {
+        self.minima.iter().map(|(_, _, state)| state)
+    }
+}
+
+/// Optimise a state by hopping between the basins of local minima
+///
+/// Each cycle perturbs every value of the basis, then relaxes the perturbed state into a local
+/// minimum. The relaxed minimum replaces the current state using the Metropolis criterion at the
+/// temperature `kt`. Rather than spending the optimisation exploring the region around a
+/// single minimum, as simulated annealing does at low temperatures, this explores the landscape
+/// of minima, making it far easier to escape from metastable structures.
+///
+/// ```
+/// # use std::convert::TryInto;
+/// # use crystal_packing::wallpaper::{WallpaperGroup, WallpaperGroups};
+/// use crystal_packing::{BasinHopping, LJShape2, PotentialState};
+/// # let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+/// let state = PotentialState::from_group(LJShape2::circle(), &group).unwrap();
+/// let optimiser = BasinHopping::new(5, 0.1, 0.1, 0);
+///
+/// let archive = optimiser.optimise_archive(state, &mut ());
+/// assert!(!archive.is_empty());
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct BasinHopping {
+    cycles: u64,
+    kt: f64,
+    perturbation: f64,
+    seed: u64,
+    relaxation: Relaxation,
+    archive_size: usize,
+    tolerance: f64,
+}
+
+impl BasinHopping {
+    /// Create an optimiser running `cycles` perturbations
+    ///
+    /// The `perturbation` is the fraction of the range of each basis value which is the
+    /// maximum size of the random perturbation.
+    ///
+    pub fn new(cycles: u64, kt: f64, perturbation: f64, seed: u64) -> Self {
+        Self {
+            cycles,
+            kt,
+            perturbation,
+            seed,
+            relaxation: Relaxation::default(),
+            archive_size: 10,
+            tolerance: 1e-6,
+        }
+    }
+
+    /// Replace the method used to relax each perturbed state
+    pub fn with_relaxation(mut self, relaxation: Relaxation) -> Self {
+        self.relaxation = relaxation;
+        self
+    }
+
+    /// Keep up to `size` minima, which are distinct when the scores differ by `tolerance` or the
+    /// structures differ
+    pub fn with_archive(mut self, size: usize, tolerance: f64) -> Self {
+        self.archive_size = size;
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Randomly modify all the values of the basis
+    ///
+    /// Each value is displaced by up to half the perturbation in either direction, except for
+    /// the length of the cell, which is the first of the cell basis. The length is only able to
+    /// expand, making room for the displaced sites. Where the perturbed state is invalid the
+    /// perturbation is attempted again at half the size, returning false when no valid
+    /// perturbation could be found.
+    ///
+    fn perturb<S: State, R: Rng + ?Sized>(&self, state: &S, rng: &mut R) -> bool {
+        let mut basis = state.generate_basis();
+        let length = basis.iter().position(Basis::is_cell);
+        if let Some(index) = length {
+            let b = basis.remove(index);
+            let (min, max) = b.bounds();
+            let upper = f64::max(max, b.get_value() * (1. + self.perturbation));
+            basis.insert(index, b.with_bounds(min, upper));
+        }
+        let previous: Vec<f64> = basis.iter().map(Basis::get_value).collect();
+
+        let mut perturbation = self.perturbation;
+        for _ in 0..10 {
+            for (index, b) in basis.iter().enumerate() {
+                let (min, max) = b.bounds();
+                let value = if Some(index) == length {
+                    b.get_value() * (1. + perturbation * rng.gen::<f64>())
+                } else {
+                    b.get_value() + perturbation * (max - min) * (rng.gen::<f64>() - 0.5)
+                };
+                b.set_value(value.max(min).min(max)).unwrap();
+            }
+            if state.score().is_some() {
+                return true;
+            }
+            for (b, &value) in basis.iter().zip(previous.iter()) {
+                b.set_value(value).unwrap();
+            }
+            perturbation *= 0.5;
+        }
+        false
+    }
+
+    /// Perform the optimisation, returning the archive of distinct minima which were found
+    pub fn optimise_archive<S: State>(
+        &self,
+        state: S,
+        observer: &mut dyn Observer<S>,
+    ) -> MinimaArchive<S> {
+        let mut rng = Pcg64Mcg::seed_from_u64(self.seed);
+        let mut archive = MinimaArchive::new(self.archive_size, self.tolerance);
+
+        let mut current = self.relaxation.relax(state, rng.gen());
+        let mut score = match current.score() {
+            Some(score) => score,
+            _ => panic!("Invalid configuration passed to function, exiting."),
+        };
+        archive.insert(&current);
+
+        let mut accepted: u64 = 0;
+        for cycle in 1..=self.cycles {
+            let candidate = current.clone();
+            if self.perturb(&candidate, &mut rng) {
+                let candidate = self.relaxation.relax(candidate, rng.gen());
+                if let Some(new) = candidate.score() {
+                    archive.insert(&candidate);
+                    let threshold: f64 = rng.gen();
+                    if new > score || threshold < f64::exp((new - score) / self.kt) {
+                        current = candidate;
+                        score = new;
+                        accepted += 1;
+                    }
+                }
+            }
+
+            let snapshot = Snapshot {
+                loop_index: cycle,
+                steps: cycle,
+                kt: self.kt,
+                score,
+                best_score: archive.scores().next().unwrap_or(score),
+                step_ratio: self.perturbation,
+                acceptance: accepted as f64 / cycle as f64,
+            };
+            if let Err(e) = observer.observe(&snapshot, &current) {
+                warn!("Observer failed at cycle {}: {}", cycle, e);
+            }
+        }
+        debug!(
+            "Found {} distinct minima, accepting {} of {} cycles",
+            archive.len(),
+            accepted,
+            self.cycles
+        );
+        archive
+    }
+}
+
+impl Optimiser for BasinHopping {
+    fn optimise_observed<S: State>(&self, state: S, observer: &mut dyn Observer<S>) -> S {
+        self.optimise_archive(state, observer)
+            .into_best()
+            .expect("The archive contains at least the initial minimum")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+    use crate::{LJShape2, LineShape, PackedState, PotentialState};
+
+    #[test]
+    fn archive_distinct() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+        let mut archive = MinimaArchive::new(2, 1e-6);
+        assert!(archive.insert(&state));
+        // The same minimum is only stored once
+        assert!(!archive.insert(&state.clone()));
+        assert_eq!(archive.len(), 1);
+    }
+
+    #[test]
+    fn archive_distinct_structures() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+        let rotated = state.clone();
+        // Rotating the shape a little keeps the packing fraction while changing the structure
+        let angle = rotated.generate_basis().pop().unwrap();
+        angle.set_value(angle.get_value() + 0.01).unwrap();
+        assert_eq!(state.score(), rotated.score());
+
+        let mut archive = MinimaArchive::new(2, 1e-6);
+        assert!(archive.insert(&state));
+        assert!(archive.insert(&rotated));
+        assert_eq!(archive.len(), 2);
+    }
+
+    #[test]
+    fn improves_packed() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+        let init_score = state.score().unwrap();
+        let final_state = BasinHopping::new(5, 0.01, 0.1, 0).optimise_state(state);
+        assert!(final_state.score().unwrap() > init_score);
+    }
+
+    #[test]
+    fn improves_potential_gradient() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PotentialState::from_group(LJShape2::circle(), &group).unwrap();
+        let init_score = state.score().unwrap();
+        let optimiser =
+            BasinHopping::new(5, 0.1, 0.1, 0).with_relaxation(Relaxation::GradientDescent {
+                steps: 50,
+                step_size: 0.1,
+                tolerance: 1e-6,
+            });
+        let archive = optimiser.optimise_archive(state, &mut ());
+        let scores: Vec<f64> = archive.scores().collect();
+        assert!(scores[0] > init_score);
+        assert!(scores.windows(2).all(|w| w[0] > w[1]));
+    }
+}
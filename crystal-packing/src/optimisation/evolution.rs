@@ -0,0 +1,442 @@
+//
+// evolution.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::cmp::Ordering;
+
+use log::{debug, warn};
+use rand::prelude::*;
+use rand_distr::StandardNormal;
+use rand_pcg::Pcg64Mcg;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::traits::*;
+use crate::{Basis, Observer, Relaxation, Snapshot};
+
+/// The scores of the population at the end of a generation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Generation {
+    /// The number of generations which have been completed, with 0 being the initial population
+    pub generation: u64,
+    /// The best score in the population
+    pub best: Option<f64>,
+    /// The median score of the valid members of the population
+    pub median: Option<f64>,
+    /// The number of members of the population which are valid
+    pub valid: usize,
+}
+
+impl Generation {
+    pub const CSV_HEADER: &'static str = "generation,best,median,valid";
+
+    fn from_population(generation: u64, population: &[Individual]) -> Self {
+        // The population is sorted with the best scores first, and the invalid members last
+        let scores: Vec<f64> = population.iter().filter_map(|i| i.score).collect();
+        Self {
+            generation,
+            best: scores.first().copied(),
+            median: scores.get(scores.len() / 2).copied(),
+            valid: scores.len(),
+        }
+    }
+
+    /// A row of the report of the generations, with the columns given by `CSV_HEADER`
+    ///
+    /// A generation without any valid members has empty values for the best and median.
+    ///
+    pub fn as_csv(&self) -> String {
+        let value = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+        format!(
+            "{},{},{},{}",
+            self.generation,
+            value(self.best),
+            value(self.median),
+            self.valid
+        )
+    }
+}
+
+/// A member of the population, being the values of the basis and the resulting score
+#[derive(Debug, Clone)]
+struct Individual {
+    genome: Vec<f64>,
+    score: Option<f64>,
+}
+
+/// Order individuals with the best score first and the invalid individuals last
+fn best_first(a: &Individual, b: &Individual) -> Ordering {
+    b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal)
+}
+
+/// A population based search over the values of the basis
+///
+/// This is a genetic algorithm in the style of USPEX, where each generation is created from the
+/// previous generation using
+///
+/// - elitism, with the best members of the population carried over unchanged,
+/// - tournament selection of the parents,
+/// - crossover, where the child inherits each block of the basis from one of the parents, so
+///   the unit cell and each occupied site are passed on as a unit, and
+/// - mutation, where each value of the basis has a chance of a normally distributed change.
+///
+/// Each child is relaxed into a local minimum before being scored, with the relaxation and
+/// scoring of the population evaluated in parallel.
+///
+/// ```
+/// # use std::convert::TryInto;
+/// # use crystal_packing::wallpaper::{WallpaperGroup, WallpaperGroups};
+/// use crystal_packing::{GeneticOptimiser, LineShape, PackedState};
+/// # let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+/// let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+/// let optimiser = GeneticOptimiser::new(4, 2, 0);
+///
+/// let (best, generations) = optimiser.optimise_generations(state);
+/// assert_eq!(generations.len(), 3);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct GeneticOptimiser {
+    population: usize,
+    generations: u64,
+    seed: u64,
+    elites: usize,
+    tournament: usize,
+    mutation_rate: f64,
+    mutation_scale: f64,
+    relaxation: Relaxation,
+}
+
+impl GeneticOptimiser {
+    pub fn new(population: usize, generations: u64, seed: u64) -> Self {
+        Self {
+            population,
+            generations,
+            seed,
+            elites: usize::max(1, population / 10),
+            tournament: 3,
+            mutation_rate: 0.1,
+            mutation_scale: 0.05,
+            relaxation: Relaxation::default(),
+        }
+    }
+
+    /// The number of the best members of the population which carry over to the next generation
+    pub fn with_elites(mut self, elites: usize) -> Self {
+        self.elites = elites;
+        self
+    }
+
+    /// The number of members of the population competing to be selected as a parent
+    pub fn with_tournament(mut self, tournament: usize) -> Self {
+        self.tournament = usize::max(1, tournament);
+        self
+    }
+
+    /// Mutate each value with probability `rate`, with a standard deviation of the mutation
+    /// being `scale` multiplied by the range of the value.
+    pub fn with_mutation(mut self, rate: f64, scale: f64) -> Self {
+        self.mutation_rate = rate;
+        self.mutation_scale = scale;
+        self
+    }
+
+    /// Replace the method used to relax each child into a local minimum
+    pub fn with_relaxation(mut self, relaxation: Relaxation) -> Self {
+        self.relaxation = relaxation;
+        self
+    }
+
+    /// The basis of the state, with the bounds fixed to those of the template
+    ///
+    /// The bounds of the cell depend on its current size, so the bounds of the initial state are
+    /// used for every member of the population.
+    ///
+    fn basis<'a, S: State>(state: &'a S, bounds: &[(f64, f64)]) -> Vec<Basis<'a>> {
+        state
+            .generate_basis()
+            .into_iter()
+            .zip(bounds.iter())
+            .map(|(b, &(min, max))| b.with_bounds(min, max))
+            .collect()
+    }
+
+    /// Create the state described by the genome
+    fn express<S: State>(template: &S, bounds: &[(f64, f64)], genome: &[f64]) -> S {
+        let state = template.clone();
+        for (b, &value) in Self::basis(&state, bounds).iter().zip(genome.iter()) {
+            let (min, max) = b.bounds();
+            b.set_value(value.max(min).min(max)).unwrap();
+        }
+        state
+    }
+
+    /// Create, relax and score the state described by the genome
+    ///
+    /// Where the state is invalid the length of the cell is increased until it becomes valid,
+    /// which is required before the state can be relaxed.
+    ///
+    fn evaluate<S: State>(
+        &self,
+        template: &S,
+        bounds: &[(f64, f64)],
+        genome: Vec<f64>,
+        seed: u64,
+    ) -> Individual {
+        let state = Self::express(template, bounds, &genome);
+        {
+            let basis = Self::basis(&state, bounds);
+            if let Some(length) = basis.iter().find(|b| b.is_cell()) {
+                let (_, max) = length.bounds();
+                while state.score().is_none() && length.get_value() < max {
+                    length
+                        .set_value(f64::min(length.get_value() * 1.1, max))
+                        .unwrap();
+                }
+            }
+        }
+        if state.score().is_none() {
+            return Individual {
+                genome,
+                score: None,
+            };
+        }
+        let state = self.relaxation.relax(state, seed);
+        let genome = state
+            .generate_basis()
+            .iter()
+            .map(Basis::get_value)
+            .collect();
+        Individual {
+            genome,
+            score: state.score(),
+        }
+    }
+
+    fn evaluate_all<S: State>(
+        &self,
+        template: &S,
+        bounds: &[(f64, f64)],
+        genomes: Vec<Vec<f64>>,
+        rng: &mut Pcg64Mcg,
+    ) -> Vec<Individual> {
+        let seeds: Vec<u64> = genomes.iter().map(|_| rng.gen()).collect();
+        genomes
+            .into_par_iter()
+            .zip(seeds)
+            .map(|(genome, seed)| self.evaluate(template, bounds, genome, seed))
+            .collect()
+    }
+
+    /// Choose a parent from the population, which is sorted with the best first
+    fn select<'a, R: Rng + ?Sized>(&self, population: &'a [Individual], rng: &mut R) -> &'a [f64] {
+        let index = (0..self.tournament)
+            .map(|_| rng.gen_range(0..population.len()))
+            .min()
+            .unwrap_or(0);
+        &population[index].genome
+    }
+
+    /// Combine two parents, inheriting each block of the basis from one of them
+    fn crossover<R: Rng + ?Sized>(
+        blocks: &[usize],
+        first: &[f64],
+        second: &[f64],
+        rng: &mut R,
+    ) -> Vec<f64> {
+        let mut child = Vec::with_capacity(first.len());
+        let mut start = 0;
+        for &length in blocks {
+            let parent = if rng.gen::<bool>() { first } else { second };
+            child.extend_from_slice(&parent[start..start + length]);
+            start += length;
+        }
+        child
+    }
+
+    fn mutate<R: Rng + ?Sized>(&self, genome: &mut [f64], bounds: &[(f64, f64)], rng: &mut R) {
+        for (value, &(min, max)) in genome.iter_mut().zip(bounds.iter()) {
+            if rng.gen::<f64>() < self.mutation_rate {
+                let change: f64 = rng.sample(StandardNormal);
+                *value = (*value + change * self.mutation_scale * (max - min))
+                    .max(min)
+                    .min(max);
+            }
+        }
+    }
+
+    /// The snapshot passed to the observers at the end of a generation
+    pub fn snapshot(&self, generation: &Generation) -> Snapshot {
+        let evaluations = self.population as u64;
+        let score = generation.best.unwrap_or(f64::NAN);
+        Snapshot {
+            loop_index: generation.generation,
+            steps: (generation.generation + 1) * evaluations,
+            kt: 0.,
+            score,
+            best_score: score,
+            step_ratio: self.mutation_scale,
+            acceptance: generation.valid as f64 / evaluations as f64,
+        }
+    }
+
+    /// Perform the optimisation, returning the best state along with the scores of each
+    /// generation
+    pub fn optimise_generations<S: State>(&self, state: S) -> (S, Vec<Generation>) {
+        let mut history = vec![];
+        let best = self.optimise_reported(state, &mut |generation, _| history.push(*generation));
+        (best, history)
+    }
+
+    /// Perform the optimisation, passing the scores and the best state of each generation to
+    /// `report`
+    pub fn optimise_reported<S: State>(
+        &self,
+        state: S,
+        report: &mut dyn FnMut(&Generation, &S),
+    ) -> S {
+        let mut rng = Pcg64Mcg::seed_from_u64(self.seed);
+        let basis = state.generate_basis();
+        let bounds: Vec<(f64, f64)> = basis.iter().map(Basis::bounds).collect();
+        let template: Vec<f64> = basis.iter().map(Basis::get_value).collect();
+        let cell: Vec<bool> = basis.iter().map(Basis::is_cell).collect();
+        drop(basis);
+        let blocks = state.basis_blocks();
+
+        // The initial population keeps the cell of the initial state, which is large enough to
+        // be valid for any positions of the sites.
+        let genomes = (0..self.population)
+            .map(|index| {
+                template
+                    .iter()
+                    .zip(bounds.iter())
+                    .zip(cell.iter())
+                    .map(|((&value, &(min, max)), &is_cell)| {
+                        if index == 0 || is_cell {
+                            value
+                        } else {
+                            rng.gen_range(min..=max)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut population = self.evaluate_all(&state, &bounds, genomes, &mut rng);
+        population.sort_by(best_first);
+
+        for generation in 0..=self.generations {
+            if generation > 0 {
+                let elites = usize::min(self.elites, population.len());
+                let children: Vec<Vec<f64>> = (elites..self.population)
+                    .map(|_| {
+                        let first = self.select(&population, &mut rng);
+                        let second = self.select(&population, &mut rng);
+                        let mut child = Self::crossover(&blocks, first, second, &mut rng);
+                        self.mutate(&mut child, &bounds, &mut rng);
+                        child
+                    })
+                    .collect();
+                population.truncate(elites);
+                population.extend(self.evaluate_all(&state, &bounds, children, &mut rng));
+                population.sort_by(best_first);
+            }
+
+            let summary = Generation::from_population(generation, &population);
+            debug!(
+                "Generation {}: best {:?}, median {:?}, {} valid",
+                summary.generation, summary.best, summary.median, summary.valid
+            );
+            report(
+                &summary,
+                &Self::express(&state, &bounds, &population[0].genome),
+            );
+        }
+
+        match population.first() {
+            Some(Individual {
+                genome,
+                score: Some(_),
+            }) => Self::express(&state, &bounds, genome),
+            _ => state,
+        }
+    }
+}
+
+impl Optimiser for GeneticOptimiser {
+    fn optimise_observed<S: State>(&self, state: S, observer: &mut dyn Observer<S>) -> S {
+        self.optimise_reported(state, &mut |generation, best| {
+            if let Err(e) = observer.observe(&self.snapshot(generation), best) {
+                warn!(
+                    "Observer failed at generation {}: {}",
+                    generation.generation, e
+                );
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+    use crate::{LJShape2, LineShape, PackedState, PotentialState};
+
+    #[test]
+    fn crossover_blocks() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        let first = [0., 0., 0., 0., 0.];
+        let second = [1., 1., 1., 1., 1.];
+        for _ in 0..10 {
+            let child = GeneticOptimiser::crossover(&[2, 3], &first, &second, &mut rng);
+            assert_eq!(child.len(), 5);
+            // Each block comes from a single parent
+            assert_eq!(child[0], child[1]);
+            assert!(child[2] == child[3] && child[3] == child[4]);
+        }
+    }
+
+    #[test]
+    fn generation_csv() {
+        let generation = Generation {
+            generation: 2,
+            best: Some(0.75),
+            median: None,
+            valid: 1,
+        };
+        let columns = Generation::CSV_HEADER.split(',').count();
+        assert_eq!(generation.as_csv(), "2,0.75,,1");
+        assert_eq!(generation.as_csv().split(',').count(), columns);
+    }
+
+    #[test]
+    fn blocks_cover_basis() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+        let blocks = state.basis_blocks();
+        assert_eq!(blocks.iter().sum::<usize>(), state.generate_basis().len());
+        assert_eq!(blocks[0], 3);
+    }
+
+    #[test]
+    fn best_never_worse() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PotentialState::from_group(LJShape2::circle(), &group).unwrap();
+        let optimiser = GeneticOptimiser::new(6, 3, 0).with_relaxation(Relaxation::MonteCarlo {
+            steps: 200,
+            inner_steps: 100,
+            max_step_size: 0.01,
+        });
+        let (best, generations) = optimiser.optimise_generations(state);
+        assert_eq!(generations.len(), 4);
+        // Elitism ensures the best score can't decrease
+        for pair in generations.windows(2) {
+            assert!(pair[1].best >= pair[0].best);
+            assert!(pair[1].best >= pair[1].median);
+        }
+        assert_eq!(best.score(), generations[3].best);
+    }
+}
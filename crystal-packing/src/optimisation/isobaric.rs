@@ -0,0 +1,318 @@
+//
+// isobaric.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fmt;
+
+use log::{debug, warn};
+use rand::prelude::*;
+use rand_pcg::Pcg64Mcg;
+use serde::{Deserialize, Serialize};
+
+use crate::traits::*;
+use crate::{MoveSet, Observer, SingleBasisMove, Snapshot};
+
+/// How the pressure changes over an isobaric optimisation
+///
+/// The pressure is the reduced pressure βPa, where a is the area of a single shape.
+///
+pub trait PressureSchedule: fmt::Debug + Send + Sync {
+    /// The pressure at a fraction of the way through the optimisation
+    fn pressure(&self, fraction: f64) -> f64;
+}
+
+/// Change the pressure linearly from `start` to `finish`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinearPressure {
+    start: f64,
+    finish: f64,
+}
+
+impl LinearPressure {
+    pub fn new(start: f64, finish: f64) -> Self {
+        Self { start, finish }
+    }
+}
+
+impl PressureSchedule for LinearPressure {
+    fn pressure(&self, fraction: f64) -> f64 {
+        self.start + (self.finish - self.start) * fraction
+    }
+}
+
+/// Change the pressure by a constant ratio from `start` to `finish`
+///
+/// The pressure required to compress a hard particle system increases rapidly close to jamming,
+/// which makes a geometric ramp spend a similar amount of time in each part of the equation of
+/// state.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeometricPressure {
+    start: f64,
+    finish: f64,
+}
+
+impl GeometricPressure {
+    pub fn new(start: f64, finish: f64) -> Self {
+        Self { start, finish }
+    }
+}
+
+impl PressureSchedule for GeometricPressure {
+    fn pressure(&self, fraction: f64) -> f64 {
+        self.start * f64::powf(self.finish / self.start, fraction)
+    }
+}
+
+/// A point on the equation of state, averaged over an inner loop at constant pressure
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StatePoint {
+    /// The number of inner loops which have been completed
+    pub loop_index: u64,
+    /// The reduced pressure βPa of the inner loop
+    pub pressure: f64,
+    /// The mean packing fraction over the inner loop
+    pub packing_fraction: f64,
+    /// The fraction of the moves in the inner loop which were accepted
+    pub acceptance: f64,
+}
+
+impl StatePoint {
+    pub const CSV_HEADER: &'static str = "loop,pressure,packing_fraction,acceptance";
+
+    pub fn as_csv(&self) -> String {
+        format!(
+            "{},{},{},{}",
+            self.loop_index, self.pressure, self.packing_fraction, self.acceptance
+        )
+    }
+}
+
+/// Compress a packing of hard shapes using isobaric (NPT) Monte Carlo
+///
+/// Rather than only accepting moves which increase the packing fraction, this samples the
+/// isothermal-isobaric ensemble, where the cell is able to expand as well as contract. A move
+/// changing the area of the cell from A to A' is accepted with probability
+///
+/// min(1, exp(-βP(A' - A) + N ln(A' / A)))
+///
+/// where N is the number of shapes in the cell. As the pressure is ramped up, the mean packing
+/// fraction at each pressure traces the equation of state of the crystal.
+///
+/// This requires the packing fraction of the state, being a [`Packed`] state like the
+/// [`PackedState`], where the area of the cell is N a / φ. The pressure is the reduced pressure
+/// βPa, with a the area of a single shape. Shapes with an internal energy U, like a flexible
+/// molecule, also have the term -N ΔU in the acceptance, with U in units of kT.
+///
+/// [`Packed`]: crate::Packed
+/// [`PackedState`]: crate::PackedState
+///
+/// ```
+/// # use std::convert::TryInto;
+/// # use crystal_packing::wallpaper::{WallpaperGroup, WallpaperGroups};
+/// use crystal_packing::{GeometricPressure, IsobaricOptimiser, LineShape, PackedState};
+/// # let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+/// let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+/// let optimiser =
+///     IsobaricOptimiser::new(Box::new(GeometricPressure::new(1., 100.)), 0.1, 2000, 100, 0);
+///
+/// let (state, equation_of_state) = optimiser.optimise_equation_of_state(state, &mut ());
+/// assert_eq!(equation_of_state.len(), 20);
+/// ```
+///
+#[derive(Debug)]
+pub struct IsobaricOptimiser {
+    pressure: Box<dyn PressureSchedule>,
+    max_step_size: f64,
+    steps: u64,
+    inner_steps: u64,
+    seed: u64,
+    moves: Box<dyn MoveSet>,
+}
+
+impl IsobaricOptimiser {
+    pub fn new(
+        pressure: Box<dyn PressureSchedule>,
+        max_step_size: f64,
+        steps: u64,
+        inner_steps: u64,
+        seed: u64,
+    ) -> Self {
+        Self {
+            pressure,
+            max_step_size,
+            steps,
+            inner_steps,
+            seed,
+            moves: Box::new(SingleBasisMove),
+        }
+    }
+
+    /// Replace the moves used to modify the state
+    pub fn with_moves(mut self, moves: Box<dyn MoveSet>) -> Self {
+        self.moves = moves;
+        self
+    }
+
+    /// The logarithm of the probability of accepting a change in packing fraction
+    ///
+    /// Since the area of the cell is N a / φ, the change in enthalpy βPΔA is
+    /// βPa N (1/φ' - 1/φ), while the entropic term N ln(A'/A) is N ln(φ/φ').
+    ///
+    fn log_acceptance(pressure: f64, shapes: f64, new: f64, old: f64) -> f64 {
+        -pressure * shapes * (1. / new - 1. / old) + shapes * f64::ln(old / new)
+    }
+
+    /// Perform the optimisation, returning the final state along with the equation of state
+    pub fn optimise_equation_of_state<S: Packed>(
+        &self,
+        state: S,
+        observer: &mut dyn Observer<S>,
+    ) -> (S, Vec<StatePoint>) {
+        let mut rng = Pcg64Mcg::seed_from_u64(self.seed);
+        let basis = state.generate_expanding_basis();
+        let shapes = state.total_shapes() as f64;
+        let mut score = match state.packing_fraction() {
+            Some(score) => score,
+            _ => panic!("Invalid configuration passed to function, exiting."),
+        };
+        let mut energy = state.internal_energy();
+        let mut best_score = score;
+        let mut step_ratio = 1.;
+
+        let total_loops = self.steps / self.inner_steps;
+        let mut equation_of_state = Vec::with_capacity(total_loops as usize);
+        for loop_index in 1..=total_loops {
+            let fraction = if total_loops > 1 {
+                (loop_index - 1) as f64 / (total_loops - 1) as f64
+            } else {
+                1.
+            };
+            let pressure = self.pressure.pressure(fraction);
+
+            let mut rejections: u64 = 0;
+            let mut sum_score = 0.;
+            for _ in 0..self.inner_steps {
+                if let Some(proposal) =
+                    self.moves
+                        .propose(&basis, self.max_step_size * step_ratio, &mut rng)
+                {
+                    let threshold: f64 = rng.gen();
+                    let new_energy = state.internal_energy();
+                    match state.packing_fraction() {
+                        Some(new)
+                            if threshold.ln()
+                                < Self::log_acceptance(pressure, shapes, new, score)
+                                    - shapes * (new_energy - energy) =>
+                        {
+                            score = new;
+                            energy = new_energy;
+                        }
+                        _ => {
+                            proposal.revert(&basis);
+                            rejections += 1;
+                        }
+                    }
+                } else {
+                    rejections += 1;
+                }
+                sum_score += score;
+            }
+            best_score = f64::max(best_score, score);
+            let acceptance = 1. - rejections as f64 / self.inner_steps as f64;
+
+            let point = StatePoint {
+                loop_index,
+                pressure,
+                packing_fraction: sum_score / self.inner_steps as f64,
+                acceptance,
+            };
+            debug!("{:?}", point);
+            equation_of_state.push(point);
+
+            let snapshot = Snapshot {
+                loop_index,
+                steps: loop_index * self.inner_steps,
+                kt: 1.,
+                score,
+                best_score,
+                step_ratio,
+                acceptance,
+            };
+            if let Err(e) = observer.observe(&snapshot, &state) {
+                warn!("Observer failed at loop {}: {}", loop_index, e);
+            }
+
+            // Aim for around half the moves being accepted
+            if acceptance < 0.4 {
+                step_ratio *= 0.9;
+            } else if acceptance > 0.6 && step_ratio < 1. {
+                step_ratio = f64::min(step_ratio * 1.1, 1.);
+            }
+        }
+        (state, equation_of_state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+    use crate::{LineShape, PackedState};
+
+    fn create_state() -> PackedState<LineShape> {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap()
+    }
+
+    #[test]
+    fn pressure_schedules() {
+        assert_abs_diff_eq!(LinearPressure::new(1., 3.).pressure(0.5), 2.);
+        assert_abs_diff_eq!(GeometricPressure::new(1., 100.).pressure(0.5), 10.);
+        assert_abs_diff_eq!(GeometricPressure::new(1., 100.).pressure(1.), 100.);
+    }
+
+    #[test]
+    fn acceptance_favours_compression() {
+        // Without any change in area the move is always accepted
+        assert_abs_diff_eq!(IsobaricOptimiser::log_acceptance(10., 2., 0.5, 0.5), 0.);
+        // At high pressure compression is favoured
+        assert!(IsobaricOptimiser::log_acceptance(10., 2., 0.6, 0.5) > 0.);
+        // At zero pressure expansion is favoured
+        assert!(IsobaricOptimiser::log_acceptance(0., 2., 0.4, 0.5) > 0.);
+    }
+
+    #[test]
+    fn cell_expands() {
+        let state = create_state();
+        let (_, eos) =
+            IsobaricOptimiser::new(Box::new(LinearPressure::new(0., 0.)), 0.5, 500, 100, 0)
+                .optimise_equation_of_state(state.clone(), &mut ());
+        // Without any pressure the cell is free to expand
+        assert!(eos.last().unwrap().packing_fraction < state.score().unwrap());
+    }
+
+    #[test]
+    fn compresses_with_pressure() {
+        let state = create_state();
+        let initial = state.score().unwrap();
+        let optimiser = IsobaricOptimiser::new(
+            Box::new(GeometricPressure::new(1., 1000.)),
+            0.1,
+            5000,
+            100,
+            0,
+        );
+        let (state, eos) = optimiser.optimise_equation_of_state(state, &mut ());
+        assert_eq!(eos.len(), 50);
+        assert_abs_diff_eq!(eos[49].pressure, 1000., epsilon = 1e-9);
+        assert!(state.score().unwrap() > initial);
+        assert!(state.score().unwrap() <= 1.);
+    }
+}
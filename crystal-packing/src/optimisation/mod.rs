@@ -0,0 +1,436 @@
+//
+// optimisation.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+pub mod basin_hopping;
+pub mod evolution;
+pub mod isobaric;
+pub mod moves;
+pub mod observer;
+pub mod schedule;
+
+pub use basin_hopping::*;
+pub use evolution::*;
+pub use isobaric::*;
+pub use moves::*;
+pub use observer::*;
+pub use schedule::*;
+
+use log::{debug, warn};
+use rand::prelude::*;
+use rand_pcg::Pcg64Mcg;
+use serde::{Deserialize, Serialize};
+
+use crate::traits::*;
+use crate::Basis;
+
+/// Optimise a state using Monte Carlo simulated annealing
+///
+/// The temperature is controlled by a [`CoolingSchedule`] and the modifications to the state are
+/// proposed by a [`MoveSet`]. By default these are an [`ExponentialSchedule`] and the
+/// [`SingleBasisMove`], which can be replaced using [`MCOptimiser::with_schedule`] and
+/// [`MCOptimiser::with_moves`].
+///
+#[derive(Debug)]
+pub struct MCOptimiser {
+    max_step_size: f64,
+    steps: u64,
+    inner_steps: u64,
+    seed: u64,
+    convergence: Option<f64>,
+    schedule: Box<dyn CoolingSchedule>,
+    moves: Box<dyn MoveSet>,
+    expanding: bool,
+}
+
+impl MCOptimiser {
+    pub fn new(
+        kt_start: f64,
+        kt_ratio: f64,
+        max_step_size: f64,
+        steps: u64,
+        inner_steps: u64,
+        seed: u64,
+        convergence: Option<f64>,
+    ) -> MCOptimiser {
+        MCOptimiser {
+            max_step_size,
+            steps,
+            inner_steps,
+            seed,
+            convergence,
+            schedule: Box::new(ExponentialSchedule::new(kt_start, kt_ratio)),
+            moves: Box::new(SingleBasisMove),
+            expanding: false,
+        }
+    }
+
+    /// Replace the schedule used to reduce the temperature
+    pub fn with_schedule(mut self, schedule: Box<dyn CoolingSchedule>) -> MCOptimiser {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Replace the moves used to modify the state
+    pub fn with_moves(mut self, moves: Box<dyn MoveSet>) -> MCOptimiser {
+        self.moves = moves;
+        self
+    }
+
+    /// Allow the unit cell to expand as well as contract
+    ///
+    /// Searching for the densest packing only requires the cell to contract, however a
+    /// simulation at a finite temperature, like testing the stability of a structure, has to
+    /// allow the cell to expand.
+    ///
+    pub fn with_expanding_cell(mut self, expanding: bool) -> MCOptimiser {
+        self.expanding = expanding;
+        self
+    }
+
+    fn basis<'a, S: State>(&self, state: &'a S) -> Vec<Basis<'a>> {
+        if self.expanding {
+            state.generate_expanding_basis()
+        } else {
+            state.generate_basis()
+        }
+    }
+
+    #[inline]
+    fn energy_surface(&self, new: f64, old: f64, kt: f64) -> f64 {
+        f64::min(f64::exp((new - old) / kt), 1.)
+    }
+
+    #[inline]
+    fn test_acceptance(&self, threshold: f64, new: f64, old: f64, kt: f64) -> bool {
+        threshold < self.energy_surface(new, old, kt)
+    }
+
+    fn accept_score<R: Rng + ?Sized>(
+        &self,
+        new: Option<f64>,
+        old: f64,
+        kt: f64,
+        rng: &mut R,
+    ) -> Option<f64> {
+        let threshold: f64 = rng.gen();
+
+        match new {
+            // New score is better, keep updated state
+            Some(new_score) if new_score > old => Some(new_score),
+            // When the score increases, there is a probability of accepting the new
+            // score, which is evaluated based on the difference between the new score,
+            // and the old score along with the temperature value (kt).
+            // The acceptance occurs when a random number smaller than the acceptance
+            // probability is drawn.
+            Some(new_score) if self.test_acceptance(threshold, new_score, old, kt) => {
+                Some(new_score)
+            }
+            // If the first two tests fail, then the score is rejected.
+            _ => None,
+        }
+    }
+}
+
+/// Whether an optimisation should continue after an inner loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+/// The progress of a Monte Carlo optimisation
+///
+/// This contains all the values which change over the course of an optimisation, including the
+/// state of the random number generator. Together with the state being optimised this allows an
+/// optimisation to be stopped and later resumed, giving a result identical to an uninterrupted
+/// optimisation.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MCCheckpoint {
+    loop_index: u64,
+    kt: f64,
+    step_ratio: f64,
+    score: f64,
+    best_score: f64,
+    loops_since_improvement: u64,
+    convergence_count: u64,
+    rejections: u64,
+    rng: Pcg64Mcg,
+    /// The bounds of each basis when the optimisation started
+    ///
+    /// The bounds of the cell depend on its size when the basis is generated, so these are kept
+    /// to ensure the resumed optimisation uses the same bounds as the original.
+    bounds: Vec<(f64, f64)>,
+    finished: bool,
+}
+
+impl MCCheckpoint {
+    /// The number of inner loops which have been completed
+    pub fn loop_index(&self) -> u64 {
+        self.loop_index
+    }
+
+    /// Whether the optimisation has run to completion
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+impl MCOptimiser {
+    /// The progress of an optimisation of `state` which hasn't yet started
+    pub fn checkpoint<S: State>(&self, state: &S) -> MCCheckpoint {
+        let score = match state.score() {
+            Some(score) => score,
+            _ => panic!("Invalid configuration passed to function, exiting."),
+        };
+        MCCheckpoint {
+            loop_index: 0,
+            kt: self.schedule.initial(),
+            step_ratio: 1.,
+            score,
+            best_score: score,
+            loops_since_improvement: 0,
+            convergence_count: 0,
+            rejections: 0,
+            rng: Pcg64Mcg::seed_from_u64(self.seed),
+            bounds: self.basis(state).iter().map(Basis::bounds).collect(),
+            finished: false,
+        }
+    }
+
+    /// Continue an optimisation from a checkpoint
+    ///
+    /// The state should be the one which was being optimised when the checkpoint was taken.
+    /// After every inner loop, once the observer has been called, the `control` is passed the
+    /// state along with the checkpoint at that point. Where `control` returns [`Control::Stop`]
+    /// the optimisation is stopped, returning the state and the checkpoint from which it can be
+    /// resumed.
+    ///
+    pub fn optimise_checkpointed<S: State>(
+        &self,
+        state: S,
+        checkpoint: MCCheckpoint,
+        observer: &mut dyn Observer<S>,
+        control: &mut dyn FnMut(&S, &MCCheckpoint) -> Control,
+    ) -> (S, MCCheckpoint) {
+        let mut progress = checkpoint;
+        let basis: Vec<Basis> = self
+            .basis(&state)
+            .into_iter()
+            .zip(progress.bounds.iter())
+            .map(|(basis, &(min, max))| basis.with_bounds(min, max))
+            .collect();
+
+        let total_loops = self.steps / self.inner_steps;
+        while !progress.finished && progress.loop_index < total_loops {
+            progress.loop_index += 1;
+            let loop_counter = progress.loop_index;
+            let kt = progress.kt;
+            let step_ratio = progress.step_ratio;
+
+            let score_start = progress.score;
+            let mut loop_rejections: u64 = 0;
+            for _ in 0..self.inner_steps {
+                // Make a random modification to the basis, keeping the proposal so the change
+                // can be undone.
+                let proposal = match self.moves.propose(
+                    &basis,
+                    self.max_step_size * step_ratio,
+                    &mut progress.rng,
+                ) {
+                    Some(proposal) => proposal,
+                    None => {
+                        loop_rejections += 1;
+                        continue;
+                    }
+                };
+
+                // Check if modification was good
+                progress.score =
+                    match self.accept_score(state.score(), progress.score, kt, &mut progress.rng) {
+                        Some(score) => score,
+                        // Score was rejected so we have to undo the change
+                        None => {
+                            proposal.revert(&basis);
+                            // Increment counter of rejections
+                            loop_rejections += 1;
+                            progress.score
+                        }
+                    };
+            }
+            progress.rejections += loop_rejections;
+            let score_current = progress.score;
+
+            if score_current > progress.best_score {
+                progress.best_score = score_current;
+                progress.loops_since_improvement = 0;
+            } else {
+                progress.loops_since_improvement += 1;
+            }
+            let acceptance = 1. - loop_rejections as f64 / self.inner_steps as f64;
+
+            let snapshot = Snapshot {
+                loop_index: loop_counter,
+                steps: loop_counter * self.inner_steps,
+                kt,
+                score: score_current,
+                best_score: progress.best_score,
+                step_ratio,
+                acceptance,
+            };
+            if let Err(e) = observer.observe(&snapshot, &state) {
+                warn!("Observer failed at loop {}: {}", loop_counter, e);
+            }
+
+            progress.kt = self.schedule.next(&Progress {
+                loop_index: loop_counter,
+                total_loops,
+                kt,
+                acceptance,
+                loops_since_improvement: progress.loops_since_improvement,
+            });
+
+            // Where the score has converged to the precision of the convergence we can exit early
+            if let Some(precision) = self.convergence {
+                // The current score should be larger than the original score -> optimising to
+                // larger numbers
+                if score_current - score_start < precision {
+                    progress.convergence_count += 1;
+                    if progress.convergence_count > 5 {
+                        debug!(
+                            "Found convergence of score after {} steps, difference of {}",
+                            loop_counter * self.inner_steps,
+                            score_current - score_start,
+                        );
+                        progress.finished = true;
+                        return (state, progress);
+                    }
+                } else {
+                    // Reset to zero, convergence has to be consecutive loops
+                    progress.convergence_count = 0;
+                }
+            }
+
+            // Scale step ratio with goal of 75% rejections
+            // Taking shinking the cell as an example, 50% of steps will  increase the cell, so
+            // we want 50% of the steps which can improve the performance to be accepted.
+            // There is a limit to the usefulness though and 1e-4 has been good.
+            if step_ratio > 1e-4 {
+                progress.step_ratio *= self.inner_steps as f64 / (loop_rejections as f64 + 1.);
+            }
+
+            if progress.loop_index < total_loops && control(&state, &progress) == Control::Stop {
+                return (state, progress);
+            }
+        }
+        progress.finished = true;
+        debug!(
+            "Score: {:.4}, Rejected Fraction: {:.2}%",
+            progress.score,
+            100. * progress.rejections as f64 / self.steps as f64,
+        );
+
+        assert!(
+            state.score().is_some(),
+            "Final score is invalid, this shouldn't occur in normal operation"
+        );
+        (state, progress)
+    }
+}
+
+impl Optimiser for MCOptimiser {
+    fn optimise_observed<S: State>(&self, state: S, observer: &mut dyn Observer<S>) -> S {
+        let checkpoint = self.checkpoint(&state);
+        self.optimise_checkpointed(state, checkpoint, observer, &mut |_, _| Control::Continue)
+            .0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use proptest_attr_macro::proptest;
+
+    fn create_optimiser() -> MCOptimiser {
+        MCOptimiser::new(0., 0., 0., 0, 0, 0, None)
+    }
+
+    #[proptest]
+    fn test_energy_surface(new: f64, old: f64) {
+        let result = create_optimiser().energy_surface(new, old, 0.);
+        if new < old {
+            assert_abs_diff_eq!(result, 0.)
+        } else if new >= old {
+            assert_abs_diff_eq!(result, 1.)
+        } else {
+            panic!("This should not be reachable");
+        }
+    }
+
+    #[proptest]
+    fn test_energy_surface_temperature(new: f64, old: f64) {
+        let result = create_optimiser().energy_surface(new, old, 0.5);
+        if new < old {
+            assert!((0. ..=1.).contains(&result));
+        } else if new >= old {
+            assert_abs_diff_eq!(result, 1.)
+        } else {
+            panic!("This should not be reachable")
+        }
+    }
+
+    #[test]
+    fn expanding_cell_bounds() {
+        use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+        use crate::{LJShape2, PotentialState};
+        use std::convert::TryInto;
+
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PotentialState::from_group(LJShape2::circle(), &group).unwrap();
+        let length = state.generate_basis()[0].get_value();
+
+        let optimiser = MCOptimiser::new(0.1, 1., 0.01, 1000, 100, 0, None);
+        assert_eq!(optimiser.checkpoint(&state).bounds[0].1, length);
+        let optimiser = optimiser.with_expanding_cell(true);
+        assert_eq!(optimiser.checkpoint(&state).bounds[0].1, f64::INFINITY);
+    }
+
+    #[test]
+    fn resume_identical() {
+        use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+        use crate::{LineShape, PackedState};
+        use std::convert::TryInto;
+
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+        let optimiser = MCOptimiser::new(0.1, 0.9, 0.01, 1000, 100, 0, None);
+
+        let uninterrupted = optimiser.optimise_state(state.clone());
+
+        let checkpoint = optimiser.checkpoint(&state);
+        let (stopped, checkpoint) =
+            optimiser.optimise_checkpointed(state, checkpoint, &mut (), &mut |_, c| {
+                if c.loop_index() == 4 {
+                    Control::Stop
+                } else {
+                    Control::Continue
+                }
+            });
+        assert_eq!(checkpoint.loop_index(), 4);
+        assert!(!checkpoint.is_finished());
+
+        let (resumed, checkpoint) =
+            optimiser
+                .optimise_checkpointed(stopped, checkpoint, &mut (), &mut |_, _| Control::Continue);
+        assert!(checkpoint.is_finished());
+        assert_eq!(
+            resumed.as_positions().unwrap(),
+            uninterrupted.as_positions().unwrap()
+        );
+        assert_eq!(resumed.score(), uninterrupted.score());
+    }
+}
@@ -0,0 +1,240 @@
+//
+// moves.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fmt;
+
+use anyhow::{anyhow, Error};
+use rand::distributions::Uniform;
+use rand::prelude::*;
+use rand_distr::StandardNormal;
+
+use crate::Basis;
+
+/// A record of the changes made to a basis by a move
+///
+/// This keeps the previous values of every modified basis, allowing a rejected move to be undone.
+///
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Proposal {
+    previous: Vec<(usize, f64)>,
+}
+
+impl Proposal {
+    /// Set the value of the basis at `index`, keeping track of the previous value
+    pub fn set(&mut self, basis: &[Basis], index: usize, value: f64) -> Result<(), Error> {
+        let b = basis
+            .get(index)
+            .ok_or_else(|| anyhow!("Trying to access basis which doesn't exist"))?;
+        let previous = b.get_value();
+        b.set_value(value)?;
+        self.previous.push((index, previous));
+        Ok(())
+    }
+
+    /// Return all the modified values of the basis to their state before the move
+    pub fn revert(self, basis: &[Basis]) {
+        for (index, value) in self.previous.into_iter().rev() {
+            basis[index]
+                .set_value(value)
+                .expect("Returning to original value");
+        }
+    }
+}
+
+/// A method of proposing changes to the basis of a state
+///
+/// The move modifies the values within the basis, returning a [`Proposal`] which can be used to
+/// undo the change when the optimiser rejects it. Where the move can't be made, for example when
+/// a value would go out of bounds, the basis must be left unchanged and `None` returned.
+///
+/// The `step_size` is the current maximum size of a move, which is adjusted by the optimiser
+/// over the course of an optimisation.
+///
+pub trait MoveSet: fmt::Debug + Send + Sync {
+    fn propose(&self, basis: &[Basis], step_size: f64, rng: &mut dyn RngCore) -> Option<Proposal>;
+}
+
+/// Modify a single basis chosen at random by a uniformly distributed step
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SingleBasisMove;
+
+impl MoveSet for SingleBasisMove {
+    fn propose(&self, basis: &[Basis], step_size: f64, rng: &mut dyn RngCore) -> Option<Proposal> {
+        if basis.is_empty() {
+            return None;
+        }
+        let index = Uniform::new(0, basis.len()).sample(rng);
+        let b = &basis[index];
+        let value = b.get_value() + step_size * b.scale() * Uniform::from(-0.5..0.5).sample(rng);
+
+        let mut proposal = Proposal::default();
+        proposal.set(basis, index, value).ok()?;
+        Some(proposal)
+    }
+}
+
+/// Modify every basis at once with normally distributed steps
+///
+/// The width of the distribution is half the step size, giving a similar spread of values to
+/// the [`SingleBasisMove`].
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GaussianMove;
+
+impl MoveSet for GaussianMove {
+    fn propose(&self, basis: &[Basis], step_size: f64, rng: &mut dyn RngCore) -> Option<Proposal> {
+        let mut proposal = Proposal::default();
+        for (index, b) in basis.iter().enumerate() {
+            let step: f64 = rng.sample(StandardNormal);
+            let value = b.get_value() + 0.5 * step_size * b.scale() * step;
+            if proposal.set(basis, index, value).is_err() {
+                proposal.revert(basis);
+                return None;
+            }
+        }
+        Some(proposal)
+    }
+}
+
+/// Compress the unit cell, leaving the positions of the sites unchanged
+///
+/// A single parameter of the unit cell is chosen at random and reduced in value. Reducing any
+/// of the lengths, or the angle away from a right angle, reduces the area of the cell.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CellMove;
+
+impl MoveSet for CellMove {
+    fn propose(&self, basis: &[Basis], step_size: f64, rng: &mut dyn RngCore) -> Option<Proposal> {
+        let cell: Vec<usize> = (0..basis.len()).filter(|&i| basis[i].is_cell()).collect();
+        let index = *cell.choose(rng)?;
+        let b = &basis[index];
+        let value = b.get_value() - step_size * b.scale() * Uniform::from(0.0..0.5).sample(rng);
+
+        let mut proposal = Proposal::default();
+        proposal.set(basis, index, value).ok()?;
+        Some(proposal)
+    }
+}
+
+/// Rotate a single site to a new orientation chosen uniformly at random
+///
+/// Unlike the other moves, the size of this move is independent of the step size, which allows
+/// a site to move between orientations which are separated by a large barrier.
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RotationMove;
+
+impl MoveSet for RotationMove {
+    fn propose(&self, basis: &[Basis], _step_size: f64, rng: &mut dyn RngCore) -> Option<Proposal> {
+        let angles: Vec<usize> = (0..basis.len()).filter(|&i| basis[i].is_angle()).collect();
+        let index = *angles.choose(rng)?;
+        let (min, max) = basis[index].bounds();
+        let value = Uniform::new_inclusive(min, max).sample(rng);
+
+        let mut proposal = Proposal::default();
+        proposal.set(basis, index, value).ok()?;
+        Some(proposal)
+    }
+}
+
+/// Choose one of a collection of moves uniformly at random
+#[derive(Debug)]
+pub struct MixedMove {
+    moves: Vec<Box<dyn MoveSet>>,
+}
+
+impl MixedMove {
+    pub fn new(moves: Vec<Box<dyn MoveSet>>) -> Self {
+        Self { moves }
+    }
+}
+
+impl MoveSet for MixedMove {
+    fn propose(&self, basis: &[Basis], step_size: f64, rng: &mut dyn RngCore) -> Option<Proposal> {
+        let index = Uniform::new(0, self.moves.len()).sample(rng);
+        self.moves[index].propose(basis, step_size, rng)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // Values which are unchanged should be exactly equal, so using standard equality is valid.
+    #![allow(clippy::float_cmp)]
+
+    use rand_pcg::Pcg64Mcg;
+
+    use super::*;
+    use crate::traits::State;
+    use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+    use crate::{LineShape, PackedState};
+    use std::convert::TryInto;
+
+    fn create_state() -> PackedState<LineShape> {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap()
+    }
+
+    fn values(basis: &[Basis]) -> Vec<f64> {
+        basis.iter().map(Basis::get_value).collect()
+    }
+
+    #[test]
+    fn revert_proposal() {
+        let state = create_state();
+        let basis = state.generate_basis();
+        let initial = values(&basis);
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+
+        // Moves which go out of bounds are not made, so try until one is successful
+        let proposal = (0..100)
+            .find_map(|_| GaussianMove.propose(&basis, 0.01, &mut rng))
+            .unwrap();
+        assert_ne!(values(&basis), initial);
+        proposal.revert(&basis);
+        assert_eq!(values(&basis), initial);
+    }
+
+    #[test]
+    fn cell_move_compresses() {
+        let state = create_state();
+        let basis = state.generate_basis();
+        let area = state.cell.area();
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+
+        for _ in 0..10 {
+            CellMove.propose(&basis, 0.1, &mut rng);
+        }
+        assert!(state.cell.area() < area);
+    }
+
+    #[test]
+    fn rotation_move_only_angles() {
+        let state = create_state();
+        let basis = state.generate_basis();
+        let initial = values(&basis);
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+
+        RotationMove.propose(&basis, 0., &mut rng).unwrap();
+        for (b, (before, after)) in basis.iter().zip(initial.iter().zip(values(&basis))) {
+            if !b.is_angle() {
+                assert_eq!(*before, after);
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_bounds_unchanged() {
+        let state = create_state();
+        let basis = state.generate_basis();
+        let initial = values(&basis);
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+
+        // A step this large will take at least one value out of bounds
+        assert!(GaussianMove.propose(&basis, 1e6, &mut rng).is_none());
+        assert_eq!(values(&basis), initial);
+    }
+}
@@ -0,0 +1,331 @@
+//
+// observer.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::traits::State;
+
+/// The values describing an optimisation at the end of an inner loop
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The number of inner loops which have been completed
+    pub loop_index: u64,
+    /// The number of Monte Carlo steps which have been completed
+    pub steps: u64,
+    /// The temperature used for the inner loop
+    pub kt: f64,
+    /// The score of the state at the end of the inner loop
+    pub score: f64,
+    /// The best score found so far in the optimisation
+    pub best_score: f64,
+    /// The multiplier of the maximum step size used for the inner loop
+    pub step_ratio: f64,
+    /// The fraction of the moves in the inner loop which were accepted
+    pub acceptance: f64,
+}
+
+impl Snapshot {
+    pub const CSV_HEADER: &'static str = "loop,steps,kt,score,best_score,step_ratio,acceptance";
+
+    pub fn as_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{}",
+            self.loop_index,
+            self.steps,
+            self.kt,
+            self.score,
+            self.best_score,
+            self.step_ratio,
+            self.acceptance
+        )
+    }
+}
+
+/// A hook which is called at the end of every inner loop of an optimisation
+///
+/// This allows for monitoring the progress of an optimisation, with the [`Snapshot`] describing
+/// the optimisation, and the state in its current configuration. An observer can be a closure
+/// taking the same arguments as [`Observer::observe`].
+///
+/// ```
+/// # use std::convert::TryInto;
+/// # use crystal_packing::wallpaper::{WallpaperGroup, WallpaperGroups};
+/// use crystal_packing::{LineShape, MCOptimiser, Optimiser, PackedState, Snapshot};
+/// # let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+/// let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+/// let optimiser = MCOptimiser::new(0.1, 0.9, 0.01, 1000, 100, 0, None);
+///
+/// let mut scores: Vec<f64> = vec![];
+/// optimiser.optimise_observed(state, &mut |snapshot: &Snapshot, _: &PackedState<LineShape>| {
+///     scores.push(snapshot.score);
+///     Ok(())
+/// });
+/// # assert_eq!(scores.len(), 10);
+/// ```
+///
+/// Where an observer returns an error, the optimisation will continue, logging the error.
+///
+pub trait Observer<S: State> {
+    fn observe(&mut self, snapshot: &Snapshot, state: &S) -> Result<(), Error>;
+}
+
+/// The empty observer, which does nothing
+impl<S: State> Observer<S> for () {
+    fn observe(&mut self, _snapshot: &Snapshot, _state: &S) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<S, F> Observer<S> for F
+where
+    S: State,
+    F: FnMut(&Snapshot, &S) -> Result<(), Error>,
+{
+    fn observe(&mut self, snapshot: &Snapshot, state: &S) -> Result<(), Error> {
+        self(snapshot, state)
+    }
+}
+
+/// Pass each snapshot to a collection of observers
+pub struct Observers<'a, S: State> {
+    observers: Vec<Box<dyn Observer<S> + 'a>>,
+}
+
+impl<'a, S: State> Default for Observers<'a, S> {
+    fn default() -> Self {
+        Self { observers: vec![] }
+    }
+}
+
+impl<'a, S: State> Observers<'a, S> {
+    pub fn push(&mut self, observer: impl Observer<S> + 'a) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+}
+
+impl<'a, S: State> Observer<S> for Observers<'a, S> {
+    /// Pass the snapshot to every observer, returning the first error once all are called
+    ///
+    /// A failing observer doesn't stop the observers after it, like one saving a checkpoint.
+    ///
+    fn observe(&mut self, snapshot: &Snapshot, state: &S) -> Result<(), Error> {
+        let mut result = Ok(());
+        for observer in self.observers.iter_mut() {
+            if let Err(e) = observer.observe(snapshot, state) {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+}
+
+/// Write each snapshot as a row of a CSV file
+pub struct CsvTrace<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> CsvTrace<W> {
+    /// Create a new trace, writing the header to the writer
+    pub fn new(mut writer: W) -> Result<Self, Error> {
+        writeln!(writer, "{}", Snapshot::CSV_HEADER)?;
+        Ok(Self { writer })
+    }
+}
+
+impl CsvTrace<BufWriter<File>> {
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Continue writing to an existing trace, only writing the header to a new file
+    pub fn append(path: &Path) -> Result<Self, Error> {
+        let exists = path.exists();
+        let writer = BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?);
+        if exists {
+            Ok(Self { writer })
+        } else {
+            Self::new(writer)
+        }
+    }
+}
+
+impl<S: State, W: Write> Observer<S> for CsvTrace<W> {
+    fn observe(&mut self, snapshot: &Snapshot, _state: &S) -> Result<(), Error> {
+        writeln!(self.writer, "{}", snapshot.as_csv())?;
+        Ok(())
+    }
+}
+
+/// Write the state every `every` inner loops as a frame of an extended XYZ trajectory
+pub struct XYZTrajectory<W: Write> {
+    writer: W,
+    every: u64,
+}
+
+impl<W: Write> XYZTrajectory<W> {
+    pub fn new(writer: W, every: u64) -> Self {
+        Self { writer, every }
+    }
+}
+
+impl XYZTrajectory<BufWriter<File>> {
+    pub fn create(path: &Path, every: u64) -> Result<Self, Error> {
+        Ok(Self::new(BufWriter::new(File::create(path)?), every))
+    }
+
+    /// Continue writing frames to the end of an existing trajectory
+    pub fn append(path: &Path, every: u64) -> Result<Self, Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(BufWriter::new(file), every))
+    }
+}
+
+impl<S: State, W: Write> Observer<S> for XYZTrajectory<W> {
+    fn observe(&mut self, snapshot: &Snapshot, state: &S) -> Result<(), Error> {
+        if snapshot.loop_index.checked_rem(self.every) == Some(0) {
+            write!(self.writer, "{}", state.as_xyz())?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the state every `every` inner loops to a numbered series of SVG files
+///
+/// The files are named using the `prefix` followed by the loop index, so a prefix of
+/// `trajectory` will create the files `trajectory-00001.svg`, `trajectory-00002.svg`, ...
+///
+pub struct SVGTrajectory {
+    prefix: PathBuf,
+    every: u64,
+}
+
+impl SVGTrajectory {
+    pub fn new(prefix: &Path, every: u64) -> Self {
+        Self {
+            prefix: prefix.to_path_buf(),
+            every,
+        }
+    }
+
+    pub fn frame_path(&self, loop_index: u64) -> PathBuf {
+        let mut name = self.prefix.file_stem().unwrap_or_default().to_os_string();
+        name.push(format!("-{:05}.svg", loop_index));
+        self.prefix.with_file_name(name)
+    }
+}
+
+impl<S: State> Observer<S> for SVGTrajectory {
+    fn observe(&mut self, snapshot: &Snapshot, state: &S) -> Result<(), Error> {
+        if snapshot.loop_index.checked_rem(self.every) == Some(0) {
+            svg::save(self.frame_path(snapshot.loop_index), &state.as_svg())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+    use crate::{LineShape, MCOptimiser, Optimiser, PackedState};
+
+    fn create_state() -> PackedState<LineShape> {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap()
+    }
+
+    #[test]
+    fn observed_every_loop() {
+        let mut snapshots = vec![];
+        let optimiser = MCOptimiser::new(0.1, 0.9, 0.01, 1000, 100, 0, None);
+        optimiser.optimise_observed(
+            create_state(),
+            &mut |snapshot: &Snapshot, _: &PackedState<LineShape>| {
+                snapshots.push(*snapshot);
+                Ok(())
+            },
+        );
+        assert_eq!(snapshots.len(), 10);
+        assert_eq!(snapshots[9].steps, 1000);
+        for snapshot in snapshots {
+            assert!(snapshot.score <= snapshot.best_score);
+        }
+    }
+
+    #[test]
+    fn failing_observer_continues() {
+        let mut count = 0;
+        {
+            let mut observers = Observers::default();
+            observers.push(|_: &Snapshot, _: &PackedState<LineShape>| {
+                Err(anyhow::anyhow!("Unable to observe"))
+            });
+            observers.push(|_: &Snapshot, _: &PackedState<LineShape>| {
+                count += 1;
+                Ok(())
+            });
+            let optimiser = MCOptimiser::new(0.1, 0.9, 0.01, 1000, 100, 0, None);
+            let snapshot = Snapshot {
+                loop_index: 1,
+                steps: 100,
+                kt: 0.1,
+                score: 0.5,
+                best_score: 0.5,
+                step_ratio: 1.,
+                acceptance: 0.5,
+            };
+            let state = create_state();
+            let error = observers.observe(&snapshot, &state).unwrap_err();
+            assert_eq!(error.to_string(), "Unable to observe");
+            optimiser.optimise_observed(state, &mut observers);
+        }
+        // Every loop of the optimisation is counted despite the first observer failing
+        assert_eq!(count, 11);
+    }
+
+    #[test]
+    fn csv_trace() {
+        let optimiser = MCOptimiser::new(0.1, 0.9, 0.01, 1000, 100, 0, None);
+        let mut trace = CsvTrace::new(vec![]).unwrap();
+        optimiser.optimise_observed(create_state(), &mut trace);
+
+        let output = String::from_utf8(trace.writer).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], Snapshot::CSV_HEADER);
+    }
+
+    #[test]
+    fn xyz_trajectory() {
+        let optimiser = MCOptimiser::new(0.1, 0.9, 0.01, 1000, 100, 0, None);
+        let mut trajectory = XYZTrajectory::new(vec![], 5);
+        optimiser.optimise_observed(create_state(), &mut trajectory);
+
+        let output = String::from_utf8(trajectory.writer).unwrap();
+        // Two frames, each of 2 particles with 2 header lines
+        assert_eq!(output.lines().count(), 8);
+    }
+
+    #[test]
+    fn svg_frame_path() {
+        let trajectory = SVGTrajectory::new(Path::new("output/traj.svg"), 1);
+        assert_eq!(
+            trajectory.frame_path(12),
+            PathBuf::from("output/traj-00012.svg")
+        );
+    }
+}
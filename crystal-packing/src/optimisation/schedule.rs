@@ -0,0 +1,246 @@
+//
+// schedule.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fmt;
+
+/// The state of an optimisation at the end of an inner loop
+///
+/// This is all the information a cooling schedule has available when choosing the temperature
+/// for the next inner loop.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// The number of inner loops which have been completed
+    pub loop_index: u64,
+    /// The total number of inner loops in the optimisation
+    pub total_loops: u64,
+    /// The temperature used for the inner loop which has just completed
+    pub kt: f64,
+    /// The fraction of the moves in the inner loop which were accepted
+    pub acceptance: f64,
+    /// The number of consecutive inner loops in which the best score has not improved
+    pub loops_since_improvement: u64,
+}
+
+impl Progress {
+    /// The fraction of the optimisation which has been completed
+    pub fn fraction(&self) -> f64 {
+        if self.total_loops == 0 {
+            1.
+        } else {
+            self.loop_index as f64 / self.total_loops as f64
+        }
+    }
+}
+
+/// How the temperature of a Monte Carlo optimisation changes over time
+///
+/// The schedule is evaluated at the end of every inner loop, taking the progress of the
+/// optimisation and returning the temperature for the next inner loop. Since all the information
+/// is passed in through the [`Progress`], a schedule doesn't need to keep any state of its own.
+///
+pub trait CoolingSchedule: fmt::Debug + Send + Sync {
+    /// The temperature of the first inner loop
+    fn initial(&self) -> f64;
+    /// The temperature of the next inner loop
+    fn next(&self, progress: &Progress) -> f64;
+}
+
+/// Reduce the temperature by a constant ratio every inner loop
+///
+/// ```
+/// use crystal_packing::{CoolingSchedule, ExponentialSchedule};
+/// let schedule = ExponentialSchedule::new(1., 0.5);
+/// assert_eq!(schedule.initial(), 1.);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExponentialSchedule {
+    kt_start: f64,
+    ratio: f64,
+}
+
+impl ExponentialSchedule {
+    pub fn new(kt_start: f64, ratio: f64) -> Self {
+        Self { kt_start, ratio }
+    }
+}
+
+impl CoolingSchedule for ExponentialSchedule {
+    fn initial(&self) -> f64 {
+        self.kt_start
+    }
+
+    fn next(&self, progress: &Progress) -> f64 {
+        progress.kt * self.ratio
+    }
+}
+
+/// Reduce the temperature linearly from `kt_start` to `kt_finish`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinearSchedule {
+    kt_start: f64,
+    kt_finish: f64,
+}
+
+impl LinearSchedule {
+    pub fn new(kt_start: f64, kt_finish: f64) -> Self {
+        Self {
+            kt_start,
+            kt_finish,
+        }
+    }
+}
+
+impl CoolingSchedule for LinearSchedule {
+    fn initial(&self) -> f64 {
+        self.kt_start
+    }
+
+    fn next(&self, progress: &Progress) -> f64 {
+        let kt = self.kt_start + (self.kt_finish - self.kt_start) * progress.fraction();
+        f64::max(kt, 0.)
+    }
+}
+
+/// An adaptive schedule which follows a target acceptance rate
+///
+/// This is the schedule of [Lam and Delosme](https://doi.org/10.1109/ICCAD.1988.122461), in the
+/// modified form of Swartz and Sechen. Rather than prescribing the temperature, the schedule
+/// prescribes the fraction of moves which should be accepted, starting with nearly all moves
+/// being accepted, holding at 44% for the bulk of the optimisation, then decaying towards zero.
+/// The temperature is increased or decreased by `rate` after each inner loop to follow the
+/// target.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LamSchedule {
+    kt_start: f64,
+    rate: f64,
+}
+
+impl LamSchedule {
+    pub fn new(kt_start: f64, rate: f64) -> Self {
+        Self { kt_start, rate }
+    }
+
+    /// The fraction of moves which should be accepted at a fraction of the optimisation
+    pub fn target_acceptance(fraction: f64) -> f64 {
+        if fraction < 0.15 {
+            0.44 + 0.56 * f64::powf(560., -fraction / 0.15)
+        } else if fraction < 0.65 {
+            0.44
+        } else {
+            0.44 * f64::powf(440., -(fraction - 0.65) / 0.35)
+        }
+    }
+}
+
+impl CoolingSchedule for LamSchedule {
+    fn initial(&self) -> f64 {
+        self.kt_start
+    }
+
+    fn next(&self, progress: &Progress) -> f64 {
+        if progress.acceptance > Self::target_acceptance(progress.fraction()) {
+            progress.kt * (1. - self.rate)
+        } else {
+            progress.kt / (1. - self.rate)
+        }
+    }
+}
+
+/// An exponential schedule which returns to the initial temperature when stuck
+///
+/// When the best score hasn't improved in `patience` inner loops the temperature is reset to
+/// `kt_start`, allowing the optimisation to escape from a local minimum.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReheatSchedule {
+    kt_start: f64,
+    ratio: f64,
+    patience: u64,
+}
+
+impl ReheatSchedule {
+    pub fn new(kt_start: f64, ratio: f64, patience: u64) -> Self {
+        Self {
+            kt_start,
+            ratio,
+            patience,
+        }
+    }
+}
+
+impl CoolingSchedule for ReheatSchedule {
+    fn initial(&self) -> f64 {
+        self.kt_start
+    }
+
+    fn next(&self, progress: &Progress) -> f64 {
+        let stuck = progress.loops_since_improvement;
+        if stuck > 0 && stuck.checked_rem(self.patience) == Some(0) {
+            self.kt_start
+        } else {
+            progress.kt * self.ratio
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    fn progress(loop_index: u64, kt: f64) -> Progress {
+        Progress {
+            loop_index,
+            total_loops: 10,
+            kt,
+            acceptance: 0.5,
+            loops_since_improvement: 0,
+        }
+    }
+
+    #[test]
+    fn exponential() {
+        let schedule = ExponentialSchedule::new(1., 0.5);
+        assert_abs_diff_eq!(schedule.next(&progress(1, schedule.initial())), 0.5);
+    }
+
+    #[test]
+    fn linear() {
+        let schedule = LinearSchedule::new(1., 0.);
+        assert_abs_diff_eq!(schedule.initial(), 1.);
+        assert_abs_diff_eq!(schedule.next(&progress(5, 1.)), 0.5);
+        assert_abs_diff_eq!(schedule.next(&progress(10, 1.)), 0.);
+    }
+
+    #[test]
+    fn lam_target() {
+        assert_abs_diff_eq!(LamSchedule::target_acceptance(0.), 1.);
+        assert_abs_diff_eq!(LamSchedule::target_acceptance(0.5), 0.44);
+        assert_abs_diff_eq!(LamSchedule::target_acceptance(1.), 0.001);
+    }
+
+    #[test]
+    fn lam_adapts() {
+        let schedule = LamSchedule::new(1., 0.1);
+        // At the start nearly every move should be accepted, so increase the temperature
+        assert!(schedule.next(&progress(0, 1.)) > 1.);
+        // In the middle 44% should be accepted, so decrease the temperature
+        assert!(schedule.next(&progress(5, 1.)) < 1.);
+    }
+
+    #[test]
+    fn reheat() {
+        let schedule = ReheatSchedule::new(1., 0.5, 3);
+        let mut stuck = progress(5, 0.25);
+        assert_abs_diff_eq!(schedule.next(&stuck), 0.125);
+        stuck.loops_since_improvement = 3;
+        assert_abs_diff_eq!(schedule.next(&stuck), 1.);
+    }
+}
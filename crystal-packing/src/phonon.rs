@@ -0,0 +1,492 @@
+//
+// phonon.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+
+use itertools::iproduct;
+use nalgebra::{Complex, DMatrix, DVector, Matrix2, Point2, Rotation2, SymmetricEigen, Vector2};
+
+use crate::traits::{Inertia, Potential, Shape};
+use crate::{Cell2, CrystalFamily, PotentialState, Transform2};
+
+/// The mass, centre of mass and moment of inertia of a shape
+fn mass_properties<S: Inertia>(shape: &S) -> (f64, Point2<f64>, f64) {
+    let masses = shape.point_masses();
+    let mass: f64 = masses.iter().map(|(_, m)| m).sum();
+    let centre = masses
+        .iter()
+        .fold(Vector2::zeros(), |acc, (p, m)| acc + p.coords * *m / mass);
+    let inertia = masses
+        .iter()
+        .map(|(p, m)| m * (p.coords - centre).norm_squared())
+        .sum();
+    (mass, Point2::from(centre), inertia)
+}
+
+/// Move a shape as a rigid body, translating the centre and rotating about it
+///
+/// The displacement is the translation along x and y, followed by an optional rotation.
+///
+fn displace<S: Shape>(shape: &S, centre: Point2<f64>, displacement: &[f64]) -> S {
+    let angle = displacement.get(2).copied().unwrap_or(0.);
+    let rotated = Rotation2::new(angle) * centre;
+    shape.transform(&Transform2::new(
+        angle,
+        (
+            centre.x + displacement[0] - rotated.x,
+            centre.y + displacement[1] - rotated.y,
+        ),
+    ))
+}
+
+/// The second derivative of a function about the origin using central differences
+fn second_derivative(f: &dyn Fn(&[f64]) -> f64, size: usize, a: usize, b: usize, h: f64) -> f64 {
+    let shifted = |da: f64, db: f64| {
+        let mut values = vec![0.; size];
+        values[a] += da;
+        values[b] += db;
+        f(&values)
+    };
+    if a == b {
+        (shifted(h, 0.) - 2. * shifted(0., 0.) + shifted(-h, 0.)) / (h * h)
+    } else {
+        (shifted(h, h) - shifted(h, -h) - shifted(-h, h) + shifted(-h, -h)) / (4. * h * h)
+    }
+}
+
+/// The points of the Brillouin zone visited when calculating the dispersion
+///
+/// The points are in fractional coordinates of the reciprocal lattice. Hexagonal cells follow
+/// the path Γ-M-K-Γ, while all other cells follow the path Γ-X-M-Y-Γ through the centres of
+/// the edges and the corner of the reciprocal cell.
+///
+/// ```
+/// use crystal_packing::{brillouin_zone_path, Cell2, CrystalFamily};
+/// let path = brillouin_zone_path(&Cell2::from_family(CrystalFamily::Tetragonal, 1.));
+/// let labels: Vec<_> = path.iter().map(|(label, _)| *label).collect();
+/// assert_eq!(labels, vec!["Γ", "X", "M", "Y", "Γ"]);
+/// ```
+///
+pub fn brillouin_zone_path(cell: &Cell2) -> Vec<(&'static str, Vector2<f64>)> {
+    match cell.family() {
+        CrystalFamily::Hexagonal => {
+            let reciprocal = reciprocal_lattice(cell);
+            // The corner of the Brillouin zone depends on the angle between the vectors
+            let k = if reciprocal.column(0).dot(&reciprocal.column(1)) < 0. {
+                Vector2::new(2. / 3., 1. / 3.)
+            } else {
+                Vector2::new(1. / 3., 1. / 3.)
+            };
+            vec![
+                ("Γ", Vector2::zeros()),
+                ("M", Vector2::new(0.5, 0.)),
+                ("K", k),
+                ("Γ", Vector2::zeros()),
+            ]
+        }
+        _ => vec![
+            ("Γ", Vector2::zeros()),
+            ("X", Vector2::new(0.5, 0.)),
+            ("M", Vector2::new(0.5, 0.5)),
+            ("Y", Vector2::new(0., 0.5)),
+            ("Γ", Vector2::zeros()),
+        ],
+    }
+}
+
+/// The reciprocal lattice vectors of the cell as the columns of a matrix, including the 2π
+fn reciprocal_lattice(cell: &Cell2) -> Matrix2<f64> {
+    2. * PI
+        * cell
+            .lattice()
+            .try_inverse()
+            .expect("The unit cell has no area")
+            .transpose()
+}
+
+/// The frequencies of the vibrational modes at a single point of the Brillouin zone
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandPoint {
+    /// The distance along the path through the Brillouin zone
+    pub distance: f64,
+    /// The wavevector in fractional coordinates of the reciprocal lattice
+    pub k: Vector2<f64>,
+    /// The name of the high symmetry point at this wavevector
+    pub label: Option<&'static str>,
+    /// The frequency of each mode in increasing order, where imaginary frequencies are negative
+    pub frequencies: Vec<f64>,
+    /// The number of modes with an imaginary frequency, which are the first of the frequencies
+    pub imaginary: usize,
+}
+
+impl BandPoint {
+    pub const CSV_HEADER: &'static str = "distance,k1,k2,label,band,frequency,imaginary";
+
+    /// Each band at this point of the Brillouin zone as a separate row
+    pub fn as_csv(&self) -> String {
+        self.frequencies
+            .iter()
+            .enumerate()
+            .map(|(band, frequency)| {
+                format!(
+                    "{},{},{},{},{},{},{}",
+                    self.distance,
+                    self.k.x,
+                    self.k.y,
+                    self.label.unwrap_or(""),
+                    band,
+                    frequency,
+                    band < self.imaginary
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// The harmonic vibrations of a crystal of rigid molecules interacting through a potential
+///
+/// Each molecule is able to translate and, when it has a moment of inertia, rotate about its
+/// centre of mass. The force constants between molecules in the unit cell and their periodic
+/// images are found from finite differences of the pair energies, from which the Hessian of the
+/// lattice energy and the dynamical matrix at any wavevector follow. A structure which is a true
+/// minimum of the lattice energy has no modes with an imaginary frequency, which are reported
+/// as negative frequencies.
+///
+/// ```
+/// # use std::convert::TryInto;
+/// # use crystal_packing::wallpaper::{WallpaperGroup, WallpaperGroups};
+/// use crystal_packing::{brillouin_zone_path, LJShape2, Phonons, PotentialState};
+/// # let group: WallpaperGroup = WallpaperGroups::p1.try_into().unwrap();
+/// let state = PotentialState::from_group(LJShape2::circle(), &group).unwrap();
+/// let phonons = Phonons::from_state(&state, 2);
+///
+/// let bands = phonons.dispersion(&brillouin_zone_path(&state.cell), 10);
+/// assert_eq!(bands[0].frequencies.len(), 2);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct Phonons {
+    /// The number of degrees of freedom of each shape
+    degrees: usize,
+    /// The force constants between the shapes in the unit cell and each periodic image
+    force_constants: Vec<(Vector2<f64>, DMatrix<f64>)>,
+    /// The mass or moment of inertia for each degree of freedom
+    masses: DVector<f64>,
+    reciprocal: Matrix2<f64>,
+    tolerance: f64,
+}
+
+impl Phonons {
+    /// The size of the displacements used for the finite differences
+    const STEP: f64 = 1e-4;
+
+    /// Calculate the force constants including periodic images up to `shells` cells away
+    pub fn from_state<S>(state: &PotentialState<S>, shells: i64) -> Self
+    where
+        S: Shape + Potential + Inertia,
+    {
+        let shapes: Vec<S> = state
+            .cartesian_positions()
+            .map(|p| state.shape.transform(&p))
+            .collect();
+        let properties: Vec<_> = shapes.iter().map(mass_properties).collect();
+
+        // Rotations are only meaningful when the shape has a moment of inertia
+        let degrees = if properties.iter().all(|&(_, _, inertia)| inertia > 1e-12) {
+            3
+        } else {
+            2
+        };
+        let size = degrees * shapes.len();
+        let masses = DVector::from_iterator(
+            size,
+            properties.iter().flat_map(|&(mass, _, inertia)| {
+                vec![mass, mass, inertia].into_iter().take(degrees)
+            }),
+        );
+
+        let mut on_site = DMatrix::zeros(size, size);
+        let mut force_constants: Vec<(Vector2<f64>, DMatrix<f64>)> =
+            iproduct!(-shells..=shells, -shells..=shells)
+                .map(|(x, y)| {
+                    let (tx, ty) = state.cell.to_cartesian(x as f64, y as f64);
+                    let translation = Transform2::new(0., (tx, ty));
+                    let mut block = DMatrix::zeros(size, size);
+                    for (i, j) in iproduct!(0..shapes.len(), 0..shapes.len()) {
+                        if x == 0 && y == 0 && i == j {
+                            continue;
+                        }
+                        let centre = properties[i].1;
+                        let other = shapes[j].transform(&translation);
+                        let other_centre = translation * properties[j].1;
+                        // The mean of the energy in both directions keeps the force constants
+                        // symmetric when the potential isn't
+                        let pair = |q: &[f64]| {
+                            let first = displace(&shapes[i], centre, &q[..degrees]);
+                            let second = displace(&other, other_centre, &q[degrees..]);
+                            (first.energy(&second) + second.energy(&first)) / 2.
+                        };
+                        for (a, b) in iproduct!(0..degrees, 0..degrees) {
+                            let (row, column) = (i * degrees + a, j * degrees + b);
+                            block[(row, column)] +=
+                                second_derivative(&pair, 2 * degrees, a, degrees + b, Self::STEP);
+                            // The pair energy is unchanged by translating both shapes, so
+                            // derivatives with respect to the translation of the first shape are
+                            // the negative of those for the second. Using these ensures the
+                            // translations have no energy despite the finite differences.
+                            on_site[(row, i * degrees + b)] += match (a, b) {
+                                (a, b) if a < 2 => -second_derivative(
+                                    &pair,
+                                    2 * degrees,
+                                    degrees + a,
+                                    b,
+                                    Self::STEP,
+                                ),
+                                (a, b) if b < 2 => -second_derivative(
+                                    &pair,
+                                    2 * degrees,
+                                    a,
+                                    degrees + b,
+                                    Self::STEP,
+                                ),
+                                (a, b) => second_derivative(&pair, 2 * degrees, a, b, Self::STEP),
+                            };
+                        }
+                    }
+                    (Vector2::new(x as f64, y as f64), block)
+                })
+                .collect();
+        for (translation, block) in force_constants.iter_mut() {
+            if *translation == Vector2::zeros() {
+                *block += &on_site;
+            }
+        }
+
+        Self {
+            degrees,
+            force_constants,
+            masses,
+            reciprocal: reciprocal_lattice(&state.cell),
+            tolerance: 1e-3,
+        }
+    }
+
+    /// The frequency below which a negative frequency is treated as zero
+    ///
+    /// This accounts for the numerical error in the finite differences, which is most
+    /// noticeable for the acoustic modes at Γ.
+    ///
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// The number of degrees of freedom of each shape, being 3 when rotations are included
+    pub fn degrees_of_freedom(&self) -> usize {
+        self.degrees
+    }
+
+    /// The Hessian of the lattice energy with respect to the motion of each shape
+    ///
+    /// This is the second derivative of the energy of the unit cell when every periodic image
+    /// of a shape moves along with it, which is the force constant matrix at Γ.
+    ///
+    pub fn hessian(&self) -> DMatrix<f64> {
+        let size = self.masses.len();
+        let hessian = self
+            .force_constants
+            .iter()
+            .fold(DMatrix::zeros(size, size), |acc, (_, block)| acc + block);
+        (&hessian + hessian.transpose()) / 2.
+    }
+
+    /// The eigenvalues of the Hessian in increasing order
+    pub fn hessian_eigenvalues(&self) -> Vec<f64> {
+        let mut eigenvalues: Vec<f64> = SymmetricEigen::new(self.hessian())
+            .eigenvalues
+            .iter()
+            .copied()
+            .collect();
+        eigenvalues.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        eigenvalues
+    }
+
+    /// The mass weighted dynamical matrix at a wavevector in fractional coordinates
+    pub fn dynamical_matrix(&self, k: Vector2<f64>) -> DMatrix<Complex<f64>> {
+        let size = self.masses.len();
+        let mut dynamical = DMatrix::zeros(size, size);
+        for (translation, block) in self.force_constants.iter() {
+            let angle = 2. * PI * k.dot(translation);
+            let phase = Complex::new(angle.cos(), angle.sin());
+            dynamical += block.map(|v| Complex::new(v, 0.) * phase);
+        }
+        let dynamical = (&dynamical + dynamical.adjoint()) / Complex::new(2., 0.);
+        DMatrix::from_fn(size, size, |i, j| {
+            dynamical[(i, j)] / f64::sqrt(self.masses[i] * self.masses[j])
+        })
+    }
+
+    /// The frequencies of the modes at a wavevector in increasing order
+    ///
+    /// An imaginary frequency, which indicates the structure is unstable to the motion of that
+    /// mode, is given as a negative value.
+    ///
+    pub fn frequencies(&self, k: Vector2<f64>) -> Vec<f64> {
+        let mut frequencies: Vec<f64> = self
+            .dynamical_matrix(k)
+            .symmetric_eigenvalues()
+            .iter()
+            .map(|&e| e.signum() * e.abs().sqrt())
+            .collect();
+        frequencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        frequencies
+    }
+
+    /// The frequencies along a path of straight lines through the Brillouin zone
+    ///
+    /// Each segment of the path is divided into `points` equal steps.
+    ///
+    pub fn dispersion(
+        &self,
+        path: &[(&'static str, Vector2<f64>)],
+        points: usize,
+    ) -> Vec<BandPoint> {
+        let mut bands = Vec::new();
+        let mut distance = 0.;
+        for (index, (label, k)) in path.iter().enumerate() {
+            if let Some((_, previous)) = index.checked_sub(1).and_then(|i| path.get(i)) {
+                let step = (k - previous) / points as f64;
+                let length = (self.reciprocal * step).norm();
+                for point in 1..points {
+                    distance += length;
+                    bands.push(self.band_point(distance, previous + step * point as f64, None));
+                }
+                distance += length;
+            }
+            bands.push(self.band_point(distance, *k, Some(*label)));
+        }
+        bands
+    }
+
+    /// The classical harmonic vibrational free energy of the unit cell
+    ///
+    /// Each mode contributes kT ln(ω / kT), in units where Planck's constant is 1, averaged
+    /// over an `n` by `n` grid of wavevectors spanning the reciprocal cell, starting at Γ. The
+    /// two acoustic modes at Γ, which have no frequency, are excluded. When any of the other
+    /// modes has an imaginary frequency the structure isn't a minimum of the lattice energy, so
+    /// there is no harmonic free energy.
+    ///
+    pub fn vibrational_free_energy(&self, temperature: f64, n: usize) -> Option<f64> {
+        let mut sum = 0.;
+        for (i, j) in iproduct!(0..n, 0..n) {
+            let k = Vector2::new(i as f64, j as f64) / n as f64;
+            let acoustic = if i == 0 && j == 0 { 2 } else { 0 };
+            for frequency in self.frequencies(k).into_iter().skip(acoustic) {
+                if frequency <= 0. {
+                    return None;
+                }
+                sum += f64::ln(frequency / temperature);
+            }
+        }
+        Some(temperature * sum / (n * n) as f64)
+    }
+
+    fn band_point(&self, distance: f64, k: Vector2<f64>, label: Option<&'static str>) -> BandPoint {
+        let frequencies = self.frequencies(k);
+        let imaginary = frequencies.iter().filter(|&&f| f < -self.tolerance).count();
+        BandPoint {
+            distance,
+            k,
+            label,
+            frequencies,
+            imaginary,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::wallpaper::{Wallpaper, WyckoffSite};
+    use crate::LJShape2;
+
+    fn lattice(shape: LJShape2, family: CrystalFamily, length: f64) -> PotentialState<LJShape2> {
+        let wallpaper = Wallpaper {
+            name: String::from("p1"),
+            family,
+        };
+        let isopointal = vec![WyckoffSite {
+            letter: 'a',
+            symmetries: vec![Transform2::from_operations("x,y").unwrap()],
+            num_rotations: 1,
+            mirror_primary: false,
+            mirror_secondary: false,
+        }];
+        let mut state = PotentialState::initialise(shape, wallpaper, &isopointal).unwrap();
+        state.cell = Cell2::from_family(family, length);
+        state
+    }
+
+    #[test]
+    fn translations_have_no_energy() {
+        let state = lattice(LJShape2::circle(), CrystalFamily::Hexagonal, 1.2);
+        let hessian = Phonons::from_state(&state, 3).hessian();
+        let translation = DVector::from_vec(vec![1., 0.]);
+        assert_abs_diff_eq!((hessian * translation).norm(), 0., epsilon = 1e-4);
+    }
+
+    #[test]
+    fn hexagonal_lattice_stable() {
+        let state = lattice(LJShape2::circle(), CrystalFamily::Hexagonal, 1.11);
+        let phonons = Phonons::from_state(&state, 3);
+        let bands = phonons.dispersion(&brillouin_zone_path(&state.cell), 10);
+        assert_eq!(bands.len(), 31);
+        assert!(bands.iter().all(|b| b.imaginary == 0));
+        // The acoustic modes have no frequency at Γ
+        assert_abs_diff_eq!(bands[0].frequencies[1], 0., epsilon = 1e-5);
+        assert!(bands[10].frequencies[0] > 1.);
+    }
+
+    #[test]
+    fn square_lattice_unstable() {
+        let state = lattice(LJShape2::circle(), CrystalFamily::Tetragonal, 1.11);
+        let phonons = Phonons::from_state(&state, 3);
+        let bands = phonons.dispersion(&brillouin_zone_path(&state.cell), 10);
+        assert!(bands.iter().any(|b| b.imaginary > 0));
+    }
+
+    #[test]
+    fn softer_lattice_more_entropy() {
+        let compressed = Phonons::from_state(
+            &lattice(LJShape2::circle(), CrystalFamily::Hexagonal, 1.08),
+            3,
+        );
+        let expanded = Phonons::from_state(
+            &lattice(LJShape2::circle(), CrystalFamily::Hexagonal, 1.14),
+            3,
+        );
+        let compressed = compressed.vibrational_free_energy(0.1, 4).unwrap();
+        let expanded = expanded.vibrational_free_energy(0.1, 4).unwrap();
+        assert!(expanded < compressed);
+    }
+
+    #[test]
+    fn molecules_rotate() {
+        let shape = LJShape2::from_trimer(0.7, 120., 1.);
+        let state = lattice(shape, CrystalFamily::Monoclinic, 3.);
+        let phonons = Phonons::from_state(&state, 2);
+        assert_eq!(phonons.degrees_of_freedom(), 3);
+        let k = Vector2::new(0.2, 0.1);
+        assert_eq!(phonons.frequencies(k).len(), 3);
+        // Time reversal symmetry means the frequencies at k and -k are the same
+        for (f, r) in phonons.frequencies(k).iter().zip(phonons.frequencies(-k)) {
+            assert_abs_diff_eq!(*f, r, epsilon = 1e-8);
+        }
+    }
+}
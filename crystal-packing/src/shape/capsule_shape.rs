@@ -0,0 +1,341 @@
+//
+// capsule_shape.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+use std::{fmt, slice};
+
+use anyhow::{bail, Error};
+use itertools::{iproduct, Itertools};
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use super::{Capsule2, Line2, Transform2};
+use crate::traits::{Boundary, Intersect, Shape, Support};
+use crate::{PointGroup, SYMMETRY_TOLERANCE};
+
+/// A shape defined by a collection of Capsules
+///
+/// This is able to describe rod-like molecules with a single capsule, or bent-core molecules
+/// where the capsules are joined at their ends. Capsules with a length of zero are discs, so
+/// this is also able to describe the shapes of a [`MolecularShape2`].
+///
+/// The area is exact for capsules of the same radius which are joined at an end, provided the
+/// arms are long enough that the overlap is contained near the joint. Any other capsules making
+/// up the shape are assumed not to overlap each other.
+///
+/// [`MolecularShape2`]: crate::MolecularShape2
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub struct CapsuleShape {
+    pub name: String,
+    pub items: Vec<Capsule2>,
+}
+
+impl<'a> IntoIterator for &'a CapsuleShape {
+    type Item = &'a Capsule2;
+    type IntoIter = slice::Iter<'a, Capsule2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl Intersect for CapsuleShape {
+    fn intersects(&self, other: &Self) -> bool {
+        iproduct!(self.items.iter(), other.items.iter()).any(|(s, o)| s.intersects(o))
+    }
+
+    fn area(&self) -> f64 {
+        let total_area: f64 = self.items.iter().map(Intersect::area).sum();
+        let joint_overlap: f64 = self
+            .items
+            .iter()
+            .tuple_combinations()
+            .map(|(c1, c2)| Self::joint_overlap(c1, c2))
+            .sum();
+        total_area - joint_overlap
+    }
+}
+
+impl Support for CapsuleShape {
+    fn support(&self, direction: Vector2<f64>) -> f64 {
+        self.items
+            .iter()
+            .map(|c| c.support(direction))
+            .fold(std::f64::MIN, f64::max)
+    }
+}
+
+impl Boundary for CapsuleShape {
+    fn crosses(&self, edge: &Line2) -> bool {
+        self.items
+            .iter()
+            .any(|c| edge.distance_to_line(&c.segment) < c.radius)
+    }
+
+    fn contains(&self, point: &Point2<f64>) -> bool {
+        self.items
+            .iter()
+            .any(|c| c.segment.distance_to(point) < c.radius)
+    }
+}
+
+impl Shape for CapsuleShape {
+    type Component = Capsule2;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn score(&self, other: &Self) -> Option<f64> {
+        if self.intersects(other) {
+            None
+        } else {
+            Some(self.area())
+        }
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        self.items
+            .iter()
+            .map(|c| {
+                f64::max(c.segment.start.coords.norm(), c.segment.end.coords.norm()) + c.radius
+            })
+            .fold(std::f64::MIN, f64::max)
+    }
+
+    fn point_group(&self) -> PointGroup {
+        let points: Vec<_> = self
+            .items
+            .iter()
+            .flat_map(Capsule2::symmetry_points)
+            .collect();
+        PointGroup::detect(&points, SYMMETRY_TOLERANCE)
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.items.clone()
+    }
+
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform2) -> Self {
+        Self {
+            name: self.name.clone(),
+            items: self.into_iter().map(|i| i * transform).collect(),
+        }
+    }
+}
+
+impl fmt::Display for CapsuleShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CapsuleShape {{ {} }}", self.items.iter().format(", "))
+    }
+}
+
+impl CapsuleShape {
+    /// The ends of the capsules which are at the same point, along with the other end of each
+    fn shared_end(c1: &Capsule2, c2: &Capsule2) -> Option<(Point2<f64>, Point2<f64>, Point2<f64>)> {
+        let (s1, s2) = (c1.segment, c2.segment);
+        iproduct!(
+            [(s1.start, s1.end), (s1.end, s1.start)].iter(),
+            [(s2.start, s2.end), (s2.end, s2.start)].iter()
+        )
+        .find(|((joint1, _), (joint2, _))| nalgebra::distance(joint1, joint2) < 1e-12)
+        .map(|(&(joint, end1), &(_, end2))| (joint, end1, end2))
+    }
+
+    /// The area of overlap between two capsules which are joined at an end
+    ///
+    /// For two arms of radius r with an angle θ between them, the overlap is the disc at the
+    /// joint, along with the kite where the two arms cross beyond the disc, giving an area of
+    /// πr² + r² cot(θ/2) - r²(π - θ)/2. This requires each arm to be at least r cot(θ/2) long.
+    /// A disc at the end of a capsule is completely contained within the capsule when it is
+    /// no larger than the capsule.
+    ///
+    fn joint_overlap(c1: &Capsule2, c2: &Capsule2) -> f64 {
+        let (joint, end1, end2) = match Self::shared_end(c1, c2) {
+            Some(ends) => ends,
+            None => return 0.,
+        };
+        let (arm1, arm2) = (end1 - joint, end2 - joint);
+        let radius = f64::min(c1.radius, c2.radius);
+        if arm1.norm() == 0. && c1.radius <= c2.radius
+            || arm2.norm() == 0. && c2.radius <= c1.radius
+        {
+            return PI * radius.powi(2);
+        }
+        if arm1.norm() == 0. || arm2.norm() == 0. || c1.radius != c2.radius {
+            return 0.;
+        }
+        let angle = arm1.angle(&arm2);
+        radius.powi(2) * (PI + 1. / f64::tan(angle / 2.) - (PI - angle) / 2.)
+    }
+
+    /// Create a single capsule at the origin with the long axis along x
+    ///
+    /// The capsule has a total length of 2, with the width being smaller by the `aspect_ratio`,
+    /// which needs to be at least 1. An aspect ratio of 1 is a disc.
+    ///
+    /// ```
+    /// use crystal_packing::{CapsuleShape, Intersect};
+    /// let shape = CapsuleShape::capsule(2.).unwrap();
+    /// assert!((shape.area() - (1. + std::f64::consts::PI / 4.)).abs() < 1e-12);
+    /// ```
+    ///
+    pub fn capsule(aspect_ratio: f64) -> Result<CapsuleShape, Error> {
+        if aspect_ratio.is_nan() || aspect_ratio < 1. {
+            bail!(
+                "The aspect ratio of a capsule needs to be at least 1, got {}",
+                aspect_ratio
+            );
+        }
+        let radius = 1. / aspect_ratio;
+        Ok(CapsuleShape {
+            name: String::from("Capsule"),
+            items: vec![Capsule2::new((radius - 1., 0.), (1. - radius, 0.), radius)],
+        })
+    }
+
+    /// Create a bent-core molecule from two capsules joined at an end
+    ///
+    /// Each of the arms is a capsule with the same dimensions as [`CapsuleShape::capsule`],
+    /// with `angle` being the angle between the arms in degrees. Like the Trimer, the shape is
+    /// symmetric about the y axis, with the centre of the joint and the two ends at the origin.
+    ///
+    /// The angle needs to be large enough for the overlap of the arms to be contained near the
+    /// joint, so the area can be calculated exactly.
+    ///
+    /// ```
+    /// use crystal_packing::CapsuleShape;
+    /// let shape = CapsuleShape::bent_core(4., 120.).unwrap();
+    /// assert_eq!(shape.items.len(), 2);
+    /// ```
+    ///
+    pub fn bent_core(aspect_ratio: f64, angle: f64) -> Result<CapsuleShape, Error> {
+        let arm = Self::capsule(aspect_ratio)?.items[0];
+        let (radius, length) = (arm.radius, arm.segment.length());
+        let half_angle = angle.to_radians() / 2.;
+        if angle.is_nan() || angle <= 0. || angle > 180. {
+            bail!(
+                "The angle between the arms needs to be in (0, 180], got {}",
+                angle
+            );
+        }
+        if length < radius / f64::tan(half_angle) {
+            bail!(
+                "The arms with aspect ratio {} are too short for an angle of {}",
+                aspect_ratio,
+                angle
+            );
+        }
+        let joint = (0., -2. / 3. * length * f64::cos(half_angle));
+        let end_y = 1. / 3. * length * f64::cos(half_angle);
+        let end_x = length * f64::sin(half_angle);
+        Ok(CapsuleShape {
+            name: String::from("BentCore"),
+            items: vec![
+                Capsule2::new(joint, (-end_x, end_y), radius),
+                Capsule2::new(joint, (end_x, end_y), radius),
+            ],
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use approx::assert_abs_diff_eq;
+    use rand::prelude::*;
+    use rand_pcg::Pcg64Mcg;
+
+    use super::*;
+    use crate::traits::{Optimiser, State};
+    use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+    use crate::{MCOptimiser, PackedState};
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(CapsuleShape::capsule(0.5).is_err());
+        assert!(CapsuleShape::capsule(f64::NAN).is_err());
+        assert!(CapsuleShape::bent_core(4., 0.).is_err());
+        assert!(CapsuleShape::bent_core(4., 200.).is_err());
+        // The arms are too short for the angle
+        assert!(CapsuleShape::bent_core(1.5, 20.).is_err());
+    }
+
+    #[test]
+    fn straight_bent_core() {
+        // With an angle of 180 degrees the arms form a single capsule
+        let shape = CapsuleShape::bent_core(4., 180.).unwrap();
+        let arm = CapsuleShape::capsule(4.).unwrap();
+        let expected = 2. * arm.area() - PI * 0.25f64.powi(2);
+        assert_abs_diff_eq!(shape.area(), expected, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn bent_core_area() {
+        // Estimate the area by sampling points within a bounding box
+        let shape = CapsuleShape::bent_core(3., 90.).unwrap();
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        let samples = 400_000;
+        let extent = shape.enclosing_radius();
+        let inside = (0..samples)
+            .filter(|_| {
+                let point = Point2::new(
+                    rng.gen_range(-extent..extent),
+                    rng.gen_range(-extent..extent),
+                );
+                shape
+                    .items
+                    .iter()
+                    .any(|c| c.segment.distance_to(&point) < c.radius)
+            })
+            .count();
+        let estimate = inside as f64 / samples as f64 * (2. * extent).powi(2);
+        assert_abs_diff_eq!(shape.area(), estimate, epsilon = 0.01);
+    }
+
+    #[test]
+    fn centred() {
+        let shape = CapsuleShape::bent_core(4., 120.).unwrap();
+        let centre = shape.items[0].segment.start.coords
+            + shape.items[0].segment.end.coords
+            + shape.items[1].segment.end.coords;
+        assert_abs_diff_eq!(centre, Vector2::zeros(), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn crosses_boundary() {
+        let shape = CapsuleShape::capsule(2.).unwrap();
+        assert!(shape.crosses(&Line2::new((0.9, -1.), (0.9, 1.))));
+        assert!(!shape.crosses(&Line2::new((1.1, -1.), (1.1, 1.))));
+        assert!(shape.crosses(&Line2::new((-1., 0.4), (1., 0.4))));
+    }
+
+    #[test]
+    fn optimise_capsules() {
+        let shape = CapsuleShape::capsule(3.).unwrap();
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(shape, &group).unwrap();
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 5000, 500, 0, None).optimise_state(state);
+        let score = state.score().unwrap();
+        assert!(score > 0.75, "{}", score);
+    }
+
+    #[test]
+    fn capsule_point_group() {
+        let shape = CapsuleShape::capsule(2.).unwrap();
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(2));
+        assert!(!shape.is_chiral());
+        let shape = CapsuleShape::bent_core(4., 120.).unwrap();
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(1));
+    }
+}
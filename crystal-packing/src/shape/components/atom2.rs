@@ -47,6 +47,11 @@ impl Atom2 {
             radius,
         }
     }
+
+    /// The points describing the disc when finding the symmetry of a shape
+    pub(crate) fn symmetry_points(&self) -> Vec<(Point2<f64>, Vec<f64>)> {
+        vec![(self.position, vec![self.radius])]
+    }
 }
 
 #[cfg(test)]
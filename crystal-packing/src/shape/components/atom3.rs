@@ -0,0 +1,75 @@
+//
+// atom3.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fmt;
+
+use nalgebra::Point3;
+use serde::{Deserialize, Serialize};
+
+use crate::traits::Intersect3;
+
+/// A sphere, the three dimensional counterpart of an [`Atom2`]
+///
+/// [`Atom2`]: crate::Atom2
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Atom3 {
+    pub position: Point3<f64>,
+    pub radius: f64,
+}
+
+impl Intersect3 for Atom3 {
+    fn intersects(&self, other: &Self) -> bool {
+        let r_squared = (self.radius + other.radius).powi(2);
+        (self.position - other.position).norm_squared() < r_squared
+    }
+
+    fn volume(&self) -> f64 {
+        4. / 3. * std::f64::consts::PI * self.radius.powi(3)
+    }
+}
+
+impl fmt::Display for Atom3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Atom3 {{ {}, {}, {}, {} }}",
+            self.position.x, self.position.y, self.position.z, self.radius
+        )
+    }
+}
+
+impl Atom3 {
+    pub fn new(x: f64, y: f64, z: f64, radius: f64) -> Self {
+        Atom3 {
+            position: Point3::new(x, y, z),
+            radius,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn intersection_test() {
+        let a0 = Atom3::new(0., 0., 0., 1.);
+        let a1 = Atom3::new(1., 1., 1., 1.);
+        let a2 = Atom3::new(2., 0., 0., 1.);
+        assert!(a0.intersects(&a1));
+        // Spheres which are just touching don't intersect
+        assert!(!a0.intersects(&a2));
+    }
+
+    #[test]
+    fn volume_test() {
+        let a = Atom3::new(0., 0., 0., 1.);
+        assert_abs_diff_eq!(a.volume(), 4.18879, epsilon = 1e-5);
+    }
+}
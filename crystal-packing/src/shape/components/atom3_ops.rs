@@ -0,0 +1,34 @@
+//
+// atom3_ops.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+#![allow(clippy::op_ref)]
+
+use std::ops::Mul;
+
+use super::Atom3;
+use crate::Transform3;
+
+binop_impl_all!(
+    Mul, mul;
+    self: Transform3, rhs: Atom3, Output = Atom3;
+    [ref ref] => {
+        Atom3 {
+            position: self * rhs.position,
+            radius: rhs.radius,
+        }
+    };
+);
+
+binop_impl_all!(
+    Mul, mul;
+    self: Atom3, rhs: Transform3, Output = Atom3;
+    [ref ref] => {
+        Atom3 {
+            position: rhs * self.position,
+            radius: self.radius,
+        }
+    };
+);
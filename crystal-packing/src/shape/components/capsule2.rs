@@ -0,0 +1,123 @@
+//
+// capsule2.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+use std::fmt;
+
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use super::Line2;
+use crate::traits::{Intersect, Support};
+
+/// A line segment swept by a disc, also known as a spherocylinder or stadium
+///
+/// This is every point within `radius` of the line segment, giving a rectangle capped at each
+/// end with a semicircle. A segment with zero length is a disc.
+///
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Capsule2 {
+    pub segment: Line2,
+    pub radius: f64,
+}
+
+impl Intersect for Capsule2 {
+    /// Whether the two capsules overlap, which is when the segments are closer than the sum of
+    /// the radii
+    fn intersects(&self, other: &Self) -> bool {
+        self.segment.distance_to_line(&other.segment) < self.radius + other.radius
+    }
+
+    fn area(&self) -> f64 {
+        2. * self.radius * self.segment.length() + PI * self.radius.powi(2)
+    }
+}
+
+impl Support for Capsule2 {
+    fn support(&self, direction: Vector2<f64>) -> f64 {
+        f64::max(
+            self.segment.start.coords.dot(&direction),
+            self.segment.end.coords.dot(&direction),
+        ) + self.radius
+    }
+}
+
+impl fmt::Display for Capsule2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Capsule2 {{ {}, {} }}", self.segment, self.radius)
+    }
+}
+
+impl Capsule2 {
+    pub fn new(start: (f64, f64), end: (f64, f64), radius: f64) -> Self {
+        Capsule2 {
+            segment: Line2::new(start, end),
+            radius,
+        }
+    }
+
+    /// The points describing the capsule when finding the symmetry of a shape
+    pub(crate) fn symmetry_points(&self) -> Vec<(Point2<f64>, Vec<f64>)> {
+        self.segment
+            .symmetry_points()
+            .into_iter()
+            .map(|(p, mut properties)| {
+                properties.push(self.radius);
+                (p, properties)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::Transform2;
+
+    #[test]
+    fn area() {
+        let capsule = Capsule2::new((-1., 0.), (1., 0.), 0.5);
+        assert_abs_diff_eq!(capsule.area(), 2. + PI / 4.);
+        // A disc
+        let capsule = Capsule2::new((0., 0.), (0., 0.), 1.);
+        assert_abs_diff_eq!(capsule.area(), PI);
+    }
+
+    #[test]
+    fn intersects() {
+        let capsule = Capsule2::new((-1., 0.), (1., 0.), 0.5);
+        // Side by side
+        assert!(capsule.intersects(&Capsule2::new((-1., 0.9), (1., 0.9), 0.5)));
+        assert!(!capsule.intersects(&Capsule2::new((-1., 1.), (1., 1.), 0.5)));
+        // End to end
+        assert!(capsule.intersects(&Capsule2::new((1.9, 0.), (3., 0.), 0.5)));
+        assert!(!capsule.intersects(&Capsule2::new((2.1, 0.), (3., 0.), 0.5)));
+        // Crossing
+        assert!(capsule.intersects(&Capsule2::new((0., -1.), (0., 1.), 0.1)));
+        // The end cap against the side
+        assert!(capsule.intersects(&Capsule2::new((0., 0.9), (0., 2.), 0.5)));
+        assert!(!capsule.intersects(&Capsule2::new((0., 1.1), (0., 2.), 0.5)));
+    }
+
+    #[test]
+    fn support() {
+        let capsule = Capsule2::new((-1., 0.), (1., 0.), 0.5);
+        assert_abs_diff_eq!(capsule.support(Vector2::new(1., 0.)), 1.5);
+        assert_abs_diff_eq!(capsule.support(Vector2::new(0., -1.)), 0.5);
+    }
+
+    #[test]
+    fn transform() {
+        let capsule = Capsule2::new((-1., 0.), (1., 0.), 0.5);
+        let transformed = capsule * Transform2::new(PI / 2., (1., 0.));
+        assert_abs_diff_eq!(transformed.segment.start.x, 1., epsilon = 1e-12);
+        assert_abs_diff_eq!(transformed.segment.start.y, -1., epsilon = 1e-12);
+        assert_abs_diff_eq!(transformed.segment.end.y, 1., epsilon = 1e-12);
+        assert_eq!(transformed.radius, 0.5);
+    }
+}
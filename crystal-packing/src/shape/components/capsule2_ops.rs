@@ -0,0 +1,34 @@
+//
+// capsule2_ops.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+#![allow(clippy::op_ref)]
+
+use std::ops::Mul;
+
+use super::Capsule2;
+use crate::Transform2;
+
+binop_impl_all!(
+    Mul, mul;
+    self: Transform2, rhs: Capsule2, Output = Capsule2;
+    [ref ref] => {
+        Capsule2 {
+            segment: self * rhs.segment,
+            radius: rhs.radius,
+        }
+    };
+);
+
+binop_impl_all!(
+    Mul, mul;
+    self: Capsule2, rhs: Transform2, Output = Capsule2;
+    [ref ref] => {
+        Capsule2 {
+            segment: rhs * self.segment,
+            radius: self.radius,
+        }
+    };
+);
@@ -0,0 +1,245 @@
+//
+// ellipse2.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+use std::fmt;
+
+use nalgebra::{Matrix2, Point2, Rotation2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::traits::{Intersect, Support};
+use crate::SYMMETRY_TOLERANCE;
+
+/// The number of iterations of the golden section search for the contact function
+///
+/// Each iteration reduces the interval by a factor of 0.618, so this finds the maximum to
+/// within the precision of an f64.
+const CONTACT_ITERATIONS: usize = 80;
+
+/// An ellipse with a position, semi-axes, and orientation
+///
+/// The angle is the orientation of the major axis anti-clockwise from the x axis.
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Ellipse2 {
+    pub position: Point2<f64>,
+    pub semi_major: f64,
+    pub semi_minor: f64,
+    pub angle: f64,
+}
+
+impl Intersect for Ellipse2 {
+    /// Whether the two ellipses overlap, using the Perram–Wertheim contact function
+    ///
+    /// Ellipses which are only touching don't intersect.
+    ///
+    fn intersects(&self, other: &Self) -> bool {
+        let distance = (other.position - self.position).norm();
+        if distance >= self.semi_major + other.semi_major {
+            return false;
+        }
+        if distance < self.semi_minor + other.semi_minor {
+            return true;
+        }
+        self.contact(other) < 1.
+    }
+
+    fn area(&self) -> f64 {
+        PI * self.semi_major * self.semi_minor
+    }
+}
+
+impl Support for Ellipse2 {
+    fn support(&self, direction: Vector2<f64>) -> f64 {
+        self.position.coords.dot(&direction)
+            + f64::sqrt(direction.dot(&(self.shape_matrix() * direction)))
+    }
+}
+
+impl fmt::Display for Ellipse2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Ellipse2 {{ {}, {}, {}, {}, {} }}",
+            self.position.x, self.position.y, self.semi_major, self.semi_minor, self.angle
+        )
+    }
+}
+
+impl Ellipse2 {
+    pub fn new(x: f64, y: f64, semi_major: f64, semi_minor: f64, angle: f64) -> Self {
+        Ellipse2 {
+            position: Point2::new(x, y),
+            semi_major,
+            semi_minor,
+            angle,
+        }
+    }
+
+    /// The matrix A where the ellipse is the points x with (x - c)ᵀ A⁻¹ (x - c) ≤ 1
+    pub fn shape_matrix(&self) -> Matrix2<f64> {
+        let rotation = Rotation2::new(self.angle);
+        rotation
+            * Matrix2::new(self.semi_major.powi(2), 0., 0., self.semi_minor.powi(2))
+            * rotation.transpose()
+    }
+
+    /// The points describing the ellipse when finding the symmetry of a shape
+    ///
+    /// These are the centre along with the ends of each axis, which are labelled by the axis
+    /// they belong to. A circle only has the centre, since it has every symmetry about it.
+    ///
+    pub(crate) fn symmetry_points(&self) -> Vec<(Point2<f64>, Vec<f64>)> {
+        let properties = |axis: f64| vec![axis, self.semi_major, self.semi_minor];
+        let mut points = vec![(self.position, properties(0.))];
+        if (self.semi_major - self.semi_minor).abs() > SYMMETRY_TOLERANCE * self.semi_major {
+            let rotation = Rotation2::new(self.angle);
+            let major = rotation * Vector2::new(self.semi_major, 0.);
+            let minor = rotation * Vector2::new(0., self.semi_minor);
+            points.extend(vec![
+                (self.position + major, properties(1.)),
+                (self.position - major, properties(1.)),
+                (self.position + minor, properties(2.)),
+                (self.position - minor, properties(2.)),
+            ]);
+        }
+        points
+    }
+
+    /// The Perram–Wertheim contact function of a pair of ellipses
+    ///
+    /// This is the maximum over λ in [0, 1] of
+    ///
+    /// F(λ) = λ(1 - λ) rᵀ [(1 - λ)A + λB]⁻¹ r
+    ///
+    /// where r is the vector between the centres and A and B are the shape matrices. The
+    /// ellipses overlap when the contact function is less than 1, are touching when it is
+    /// exactly 1, and are separate when it is greater than 1. Since F(λ) is concave, the
+    /// maximum is found using a golden section search.
+    ///
+    /// ```
+    /// use crystal_packing::Ellipse2;
+    /// // Ellipses side by side touch when the centres are separated by the minor axes
+    /// let e1 = Ellipse2::new(0., 0., 2., 1., std::f64::consts::PI / 2.);
+    /// let e2 = Ellipse2::new(2., 0., 2., 1., std::f64::consts::PI / 2.);
+    /// assert!((e1.contact(&e2) - 1.).abs() < 1e-12);
+    /// ```
+    ///
+    pub fn contact(&self, other: &Self) -> f64 {
+        let r = other.position - self.position;
+        let (a, b) = (self.shape_matrix(), other.shape_matrix());
+        let f = |lambda: f64| {
+            let c = (1. - lambda) * a + lambda * b;
+            match c.try_inverse() {
+                Some(inverse) => lambda * (1. - lambda) * r.dot(&(inverse * r)),
+                None => 0.,
+            }
+        };
+
+        let ratio = (f64::sqrt(5.) - 1.) / 2.;
+        let (mut low, mut high) = (0., 1.);
+        let mut x1 = high - ratio * (high - low);
+        let mut x2 = low + ratio * (high - low);
+        let (mut f1, mut f2) = (f(x1), f(x2));
+        for _ in 0..CONTACT_ITERATIONS {
+            if f1 < f2 {
+                low = x1;
+                x1 = x2;
+                f1 = f2;
+                x2 = low + ratio * (high - low);
+                f2 = f(x2);
+            } else {
+                high = x2;
+                x2 = x1;
+                f2 = f1;
+                x1 = high - ratio * (high - low);
+                f1 = f(x1);
+            }
+        }
+        f64::max(f1, f2)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn circles() {
+        // With equal axes the contact function is the squared ratio of distance to radii
+        let e1 = Ellipse2::new(0., 0., 1., 1., 0.);
+        let e2 = Ellipse2::new(1.5, 0., 0.5, 0.5, 0.);
+        assert_abs_diff_eq!(e1.contact(&e2), 1., epsilon = 1e-12);
+        let e3 = Ellipse2::new(3., 0., 1., 1., 0.);
+        assert_abs_diff_eq!(e1.contact(&e3), 2.25, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn end_to_end() {
+        let e1 = Ellipse2::new(0., 0., 2., 1., 0.);
+        assert!(!e1.intersects(&Ellipse2::new(4.001, 0., 2., 1., 0.)));
+        assert!(e1.intersects(&Ellipse2::new(3.999, 0., 2., 1., 0.)));
+        // Side by side
+        assert!(!e1.intersects(&Ellipse2::new(0., 2.001, 2., 1., 0.)));
+        assert!(e1.intersects(&Ellipse2::new(0., 1.999, 2., 1., 0.)));
+    }
+
+    #[test]
+    fn crossed() {
+        // A T shape, where the end of one ellipse touches the side of the other
+        let e1 = Ellipse2::new(0., 0., 2., 1., 0.);
+        let e2 = Ellipse2::new(0., 3., 2., 1., PI / 2.);
+        assert_abs_diff_eq!(e1.contact(&e2), 1., epsilon = 1e-9);
+        let e3 = Ellipse2::new(0., 2.99, 2., 1., PI / 2.);
+        assert!(e1.intersects(&e3));
+    }
+
+    #[test]
+    fn matches_sampling() {
+        // Compare against checking whether points on the boundary of one are within the other
+        let inside = |e: &Ellipse2, p: Point2<f64>| {
+            let r = p - e.position;
+            r.dot(&(e.shape_matrix().try_inverse().unwrap() * r)) < 1.
+        };
+        let boundary = |e: &Ellipse2| -> Vec<Point2<f64>> {
+            let rotation = Rotation2::new(e.angle);
+            (0..2000)
+                .map(|i| {
+                    let t = i as f64 / 2000. * 2. * PI;
+                    e.position
+                        + rotation * Vector2::new(e.semi_major * t.cos(), e.semi_minor * t.sin())
+                })
+                .collect()
+        };
+        let e1 = Ellipse2::new(0., 0., 1.5, 0.5, 0.3);
+        for i in 0..100 {
+            let t = i as f64 * 0.7;
+            let e2 = Ellipse2::new(
+                (1. + 0.01 * i as f64) * t.cos(),
+                (1. + 0.01 * i as f64) * t.sin(),
+                1.,
+                0.4,
+                1.3 * t,
+            );
+            let contact = e1.contact(&e2);
+            // Sampling is only reliable away from touching
+            if (contact - 1.).abs() > 0.05 {
+                let sampled = boundary(&e2).into_iter().any(|p| inside(&e1, p))
+                    || boundary(&e1).into_iter().any(|p| inside(&e2, p));
+                assert_eq!(e1.intersects(&e2), sampled, "{}", i);
+            }
+        }
+    }
+
+    #[test]
+    fn support() {
+        let e = Ellipse2::new(1., 0., 2., 1., PI / 2.);
+        assert_abs_diff_eq!(e.support(Vector2::new(1., 0.)), 2., epsilon = 1e-12);
+        assert_abs_diff_eq!(e.support(Vector2::new(0., 1.)), 2., epsilon = 1e-12);
+    }
+}
@@ -0,0 +1,44 @@
+//
+// ellipse2_ops.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+#![allow(clippy::op_ref)]
+
+use std::ops::Mul;
+
+use nalgebra::Vector2;
+
+use super::Ellipse2;
+use crate::Transform2;
+
+/// The ellipse has mirror symmetry about both axes, so a transform containing a reflection
+/// gives another ellipse with the major axis along the transformed direction.
+fn transform_ellipse(transform: &Transform2, ellipse: &Ellipse2) -> Ellipse2 {
+    let position = transform * ellipse.position;
+    let axis = Vector2::new(ellipse.angle.cos(), ellipse.angle.sin());
+    let axis = transform * (ellipse.position + axis) - position;
+    Ellipse2 {
+        position,
+        semi_major: ellipse.semi_major,
+        semi_minor: ellipse.semi_minor,
+        angle: f64::atan2(axis.y, axis.x),
+    }
+}
+
+binop_impl_all!(
+    Mul, mul;
+    self: Transform2, rhs: Ellipse2, Output = Ellipse2;
+    [ref ref] => {
+        transform_ellipse(self, rhs)
+    };
+);
+
+binop_impl_all!(
+    Mul, mul;
+    self: Ellipse2, rhs: Transform2, Output = Ellipse2;
+    [ref ref] => {
+        transform_ellipse(rhs, self)
+    };
+);
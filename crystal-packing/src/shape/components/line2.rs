@@ -99,6 +99,84 @@ impl Line2 {
     pub fn dy(&self) -> f64 {
         self.end.y - self.start.y
     }
+
+    /// The shortest distance from a point to any point on the line segment
+    ///
+    /// ```
+    /// use crystal_packing::Line2;
+    /// use nalgebra::Point2;
+    /// let line = Line2::new((0., 0.), (1., 0.));
+    /// assert_eq!(line.distance_to(&Point2::new(0.5, 2.)), 2.);
+    /// assert_eq!(line.distance_to(&Point2::new(4., 4.)), 5.);
+    /// ```
+    ///
+    pub fn distance_to(&self, point: &Point2<f64>) -> f64 {
+        let line = self.end - self.start;
+        // A line of zero length is a single point
+        if line.norm_squared() == 0. {
+            return (point - self.start).norm();
+        }
+        let fraction = ((point - self.start).dot(&line) / line.norm_squared()).clamp(0., 1.);
+        (point - (self.start + line * fraction)).norm()
+    }
+
+    /// The length of the line segment
+    pub fn length(&self) -> f64 {
+        (self.end - self.start).norm()
+    }
+
+    /// The shortest distance between any two points on each of the line segments
+    ///
+    /// Where the segments don't cross, the closest approach always involves one of the
+    /// endpoints, so it is the smallest distance from an endpoint to the other segment.
+    ///
+    /// ```
+    /// use crystal_packing::Line2;
+    /// let line = Line2::new((0., 0.), (1., 0.));
+    /// assert_eq!(line.distance_to_line(&Line2::new((0.5, -1.), (0.5, 1.))), 0.);
+    /// assert_eq!(line.distance_to_line(&Line2::new((3., 1.), (5., 1.))), f64::sqrt(5.));
+    /// ```
+    ///
+    pub fn distance_to_line(&self, other: &Line2) -> f64 {
+        if self.intersects(other) {
+            return 0.;
+        }
+        [
+            self.distance_to(&other.start),
+            self.distance_to(&other.end),
+            other.distance_to(&self.start),
+            other.distance_to(&self.end),
+        ]
+        .iter()
+        .fold(std::f64::MAX, |acc, &d| f64::min(acc, d))
+    }
+
+    /// The points describing the segment when finding the symmetry of a shape
+    ///
+    /// The midpoint is distinguished from the ends, so a symmetry operation has to map the
+    /// whole segment onto another segment.
+    ///
+    pub(crate) fn symmetry_points(&self) -> Vec<(Point2<f64>, Vec<f64>)> {
+        vec![
+            (self.start, vec![0.]),
+            (self.end, vec![0.]),
+            (nalgebra::center(&self.start, &self.end), vec![1.]),
+        ]
+    }
+}
+
+/// Whether the point is inside the polygon with these edges, using the even-odd rule
+pub(crate) fn polygon_contains<'a>(
+    edges: impl IntoIterator<Item = &'a Line2>,
+    point: &Point2<f64>,
+) -> bool {
+    edges
+        .into_iter()
+        .filter(|l| (l.start.y <= point.y) != (l.end.y <= point.y))
+        .filter(|l| point.x < l.start.x + (point.y - l.start.y) / l.dy() * l.dx())
+        .count()
+        % 2
+        == 1
 }
 
 #[cfg(test)]
@@ -179,4 +257,29 @@ mod test {
         assert!(line1.intersects(&line3));
         assert!(line3.intersects(&line1));
     }
+
+    #[test]
+    fn distance_to_line() {
+        let line = Line2::new((0., 0.), (2., 0.));
+        // Parallel and overlapping
+        assert_eq!(line.distance_to_line(&Line2::new((1., 0.), (3., 0.))), 0.);
+        // Parallel and offset
+        assert_eq!(line.distance_to_line(&Line2::new((1., 1.), (3., 1.))), 1.);
+        // The closest point is in the middle of one of the segments
+        assert_eq!(line.distance_to_line(&Line2::new((1., 2.), (1., 0.5))), 0.5);
+        // A line of zero length
+        assert_eq!(line.distance_to_line(&Line2::new((3., 0.), (3., 0.))), 1.);
+    }
+
+    #[test]
+    fn point_in_polygon() {
+        let square = vec![
+            Line2::new((-1., -1.), (1., -1.)),
+            Line2::new((1., -1.), (1., 1.)),
+            Line2::new((1., 1.), (-1., 1.)),
+            Line2::new((-1., 1.), (-1., -1.)),
+        ];
+        assert!(polygon_contains(&square, &Point2::new(0.5, 0.9)));
+        assert!(!polygon_contains(&square, &Point2::new(1.5, 0.)));
+    }
 }
@@ -6,11 +6,24 @@
 
 pub mod atom2;
 pub mod atom2_ops;
+pub mod atom3;
+pub mod atom3_ops;
+pub mod capsule2;
+pub mod capsule2_ops;
+pub mod ellipse2;
+pub mod ellipse2_ops;
 pub mod line2;
 pub mod line2_ops;
 pub mod lj2;
 pub mod lj2_ops;
+pub mod primitive;
+pub mod primitive_ops;
 
 pub use atom2::Atom2;
+pub use atom3::Atom3;
+pub use capsule2::Capsule2;
+pub use ellipse2::Ellipse2;
+pub(crate) use line2::polygon_contains;
 pub use line2::Line2;
 pub use lj2::LJ2;
+pub use primitive::Primitive;
@@ -0,0 +1,347 @@
+//
+// primitive.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fmt;
+
+use itertools::{iproduct, Itertools};
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use super::{Atom2, Capsule2, Line2};
+use crate::traits::{Intersect, Support};
+use crate::LineShape;
+
+/// One of the geometric primitives which can be combined into a [`MixedShape`]
+///
+/// Each of the primitives is a core of a point, a line segment, or a polygon, which is swept by
+/// a disc of some radius. Two primitives intersect when the distance between the cores is less
+/// than the sum of the radii, where a point inside a polygon is a distance of zero from it.
+///
+/// [`MixedShape`]: crate::MixedShape
+///
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Primitive {
+    Disc(Atom2),
+    Segment(Line2),
+    Capsule(Capsule2),
+    Polygon(LineShape),
+}
+
+/// The part of a primitive which is swept by the disc
+enum Core<'a> {
+    Point(Point2<f64>),
+    Segment(Line2),
+    Polygon(&'a LineShape),
+}
+
+/// The edges of the polygon as pairs of vertices
+///
+/// The transformed edges of a polygon don't exactly share their ends, so the start of each
+/// edge is used as the vertex, keeping calculations using the vertices consistent.
+///
+fn edges(polygon: &LineShape) -> impl Iterator<Item = (Point2<f64>, Point2<f64>)> + '_ {
+    let vertices = polygon.items.iter().map(|l| l.start);
+    vertices.clone().zip(vertices.cycle().skip(1))
+}
+
+/// The values of x where the edges of the polygon cross the horizontal line at `y`
+fn crossings(polygon: &LineShape, y: f64) -> impl Iterator<Item = f64> + '_ {
+    edges(polygon)
+        .filter(move |(start, end)| (start.y <= y) != (end.y <= y))
+        .map(move |(start, end)| start.x + (y - start.y) / (end.y - start.y) * (end.x - start.x))
+}
+
+/// Whether the point is inside the polygon, using the even-odd rule
+fn contains(polygon: &LineShape, point: &Point2<f64>) -> bool {
+    crossings(polygon, point.y).filter(|&x| point.x < x).count() % 2 == 1
+}
+
+impl<'a> Core<'a> {
+    /// The points which are checked for being inside a polygon
+    fn points(&self) -> Vec<Point2<f64>> {
+        match self {
+            Core::Point(p) => vec![*p],
+            Core::Segment(l) => vec![l.start, l.end],
+            Core::Polygon(s) => s.items.iter().map(|l| l.start).collect(),
+        }
+    }
+
+    /// The line segments making up the core, which is empty for a point
+    fn lines(&self) -> Vec<Line2> {
+        match self {
+            Core::Point(_) => vec![],
+            Core::Segment(l) => vec![*l],
+            Core::Polygon(s) => s.items.clone(),
+        }
+    }
+
+    /// The shortest distance between the two cores
+    fn distance(&self, other: &Core) -> f64 {
+        // A core which is partly inside a polygon is a distance of zero from it
+        let inside = |core: &Core, other: &Core| match core {
+            Core::Polygon(polygon) => other.points().iter().any(|p| contains(polygon, p)),
+            _ => false,
+        };
+        if inside(self, other) || inside(other, self) {
+            return 0.;
+        }
+        match (self, other) {
+            (Core::Point(p1), Core::Point(p2)) => (p1 - p2).norm(),
+            (Core::Point(p), c) | (c, Core::Point(p)) => c
+                .lines()
+                .iter()
+                .map(|l| l.distance_to(p))
+                .fold(std::f64::MAX, f64::min),
+            _ => iproduct!(self.lines(), other.lines())
+                .map(|(l1, l2)| l1.distance_to_line(&l2))
+                .fold(std::f64::MAX, f64::min),
+        }
+    }
+}
+
+impl Primitive {
+    fn core(&self) -> Core {
+        match self {
+            Primitive::Disc(a) => Core::Point(a.position),
+            Primitive::Segment(l) => Core::Segment(*l),
+            Primitive::Capsule(c) => Core::Segment(c.segment),
+            Primitive::Polygon(s) => Core::Polygon(s),
+        }
+    }
+
+    /// Whether the point is inside the primitive
+    pub fn contains(&self, point: &Point2<f64>) -> bool {
+        match self {
+            Primitive::Disc(a) => (point - a.position).norm() < a.radius,
+            Primitive::Segment(_) => false,
+            Primitive::Capsule(c) => c.segment.distance_to(point) < c.radius,
+            Primitive::Polygon(s) => contains(s, point),
+        }
+    }
+
+    /// The radius of the disc sweeping the core of the primitive
+    pub fn radius(&self) -> f64 {
+        match self {
+            Primitive::Disc(a) => a.radius,
+            Primitive::Capsule(c) => c.radius,
+            Primitive::Segment(_) | Primitive::Polygon(_) => 0.,
+        }
+    }
+
+    /// The distance from the origin to the furthest point of the primitive
+    pub fn extent(&self) -> f64 {
+        self.core()
+            .points()
+            .iter()
+            .map(|p| p.coords.norm())
+            .fold(std::f64::MIN, f64::max)
+            + self.radius()
+    }
+
+    /// The points describing the primitive when finding the symmetry of a shape
+    ///
+    /// The properties of each point start with the kind of primitive, so a symmetry operation
+    /// only maps a primitive onto another of the same kind.
+    ///
+    pub(crate) fn symmetry_points(&self) -> Vec<(Point2<f64>, Vec<f64>)> {
+        let (kind, points) = match self {
+            Primitive::Disc(a) => (0., a.symmetry_points()),
+            Primitive::Segment(l) => (1., l.symmetry_points()),
+            Primitive::Capsule(c) => (2., c.symmetry_points()),
+            Primitive::Polygon(s) => (
+                3.,
+                s.items.iter().flat_map(Line2::symmetry_points).collect(),
+            ),
+        };
+        points
+            .into_iter()
+            .map(|(p, properties)| (p, std::iter::once(kind).chain(properties).collect()))
+            .collect()
+    }
+
+    /// The values of y where the width of the primitive changes abruptly
+    ///
+    /// These are the vertices of polygons, along with the top and bottom of each disc.
+    ///
+    pub(crate) fn breakpoints(&self) -> Vec<f64> {
+        let disc =
+            |centre: Point2<f64>, radius: f64| vec![centre.y - radius, centre.y, centre.y + radius];
+        match self {
+            Primitive::Disc(a) => disc(a.position, a.radius),
+            Primitive::Segment(_) => vec![],
+            Primitive::Capsule(c) => {
+                let length = c.segment.length();
+                let normal = if length > 0. {
+                    Vector2::new(-c.segment.dy(), c.segment.dx()) / length * c.radius
+                } else {
+                    Vector2::zeros()
+                };
+                let mut points = disc(c.segment.start, c.radius);
+                points.extend(disc(c.segment.end, c.radius));
+                points.extend(
+                    [c.segment.start, c.segment.end]
+                        .iter()
+                        .flat_map(|p| vec![(p + normal).y, (p - normal).y]),
+                );
+                points
+            }
+            Primitive::Polygon(s) => s.items.iter().map(|l| l.start.y).collect(),
+        }
+    }
+
+    /// The intervals of x where the horizontal line at `y` is inside the primitive
+    pub(crate) fn spans(&self, y: f64) -> Vec<(f64, f64)> {
+        let disc = |centre: Point2<f64>, radius: f64| {
+            let dy = y - centre.y;
+            if dy.abs() < radius {
+                let half = f64::sqrt(radius.powi(2) - dy.powi(2));
+                Some((centre.x - half, centre.x + half))
+            } else {
+                None
+            }
+        };
+        let polygon = |shape: &LineShape| {
+            crossings(shape, y)
+                .sorted_by(|a, b| a.partial_cmp(b).unwrap())
+                .collect::<Vec<f64>>()
+                .chunks(2)
+                .filter(|c| c.len() == 2)
+                .map(|c| (c[0], c[1]))
+                .collect::<Vec<_>>()
+        };
+        match self {
+            Primitive::Disc(a) => disc(a.position, a.radius).into_iter().collect(),
+            Primitive::Segment(_) => vec![],
+            Primitive::Capsule(c) => {
+                let (start, end) = (c.segment.start, c.segment.end);
+                let mut spans: Vec<(f64, f64)> = vec![disc(start, c.radius), disc(end, c.radius)]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                let length = c.segment.length();
+                if length > 0. {
+                    let normal = Vector2::new(-c.segment.dy(), c.segment.dx()) / length * c.radius;
+                    let rectangle = LineShape::from_vertices(
+                        "Rectangle",
+                        &[start + normal, end + normal, end - normal, start - normal],
+                    );
+                    if let Ok(rectangle) = rectangle {
+                        spans.extend(polygon(&rectangle));
+                    }
+                }
+                // The capsule is convex, so all the spans overlap
+                spans
+                    .into_iter()
+                    .fold1(|(a1, b1), (a2, b2)| (f64::min(a1, a2), f64::max(b1, b2)))
+                    .into_iter()
+                    .collect()
+            }
+            Primitive::Polygon(s) => polygon(s),
+        }
+    }
+}
+
+impl Intersect for Primitive {
+    /// Whether the primitives overlap, dispatching on the type of each primitive
+    ///
+    /// Where neither primitive has a radius, like a pair of line segments, the primitives
+    /// intersect when they touch.
+    ///
+    fn intersects(&self, other: &Self) -> bool {
+        let radii = self.radius() + other.radius();
+        let distance = self.core().distance(&other.core());
+        if radii > 0. {
+            distance < radii
+        } else {
+            distance == 0.
+        }
+    }
+
+    fn area(&self) -> f64 {
+        match self {
+            Primitive::Disc(a) => a.area(),
+            Primitive::Segment(_) => 0.,
+            Primitive::Capsule(c) => c.area(),
+            Primitive::Polygon(s) => s.area(),
+        }
+    }
+}
+
+impl Support for Primitive {
+    fn support(&self, direction: Vector2<f64>) -> f64 {
+        self.core()
+            .points()
+            .iter()
+            .map(|p| p.coords.dot(&direction))
+            .fold(std::f64::MIN, f64::max)
+            + self.radius()
+    }
+}
+
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Primitive::Disc(a) => write!(f, "{}", a),
+            Primitive::Segment(l) => write!(f, "{}", l),
+            Primitive::Capsule(c) => write!(f, "{}", c),
+            Primitive::Polygon(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn square(x: f64, y: f64) -> Primitive {
+        let vertices = [
+            Point2::new(x - 1., y - 1.),
+            Point2::new(x + 1., y - 1.),
+            Point2::new(x + 1., y + 1.),
+            Point2::new(x - 1., y + 1.),
+        ];
+        Primitive::Polygon(LineShape::from_vertices("Square", &vertices).unwrap())
+    }
+
+    #[test]
+    fn disc_polygon() {
+        let polygon = square(0., 0.);
+        assert!(polygon.intersects(&Primitive::Disc(Atom2::new(1.4, 0., 0.5))));
+        assert!(!polygon.intersects(&Primitive::Disc(Atom2::new(1.6, 0., 0.5))));
+        // A disc completely inside the polygon
+        assert!(polygon.intersects(&Primitive::Disc(Atom2::new(0., 0., 0.1))));
+        // Close to the corner along the diagonal
+        assert!(!polygon.intersects(&Primitive::Disc(Atom2::new(1.4, 1.4, 0.5))));
+    }
+
+    #[test]
+    fn segment_pairs() {
+        let segment = Primitive::Segment(Line2::new((-1., 0.), (1., 0.)));
+        assert!(segment.intersects(&Primitive::Segment(Line2::new((0., -1.), (0., 1.)))));
+        assert!(!segment.intersects(&Primitive::Segment(Line2::new((0., 0.1), (0., 1.)))));
+        assert!(segment.intersects(&Primitive::Disc(Atom2::new(0., 0.5, 0.6))));
+        assert!(segment.intersects(&Primitive::Capsule(Capsule2::new((0., 0.5), (0., 1.), 0.6))));
+        // A segment inside a polygon
+        let inside = Primitive::Segment(Line2::new((-0.5, 0.), (0.5, 0.)));
+        assert!(inside.intersects(&square(0., 0.)));
+    }
+
+    #[test]
+    fn polygon_pairs() {
+        assert!(square(0., 0.).intersects(&square(1.9, 0.)));
+        assert!(!square(0., 0.).intersects(&square(2.1, 0.)));
+        let capsule = Primitive::Capsule(Capsule2::new((1.2, -3.), (1.2, 3.), 0.25));
+        assert!(square(0., 0.).intersects(&capsule));
+        assert!(!square(-0.1, 0.).intersects(&capsule));
+    }
+
+    #[test]
+    fn capsule_spans() {
+        let capsule = Primitive::Capsule(Capsule2::new((-1., 0.), (1., 0.), 0.5));
+        assert_eq!(capsule.spans(0.), vec![(-1.5, 1.5)]);
+        assert_eq!(capsule.spans(1.), vec![]);
+    }
+}
@@ -0,0 +1,38 @@
+//
+// primitive_ops.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+#![allow(clippy::op_ref)]
+
+use std::ops::Mul;
+
+use super::Primitive;
+use crate::traits::Shape;
+use crate::Transform2;
+
+fn transform_primitive(transform: &Transform2, primitive: &Primitive) -> Primitive {
+    match primitive {
+        Primitive::Disc(a) => Primitive::Disc(transform * a),
+        Primitive::Segment(l) => Primitive::Segment(transform * l),
+        Primitive::Capsule(c) => Primitive::Capsule(transform * c),
+        Primitive::Polygon(s) => Primitive::Polygon(s.transform(transform)),
+    }
+}
+
+binop_impl_all!(
+    Mul, mul;
+    self: Transform2, rhs: Primitive, Output = Primitive;
+    [ref ref] => {
+        transform_primitive(self, rhs)
+    };
+);
+
+binop_impl_all!(
+    Mul, mul;
+    self: Primitive, rhs: Transform2, Output = Primitive;
+    [ref ref] => {
+        transform_primitive(rhs, self)
+    };
+);
@@ -0,0 +1,223 @@
+//
+// ellipse_shape.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::{fmt, slice};
+
+use anyhow::{bail, Error};
+use itertools::{iproduct, Itertools};
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use super::{Ellipse2, Transform2};
+use crate::traits::{Intersect, Shape, Support};
+use crate::{PointGroup, SYMMETRY_TOLERANCE};
+
+/// A shape defined by a collection of Ellipses
+///
+/// This is the anisotropic counterpart of the [`MolecularShape2`], where each of the components
+/// is an ellipse. The ellipses making up the shape are assumed not to overlap each other, so
+/// the area is the sum of the areas of the components.
+///
+/// [`MolecularShape2`]: crate::MolecularShape2
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub struct EllipseShape {
+    pub name: String,
+    pub items: Vec<Ellipse2>,
+}
+
+impl<'a> IntoIterator for &'a EllipseShape {
+    type Item = &'a Ellipse2;
+    type IntoIter = slice::Iter<'a, Ellipse2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl Intersect for EllipseShape {
+    fn intersects(&self, other: &Self) -> bool {
+        iproduct!(self.items.iter(), other.items.iter()).any(|(s, o)| s.intersects(o))
+    }
+
+    fn area(&self) -> f64 {
+        self.items.iter().map(Intersect::area).sum()
+    }
+}
+
+impl Support for EllipseShape {
+    fn support(&self, direction: Vector2<f64>) -> f64 {
+        self.items
+            .iter()
+            .map(|e| e.support(direction))
+            .fold(std::f64::MIN, f64::max)
+    }
+}
+
+impl Shape for EllipseShape {
+    type Component = Ellipse2;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn score(&self, other: &Self) -> Option<f64> {
+        if self.intersects(other) {
+            None
+        } else {
+            Some(self.area())
+        }
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        self.items
+            .iter()
+            .map(|e| e.position.coords.norm() + f64::max(e.semi_major, e.semi_minor))
+            .fold(std::f64::MIN, f64::max)
+    }
+
+    fn point_group(&self) -> PointGroup {
+        let points: Vec<_> = self
+            .items
+            .iter()
+            .flat_map(Ellipse2::symmetry_points)
+            .collect();
+        PointGroup::detect(&points, SYMMETRY_TOLERANCE)
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.items.clone()
+    }
+
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform2) -> Self {
+        Self {
+            name: self.name.clone(),
+            items: self.into_iter().map(|i| i * transform).collect(),
+        }
+    }
+}
+
+impl fmt::Display for EllipseShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EllipseShape {{ {} }}", self.items.iter().format(", "))
+    }
+}
+
+impl EllipseShape {
+    /// Create a single ellipse at the origin with the major axis along x
+    ///
+    /// The semi-major axis has a length of 1, with the semi-minor axis being shorter by the
+    /// `aspect_ratio`, which needs to be at least 1.
+    ///
+    /// ```
+    /// use crystal_packing::{EllipseShape, Intersect};
+    /// let shape = EllipseShape::ellipse(2.).unwrap();
+    /// assert!((shape.area() - std::f64::consts::PI / 2.).abs() < 1e-12);
+    /// ```
+    ///
+    pub fn ellipse(aspect_ratio: f64) -> Result<EllipseShape, Error> {
+        if aspect_ratio.is_nan() || aspect_ratio < 1. {
+            bail!(
+                "The aspect ratio of an ellipse needs to be at least 1, got {}",
+                aspect_ratio
+            );
+        }
+        Ok(EllipseShape {
+            name: String::from("Ellipse"),
+            items: vec![Ellipse2::new(0., 0., 1., 1. / aspect_ratio, 0.)],
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+    use std::f64::consts::PI;
+
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::traits::{Optimiser, State};
+    use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+    use crate::{MCOptimiser, PackedState};
+
+    #[test]
+    fn invalid_aspect_ratio() {
+        assert!(EllipseShape::ellipse(0.5).is_err());
+        assert!(EllipseShape::ellipse(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn transform() {
+        let shape = EllipseShape::ellipse(2.).unwrap();
+        let transformed = shape.transform(&Transform2::new(PI / 2., (1., 2.)));
+        assert_abs_diff_eq!(transformed.items[0].angle, PI / 2., epsilon = 1e-12);
+        assert_abs_diff_eq!(transformed.items[0].position.x, 1., epsilon = 1e-12);
+        // A mirror through the line y = x swaps the axes
+        let mirror = Transform2::from_operations("y,x").unwrap();
+        let mirrored = shape.transform(&mirror);
+        assert_abs_diff_eq!(mirrored.items[0].angle, PI / 2., epsilon = 1e-12);
+    }
+
+    /// The densest packing of ellipses is the affine stretch of the hexagonal packing of circles
+    #[test]
+    fn densest_packing() {
+        let ratio = 2.;
+        let shape = EllipseShape::ellipse(ratio).unwrap();
+        let group: WallpaperGroup = WallpaperGroups::p1.try_into().unwrap();
+        let state = PackedState::from_group(shape, &group).unwrap();
+
+        // The hexagonal lattice of circles with a radius b = 1 / ratio, stretched along x. The
+        // lattice is rotated so the first vector is along the x axis, with the ellipse rotated
+        // by the same amount. The second vector is chosen so the angle between them is acute.
+        let b = 1. / ratio;
+        let set_lattice = |scale: f64| {
+            let a_vector = Vector2::new(1., f64::sqrt(3.) * b) * scale;
+            let b_vector = Vector2::new(0., 2. * f64::sqrt(3.) * b) * scale;
+            let rotation = f64::atan2(a_vector.y, a_vector.x);
+            let basis = state.generate_expanding_basis();
+            basis[0].set_value(a_vector.norm()).unwrap();
+            basis[1]
+                .set_value(b_vector.norm() / a_vector.norm())
+                .unwrap();
+            basis[2].set_value(a_vector.angle(&b_vector)).unwrap();
+            basis[5].set_value(PI - rotation).unwrap();
+        };
+        set_lattice(1. + 1e-9);
+        assert_abs_diff_eq!(state.score().unwrap(), PI / f64::sqrt(12.), epsilon = 1e-8);
+        set_lattice(1. - 1e-6);
+        assert_eq!(state.score(), None);
+    }
+
+    #[test]
+    fn optimise_ellipses() {
+        let shape = EllipseShape::ellipse(2.).unwrap();
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(shape, &group).unwrap();
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 10000, 500, 0, None).optimise_state(state);
+        let score = state.score().unwrap();
+        assert!(score > 0.75, "{}", score);
+        assert!(score <= PI / f64::sqrt(12.) + 1e-9);
+    }
+
+    #[test]
+    fn ellipse_point_group() {
+        let shape = EllipseShape::ellipse(2.).unwrap();
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(2));
+        assert!(!shape.is_chiral());
+        // The axes of an ellipse away from the origin don't have to line up with it
+        let shape = EllipseShape {
+            name: "Tilted".to_string(),
+            items: vec![Ellipse2::new(1., 0., 1., 0.5, PI / 4.)],
+        };
+        assert_eq!(shape.point_group(), PointGroup::Cyclic(1));
+    }
+}
@@ -0,0 +1,430 @@
+//
+// flexible_trimer.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::f64::consts::PI;
+use std::{fmt, slice};
+
+use anyhow::{bail, Error};
+use itertools::{iproduct, Itertools};
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use super::{Atom2, Line2, MolecularShape2, Transform2};
+use crate::traits::{Boundary, Intersect, Shape, Support};
+use crate::{Basis, PointGroup, SharedValue};
+
+/// A Trimer where the angle between the smaller particles is able to change
+///
+/// The angle is an internal degree of freedom of the shape, which is optimised along with the
+/// packing within the bounds `min_angle` and `max_angle`. Bending the molecule away from the
+/// angle it was created with has an energy of `stiffness` multiplied by the square of the change
+/// in angle, which is zero unless set using [`FlexibleTrimer::with_stiffness`].
+///
+/// The angles are in degrees, matching [`MolecularShape2::from_trimer`].
+///
+/// ```
+/// use crystal_packing::{FlexibleTrimer, Shape};
+/// let shape = FlexibleTrimer::new(0.7, 120., 1., 90., 180.).unwrap();
+/// assert_eq!(shape.internal_basis().len(), 1);
+/// ```
+///
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(into = "Conformation", try_from = "Conformation")]
+pub struct FlexibleTrimer {
+    pub radius: f64,
+    pub distance: f64,
+    pub min_angle: f64,
+    pub max_angle: f64,
+    pub rest_angle: f64,
+    pub stiffness: f64,
+    /// The angle between the smaller particles in radians
+    angle: SharedValue,
+    /// The transform applied to the shape since it was created
+    placement: Transform2,
+    /// The atoms in the conformation of `items_angle`, which are the atoms of the current
+    /// conformation unless the angle has changed since the shape was last transformed
+    items: Vec<Atom2>,
+    items_angle: f64,
+}
+
+/// The representation of a [`FlexibleTrimer`] in the output, with the chosen conformation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename = "FlexibleTrimer")]
+struct Conformation {
+    name: String,
+    radius: f64,
+    distance: f64,
+    angle: f64,
+    min_angle: f64,
+    max_angle: f64,
+    rest_angle: f64,
+    #[serde(default)]
+    stiffness: f64,
+    #[serde(default)]
+    items: Vec<Atom2>,
+}
+
+impl From<FlexibleTrimer> for Conformation {
+    fn from(shape: FlexibleTrimer) -> Conformation {
+        Conformation {
+            name: String::from(shape.name()),
+            radius: shape.radius,
+            distance: shape.distance,
+            angle: shape.angle(),
+            min_angle: shape.min_angle,
+            max_angle: shape.max_angle,
+            rest_angle: shape.rest_angle,
+            stiffness: shape.stiffness,
+            items: shape.get_items(),
+        }
+    }
+}
+
+impl TryFrom<Conformation> for FlexibleTrimer {
+    type Error = Error;
+
+    fn try_from(c: Conformation) -> Result<FlexibleTrimer, Error> {
+        let shape =
+            FlexibleTrimer::new(c.radius, c.rest_angle, c.distance, c.min_angle, c.max_angle)?
+                .with_stiffness(c.stiffness);
+        shape.angle.set_value(c.angle.to_radians());
+        Ok(shape.transform(&Transform2::identity()))
+    }
+}
+
+impl Clone for FlexibleTrimer {
+    fn clone(&self) -> Self {
+        FlexibleTrimer {
+            radius: self.radius,
+            distance: self.distance,
+            min_angle: self.min_angle,
+            max_angle: self.max_angle,
+            rest_angle: self.rest_angle,
+            stiffness: self.stiffness,
+            angle: SharedValue::new(self.angle.get_value()),
+            placement: self.placement,
+            items: self.items.clone(),
+            items_angle: self.items_angle,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a FlexibleTrimer {
+    type Item = &'a Atom2;
+    type IntoIter = slice::Iter<'a, Atom2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl Intersect for FlexibleTrimer {
+    fn intersects(&self, other: &Self) -> bool {
+        iproduct!(self.atoms().iter(), other.atoms().iter()).any(|(s, o)| s.intersects(o))
+    }
+
+    fn area(&self) -> f64 {
+        let atoms = self.atoms();
+        let total_area: f64 = atoms.iter().map(|a| PI * a.radius.powi(2)).sum();
+        let overlap: f64 = atoms
+            .iter()
+            .tuple_combinations()
+            .map(|(a1, a2)| MolecularShape2::circle_overlap(a1, a2))
+            .sum();
+        total_area - overlap
+    }
+}
+
+impl Support for FlexibleTrimer {
+    fn support(&self, direction: Vector2<f64>) -> f64 {
+        self.atoms()
+            .iter()
+            .map(|a| a.position.coords.dot(&direction) + a.radius)
+            .fold(std::f64::MIN, f64::max)
+    }
+}
+
+impl Boundary for FlexibleTrimer {
+    fn crosses(&self, edge: &Line2) -> bool {
+        self.atoms()
+            .iter()
+            .any(|a| edge.distance_to(&a.position) < a.radius)
+    }
+
+    fn contains(&self, point: &Point2<f64>) -> bool {
+        self.atoms()
+            .iter()
+            .any(|a| (point - a.position).norm() < a.radius)
+    }
+}
+
+impl Shape for FlexibleTrimer {
+    type Component = Atom2;
+
+    fn name(&self) -> &str {
+        "FlexibleTrimer"
+    }
+
+    fn score(&self, other: &Self) -> Option<f64> {
+        if self.intersects(other) {
+            None
+        } else {
+            Some(self.area())
+        }
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        // A bound for every conformation, with the central particle at most 2/3 of the distance
+        // from the origin, and the smaller particles at most the distance
+        f64::max(1. + 2. / 3. * self.distance, self.radius + self.distance)
+    }
+
+    fn point_group(&self) -> PointGroup {
+        // Every conformation has a mirror line bisecting the angle
+        PointGroup::Dihedral(1)
+    }
+
+    fn internal_basis(&self) -> Vec<Basis> {
+        vec![Basis::StandardBasis {
+            value: &self.angle,
+            min: self.min_angle.to_radians(),
+            max: self.max_angle.to_radians(),
+        }]
+    }
+
+    fn internal_energy(&self) -> f64 {
+        self.stiffness * (self.angle.get_value() - self.rest_angle.to_radians()).powi(2)
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.atoms().into_owned()
+    }
+
+    /// The atoms of the shape when it was last transformed
+    ///
+    /// Changing the angle through the internal basis doesn't update these atoms, use
+    /// [`Shape::get_items`] for the atoms in the current conformation.
+    ///
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform2) -> Self {
+        let placement = transform * self.placement;
+        FlexibleTrimer {
+            placement,
+            items: self.conformation_at(&placement).items,
+            items_angle: self.angle.get_value(),
+            ..self.clone()
+        }
+    }
+}
+
+impl fmt::Display for FlexibleTrimer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FlexibleTrimer {{ angle: {:.2}, ", self.angle())?;
+        for item in self.items.iter() {
+            write!(f, "{},", item)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+impl FlexibleTrimer {
+    /// Create a Trimer with an angle which can change between `min_angle` and `max_angle`
+    ///
+    /// The arguments are the same as [`MolecularShape2::from_trimer`], with the initial `angle`
+    /// being the angle without any bending energy.
+    ///
+    pub fn new(
+        radius: f64,
+        angle: f64,
+        distance: f64,
+        min_angle: f64,
+        max_angle: f64,
+    ) -> Result<FlexibleTrimer, Error> {
+        if !(radius > 0. && distance > 0.) {
+            bail!(
+                "The radius and distance of a Trimer need to be positive, got {} and {}",
+                radius,
+                distance
+            );
+        }
+        if !(0. ..=180.).contains(&min_angle) || !(0. ..=180.).contains(&max_angle) {
+            bail!(
+                "The bounds of the angle need to be between 0 and 180 degrees, got {} and {}",
+                min_angle,
+                max_angle
+            );
+        }
+        if !(min_angle..=max_angle).contains(&angle) {
+            bail!(
+                "The angle {} needs to be between the bounds {} and {}",
+                angle,
+                min_angle,
+                max_angle
+            );
+        }
+        Ok(FlexibleTrimer {
+            radius,
+            distance,
+            min_angle,
+            max_angle,
+            rest_angle: angle,
+            stiffness: 0.,
+            angle: SharedValue::new(angle.to_radians()),
+            placement: Transform2::identity(),
+            items: MolecularShape2::from_trimer(radius, angle, distance).items,
+            items_angle: angle.to_radians(),
+        })
+    }
+
+    /// Penalise bending the molecule with an energy of `stiffness` per square radian
+    pub fn with_stiffness(mut self, stiffness: f64) -> Self {
+        self.stiffness = stiffness;
+        self
+    }
+
+    /// The current angle between the smaller particles in degrees
+    pub fn angle(&self) -> f64 {
+        self.angle.get_value().to_degrees()
+    }
+
+    /// The rigid shape with the current angle
+    pub fn conformation(&self) -> MolecularShape2 {
+        self.conformation_at(&self.placement)
+    }
+
+    /// The atoms of the current conformation
+    ///
+    /// The shapes being compared are transformed copies, which keep the atoms from when they
+    /// were transformed, so these are only recalculated when the angle has since changed.
+    ///
+    fn atoms(&self) -> Cow<'_, [Atom2]> {
+        if self.angle.get_value() == self.items_angle {
+            Cow::Borrowed(&self.items)
+        } else {
+            Cow::Owned(self.conformation().items)
+        }
+    }
+
+    fn conformation_at(&self, placement: &Transform2) -> MolecularShape2 {
+        MolecularShape2::from_trimer(self.radius, self.angle(), self.distance).transform(placement)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+    use std::f64::consts::FRAC_PI_2 as PI_2;
+
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::traits::{Optimiser, Packed, State, Supercell};
+    use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+    use crate::{IsobaricOptimiser, LinearPressure, MCOptimiser, PackedState};
+
+    #[test]
+    fn invalid_bounds() {
+        assert!(FlexibleTrimer::new(0.7, 120., 1., 90., 200.).is_err());
+        assert!(FlexibleTrimer::new(0.7, 60., 1., 90., 180.).is_err());
+        assert!(FlexibleTrimer::new(0., 120., 1., 90., 180.).is_err());
+        assert!(FlexibleTrimer::new(0.7, 120., -1., 90., 180.).is_err());
+        assert!(FlexibleTrimer::new(f64::NAN, 120., 1., 90., 180.).is_err());
+    }
+
+    #[test]
+    fn basis_changes_conformation() {
+        let shape = FlexibleTrimer::new(0.7, 120., 1., 60., 180.).unwrap();
+        shape.internal_basis()[0].set_value(PI_2).unwrap();
+        assert_abs_diff_eq!(shape.angle(), 90.);
+        let expected = MolecularShape2::from_trimer(0.7, 90., 1.);
+        assert_eq!(shape.get_items(), expected.items);
+        let transform = Transform2::new(0.5, (1., 2.));
+        assert_eq!(
+            shape.transform(&transform).get_items(),
+            expected.transform(&transform).items
+        );
+        assert!(shape.internal_basis()[0].set_value(0.5).is_err());
+    }
+
+    #[test]
+    fn bending_energy() {
+        let shape = FlexibleTrimer::new(0.7, 120., 1., 60., 180.)
+            .unwrap()
+            .with_stiffness(2.);
+        assert_abs_diff_eq!(shape.internal_energy(), 0.);
+        shape.internal_basis()[0].set_value(PI_2).unwrap();
+        assert_abs_diff_eq!(shape.internal_energy(), 2. * (PI_2 / 3.).powi(2));
+    }
+
+    #[test]
+    fn serialise_conformation() {
+        let shape = FlexibleTrimer::new(0.7, 120., 1., 60., 180.).unwrap();
+        shape.internal_basis()[0].set_value(PI_2).unwrap();
+        let json = serde_json::to_string(&shape).unwrap();
+        assert!(json.contains("\"angle\":90"));
+        let read: FlexibleTrimer = serde_json::from_str(&json).unwrap();
+        assert_abs_diff_eq!(read.angle(), 90.);
+        assert_eq!(read.rest_angle, 120.);
+        assert_eq!(read.iter().cloned().collect::<Vec<_>>(), shape.get_items());
+    }
+
+    #[test]
+    fn optimise_conformation() {
+        let shape = FlexibleTrimer::new(0.7, 120., 1., 60., 180.).unwrap();
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(shape, &group).unwrap();
+        assert_eq!(state.generate_basis().len(), 7);
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 200, 0, None).optimise_state(state);
+        assert!(state.score().unwrap() > 0.5);
+        assert!(state.shape.angle() >= 60. && state.shape.angle() <= 180.);
+    }
+
+    #[test]
+    fn packing_fraction_excludes_bending() {
+        let shape = FlexibleTrimer::new(0.7, 120., 1., 60., 180.)
+            .unwrap()
+            .with_stiffness(2.);
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(shape, &group).unwrap();
+        assert_abs_diff_eq!(state.score().unwrap(), state.packing_fraction().unwrap());
+
+        // Only the score has the penalty for bending the molecule
+        state.shape.internal_basis()[0].set_value(PI_2).unwrap();
+        let fraction = state.shape.area() * state.total_shapes() as f64 / state.cell().area();
+        assert_abs_diff_eq!(state.packing_fraction().unwrap(), fraction);
+        assert_abs_diff_eq!(
+            state.score().unwrap(),
+            fraction - 2. * (PI_2 / 3.).powi(2),
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn compress_bent_conformation() {
+        let shape = FlexibleTrimer::new(0.7, 120., 1., 60., 180.)
+            .unwrap()
+            .with_stiffness(100.);
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(shape, &group).unwrap();
+        state.shape.internal_basis()[0].set_value(PI_2).unwrap();
+        assert!(state.score().unwrap() < 0.);
+
+        let optimiser =
+            IsobaricOptimiser::new(Box::new(LinearPressure::new(10., 10.)), 0.1, 2000, 100, 0);
+        let (state, eos) = optimiser.optimise_equation_of_state(state, &mut ());
+        // The equation of state is the packing fraction, rather than the penalised score
+        for point in eos.iter() {
+            assert!(point.packing_fraction > 0. && point.packing_fraction <= 1.);
+        }
+        // The bending energy relaxes the molecule towards the rest angle
+        assert!(state.shape.angle() > 90.);
+    }
+}
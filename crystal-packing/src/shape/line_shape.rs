@@ -11,12 +11,12 @@ use std::vec;
 
 use anyhow::{bail, Error};
 use itertools::{iproduct, Itertools};
-use nalgebra::{distance, Point2};
+use nalgebra::{distance, Point2, Vector2};
 use serde::{Deserialize, Serialize};
 
-use super::Line2;
-use crate::traits::{Intersect, Shape};
-use crate::Transform2;
+use super::{polygon_contains, Line2};
+use crate::traits::{Boundary, Intersect, Scale, Shape, Support};
+use crate::{PointGroup, Transform2, SYMMETRY_TOLERANCE};
 
 /// A Shape constructed from a collection of Lines
 ///
@@ -24,6 +24,7 @@ use crate::Transform2;
 /// a shape. It is assumed that the lines completely enclose an area, and that the enclosed area is
 /// close to the origin.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub struct LineShape {
     pub name: String,
     pub items: Vec<Line2>,
@@ -38,6 +39,24 @@ impl<'a> IntoIterator for &'a LineShape {
     }
 }
 
+impl Scale for LineShape {
+    fn scale(&self, factor: f64) -> Self {
+        Self {
+            name: self.name.clone(),
+            items: self
+                .items
+                .iter()
+                .map(|l| {
+                    Line2::new(
+                        (l.start.x * factor, l.start.y * factor),
+                        (l.end.x * factor, l.end.y * factor),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
 impl fmt::Display for LineShape {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "LineShape {{ {} }}", self.items.iter().format(", "))
@@ -58,23 +77,41 @@ impl Intersect for LineShape {
     }
 
     fn area(&self) -> f64 {
-        // This is the sine of the angle between each point, this is used for every calculation
-        // so pre-calculate here.
-        let angle_term: f64 = f64::sin(2. * PI / self.items.len() as f64);
+        // The shoelace formula, being the sum of the signed areas of the triangles made by each
+        // line and the origin, which is correct for any simple polygon.
         self.iter()
-            // Calculate the area of the of triangle made by the line and the origin
-            .map(|p| {
-                0.5 * angle_term
-                    * distance(&Point2::origin(), &p.start)
-                    * distance(&Point2::origin(), &p.end)
-            })
-            .sum()
+            .map(|p| p.start.coords.perp(&p.end.coords) / 2.)
+            .sum::<f64>()
+            .abs()
+    }
+}
+
+impl Support for LineShape {
+    fn support(&self, direction: Vector2<f64>) -> f64 {
+        // The furthest point of a polygon in any direction is one of the vertices
+        self.iter()
+            .map(|l| l.start.coords.dot(&direction))
+            .fold(std::f64::MIN, f64::max)
+    }
+}
+
+impl Boundary for LineShape {
+    fn crosses(&self, edge: &Line2) -> bool {
+        self.iter().any(|l| l.intersects(edge))
+    }
+
+    fn contains(&self, point: &Point2<f64>) -> bool {
+        polygon_contains(self.iter(), point)
     }
 }
 
 impl Shape for LineShape {
     type Component = Line2;
 
+    fn name(&self) -> &str {
+        &self.name
+    }
+
     fn score(&self, other: &Self) -> Option<f64> {
         if self.intersects(other) {
             None
@@ -92,6 +129,11 @@ impl Shape for LineShape {
             .fold(std::f64::MIN, f64::max)
     }
 
+    fn point_group(&self) -> PointGroup {
+        let points: Vec<_> = self.items.iter().flat_map(Line2::symmetry_points).collect();
+        PointGroup::detect(&points, SYMMETRY_TOLERANCE)
+    }
+
     fn get_items(&self) -> Vec<Self::Component> {
         self.items.clone()
     }
@@ -148,6 +190,66 @@ impl LineShape {
     pub fn polygon(sides: usize) -> Result<LineShape, Error> {
         LineShape::from_radial("Polygon", vec![1.; sides])
     }
+
+    /// Instantiate a LineShape from the vertices of a polygon
+    ///
+    /// The vertices are joined in order, with the last vertex joined to the first. The polygon
+    /// needs at least three vertices, and the edges can't cross each other.
+    ///
+    /// ```
+    /// use crystal_packing::{Intersect, LineShape};
+    /// use nalgebra::Point2;
+    /// let vertices = [Point2::new(0., 0.), Point2::new(2., 0.), Point2::new(0., 1.)];
+    /// let triangle = LineShape::from_vertices("Triangle", &vertices).unwrap();
+    /// assert_eq!(triangle.area(), 1.);
+    /// ```
+    ///
+    pub fn from_vertices(name: &str, vertices: &[Point2<f64>]) -> Result<LineShape, Error> {
+        if vertices.len() < 3 {
+            bail!("The number of points provided is too few to create a 2D shape.")
+        }
+        if vertices
+            .iter()
+            .any(|v| !(v.x.is_finite() && v.y.is_finite()))
+        {
+            bail!("The vertices of a shape need to be finite");
+        }
+        let items: Vec<Line2> = vertices
+            .iter()
+            .zip(vertices.iter().cycle().skip(1))
+            .map(|(&start, &end)| Line2 { start, end })
+            .collect();
+        // Adjacent edges share a vertex, so only the edges further apart are checked
+        let count = items.len();
+        for (i, j) in (0..count).tuple_combinations() {
+            let adjacent = j == i + 1 || (i == 0 && j == count - 1);
+            if !adjacent && items[i].intersects(&items[j]) {
+                bail!("The edges {} and {} of the polygon cross each other", i, j);
+            }
+        }
+        let shape = LineShape {
+            name: String::from(name),
+            items,
+        };
+        if shape.area() == 0. {
+            bail!("The polygon encloses no area");
+        }
+        Ok(shape)
+    }
+
+    /// The centre of mass of the area enclosed by the shape
+    pub fn centroid(&self) -> Point2<f64> {
+        let (weighted, signed_area) =
+            self.iter()
+                .fold((Vector2::zeros(), 0.), |(weighted, area), line| {
+                    let cross = line.start.coords.perp(&line.end.coords);
+                    (
+                        weighted + (line.start.coords + line.end.coords) * cross,
+                        area + cross / 2.,
+                    )
+                });
+        Point2::from(weighted / (6. * signed_area))
+    }
 }
 
 #[cfg(test)]
@@ -172,6 +274,23 @@ mod test {
         assert_abs_diff_eq!(square.area(), 2.);
     }
 
+    #[test]
+    fn from_vertices() {
+        let vertices = [
+            Point2::new(0., 0.),
+            Point2::new(2., 0.),
+            Point2::new(2., 1.),
+            Point2::new(0., 1.),
+        ];
+        let rectangle = LineShape::from_vertices("Rectangle", &vertices).unwrap();
+        assert_abs_diff_eq!(rectangle.area(), 2.);
+        assert_abs_diff_eq!(rectangle.centroid(), Point2::new(1., 0.5), epsilon = 1e-12);
+        // Swapping two vertices makes the edges cross
+        let crossed = [vertices[0], vertices[2], vertices[1], vertices[3]];
+        assert!(LineShape::from_vertices("Crossed", &crossed).is_err());
+        assert!(LineShape::from_vertices("Line", &vertices[..2]).is_err());
+    }
+
     #[test]
     fn max_radius() {
         let shape = LineShape::from_radial("iter_test", vec![1., 2., 3., 4.]).unwrap();
@@ -179,6 +298,14 @@ mod test {
         assert_abs_diff_eq!(shape.enclosing_radius(), 4.);
     }
 
+    #[test]
+    fn support() {
+        let square = create_square();
+        assert_abs_diff_eq!(square.support(Vector2::new(1., 0.)), 1., epsilon = 1e-12);
+        let diagonal = Vector2::new(1., 1.).normalize();
+        assert_abs_diff_eq!(square.support(diagonal), f64::sqrt(0.5), epsilon = 1e-12);
+    }
+
     #[test]
     fn intersection() {
         let square = create_square();
@@ -206,4 +333,33 @@ mod test {
         let transform = Transform2::new(0., (2.01, 2.01));
         assert!(!square.intersects(&square.transform(&transform)));
     }
+
+    #[test]
+    fn polygon_point_group() {
+        for sides in 3..8 {
+            let shape = LineShape::polygon(sides).unwrap();
+            assert_eq!(shape.point_group(), PointGroup::Dihedral(sides));
+            assert_eq!(shape.rotational_symmetries(), sides as u64);
+        }
+        let shape = LineShape::from_radial("Kite", vec![1., 0.5, 2., 0.5]).unwrap();
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(1));
+    }
+
+    #[test]
+    fn mirror_of_chiral_shape() {
+        let vertices = [
+            Point2::new(-1., -1.),
+            Point2::new(2., -1.),
+            Point2::new(-1., 1.),
+        ];
+        let shape = LineShape::from_vertices("Scalene", &vertices).unwrap();
+        assert!(shape.is_chiral());
+        let mirror = shape.mirror();
+        assert_abs_diff_eq!(mirror.area(), shape.area());
+        assert_abs_diff_eq!(mirror.items[1].start, Point2::new(-2., -1.));
+        // The mirror image is the shape placed by an improper operation
+        let reflection = Transform2::from_operations("-x,y").unwrap();
+        assert_eq!(shape.transform(&reflection).items, mirror.items);
+        assert!(!LineShape::polygon(5).unwrap().is_chiral());
+    }
 }
@@ -7,16 +7,18 @@
 use std::{fmt, slice, vec};
 
 use itertools::iproduct;
-use nalgebra::{distance, Point2};
+use nalgebra::{distance, Point2, Vector2};
 use serde::{Deserialize, Serialize};
 
 use super::{Transform2, LJ2};
-use crate::traits::{Potential, Shape};
+use crate::traits::{Inertia, Potential, Shape, WallPotential};
+use crate::{PointGroup, SYMMETRY_TOLERANCE};
 
 /// A shape defined by a collection of Atoms
 ///
 /// This is a shape comprised of a series of circles which each have a position and radius.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub struct LJShape2 {
     pub name: String,
     pub items: Vec<LJ2>,
@@ -39,9 +41,42 @@ impl Potential for LJShape2 {
     }
 }
 
+/// Each particle interacts with the wall through the 9-3 Lennard Jones potential
+///
+/// This is the potential from integrating the 12-6 potential over a continuum of particles
+/// filling the space beyond the wall, $ \epsilon [\frac{2}{15} (\sigma / r)^9 - (\sigma / r)^3] $
+/// where r is the distance of the particle from the wall.
+///
+impl WallPotential for LJShape2 {
+    fn wall_energy(&self, normal: Vector2<f64>, offset: f64) -> f64 {
+        self.items
+            .iter()
+            .map(|i| {
+                let distance = offset - i.position.coords.dot(&normal);
+                if distance <= 0. {
+                    return f64::INFINITY;
+                }
+                let ratio = (i.sigma / distance).powi(3);
+                i.epsilon * (2. / 15. * ratio.powi(3) - ratio)
+            })
+            .sum()
+    }
+}
+
+/// Each particle has a unit mass, being the reduced units of the Lennard Jones potential
+impl Inertia for LJShape2 {
+    fn point_masses(&self) -> Vec<(Point2<f64>, f64)> {
+        self.items.iter().map(|i| (i.position, 1.)).collect()
+    }
+}
+
 impl Shape for LJShape2 {
     type Component = LJ2;
 
+    fn name(&self) -> &str {
+        &self.name
+    }
+
     fn score(&self, other: &Self) -> Option<f64> {
         Some(
             iproduct!(self.items.iter(), other.items.iter())
@@ -59,6 +94,18 @@ impl Shape for LJShape2 {
             .fold(std::f64::MIN, f64::max)
     }
 
+    fn point_group(&self) -> PointGroup {
+        let points: Vec<_> = self
+            .items
+            .iter()
+            .map(|a| {
+                let cutoff = a.cutoff.unwrap_or(0.);
+                (a.position, vec![a.sigma, a.epsilon, cutoff])
+            })
+            .collect();
+        PointGroup::detect(&points, SYMMETRY_TOLERANCE)
+    }
+
     fn get_items(&self) -> Vec<Self::Component> {
         self.items.clone()
     }
@@ -158,6 +205,18 @@ mod test {
     use super::*;
     use nalgebra::Point2;
 
+    #[test]
+    fn wall_minimum() {
+        let circle = LJShape2::circle();
+        let normal = Vector2::new(0., 1.);
+        // The minimum of the 9-3 potential is at r = (2 / 5)^(1/6) sigma
+        let minimum = f64::powf(0.4, 1. / 6.);
+        let energy = circle.wall_energy(normal, minimum);
+        assert!(energy < circle.wall_energy(normal, minimum * 0.99));
+        assert!(energy < circle.wall_energy(normal, minimum * 1.01));
+        assert_eq!(circle.wall_energy(normal, -0.1), f64::INFINITY);
+    }
+
     #[test]
     fn from_trimer_test() {
         let shape = LJShape2::from_trimer(1., 180., 1.);
@@ -0,0 +1,335 @@
+//
+// mixed_shape.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::{fmt, slice};
+
+use anyhow::{bail, Error};
+use itertools::{iproduct, Itertools};
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use super::{Atom2, CapsuleShape, Line2, LineShape, Primitive, Transform2};
+use crate::traits::{Boundary, Intersect, Shape, Support};
+use crate::{PointGroup, SYMMETRY_TOLERANCE};
+
+/// The number of horizontal lines used to integrate the area of a shape
+const AREA_LINES: usize = 4096;
+
+/// A shape made from a combination of different primitives
+///
+/// The components are able to be discs, line segments, capsules and polygons, allowing for
+/// shapes like a polygon with discs at the corners, or a rod with a spherical head. The overlap
+/// of each pair of components is dispatched on the types of the primitives.
+///
+/// Unlike the other shapes, the components can overlap each other in any way, so the area of
+/// the union is integrated numerically when the shape is created.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub struct MixedShape {
+    pub name: String,
+    pub items: Vec<Primitive>,
+    area: f64,
+}
+
+impl<'a> IntoIterator for &'a MixedShape {
+    type Item = &'a Primitive;
+    type IntoIter = slice::Iter<'a, Primitive>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl Intersect for MixedShape {
+    fn intersects(&self, other: &Self) -> bool {
+        iproduct!(self.items.iter(), other.items.iter()).any(|(s, o)| s.intersects(o))
+    }
+
+    fn area(&self) -> f64 {
+        self.area
+    }
+}
+
+impl Support for MixedShape {
+    fn support(&self, direction: Vector2<f64>) -> f64 {
+        self.items
+            .iter()
+            .map(|p| p.support(direction))
+            .fold(std::f64::MIN, f64::max)
+    }
+}
+
+impl Boundary for MixedShape {
+    fn crosses(&self, edge: &Line2) -> bool {
+        let edge = Primitive::Segment(*edge);
+        self.items.iter().any(|p| p.intersects(&edge))
+    }
+
+    fn contains(&self, point: &Point2<f64>) -> bool {
+        self.items.iter().any(|p| p.contains(point))
+    }
+}
+
+impl Shape for MixedShape {
+    type Component = Primitive;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn score(&self, other: &Self) -> Option<f64> {
+        if self.intersects(other) {
+            None
+        } else {
+            Some(self.area())
+        }
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        self.items
+            .iter()
+            .map(Primitive::extent)
+            .fold(std::f64::MIN, f64::max)
+    }
+
+    fn point_group(&self) -> PointGroup {
+        let points: Vec<_> = self
+            .items
+            .iter()
+            .flat_map(Primitive::symmetry_points)
+            .collect();
+        PointGroup::detect(&points, SYMMETRY_TOLERANCE)
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.items.clone()
+    }
+
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform2) -> Self {
+        Self {
+            name: self.name.clone(),
+            items: self.into_iter().map(|i| i * transform).collect(),
+            area: self.area,
+        }
+    }
+}
+
+impl fmt::Display for MixedShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MixedShape {{ {} }}", self.items.iter().format(", "))
+    }
+}
+
+impl MixedShape {
+    pub fn new(name: &str, items: Vec<Primitive>) -> Result<MixedShape, Error> {
+        if items.is_empty() {
+            bail!("A shape needs at least one component");
+        }
+        let area = Self::union_area(&items);
+        Ok(MixedShape {
+            name: String::from(name),
+            items,
+            area,
+        })
+    }
+
+    /// The length of the horizontal line at `y` which is within any of the primitives
+    fn union_width(items: &[Primitive], y: f64) -> f64 {
+        let spans = items
+            .iter()
+            .flat_map(|p| p.spans(y))
+            .sorted_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        // Merge the overlapping spans, summing the length of each merged span
+        let (length, current) =
+            spans.fold(
+                (0., None),
+                |(length, current), (start, end)| match current {
+                    Some((s, e)) if start <= e => (length, Some((s, f64::max(e, end)))),
+                    Some((s, e)) => (length + e - s, Some((start, end))),
+                    None => (length, Some((start, end))),
+                },
+            );
+        length + current.map_or(0., |(s, e)| e - s)
+    }
+
+    /// The area covered by any of the primitives
+    ///
+    /// This integrates the width of the shape along y using the midpoint rule. The range of y is
+    /// split at each of the vertices and the tops of discs, so the width is smooth within each
+    /// of the intervals being integrated.
+    ///
+    fn union_area(items: &[Primitive]) -> f64 {
+        let breakpoints: Vec<f64> = items
+            .iter()
+            .flat_map(Primitive::breakpoints)
+            .sorted_by(|a, b| a.partial_cmp(b).unwrap())
+            .dedup()
+            .collect();
+        let total = breakpoints.last().unwrap_or(&0.) - breakpoints.first().unwrap_or(&0.);
+        breakpoints
+            .iter()
+            .tuple_windows()
+            .map(|(&bottom, &top)| {
+                let lines = usize::max(8, (AREA_LINES as f64 * (top - bottom) / total) as usize);
+                let height = (top - bottom) / lines as f64;
+                (0..lines)
+                    .map(|index| Self::union_width(items, bottom + (index as f64 + 0.5) * height))
+                    .sum::<f64>()
+                    * height
+            })
+            .sum()
+    }
+
+    /// A regular polygon with a circumradius of 1, with a disc of `radius` on each corner
+    ///
+    /// ```
+    /// use crystal_packing::MixedShape;
+    /// let shape = MixedShape::bumpy_polygon(4, 0.3).unwrap();
+    /// assert_eq!(shape.items.len(), 5);
+    /// ```
+    ///
+    pub fn bumpy_polygon(sides: usize, radius: f64) -> Result<MixedShape, Error> {
+        if radius.is_nan() || radius <= 0. {
+            bail!(
+                "The radius of the discs needs to be positive, got {}",
+                radius
+            );
+        }
+        let polygon = LineShape::polygon(sides)?;
+        let discs = polygon
+            .items
+            .iter()
+            .map(|l| Primitive::Disc(Atom2::new(l.start.x, l.start.y, radius)));
+        let items = std::iter::once(Primitive::Polygon(polygon.clone()))
+            .chain(discs)
+            .collect();
+        Self::new("BumpyPolygon", items)
+    }
+
+    /// A rod with a disc of `radius` at one end
+    ///
+    /// The rod is the capsule with the `aspect_ratio` from [`CapsuleShape::capsule`], with the
+    /// centre of the disc at the end of the rod along the positive x axis.
+    ///
+    pub fn headed_rod(aspect_ratio: f64, radius: f64) -> Result<MixedShape, Error> {
+        if radius.is_nan() || radius <= 0. {
+            bail!(
+                "The radius of the head needs to be positive, got {}",
+                radius
+            );
+        }
+        let rod = CapsuleShape::capsule(aspect_ratio)?.items[0];
+        let head = rod.segment.end;
+        Self::new(
+            "HeadedRod",
+            vec![
+                Primitive::Capsule(rod),
+                Primitive::Disc(Atom2::new(head.x, head.y, radius)),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+    use std::f64::consts::PI;
+
+    use approx::assert_abs_diff_eq;
+    use nalgebra::Point2;
+
+    use super::*;
+    use crate::traits::{Optimiser, State};
+    use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+    use crate::{Capsule2, MCOptimiser, PackedState};
+
+    #[test]
+    fn area_of_single_primitives() {
+        let disc = MixedShape::new("Disc", vec![Primitive::Disc(Atom2::new(0.3, 0.2, 1.))]);
+        assert_abs_diff_eq!(disc.unwrap().area(), PI, epsilon = 1e-4);
+        let capsule = Capsule2::new((-1., 0.5), (1., -0.5), 0.5);
+        let shape = MixedShape::new("Capsule", vec![Primitive::Capsule(capsule)]).unwrap();
+        assert_abs_diff_eq!(shape.area(), capsule.area(), epsilon = 1e-4);
+        let polygon = LineShape::polygon(6).unwrap();
+        let shape = MixedShape::new("Hexagon", vec![Primitive::Polygon(polygon.clone())]);
+        assert_abs_diff_eq!(shape.unwrap().area(), polygon.area(), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn area_of_overlapping_primitives() {
+        // A disc covering the corner of a square, which overlaps by a quarter of the disc
+        let square = LineShape::from_vertices(
+            "Square",
+            &[
+                Point2::new(0., 0.),
+                Point2::new(1., 0.),
+                Point2::new(1., 1.),
+                Point2::new(0., 1.),
+            ],
+        )
+        .unwrap();
+        let shape = MixedShape::new(
+            "Bump",
+            vec![
+                Primitive::Polygon(square),
+                Primitive::Disc(Atom2::new(1., 1., 0.5)),
+            ],
+        )
+        .unwrap();
+        assert_abs_diff_eq!(shape.area(), 1. + 0.75 * PI * 0.25, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn invalid_shapes() {
+        assert!(MixedShape::new("Empty", vec![]).is_err());
+        assert!(MixedShape::bumpy_polygon(4, 0.).is_err());
+        assert!(MixedShape::headed_rod(0.5, 0.5).is_err());
+    }
+
+    #[test]
+    fn transform_keeps_area() {
+        let shape = MixedShape::headed_rod(4., 0.5).unwrap();
+        let transformed = shape.transform(&Transform2::new(1., (2., 3.)));
+        assert_eq!(shape.area(), transformed.area());
+        assert!(shape.intersects(&transformed.transform(&Transform2::new(0., (-2., -3.)))));
+    }
+
+    #[test]
+    fn bumps_interlock() {
+        // The discs on the corners of the squares collide before the edges
+        let shape = MixedShape::bumpy_polygon(4, 0.2).unwrap();
+        let rotation = Transform2::new(PI / 4., (0., 0.));
+        let shape = shape.transform(&rotation);
+        let side = f64::sqrt(2.);
+        assert!(shape.intersects(&shape.transform(&Transform2::new(0., (side + 0.1, 0.)))));
+        let other = shape.transform(&Transform2::new(0., (side + 0.5, 0.)));
+        assert!(!shape.intersects(&other));
+    }
+
+    #[test]
+    fn optimise_headed_rods() {
+        let shape = MixedShape::headed_rod(3., 0.5).unwrap();
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(shape, &group).unwrap();
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 200, 0, None).optimise_state(state);
+        let score = state.score().unwrap();
+        assert!(score > 0.6, "{}", score);
+    }
+
+    #[test]
+    fn mixed_point_group() {
+        let shape = MixedShape::bumpy_polygon(4, 0.2).unwrap();
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(4));
+        let shape = MixedShape::headed_rod(2., 0.5).unwrap();
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(1));
+        assert!(!shape.is_chiral());
+    }
+}
@@ -9,11 +9,27 @@ pub use super::Transform2;
 
 pub mod components;
 
+pub mod capsule_shape;
+pub mod ellipse_shape;
+pub mod flexible_trimer;
 pub mod line_shape;
 pub mod lj_shape;
+pub mod mixed_shape;
 pub mod molecular_shape2;
+pub mod molecular_shape3;
+pub mod parametric_shape;
+pub mod polyhedron;
+pub mod rounded_shape;
 
+pub use capsule_shape::*;
 pub use components::*;
+pub use ellipse_shape::*;
+pub use flexible_trimer::*;
 pub use line_shape::*;
 pub use lj_shape::*;
+pub use mixed_shape::*;
 pub use molecular_shape2::*;
+pub use molecular_shape3::*;
+pub use parametric_shape::*;
+pub use polyhedron::*;
+pub use rounded_shape::*;
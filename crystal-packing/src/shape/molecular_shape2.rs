@@ -8,16 +8,18 @@ use std::f64::consts::PI;
 use std::{fmt, slice, vec};
 
 use itertools::{iproduct, Itertools};
-use nalgebra::Point2;
+use nalgebra::{Point2, Vector2};
 use serde::{Deserialize, Serialize};
 
-use super::{Atom2, Transform2};
-use crate::traits::{Intersect, Shape};
+use super::{Atom2, Line2, Transform2};
+use crate::traits::{Boundary, Intersect, Scale, Shape, Support};
+use crate::{PointGroup, SYMMETRY_TOLERANCE};
 
 /// A shape defined by a collection of Atoms
 ///
 /// This is a shape comprised of a series of circles which each have a position and radius.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub struct MolecularShape2 {
     pub name: String,
     pub items: Vec<Atom2>,
@@ -52,9 +54,36 @@ impl Intersect for MolecularShape2 {
     }
 }
 
+impl Support for MolecularShape2 {
+    fn support(&self, direction: Vector2<f64>) -> f64 {
+        self.items
+            .iter()
+            .map(|a| a.position.coords.dot(&direction) + a.radius)
+            .fold(std::f64::MIN, f64::max)
+    }
+}
+
+impl Boundary for MolecularShape2 {
+    fn crosses(&self, edge: &Line2) -> bool {
+        self.items
+            .iter()
+            .any(|a| edge.distance_to(&a.position) < a.radius)
+    }
+
+    fn contains(&self, point: &Point2<f64>) -> bool {
+        self.items
+            .iter()
+            .any(|a| (point - a.position).norm() < a.radius)
+    }
+}
+
 impl Shape for MolecularShape2 {
     type Component = Atom2;
 
+    fn name(&self) -> &str {
+        &self.name
+    }
+
     fn score(&self, other: &Self) -> Option<f64> {
         if self.intersects(other) {
             None
@@ -73,6 +102,15 @@ impl Shape for MolecularShape2 {
             .fold(std::f64::MIN, f64::max)
     }
 
+    fn point_group(&self) -> PointGroup {
+        let points: Vec<_> = self
+            .items
+            .iter()
+            .map(|a| (a.position, vec![a.radius]))
+            .collect();
+        PointGroup::detect(&points, SYMMETRY_TOLERANCE)
+    }
+
     fn get_items(&self) -> Vec<Self::Component> {
         self.items.clone()
     }
@@ -89,6 +127,25 @@ impl Shape for MolecularShape2 {
     }
 }
 
+impl Scale for MolecularShape2 {
+    fn scale(&self, factor: f64) -> Self {
+        Self {
+            name: self.name.clone(),
+            items: self
+                .items
+                .iter()
+                .map(|a| {
+                    Atom2::new(
+                        a.position.x * factor,
+                        a.position.y * factor,
+                        a.radius * factor,
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
 impl fmt::Display for MolecularShape2 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "MolShape {{ ")?;
@@ -104,7 +161,7 @@ impl MolecularShape2 {
         r.powi(2) * f64::acos(d / r) - d * f64::sqrt(r.powi(2) - d.powi(2))
     }
 
-    fn circle_overlap(a1: &Atom2, a2: &Atom2) -> f64 {
+    pub(crate) fn circle_overlap(a1: &Atom2, a2: &Atom2) -> f64 {
         let distance = nalgebra::distance(&a1.position, &a2.position);
         // There is some overlap between the circles which needs to be calculated
         if distance < a1.radius + a2.radius {
@@ -272,4 +329,12 @@ mod test {
         let transform = Transform2::new(0., (2., 2.));
         assert!(!mol.intersects(&mol.transform(&transform)));
     }
+
+    #[test]
+    fn trimer_point_group() {
+        let shape = MolecularShape2::from_trimer(0.7, 120., 1.);
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(1));
+        let shape = MolecularShape2::from_trimer(1., 180., 2.);
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(2));
+    }
 }
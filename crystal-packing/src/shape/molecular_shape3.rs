@@ -0,0 +1,189 @@
+//
+// molecular_shape3.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+use std::{fmt, slice};
+
+use itertools::{iproduct, Itertools};
+use nalgebra::Point3;
+use serde::{Deserialize, Serialize};
+
+use crate::traits::{Intersect3, Shape3};
+use crate::{Atom3, Transform3};
+
+/// A rigid three dimensional shape defined by a collection of spheres
+///
+/// This is the three dimensional counterpart of the [`MolecularShape2`].
+///
+/// [`MolecularShape2`]: crate::MolecularShape2
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MolecularShape3 {
+    pub name: String,
+    pub items: Vec<Atom3>,
+}
+
+impl<'a> IntoIterator for &'a MolecularShape3 {
+    type Item = &'a Atom3;
+    type IntoIter = slice::Iter<'a, Atom3>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl Intersect3 for MolecularShape3 {
+    fn intersects(&self, other: &Self) -> bool {
+        iproduct!(self.items.iter(), other.items.iter()).any(|(s, o)| s.intersects(o))
+    }
+
+    /// The volume of the shape, accounting for the overlap of each pair of spheres
+    ///
+    /// Like the area of a [`MolecularShape2`], the regions where three or more spheres overlap
+    /// are removed more than once.
+    ///
+    /// [`MolecularShape2`]: crate::MolecularShape2
+    ///
+    fn volume(&self) -> f64 {
+        let total_volume: f64 = self.items.iter().map(|a| a.volume()).sum();
+
+        let naive_overlap: f64 = self
+            .items
+            .iter()
+            .tuple_combinations()
+            .map(|(a1, a2)| Self::sphere_overlap(a1, a2))
+            .sum();
+
+        total_volume - naive_overlap
+    }
+}
+
+impl Shape3 for MolecularShape3 {
+    type Component = Atom3;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        self.items
+            .iter()
+            .map(|p| nalgebra::distance(&p.position, &Point3::origin()) + p.radius)
+            .fold(std::f64::MIN, f64::max)
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.items.clone()
+    }
+
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform3) -> Self {
+        Self {
+            name: self.name.clone(),
+            items: self.into_iter().map(|i| i * transform).collect(),
+        }
+    }
+}
+
+impl fmt::Display for MolecularShape3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MolShape3 {{ ")?;
+        for item in self.items.iter() {
+            write!(f, "{},", item)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+impl MolecularShape3 {
+    /// The volume of the lens shaped region where two spheres overlap
+    fn sphere_overlap(a1: &Atom3, a2: &Atom3) -> f64 {
+        let (r1, r2) = (a1.radius, a2.radius);
+        let d = nalgebra::distance(&a1.position, &a2.position);
+        if d >= r1 + r2 {
+            0.
+        } else if d <= (r1 - r2).abs() {
+            // The smaller sphere is entirely within the larger
+            4. / 3. * PI * r1.min(r2).powi(3)
+        } else {
+            PI * (r1 + r2 - d).powi(2) * (d.powi(2) + 2. * d * (r1 + r2) - 3. * (r1 - r2).powi(2))
+                / (12. * d)
+        }
+    }
+
+    /// Create an instance of a Sphere
+    ///
+    /// This is the simplest molecular shape, a single sphere at the origin with radius of 1.0.
+    pub fn sphere() -> Self {
+        Self {
+            name: String::from("sphere"),
+            items: vec![Atom3::new(0., 0., 0., 1.)],
+        }
+    }
+
+    /// Create a Dimer molecule instance
+    ///
+    /// A Dimer is a molecule of two spheres along the x axis, one with radius 1 and the other
+    /// with a radius `radius`, where the centres are separated by `distance`. The origin is
+    /// midway between the centres of the spheres.
+    ///
+    /// ```
+    /// # use crystal_packing::MolecularShape3;
+    /// let shape = MolecularShape3::from_dimer(0.5, 1.);
+    /// # assert_eq!(shape.items.len(), 2);
+    /// ```
+    ///
+    pub fn from_dimer(radius: f64, distance: f64) -> Self {
+        Self {
+            name: String::from("Dimer"),
+            items: vec![
+                Atom3::new(-distance / 2., 0., 0., 1.),
+                Atom3::new(distance / 2., 0., 0., radius),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn sphere_overlap_limits() {
+        let a1 = Atom3::new(0., 0., 0., 1.);
+        assert_abs_diff_eq!(
+            MolecularShape3::sphere_overlap(&a1, &Atom3::new(2., 0., 0., 1.)),
+            0.
+        );
+        assert_abs_diff_eq!(
+            MolecularShape3::sphere_overlap(&a1, &Atom3::new(0., 0., 0., 1.)),
+            a1.volume()
+        );
+        // The lens approaches the volume of the smaller sphere as it moves inside
+        assert_abs_diff_eq!(
+            MolecularShape3::sphere_overlap(&a1, &Atom3::new(0.5 + 1e-9, 0., 0., 0.5)),
+            Atom3::new(0., 0., 0., 0.5).volume(),
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn dimer_volume() {
+        // Two unit spheres separated by their radius overlap by a lens with volume 5π/12
+        let shape = MolecularShape3::from_dimer(1., 1.);
+        assert_abs_diff_eq!(
+            shape.volume(),
+            8. / 3. * PI - 5. * PI / 12.,
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(shape.enclosing_radius(), 1.5);
+    }
+}
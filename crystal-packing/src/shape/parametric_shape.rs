@@ -0,0 +1,464 @@
+//
+// parametric_shape.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::{PI, TAU};
+use std::{fmt, slice};
+
+use anyhow::{bail, Error};
+use itertools::iproduct;
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use super::{polygon_contains, Line2, Transform2};
+use crate::traits::{Boundary, Intersect, Shape, Support};
+use crate::{PointGroup, MAX_ORDER, SYMMETRY_TOLERANCE};
+
+/// The number of equal intervals the parameter is initially divided into
+///
+/// This ensures the features of the curve are found before the intervals are refined.
+const INITIAL_INTERVALS: usize = 32;
+
+/// The maximum number of times an interval of the parameter is halved
+const MAX_DEPTH: usize = 24;
+
+/// A closed curve defined by a parametric function
+///
+/// The curves are centred on the origin, with the parameter running over [0, 2π).
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Curve {
+    /// The curve |x/a|^n + |y/b|^n = 1, which is an ellipse for n = 2, and approaches a
+    /// rectangle as n becomes large
+    Superellipse { a: f64, b: f64, exponent: f64 },
+    /// The polar curve r = 1 + amplitude cos(points θ), which has `points` rounded arms
+    Star { points: usize, amplitude: f64 },
+}
+
+impl fmt::Display for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Curve::Superellipse { a, b, exponent } => {
+                write!(f, "Superellipse {{ {}, {}, {} }}", a, b, exponent)
+            }
+            Curve::Star { points, amplitude } => write!(f, "Star {{ {}, {} }}", points, amplitude),
+        }
+    }
+}
+
+impl Curve {
+    /// Check the parameters describe a closed curve which doesn't cross itself
+    pub fn validate(&self) -> Result<(), Error> {
+        match *self {
+            Curve::Superellipse { a, b, exponent } => {
+                if a.is_nan() || b.is_nan() || a <= 0. || b <= 0. {
+                    bail!(
+                        "The axes of a superellipse need to be positive, got {} and {}",
+                        a,
+                        b
+                    );
+                }
+                if exponent.is_nan() || exponent <= 0. {
+                    bail!(
+                        "The exponent of a superellipse needs to be positive, got {}",
+                        exponent
+                    );
+                }
+            }
+            Curve::Star { points, amplitude } => {
+                if points < 2 {
+                    bail!("A star needs at least 2 points, got {}", points);
+                }
+                if amplitude.is_nan() || !(0. ..1.).contains(&amplitude) {
+                    bail!(
+                        "The amplitude of a star needs to be in [0, 1), got {}",
+                        amplitude
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The point on the curve at the parameter `t`
+    pub fn point(&self, t: f64) -> Point2<f64> {
+        match *self {
+            Curve::Superellipse { a, b, exponent } => {
+                let power = |value: f64| value.signum() * value.abs().powf(2. / exponent);
+                Point2::new(a * power(t.cos()), b * power(t.sin()))
+            }
+            Curve::Star { points, amplitude } => {
+                let radius = 1. + amplitude * f64::cos(points as f64 * t);
+                Point2::new(radius * t.cos(), radius * t.sin())
+            }
+        }
+    }
+
+    /// The exact area enclosed by the curve, where there is an analytical expression
+    pub fn area(&self) -> Option<f64> {
+        match *self {
+            Curve::Superellipse { a, b, exponent } => {
+                Some(4. * a * b * gamma(1. + 1. / exponent).powi(2) / gamma(1. + 2. / exponent))
+            }
+            Curve::Star { amplitude, .. } => Some(PI * (1. + amplitude.powi(2) / 2.)),
+        }
+    }
+
+    /// The symmetry of the curve about the origin
+    ///
+    /// A curve which is a circle has a continuous symmetry, which is approximated by the group of
+    /// order [`MAX_ORDER`].
+    ///
+    pub fn point_group(&self) -> PointGroup {
+        match *self {
+            Curve::Superellipse { a, b, exponent } => {
+                if (a - b).abs() > SYMMETRY_TOLERANCE * f64::max(a, b) {
+                    PointGroup::Dihedral(2)
+                } else if (exponent - 2.).abs() > SYMMETRY_TOLERANCE {
+                    PointGroup::Dihedral(4)
+                } else {
+                    PointGroup::Dihedral(MAX_ORDER)
+                }
+            }
+            Curve::Star { amplitude: 0., .. } => PointGroup::Dihedral(MAX_ORDER),
+            Curve::Star { points, .. } => PointGroup::Dihedral(points),
+        }
+    }
+
+    /// Whether the chord between two parameters is within `tolerance` of the curve
+    ///
+    /// The distance is only checked at three points evenly spaced along the interval, so a
+    /// feature of the curve between these points can be missed.
+    ///
+    fn chord_within(&self, start: f64, end: f64, tolerance: f64) -> bool {
+        let chord = Line2 {
+            start: self.point(start),
+            end: self.point(end),
+        };
+        (1..4)
+            .map(|i| start + (end - start) * i as f64 / 4.)
+            .all(|t| chord.distance_to(&self.point(t)) <= tolerance)
+    }
+
+    /// Approximate the curve by a polygon, with the edges aiming to be within `tolerance` of
+    /// the curve
+    ///
+    /// The parameter is divided into equal intervals, each of which is halved until the chord
+    /// across it is within the tolerance of the curve at the sampled points. This places more
+    /// vertices where the curvature is high, like the corners of a superellipse with a large
+    /// exponent. The tolerance isn't a strict bound, since the distance is only sampled, and an
+    /// interval stops being halved after [`MAX_DEPTH`] times.
+    ///
+    pub fn flatten(&self, tolerance: f64) -> Result<Vec<Point2<f64>>, Error> {
+        self.validate()?;
+        if tolerance.is_nan() || tolerance <= 0. {
+            bail!("The tolerance needs to be positive, got {}", tolerance);
+        }
+        let mut points = vec![];
+        let step = TAU / INITIAL_INTERVALS as f64;
+        for index in 0..INITIAL_INTERVALS {
+            let start = index as f64 * step;
+            self.refine(start, start + step, tolerance, 0, &mut points);
+        }
+        Ok(points)
+    }
+
+    /// Add the vertices for the interval of the parameter, excluding the end of the interval
+    fn refine(
+        &self,
+        start: f64,
+        end: f64,
+        tolerance: f64,
+        depth: usize,
+        points: &mut Vec<Point2<f64>>,
+    ) {
+        if depth >= MAX_DEPTH || self.chord_within(start, end, tolerance) {
+            points.push(self.point(start));
+        } else {
+            let middle = (start + end) / 2.;
+            self.refine(start, middle, tolerance, depth + 1, points);
+            self.refine(middle, end, tolerance, depth + 1, points);
+        }
+    }
+}
+
+/// The gamma function using the Lanczos approximation, which is accurate to about 15 digits
+fn gamma(x: f64) -> f64 {
+    const G: f64 = 7.;
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        PI / (f64::sin(PI * x) * gamma(1. - x))
+    } else {
+        let x = x - 1.;
+        let t = x + G + 0.5;
+        let sum = COEFFICIENTS[1..]
+            .iter()
+            .enumerate()
+            .fold(COEFFICIENTS[0], |acc, (i, c)| acc + c / (x + i as f64 + 1.));
+        f64::sqrt(TAU) * t.powf(x + 0.5) * f64::exp(-t) * sum
+    }
+}
+
+/// A shape defined by a parametric curve
+///
+/// The curve is approximated by a polygon, in the same way as a [`LineShape`], where the edges
+/// are close to the curve. Both the intersections and the area use the polygon, so the packing
+/// fraction is that of the shapes which are actually checked for overlaps.
+///
+/// [`LineShape`]: crate::LineShape
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub struct ParametricShape {
+    pub name: String,
+    pub curve: Curve,
+    pub items: Vec<Line2>,
+}
+
+impl<'a> IntoIterator for &'a ParametricShape {
+    type Item = &'a Line2;
+    type IntoIter = slice::Iter<'a, Line2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl Intersect for ParametricShape {
+    fn intersects(&self, other: &Self) -> bool {
+        iproduct!(self.iter(), other.iter()).any(|(s, o)| s.intersects(o))
+    }
+
+    fn area(&self) -> f64 {
+        self.polygon_area()
+    }
+}
+
+impl Support for ParametricShape {
+    fn support(&self, direction: Vector2<f64>) -> f64 {
+        self.iter()
+            .map(|l| l.start.coords.dot(&direction))
+            .fold(std::f64::MIN, f64::max)
+    }
+}
+
+impl Boundary for ParametricShape {
+    fn crosses(&self, edge: &Line2) -> bool {
+        self.iter().any(|l| l.intersects(edge))
+    }
+
+    fn contains(&self, point: &Point2<f64>) -> bool {
+        polygon_contains(self.iter(), point)
+    }
+}
+
+impl Shape for ParametricShape {
+    type Component = Line2;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn score(&self, other: &Self) -> Option<f64> {
+        if self.intersects(other) {
+            None
+        } else {
+            Some(self.area())
+        }
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        self.iter()
+            .map(|l| l.start.coords.norm())
+            .fold(std::f64::MIN, f64::max)
+    }
+
+    /// The symmetry of the curve, since the vertices of the polygon approximating it don't
+    /// line up with the symmetry of the curve
+    fn point_group(&self) -> PointGroup {
+        self.curve.point_group()
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.items.clone()
+    }
+
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform2) -> Self {
+        Self {
+            name: self.name.clone(),
+            curve: self.curve,
+            items: self.into_iter().map(|i| i * transform).collect(),
+        }
+    }
+}
+
+impl fmt::Display for ParametricShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ParametricShape {{ {}, {} }}",
+            self.curve,
+            self.items.len()
+        )
+    }
+}
+
+impl ParametricShape {
+    /// Approximate the curve by a polygon with the edges within about `tolerance` of the curve
+    pub fn new(name: &str, curve: Curve, tolerance: f64) -> Result<ParametricShape, Error> {
+        let points = curve.flatten(tolerance)?;
+        let items = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(&start, &end)| Line2 { start, end })
+            .collect();
+        Ok(ParametricShape {
+            name: String::from(name),
+            curve,
+            items,
+        })
+    }
+
+    /// The area of the polygon approximating the curve
+    pub fn polygon_area(&self) -> f64 {
+        self.iter()
+            .map(|l| l.start.coords.perp(&l.end.coords) / 2.)
+            .sum::<f64>()
+            .abs()
+    }
+
+    /// A superellipse with a semi-major axis of 1 along x
+    ///
+    /// The semi-minor axis is shorter by the `aspect_ratio`, with the `exponent` going from a
+    /// diamond at 1, through an ellipse at 2, to a rectangle as it becomes large.
+    ///
+    /// ```
+    /// use crystal_packing::{Intersect, ParametricShape};
+    /// let shape = ParametricShape::superellipse(2., 1., 1e-3).unwrap();
+    /// assert!((shape.area() - std::f64::consts::PI).abs() < 1e-2);
+    /// ```
+    ///
+    pub fn superellipse(
+        exponent: f64,
+        aspect_ratio: f64,
+        tolerance: f64,
+    ) -> Result<ParametricShape, Error> {
+        if aspect_ratio.is_nan() || aspect_ratio < 1. {
+            bail!(
+                "The aspect ratio of a superellipse needs to be at least 1, got {}",
+                aspect_ratio
+            );
+        }
+        let curve = Curve::Superellipse {
+            a: 1.,
+            b: 1. / aspect_ratio,
+            exponent,
+        };
+        Self::new("Superellipse", curve, tolerance)
+    }
+
+    /// A star with `points` rounded arms, where the `amplitude` is how far the arms extend
+    pub fn star(points: usize, amplitude: f64, tolerance: f64) -> Result<ParametricShape, Error> {
+        Self::new("Star", Curve::Star { points, amplitude }, tolerance)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn gamma_values() {
+        assert_abs_diff_eq!(gamma(1.), 1., epsilon = 1e-13);
+        assert_abs_diff_eq!(gamma(5.), 24., epsilon = 1e-10);
+        assert_abs_diff_eq!(gamma(0.5), f64::sqrt(PI), epsilon = 1e-13);
+        assert_abs_diff_eq!(gamma(1.25), 0.906_402_477_055_477, epsilon = 1e-13);
+    }
+
+    #[test]
+    fn invalid_curves() {
+        assert!(ParametricShape::superellipse(0., 1., 1e-3).is_err());
+        assert!(ParametricShape::superellipse(2., 0.5, 1e-3).is_err());
+        assert!(ParametricShape::superellipse(2., 1., 0.).is_err());
+        assert!(ParametricShape::star(1, 0.5, 1e-3).is_err());
+        assert!(ParametricShape::star(5, 1., 1e-3).is_err());
+    }
+
+    #[test]
+    fn superellipse_area() {
+        // The diamond |x| + |y| = 1
+        let curve = Curve::Superellipse {
+            a: 1.,
+            b: 1.,
+            exponent: 1.,
+        };
+        assert_abs_diff_eq!(curve.area().unwrap(), 2., epsilon = 1e-12);
+        // The area approaches the rectangle for a large exponent
+        let curve = Curve::Superellipse {
+            a: 1.,
+            b: 0.5,
+            exponent: 1000.,
+        };
+        assert_abs_diff_eq!(curve.area().unwrap(), 2., epsilon = 1e-2);
+    }
+
+    #[test]
+    fn polygon_within_tolerance() {
+        for (shape, tolerance) in [
+            (ParametricShape::superellipse(8., 2., 1e-3).unwrap(), 1e-3),
+            (ParametricShape::superellipse(0.8, 1., 1e-3).unwrap(), 1e-3),
+            (ParametricShape::star(5, 0.4, 1e-4).unwrap(), 1e-4),
+        ] {
+            // Points densely sampled on the curve are close to the polygon
+            for i in 0..2000 {
+                let point = shape.curve.point(i as f64 / 2000. * TAU);
+                let distance = shape
+                    .iter()
+                    .map(|l| l.distance_to(&point))
+                    .fold(std::f64::MAX, f64::min);
+                assert!(distance <= tolerance * 1.01, "{} {}", shape, distance);
+            }
+            // The area of the polygon converges to the exact area
+            let exact = shape.curve.area().unwrap();
+            let relative = (shape.area() - exact).abs() / exact;
+            assert!(relative < 1e-2, "{} {}", shape, relative);
+        }
+    }
+
+    #[test]
+    fn star_area() {
+        let shape = ParametricShape::star(5, 0.3, 1e-5).unwrap();
+        assert_abs_diff_eq!(shape.area(), shape.curve.area().unwrap(), epsilon = 1e-3);
+        assert!(shape.area() < shape.curve.area().unwrap());
+    }
+
+    #[test]
+    fn parametric_point_group() {
+        let shape = ParametricShape::superellipse(4., 2., 1e-3).unwrap();
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(2));
+        let shape = ParametricShape::superellipse(4., 1., 1e-3).unwrap();
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(4));
+        let shape = ParametricShape::star(5, 0.3, 1e-3).unwrap();
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(5));
+        assert!(!shape.is_chiral());
+    }
+}
@@ -0,0 +1,778 @@
+//
+// polyhedron.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fmt::Write;
+use std::{fmt, slice};
+
+use anyhow::{anyhow, bail, Error};
+use itertools::{iproduct, Itertools};
+use nalgebra::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::traits::{Intersect3, Shape3};
+use crate::Transform3;
+
+/// The tolerance relative to the size of a shape for points to be considered coplanar
+const TOLERANCE: f64 = 1e-9;
+
+/// The golden ratio, which appears in the coordinates of the icosahedral solids
+const PHI: f64 = 1.618_033_988_749_895;
+
+/// A convex polyhedron
+///
+/// The polyhedron is the convex hull of the vertices, with each face being a polygon of
+/// indices into the vertices. The vertices of each face are ordered anti-clockwise when viewed
+/// from outside the shape. Since the shape is convex, the overlap of two polyhedra is
+/// determined exactly using the Gilbert–Johnson–Keerthi (GJK) algorithm.
+///
+/// ```
+/// use crystal_packing::{Intersect3, Polyhedron};
+/// let cube = Polyhedron::new("Cube", &[
+///     (0., 0., 0.), (1., 0., 0.), (0., 1., 0.), (1., 1., 0.),
+///     (0., 0., 1.), (1., 0., 1.), (0., 1., 1.), (1., 1., 1.),
+/// ]).unwrap();
+/// assert_eq!(cube.faces.len(), 6);
+/// assert!((cube.volume() - 1.).abs() < 1e-12);
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Polyhedron {
+    pub name: String,
+    pub vertices: Vec<Point3<f64>>,
+    pub faces: Vec<Vec<usize>>,
+}
+
+impl<'a> IntoIterator for &'a Polyhedron {
+    type Item = &'a Point3<f64>;
+    type IntoIter = slice::Iter<'a, Point3<f64>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.vertices.iter()
+    }
+}
+
+impl fmt::Display for Polyhedron {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Polyhedron {{ {}, vertices: {}, faces: {} }}",
+            self.name,
+            self.vertices.len(),
+            self.faces.len()
+        )
+    }
+}
+
+impl Intersect3 for Polyhedron {
+    /// Whether the two polyhedra overlap, using the GJK algorithm
+    ///
+    /// The polyhedra intersect when the Minkowski difference of the shapes contains the
+    /// origin. The GJK algorithm searches for a simplex within the Minkowski difference which
+    /// encloses the origin, using only the support points of each of the shapes. Shapes which
+    /// are only touching are not considered to intersect.
+    ///
+    fn intersects(&self, other: &Self) -> bool {
+        let support = |d: &Vector3<f64>| self.support_point(d) - other.support_point(&-d);
+
+        let mut direction = self.vertices[0] - other.vertices[0];
+        if direction.norm_squared() == 0. {
+            direction = Vector3::x();
+        }
+        let mut simplex = vec![support(&direction)];
+        direction = -simplex[0];
+
+        // The algorithm typically converges in a handful of iterations, so running out of
+        // iterations is a degenerate case which is treated as an intersection.
+        for _ in 0..64 {
+            if direction.norm_squared() == 0. {
+                // The origin is on the surface of the simplex
+                return false;
+            }
+            let point = support(&direction);
+            if point.dot(&direction) <= 0. {
+                return false;
+            }
+            simplex.push(point);
+            if next_simplex(&mut simplex, &mut direction) {
+                return true;
+            }
+        }
+        true
+    }
+
+    fn volume(&self) -> f64 {
+        self.triangles()
+            .map(|(a, b, c)| a.coords.dot(&b.coords.cross(&c.coords)) / 6.)
+            .sum()
+    }
+}
+
+impl Shape3 for Polyhedron {
+    type Component = Point3<f64>;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        self.vertices
+            .iter()
+            .map(|v| v.coords.norm())
+            .fold(std::f64::MIN, f64::max)
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.vertices.clone()
+    }
+
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform3) -> Self {
+        let mut faces = self.faces.clone();
+        // An improper transform turns the shape inside out, so the order of the vertices in each
+        // face is reversed to keep them anti-clockwise when viewed from outside.
+        if transform.linear().determinant() < 0. {
+            for face in faces.iter_mut() {
+                face.reverse();
+            }
+        }
+        Self {
+            name: self.name.clone(),
+            vertices: self.into_iter().map(|v| v * transform).collect(),
+            faces,
+        }
+    }
+}
+
+/// Update the simplex to the feature closest to the origin, returning whether it encloses it
+///
+/// The most recently added point is the last in the simplex, and the direction is updated to
+/// point from the remaining simplex towards the origin.
+///
+fn next_simplex(simplex: &mut Vec<Vector3<f64>>, direction: &mut Vector3<f64>) -> bool {
+    match simplex.len() {
+        2 => {
+            let (b, a) = (simplex[0], simplex[1]);
+            line_case(a, b, simplex, direction);
+            false
+        }
+        3 => {
+            let (c, b, a) = (simplex[0], simplex[1], simplex[2]);
+            triangle_case(a, b, c, simplex, direction);
+            false
+        }
+        4 => {
+            let (d, c, b, a) = (simplex[0], simplex[1], simplex[2], simplex[3]);
+            let ao = -a;
+            // The normal of each face containing a, pointing away from the remaining vertex
+            let outward = |p: Vector3<f64>, q: Vector3<f64>, opposite: Vector3<f64>| {
+                let normal = (p - a).cross(&(q - a));
+                if normal.dot(&(opposite - a)) > 0. {
+                    -normal
+                } else {
+                    normal
+                }
+            };
+            if outward(b, c, d).dot(&ao) > 0. {
+                triangle_case(a, b, c, simplex, direction);
+                false
+            } else if outward(c, d, b).dot(&ao) > 0. {
+                triangle_case(a, c, d, simplex, direction);
+                false
+            } else if outward(d, b, c).dot(&ao) > 0. {
+                triangle_case(a, d, b, simplex, direction);
+                false
+            } else {
+                true
+            }
+        }
+        _ => unreachable!("The simplex has between 2 and 4 points"),
+    }
+}
+
+/// The simplex is the line from b to the newly added point a
+fn line_case(
+    a: Vector3<f64>,
+    b: Vector3<f64>,
+    simplex: &mut Vec<Vector3<f64>>,
+    direction: &mut Vector3<f64>,
+) {
+    let (ab, ao) = (b - a, -a);
+    if ab.dot(&ao) > 0. {
+        *simplex = vec![b, a];
+        *direction = ab.cross(&ao).cross(&ab);
+        // When the origin is on the line any perpendicular direction is towards it
+        if direction.norm_squared() == 0. {
+            let axis = if ab.x.abs() < ab.y.abs() {
+                Vector3::x()
+            } else {
+                Vector3::y()
+            };
+            *direction = ab.cross(&axis);
+        }
+    } else {
+        *simplex = vec![a];
+        *direction = ao;
+    }
+}
+
+/// The simplex is the triangle of b, c, and the newly added point a
+fn triangle_case(
+    a: Vector3<f64>,
+    b: Vector3<f64>,
+    c: Vector3<f64>,
+    simplex: &mut Vec<Vector3<f64>>,
+    direction: &mut Vector3<f64>,
+) {
+    let (ab, ac, ao) = (b - a, c - a, -a);
+    let abc = ab.cross(&ac);
+    if abc.cross(&ac).dot(&ao) > 0. {
+        if ac.dot(&ao) > 0. {
+            *simplex = vec![c, a];
+            *direction = ac.cross(&ao).cross(&ac);
+        } else {
+            line_case(a, b, simplex, direction);
+        }
+    } else if ab.cross(&abc).dot(&ao) > 0. {
+        line_case(a, b, simplex, direction);
+    } else if abc.dot(&ao) > 0. {
+        *simplex = vec![c, b, a];
+        *direction = abc;
+    } else {
+        *simplex = vec![b, c, a];
+        *direction = -abc;
+    }
+}
+
+/// The triangular faces of the convex hull of the points
+///
+/// This uses the incremental algorithm, starting from a tetrahedron of four of the points and
+/// adding each of the remaining points in turn. The faces of the hull which can be seen from
+/// the new point are replaced by a cone from the horizon to the point. Points which are within
+/// the hull, or on one of the faces, are not part of the hull.
+///
+fn convex_hull(points: &[Point3<f64>]) -> Result<Vec<[usize; 3]>, Error> {
+    if points.len() < 4 {
+        bail!("A polyhedron requires at least 4 vertices");
+    }
+    let scale = points
+        .iter()
+        .map(|p| (p - points[0]).norm())
+        .fold(0., f64::max);
+    let tolerance = TOLERANCE * scale;
+
+    let normal = |face: &[usize; 3]| {
+        let [a, b, c] = face.map(|i| points[i]);
+        (b - a).cross(&(c - a)).normalize()
+    };
+    let height =
+        |face: &[usize; 3], point: &Point3<f64>| normal(face).dot(&(point - points[face[0]]));
+    let furthest = |distance: &dyn Fn(&Point3<f64>) -> f64| {
+        (0..points.len())
+            .map(|i| (i, distance(&points[i]).abs()))
+            .fold((0, 0.), |acc, x| if x.1 > acc.1 { x } else { acc })
+    };
+
+    // The initial tetrahedron is made from points as far apart as possible
+    let i0 = 0;
+    let (i1, d1) = furthest(&|p| (p - points[i0]).norm());
+    let line = (points[i1] - points[i0]).normalize();
+    let (i2, d2) = furthest(&|p| (p - points[i0]).cross(&line).norm());
+    let plane = [i0, i1, i2];
+    let (i3, d3) = if d2 > tolerance {
+        furthest(&|p| height(&plane, p))
+    } else {
+        (0, 0.)
+    };
+    if d1 <= tolerance || d2 <= tolerance || d3 <= tolerance {
+        bail!("The vertices of a polyhedron can't all be within a plane");
+    }
+
+    let centre = Point3::from(
+        (points[i0].coords + points[i1].coords + points[i2].coords + points[i3].coords) / 4.,
+    );
+    let mut faces: Vec<[usize; 3]> = vec![[i0, i1, i2], [i0, i1, i3], [i0, i2, i3], [i1, i2, i3]]
+        .into_iter()
+        .map(|f| {
+            if height(&f, &centre) > 0. {
+                [f[0], f[2], f[1]]
+            } else {
+                f
+            }
+        })
+        .collect();
+
+    for (index, point) in points.iter().enumerate() {
+        let (visible, hidden): (Vec<[usize; 3]>, Vec<[usize; 3]>) = faces
+            .iter()
+            .partition(|f| !f.contains(&index) && height(f, point) > tolerance);
+        if visible.is_empty() {
+            continue;
+        }
+        let edges: Vec<(usize, usize)> = visible
+            .iter()
+            .flat_map(|&[a, b, c]| vec![(a, b), (b, c), (c, a)])
+            .collect();
+        // The horizon is the edges of the visible region which are only part of a single
+        // visible face
+        let horizon = edges.iter().filter(|(a, b)| !edges.contains(&(*b, *a)));
+        faces = hidden;
+        faces.extend(horizon.map(|&(a, b)| [a, b, index]));
+    }
+    Ok(faces)
+}
+
+impl Polyhedron {
+    /// Create a polyhedron from the convex hull of a collection of points
+    ///
+    /// The vertices of the polyhedron are the points on the convex hull, which are translated
+    /// so the centroid of the volume is at the origin. Triangles of the hull which are within
+    /// the same plane are combined into a single polygonal face.
+    ///
+    pub fn new(name: &str, points: &[(f64, f64, f64)]) -> Result<Polyhedron, Error> {
+        let points: Vec<Point3<f64>> = points
+            .iter()
+            .map(|&(x, y, z)| Point3::new(x, y, z))
+            .collect();
+        let triangles = convex_hull(&points)?;
+
+        let scale = points
+            .iter()
+            .map(|p| (p - points[0]).norm())
+            .fold(0., f64::max);
+        let unit_normal = |&[a, b, c]: &[usize; 3]| {
+            (points[b] - points[a])
+                .cross(&(points[c] - points[a]))
+                .normalize()
+        };
+
+        // Group the triangles which are in the same plane into a face
+        let mut planes: Vec<(Vector3<f64>, f64, Vec<usize>)> = vec![];
+        for triangle in triangles.iter() {
+            let normal = unit_normal(triangle);
+            let offset = normal.dot(&points[triangle[0]].coords);
+            match planes.iter_mut().find(|(n, o, _)| {
+                n.dot(&normal) > 1. - TOLERANCE && (o - offset).abs() < TOLERANCE * scale
+            }) {
+                Some((_, _, indices)) => indices.extend(triangle.iter()),
+                None => planes.push((normal, offset, triangle.to_vec())),
+            }
+        }
+
+        // The vertices of each face are ordered anti-clockwise about the normal
+        let faces: Vec<Vec<usize>> = planes
+            .into_iter()
+            .map(|(normal, _, indices)| {
+                let indices: Vec<usize> = indices.into_iter().unique().collect();
+                let centre = indices
+                    .iter()
+                    .map(|&i| points[i].coords)
+                    .sum::<Vector3<f64>>()
+                    / indices.len() as f64;
+                let u = (points[indices[0]].coords - centre).normalize();
+                let v = normal.cross(&u);
+                let angle = |i: &usize| {
+                    let r = points[*i].coords - centre;
+                    f64::atan2(r.dot(&v), r.dot(&u))
+                };
+                indices
+                    .into_iter()
+                    .sorted_by(|a, b| angle(a).total_cmp(&angle(b)))
+                    .collect()
+            })
+            .collect();
+
+        // Only keep the points which are vertices of the hull
+        let used: Vec<usize> = faces.iter().flatten().copied().unique().sorted().collect();
+        let faces = faces
+            .iter()
+            .map(|f| f.iter().map(|i| used.binary_search(i).unwrap()).collect())
+            .collect();
+        let vertices = used.iter().map(|&i| points[i]).collect();
+
+        let polyhedron = Polyhedron {
+            name: String::from(name),
+            vertices,
+            faces,
+        };
+        let centroid = polyhedron.centroid();
+        Ok(Polyhedron {
+            vertices: polyhedron
+                .vertices
+                .iter()
+                .map(|v| Point3::from(v - centroid))
+                .collect(),
+            ..polyhedron
+        })
+    }
+
+    /// The names of the solids which can be created using [`Polyhedron::named`]
+    pub fn names() -> Vec<&'static str> {
+        vec![
+            "tetrahedron",
+            "cube",
+            "octahedron",
+            "dodecahedron",
+            "icosahedron",
+            "cuboctahedron",
+            "truncated-tetrahedron",
+            "truncated-octahedron",
+        ]
+    }
+
+    /// Create one of the Platonic or Archimedean solids
+    ///
+    /// All the solids are scaled to have a circumradius of 1, which is the same convention as
+    /// the regular polygons of the [`LineShape`].
+    ///
+    /// ```
+    /// use crystal_packing::Polyhedron;
+    /// let shape = Polyhedron::named("icosahedron").unwrap();
+    /// assert_eq!(shape.vertices.len(), 12);
+    /// assert_eq!(shape.faces.len(), 20);
+    /// ```
+    ///
+    /// [`LineShape`]: crate::LineShape
+    ///
+    pub fn named(name: &str) -> Result<Polyhedron, Error> {
+        // Every even or odd permutation of the coordinates of a point
+        let cyclic = |(x, y, z): (f64, f64, f64)| vec![(x, y, z), (y, z, x), (z, x, y)];
+        let all = |p: (f64, f64, f64)| {
+            let mut points = cyclic(p);
+            points.extend(cyclic((p.1, p.0, p.2)));
+            points
+        };
+        // Every combination of the signs of the non-zero coordinates
+        let signs = |points: Vec<(f64, f64, f64)>| -> Vec<(f64, f64, f64)> {
+            points
+                .into_iter()
+                .flat_map(|(x, y, z)| {
+                    iproduct!(&[1., -1.], &[1., -1.], &[1., -1.])
+                        .map(move |(i, j, k)| (i * x, j * y, k * z))
+                })
+                .collect()
+        };
+
+        let points = match name {
+            "tetrahedron" => vec![(1., 1., 1.), (1., -1., -1.), (-1., 1., -1.), (-1., -1., 1.)],
+            "cube" => signs(vec![(1., 1., 1.)]),
+            "octahedron" => signs(cyclic((1., 0., 0.))),
+            "dodecahedron" => {
+                let mut points = signs(vec![(1., 1., 1.)]);
+                points.extend(signs(cyclic((0., 1. / PHI, PHI))));
+                points
+            }
+            "icosahedron" => signs(cyclic((0., 1., PHI))),
+            "cuboctahedron" => signs(cyclic((1., 1., 0.))),
+            "truncated-tetrahedron" => signs(all((3., 1., 1.)))
+                .into_iter()
+                // Only the points with an even number of negative coordinates
+                .filter(|&(x, y, z)| x * y * z > 0.)
+                .collect(),
+            "truncated-octahedron" => signs(all((0., 1., 2.))),
+            _ => {
+                return Err(anyhow!(
+                    "Unknown polyhedron {}, expected one of {}",
+                    name,
+                    Self::names().join(", ")
+                ))
+            }
+        };
+        let shape = Polyhedron::new(name, &points)?;
+        let radius = shape.enclosing_radius();
+        Ok(Polyhedron {
+            vertices: shape.vertices.iter().map(|v| v / radius).collect(),
+            ..shape
+        })
+    }
+
+    /// Each face split into a fan of triangles from the first vertex
+    fn triangles(&self) -> impl Iterator<Item = (&Point3<f64>, &Point3<f64>, &Point3<f64>)> {
+        self.faces.iter().flat_map(move |face| {
+            face[1..].windows(2).map(move |pair| {
+                (
+                    &self.vertices[face[0]],
+                    &self.vertices[pair[0]],
+                    &self.vertices[pair[1]],
+                )
+            })
+        })
+    }
+
+    /// The centroid of the volume enclosed by the polyhedron
+    pub fn centroid(&self) -> Vector3<f64> {
+        let (volume, moment) =
+            self.triangles()
+                .fold((0., Vector3::zeros()), |(volume, moment), (a, b, c)| {
+                    // The tetrahedron from the origin to the triangle
+                    let v = a.coords.dot(&b.coords.cross(&c.coords)) / 6.;
+                    (
+                        volume + v,
+                        moment + v * (a.coords + b.coords + c.coords) / 4.,
+                    )
+                });
+        moment / volume
+    }
+
+    /// The number of edges, where each edge is shared by two faces
+    pub fn num_edges(&self) -> usize {
+        self.faces.iter().map(Vec::len).sum::<usize>() / 2
+    }
+
+    /// The outward unit normal of a face
+    pub fn face_normal(&self, face: &[usize]) -> Vector3<f64> {
+        let [a, b, c] = [face[0], face[1], face[2]].map(|i| self.vertices[i]);
+        (b - a).cross(&(c - a)).normalize()
+    }
+
+    /// The vertex which is furthest in the direction `direction`
+    pub fn support_point(&self, direction: &Vector3<f64>) -> Vector3<f64> {
+        self.vertices
+            .iter()
+            .map(|v| v.coords)
+            .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+            .unwrap()
+    }
+
+    /// The shape in the Object File Format (OFF)
+    ///
+    /// This is a simple text format for polygonal meshes which is able to be read by most 3D
+    /// visualisation tools, like [Meshlab](https://www.meshlab.net/).
+    ///
+    pub fn as_off(&self) -> String {
+        let mut output = String::new();
+        writeln!(output, "OFF").unwrap();
+        writeln!(
+            output,
+            "{} {} {}",
+            self.vertices.len(),
+            self.faces.len(),
+            self.num_edges()
+        )
+        .unwrap();
+        for v in self.vertices.iter() {
+            writeln!(output, "{} {} {}", v.x, v.y, v.z).unwrap();
+        }
+        for face in self.faces.iter() {
+            writeln!(output, "{} {}", face.len(), face.iter().join(" ")).unwrap();
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+
+    use approx::assert_abs_diff_eq;
+    use nalgebra::UnitQuaternion;
+
+    use super::*;
+
+    #[test]
+    fn named_solids() {
+        let expected = [
+            ("tetrahedron", 4, 4),
+            ("cube", 8, 6),
+            ("octahedron", 6, 8),
+            ("dodecahedron", 20, 12),
+            ("icosahedron", 12, 20),
+            ("cuboctahedron", 12, 14),
+            ("truncated-tetrahedron", 12, 8),
+            ("truncated-octahedron", 24, 14),
+        ];
+        for (name, vertices, faces) in expected.iter() {
+            let shape = Polyhedron::named(name).unwrap();
+            assert_eq!(shape.vertices.len(), *vertices, "{}", name);
+            assert_eq!(shape.faces.len(), *faces, "{}", name);
+            // Euler's formula for convex polyhedra
+            assert_eq!(vertices + faces, shape.num_edges() + 2, "{}", name);
+            assert_abs_diff_eq!(shape.enclosing_radius(), 1., epsilon = 1e-12);
+            assert_abs_diff_eq!(shape.centroid().norm(), 0., epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn faces_point_outwards() {
+        for name in Polyhedron::names() {
+            let shape = Polyhedron::named(name).unwrap();
+            for face in shape.faces.iter() {
+                let normal = shape.face_normal(face);
+                assert!(normal.dot(&shape.vertices[face[0]].coords) > 0., "{}", name);
+            }
+            assert!(shape.volume() > 0.);
+        }
+    }
+
+    #[test]
+    fn volume() {
+        // The volume of a regular tetrahedron is a^3 / (6 sqrt(2)), with an edge length a
+        let shape = Polyhedron::named("tetrahedron").unwrap();
+        let edge = f64::sqrt(8. / 3.);
+        assert_abs_diff_eq!(
+            shape.volume(),
+            edge.powi(3) / (6. * f64::sqrt(2.)),
+            epsilon = 1e-12
+        );
+
+        let shape = Polyhedron::named("cube").unwrap();
+        assert_abs_diff_eq!(
+            shape.volume(),
+            (2. / f64::sqrt(3.)).powi(3),
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn interior_points_removed() {
+        let shape = Polyhedron::new(
+            "Tetrahedron",
+            &[
+                (0., 0., 0.),
+                (1., 0., 0.),
+                (0., 1., 0.),
+                (0., 0., 1.),
+                (0.1, 0.1, 0.1),
+            ],
+        )
+        .unwrap();
+        assert_eq!(shape.vertices.len(), 4);
+        assert_abs_diff_eq!(shape.volume(), 1. / 6., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn degenerate() {
+        assert!(Polyhedron::new(
+            "Square",
+            &[(0., 0., 0.), (1., 0., 0.), (0., 1., 0.), (1., 1., 0.)]
+        )
+        .is_err());
+        assert!(Polyhedron::named("sphere").is_err());
+    }
+
+    #[test]
+    fn cube_intersections() {
+        let cube = Polyhedron::named("cube").unwrap();
+        let side = 2. / f64::sqrt(3.);
+        let at = |x: f64, y: f64, z: f64, rotation: UnitQuaternion<f64>| {
+            cube.transform(&Transform3::new(rotation, (x, y, z)))
+        };
+        let identity = UnitQuaternion::identity();
+
+        assert!(cube.intersects(&at(0.9 * side, 0., 0., identity)));
+        assert!(cube.intersects(&at(0.9 * side, 0.9 * side, 0.9 * side, identity)));
+        assert!(!cube.intersects(&at(1.1 * side, 0., 0., identity)));
+        assert!(!cube.intersects(&at(1.1 * side, 0.5 * side, -0.5 * side, identity)));
+
+        // Rotating about the z axis by 45 degrees the corner of the cube sticks out further
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), PI / 4.);
+        let corner = side / 2. * (1. + f64::sqrt(2.));
+        assert!(cube.intersects(&at(0.99 * corner, 0., 0., rotation)));
+        assert!(!cube.intersects(&at(1.01 * corner, 0., 0., rotation)));
+    }
+
+    #[test]
+    fn tetrahedra_intersections() {
+        // Compare GJK with sampling the points of one shape within the other
+        let shape = Polyhedron::named("tetrahedron").unwrap();
+        let inside = |shape: &Polyhedron, point: &Point3<f64>| {
+            shape
+                .faces
+                .iter()
+                .all(|f| shape.face_normal(f).dot(&(point - shape.vertices[f[0]])) < 0.)
+        };
+        for i in 0..50 {
+            let angle = i as f64 * 0.37;
+            let rotation = UnitQuaternion::from_euler_angles(angle, 2. * angle, 3. * angle);
+            let offset = 0.6 + 0.02 * i as f64;
+            let other = shape.transform(&Transform3::new(rotation, (offset, 0.1, 0.)));
+            // A vertex within the other shape is a sufficient condition for an overlap
+            if other.vertices.iter().any(|v| inside(&shape, v))
+                || shape.vertices.iter().any(|v| inside(&other, v))
+            {
+                assert!(shape.intersects(&other), "{}", i);
+            }
+            assert_eq!(shape.intersects(&other), other.intersects(&shape), "{}", i);
+        }
+        // Separated by more than the enclosing radii
+        let other = shape.transform(&Transform3::new(UnitQuaternion::identity(), (2.01, 0., 0.)));
+        assert!(!shape.intersects(&other));
+    }
+
+    /// Whether the shapes overlap using the separating axis theorem
+    ///
+    /// Two convex polyhedra are separate when the projections onto one of the face normals or
+    /// the cross product of a pair of edges don't overlap.
+    fn separating_axis(a: &Polyhedron, b: &Polyhedron) -> bool {
+        let edges = |shape: &Polyhedron| -> Vec<Vector3<f64>> {
+            shape
+                .faces
+                .iter()
+                .flat_map(|f| {
+                    f.iter()
+                        .zip(f.iter().cycle().skip(1))
+                        .map(|(&i, &j)| shape.vertices[j] - shape.vertices[i])
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        };
+        let mut axes: Vec<Vector3<f64>> = vec![];
+        axes.extend(a.faces.iter().map(|f| a.face_normal(f)));
+        axes.extend(b.faces.iter().map(|f| b.face_normal(f)));
+        for (e1, e2) in iproduct!(edges(a), edges(b)) {
+            let axis = e1.cross(&e2);
+            if axis.norm() > 1e-9 {
+                axes.push(axis.normalize());
+            }
+        }
+        let project = |shape: &Polyhedron, axis: &Vector3<f64>| {
+            shape
+                .vertices
+                .iter()
+                .map(|v| v.coords.dot(axis))
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), x| {
+                    (min.min(x), max.max(x))
+                })
+        };
+        !axes.iter().any(|axis| {
+            let (min_a, max_a) = project(a, axis);
+            let (min_b, max_b) = project(b, axis);
+            max_a <= min_b || max_b <= min_a
+        })
+    }
+
+    #[test]
+    fn matches_separating_axis() {
+        for (name, i) in iproduct!(Polyhedron::names(), 0..40) {
+            let shape = Polyhedron::named(name).unwrap();
+            let angle = i as f64 * 0.29;
+            let rotation = UnitQuaternion::from_euler_angles(angle, 0.5 * angle, 1.7 * angle);
+            let direction = Vector3::new(angle.cos(), angle.sin(), 0.3).normalize();
+            let offset = direction * (1. + 0.025 * i as f64);
+            let other = shape.transform(&Transform3::new(rotation, (offset.x, offset.y, offset.z)));
+            assert_eq!(
+                shape.intersects(&other),
+                separating_axis(&shape, &other),
+                "{} {}",
+                name,
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn off_format() {
+        let shape = Polyhedron::named("cube").unwrap();
+        let off = shape.as_off();
+        let lines: Vec<_> = off.lines().collect();
+        assert_eq!(lines[0], "OFF");
+        assert_eq!(lines[1], "8 6 12");
+        assert_eq!(lines.len(), 2 + 8 + 6);
+        assert!(lines[10].starts_with("4 "));
+    }
+}
@@ -0,0 +1,295 @@
+//
+// rounded_shape.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+use std::{fmt, slice};
+
+use anyhow::{bail, Error};
+use itertools::{iproduct, Itertools};
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use super::{polygon_contains, Capsule2, Line2, LineShape, Transform2};
+use crate::traits::{Boundary, Intersect, Shape, Support};
+use crate::{PointGroup, SYMMETRY_TOLERANCE};
+
+/// A convex polygon with rounded corners
+///
+/// This is the Minkowski sum of a polygon and a disc, being every point within a distance
+/// `radius` of the polygon. Each of the edges of the polygon is swept by the disc to give a
+/// [`Capsule2`], so two shapes intersect when the distance between any pair of edges is less
+/// than the sum of the radii. Like the [`LineShape`], a shape which is completely inside the
+/// other without any edges being close enough isn't considered an intersection.
+///
+/// As the radius increases, the shape goes from the polygon to a disc.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub struct RoundedShape {
+    pub name: String,
+    pub items: Vec<Capsule2>,
+}
+
+impl<'a> IntoIterator for &'a RoundedShape {
+    type Item = &'a Capsule2;
+    type IntoIter = slice::Iter<'a, Capsule2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl Intersect for RoundedShape {
+    fn intersects(&self, other: &Self) -> bool {
+        iproduct!(self.items.iter(), other.items.iter()).any(|(s, o)| s.intersects(o))
+    }
+
+    /// The area of the polygon, along with a rectangle along each edge and the circular sectors
+    /// at each of the corners, which combine to make up a complete disc.
+    fn area(&self) -> f64 {
+        let radius = self.radius();
+        self.polygon_area() + self.perimeter() * radius + PI * radius.powi(2)
+    }
+}
+
+impl Support for RoundedShape {
+    fn support(&self, direction: Vector2<f64>) -> f64 {
+        self.items
+            .iter()
+            .map(|c| c.segment.start.coords.dot(&direction) + c.radius)
+            .fold(std::f64::MIN, f64::max)
+    }
+}
+
+impl Boundary for RoundedShape {
+    fn crosses(&self, edge: &Line2) -> bool {
+        self.items
+            .iter()
+            .any(|c| edge.distance_to_line(&c.segment) < c.radius)
+    }
+
+    /// The point is either within the polygon, or within the radius of one of the edges
+    fn contains(&self, point: &Point2<f64>) -> bool {
+        polygon_contains(self.items.iter().map(|c| &c.segment), point)
+            || self
+                .items
+                .iter()
+                .any(|c| c.segment.distance_to(point) < c.radius)
+    }
+}
+
+impl Shape for RoundedShape {
+    type Component = Capsule2;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn score(&self, other: &Self) -> Option<f64> {
+        if self.intersects(other) {
+            None
+        } else {
+            Some(self.area())
+        }
+    }
+
+    fn enclosing_radius(&self) -> f64 {
+        self.items
+            .iter()
+            .map(|c| c.segment.start.coords.norm() + c.radius)
+            .fold(std::f64::MIN, f64::max)
+    }
+
+    fn point_group(&self) -> PointGroup {
+        let points: Vec<_> = self
+            .items
+            .iter()
+            .flat_map(Capsule2::symmetry_points)
+            .collect();
+        PointGroup::detect(&points, SYMMETRY_TOLERANCE)
+    }
+
+    fn get_items(&self) -> Vec<Self::Component> {
+        self.items.clone()
+    }
+
+    fn iter(&self) -> slice::Iter<'_, Self::Component> {
+        self.into_iter()
+    }
+
+    fn transform(&self, transform: &Transform2) -> Self {
+        Self {
+            name: self.name.clone(),
+            items: self.into_iter().map(|i| i * transform).collect(),
+        }
+    }
+}
+
+impl fmt::Display for RoundedShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RoundedShape {{ {} }}", self.items.iter().format(", "))
+    }
+}
+
+impl RoundedShape {
+    /// The radius of the disc which rounds the corners of the polygon
+    pub fn radius(&self) -> f64 {
+        self.items[0].radius
+    }
+
+    /// The signed area of the polygon without the rounding
+    ///
+    /// This is positive when the vertices are anti-clockwise and negative when they are
+    /// clockwise.
+    ///
+    pub(crate) fn signed_area(&self) -> f64 {
+        self.items
+            .iter()
+            .map(|c| c.segment.start.coords.perp(&c.segment.end.coords) / 2.)
+            .sum()
+    }
+
+    /// The area of the polygon without the rounding
+    pub fn polygon_area(&self) -> f64 {
+        self.signed_area().abs()
+    }
+
+    /// The perimeter of the polygon without the rounding
+    pub fn perimeter(&self) -> f64 {
+        self.items.iter().map(|c| c.segment.length()).sum()
+    }
+
+    /// The unit vector normal to each edge pointing out of the polygon
+    pub fn outward_normals(&self) -> Vec<Vector2<f64>> {
+        // Rotating the direction of an anti-clockwise edge clockwise points outwards
+        let sign = self.signed_area().signum();
+        self.items
+            .iter()
+            .map(|c| {
+                let direction = (c.segment.end - c.segment.start).normalize();
+                Vector2::new(direction.y, -direction.x) * sign
+            })
+            .collect()
+    }
+
+    /// The vertices of the polygon without the rounding
+    pub fn vertices(&self) -> Vec<Point2<f64>> {
+        self.items.iter().map(|c| c.segment.start).collect()
+    }
+
+    /// Round the corners of a polygon by a disc of radius `radius`
+    ///
+    /// The polygon needs to be convex and the radius positive.
+    ///
+    /// ```
+    /// use crystal_packing::{Intersect, LineShape, RoundedShape};
+    /// let square = LineShape::from_radial("Square", vec![1.; 4]).unwrap();
+    /// let shape = RoundedShape::from_line_shape(&square, 0.5).unwrap();
+    /// let area = 2. + 4. * f64::sqrt(2.) * 0.5 + std::f64::consts::PI * 0.25;
+    /// assert!((shape.area() - area).abs() < 1e-12);
+    /// ```
+    ///
+    pub fn from_line_shape(shape: &LineShape, radius: f64) -> Result<RoundedShape, Error> {
+        if radius.is_nan() || radius <= 0. {
+            bail!(
+                "The radius of the rounding needs to be positive, got {}",
+                radius
+            );
+        }
+        let turns: Vec<f64> = shape
+            .items
+            .iter()
+            .zip(shape.items.iter().cycle().skip(1))
+            .map(|(l1, l2)| (l1.end - l1.start).perp(&(l2.end - l2.start)))
+            .collect();
+        if !(turns.iter().all(|&t| t >= 0.) || turns.iter().all(|&t| t <= 0.)) {
+            bail!("Only convex polygons are able to be rounded");
+        }
+        Ok(RoundedShape {
+            name: format!("Rounded{}", shape.name),
+            items: shape
+                .items
+                .iter()
+                .map(|line| Capsule2 {
+                    segment: *line,
+                    radius,
+                })
+                .collect(),
+        })
+    }
+
+    /// A regular polygon with a circumradius of 1, rounded by a disc of radius `radius`
+    pub fn polygon(sides: usize, radius: f64) -> Result<RoundedShape, Error> {
+        Self::from_line_shape(&LineShape::polygon(sides)?, radius)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    fn create_square() -> RoundedShape {
+        let square = LineShape::from_radial("Square", vec![1.; 4]).unwrap();
+        RoundedShape::from_line_shape(&square, 0.1).unwrap()
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let square = LineShape::from_radial("Square", vec![1.; 4]).unwrap();
+        assert!(RoundedShape::from_line_shape(&square, 0.).is_err());
+        assert!(RoundedShape::from_line_shape(&square, f64::NAN).is_err());
+        let concave = LineShape::from_radial("Concave", vec![1., 1., 0.2, 1., 1., 1.]).unwrap();
+        assert!(RoundedShape::from_line_shape(&concave, 0.1).is_err());
+    }
+
+    #[test]
+    fn area_between_polygon_and_disc() {
+        let square = LineShape::from_radial("Square", vec![1.; 4]).unwrap();
+        let shape = create_square();
+        assert_abs_diff_eq!(shape.polygon_area(), square.area(), epsilon = 1e-12);
+        assert!(shape.area() > square.area());
+        assert!(shape.area() < PI * 1.1f64.powi(2));
+    }
+
+    #[test]
+    fn outward_normals() {
+        let shape = create_square();
+        for (normal, capsule) in shape.outward_normals().iter().zip(shape.iter()) {
+            let midpoint =
+                capsule.segment.start + (capsule.segment.end - capsule.segment.start) / 2.;
+            assert!(normal.dot(&midpoint.coords) > 0.);
+        }
+    }
+
+    #[test]
+    fn intersection() {
+        let shape = create_square();
+        // The distance between the flat edges is sqrt(2) + 2 * 0.1
+        let gap = f64::sqrt(2.) + 0.2;
+        let rotated = Transform2::new(PI / 4., (0., 0.));
+        let shape = shape.transform(&rotated);
+        assert!(shape.intersects(&shape.transform(&Transform2::new(0., (gap - 0.01, 0.)))));
+        assert!(!shape.intersects(&shape.transform(&Transform2::new(0., (gap + 0.01, 0.)))));
+        // The rounded corners are further than the corners of the polygon
+        assert!(!shape.intersects(&shape.transform(&Transform2::new(0., (gap, gap)))));
+    }
+
+    #[test]
+    fn support() {
+        let shape = create_square();
+        assert_abs_diff_eq!(shape.support(Vector2::new(0., 1.)), 1.1, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn rounded_point_group() {
+        assert_eq!(create_square().point_group(), PointGroup::Dihedral(4));
+        let shape = RoundedShape::polygon(5, 0.2).unwrap();
+        assert_eq!(shape.point_group(), PointGroup::Dihedral(5));
+        assert!(!shape.is_chiral());
+    }
+}
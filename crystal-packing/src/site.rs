@@ -4,11 +4,13 @@
 // Distributed under terms of the MIT license.
 //
 
+use nalgebra::{Quaternion, UnitQuaternion};
 use serde::{Deserialize, Serialize};
 
 use crate::basis::{Basis, SharedValue};
+use crate::space_group::WyckoffSite3;
 use crate::wallpaper::WyckoffSite;
-use crate::Transform2;
+use crate::{Transform2, Transform3};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OccupiedSite {
@@ -16,6 +18,13 @@ pub struct OccupiedSite {
     x: SharedValue,
     y: SharedValue,
     angle: SharedValue,
+    /// The number of rotations which leave the shape on the site unchanged
+    #[serde(default = "single_rotation")]
+    rotations: u64,
+}
+
+fn single_rotation() -> u64 {
+    1
 }
 
 impl Clone for OccupiedSite {
@@ -25,6 +34,7 @@ impl Clone for OccupiedSite {
             x: SharedValue::new(self.x.get_value()),
             y: SharedValue::new(self.y.get_value()),
             angle: SharedValue::new(self.angle.get_value()),
+            rotations: self.rotations,
         }
     }
 }
@@ -59,9 +69,36 @@ impl OccupiedSite {
             x,
             y,
             angle,
+            rotations: 1,
+        }
+    }
+
+    /// Create a site with the position and orientation of the transform
+    pub fn from_transform(wyckoff: &WyckoffSite, transform: Transform2) -> Self {
+        let position = transform.position();
+        OccupiedSite {
+            wyckoff: wyckoff.clone(),
+            x: SharedValue::new(position.x),
+            y: SharedValue::new(position.y),
+            angle: SharedValue::new(transform.angle()),
+            rotations: 1,
         }
     }
 
+    /// Limit the orientation of the site to the orientations which are distinguishable
+    ///
+    /// A shape with `rotations` fold symmetry only needs the orientations in the range
+    /// [0, 2π / `rotations`], which shrinks the space the optimisation has to search. The current
+    /// orientation is replaced with the equivalent orientation within this range.
+    ///
+    pub fn with_rotations(mut self, rotations: u64) -> Self {
+        self.rotations = rotations.max(1);
+        let period = std::f64::consts::TAU / self.rotations as f64;
+        self.angle
+            .set_value(self.angle.get_value().rem_euclid(period));
+        self
+    }
+
     pub fn get_basis(&self) -> Vec<Basis> {
         let mut basis: Vec<Basis> = vec![];
         let dof = self.wyckoff.degrees_of_freedom();
@@ -81,10 +118,10 @@ impl OccupiedSite {
             });
         }
         if dof[2] {
-            basis.push(Basis::StandardBasis {
+            basis.push(Basis::AngleBasis {
                 value: &self.angle,
                 min: 0.,
-                max: std::f64::consts::TAU,
+                max: std::f64::consts::TAU / self.rotations as f64,
             });
         }
         basis
@@ -93,3 +130,114 @@ impl OccupiedSite {
         self.wyckoff.symmetries.iter()
     }
 }
+
+/// A site within a three dimensional crystal
+///
+/// The orientation is stored as the four components of a quaternion, which are normalised
+/// when creating the transform. This avoids the singularities of describing an orientation
+/// with Euler angles, at the cost of a single redundant degree of freedom.
+///
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OccupiedSite3 {
+    wyckoff: WyckoffSite3,
+    x: SharedValue,
+    y: SharedValue,
+    z: SharedValue,
+    orientation: [SharedValue; 4],
+}
+
+impl Clone for OccupiedSite3 {
+    fn clone(&self) -> Self {
+        OccupiedSite3 {
+            wyckoff: self.wyckoff.clone(),
+            x: SharedValue::new(self.x.get_value()),
+            y: SharedValue::new(self.y.get_value()),
+            z: SharedValue::new(self.z.get_value()),
+            orientation: [
+                SharedValue::new(self.orientation[0].get_value()),
+                SharedValue::new(self.orientation[1].get_value()),
+                SharedValue::new(self.orientation[2].get_value()),
+                SharedValue::new(self.orientation[3].get_value()),
+            ],
+        }
+    }
+}
+
+impl OccupiedSite3 {
+    pub fn transform(&self) -> Transform3 {
+        let [w, i, j, k] = &self.orientation;
+        let quaternion =
+            Quaternion::new(w.get_value(), i.get_value(), j.get_value(), k.get_value());
+        // All components being zero doesn't describe an orientation
+        let rotation = if quaternion.norm() > 0. {
+            UnitQuaternion::from_quaternion(quaternion)
+        } else {
+            UnitQuaternion::identity()
+        };
+        Transform3::new(
+            rotation,
+            (self.x.get_value(), self.y.get_value(), self.z.get_value()),
+        )
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Transform3> + '_ {
+        let transform = self.transform();
+        self.symmetries()
+            .map(move |sym| sym * transform)
+            .map(|sym| sym.periodic(1., -0.5))
+    }
+
+    pub fn multiplicity(&self) -> usize {
+        self.wyckoff.multiplicity()
+    }
+
+    pub fn from_wyckoff(wyckoff: &WyckoffSite3) -> Self {
+        let position = -0.5 + 0.5 / wyckoff.multiplicity() as f64;
+        Self::from_transform(
+            wyckoff,
+            Transform3::new(UnitQuaternion::identity(), (position, position, position)),
+        )
+    }
+
+    /// Create a site with the position and orientation of the transform
+    ///
+    /// The transform needs to be a proper rotation, with no mirror or inversion.
+    ///
+    pub fn from_transform(wyckoff: &WyckoffSite3, transform: Transform3) -> Self {
+        let position = transform.position();
+        let orientation = transform.orientation();
+        OccupiedSite3 {
+            wyckoff: wyckoff.clone(),
+            x: SharedValue::new(position.x),
+            y: SharedValue::new(position.y),
+            z: SharedValue::new(position.z),
+            orientation: [
+                SharedValue::new(orientation.w),
+                SharedValue::new(orientation.i),
+                SharedValue::new(orientation.j),
+                SharedValue::new(orientation.k),
+            ],
+        }
+    }
+
+    pub fn get_basis(&self) -> Vec<Basis> {
+        let position =
+            vec![&self.x, &self.y, &self.z]
+                .into_iter()
+                .map(|value| Basis::StandardBasis {
+                    value,
+                    min: -0.5,
+                    max: 0.5,
+                });
+        let orientation = self.orientation.iter().map(|value| Basis::StandardBasis {
+            value,
+            min: -1.,
+            max: 1.,
+        });
+        position.chain(orientation).collect()
+    }
+
+    pub fn symmetries(&self) -> impl Iterator<Item = &Transform3> + '_ {
+        self.wyckoff.symmetries.iter()
+    }
+}
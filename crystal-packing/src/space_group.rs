@@ -0,0 +1,221 @@
+//
+// space_group.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use crate::{CrystalSystem, Transform3};
+
+/// The symmetry operations of a three dimensional space group
+///
+/// The operations are those of the general position, in the same notation as the
+/// International Tables of Crystallography.
+///
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpaceGroup<'a> {
+    pub name: &'a str,
+    pub system: CrystalSystem,
+    pub wyckoff_str: Vec<&'a str>,
+}
+
+/// Defining one of the space groups.
+///
+/// This is the highest level description of the symmetry operations of a three dimensional
+/// crystal.
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Space {
+    pub name: String,
+    pub system: CrystalSystem,
+}
+
+impl Space {
+    pub fn new(group: &SpaceGroup) -> Space {
+        Space {
+            name: String::from(group.name),
+            system: group.system,
+        }
+    }
+}
+
+impl<'a> From<SpaceGroup<'a>> for Space {
+    fn from(group: SpaceGroup) -> Space {
+        Space {
+            name: String::from(group.name),
+            system: group.system,
+        }
+    }
+}
+
+/// The general position of a space group
+///
+/// Only the general position is supported in three dimensions, so every shape is able to move
+/// and rotate freely.
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WyckoffSite3 {
+    pub letter: char,
+    pub symmetries: Vec<Transform3>,
+}
+
+impl WyckoffSite3 {
+    pub fn new(group: &SpaceGroup) -> Result<WyckoffSite3, Error> {
+        let symmetries = group
+            .wyckoff_str
+            .iter()
+            .map(|&a| Transform3::from_operations(a))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(WyckoffSite3 {
+            letter: 'a',
+            symmetries,
+        })
+    }
+
+    pub fn multiplicity(&self) -> usize {
+        self.symmetries.len()
+    }
+}
+
+/// The space groups which are most commonly observed for molecular crystals
+///
+/// The names use the Hermann–Mauguin notation, with the monoclinic groups in the setting with
+/// b as the unique axis.
+///
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SpaceGroups {
+    P1,
+    P_1,
+    P21_c,
+    P212121,
+    C2_c,
+}
+
+impl std::str::FromStr for SpaceGroups {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "P1" => Ok(SpaceGroups::P1),
+            "P-1" => Ok(SpaceGroups::P_1),
+            "P21/c" => Ok(SpaceGroups::P21_c),
+            "P2_1/c" => Ok(SpaceGroups::P21_c),
+            "P212121" => Ok(SpaceGroups::P212121),
+            "P2_12_12_1" => Ok(SpaceGroups::P212121),
+            "C2/c" => Ok(SpaceGroups::C2_c),
+            _ => Err(anyhow!("Invalid Value")),
+        }
+    }
+}
+
+impl std::fmt::Display for SpaceGroups {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SpaceGroups::P1 => write!(f, "P1"),
+            SpaceGroups::P_1 => write!(f, "P-1"),
+            SpaceGroups::P21_c => write!(f, "P21/c"),
+            SpaceGroups::P212121 => write!(f, "P212121"),
+            SpaceGroups::C2_c => write!(f, "C2/c"),
+        }
+    }
+}
+
+impl SpaceGroups {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["P1", "P-1", "P21/c", "P212121", "C2/c"]
+    }
+}
+
+impl<'a> TryFrom<SpaceGroups> for SpaceGroup<'a> {
+    type Error = Error;
+
+    fn try_from(name: SpaceGroups) -> Result<Self, Self::Error> {
+        match name {
+            SpaceGroups::P1 => Ok(SpaceGroup {
+                name: "P1",
+                system: CrystalSystem::Triclinic,
+                wyckoff_str: vec!["x,y,z"],
+            }),
+            SpaceGroups::P_1 => Ok(SpaceGroup {
+                name: "P-1",
+                system: CrystalSystem::Triclinic,
+                wyckoff_str: vec!["x,y,z", "-x,-y,-z"],
+            }),
+            SpaceGroups::P21_c => Ok(SpaceGroup {
+                name: "P21/c",
+                system: CrystalSystem::Monoclinic,
+                wyckoff_str: vec!["x,y,z", "-x,y+1/2,-z+1/2", "-x,-y,-z", "x,-y+1/2,z+1/2"],
+            }),
+            SpaceGroups::P212121 => Ok(SpaceGroup {
+                name: "P212121",
+                system: CrystalSystem::Orthorhombic,
+                wyckoff_str: vec![
+                    "x,y,z",
+                    "-x+1/2,-y,z+1/2",
+                    "-x,y+1/2,-z+1/2",
+                    "x+1/2,-y+1/2,-z",
+                ],
+            }),
+            SpaceGroups::C2_c => Ok(SpaceGroup {
+                name: "C2/c",
+                system: CrystalSystem::Monoclinic,
+                wyckoff_str: vec![
+                    "x,y,z",
+                    "-x,y,-z+1/2",
+                    "-x,-y,-z",
+                    "x,-y,z+1/2",
+                    "x+1/2,y+1/2,z",
+                    "-x+1/2,y+1/2,-z+1/2",
+                    "-x+1/2,-y+1/2,-z",
+                    "x+1/2,-y+1/2,z+1/2",
+                ],
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use itertools::iproduct;
+    use nalgebra::Point3;
+
+    use super::*;
+
+    #[test]
+    fn all_groups_valid() {
+        for name in SpaceGroups::variants() {
+            let group: SpaceGroups = name.parse().unwrap();
+            assert_eq!(group.to_string(), name);
+            let group: SpaceGroup = group.try_into().unwrap();
+            assert!(WyckoffSite3::new(&group).is_ok());
+        }
+    }
+
+    #[test]
+    fn operations_form_group() {
+        // The product of any two operations is another operation modulo a lattice translation
+        let point = Point3::new(0.1, 0.2, 0.3);
+        for name in SpaceGroups::variants() {
+            let group: SpaceGroup = name.parse::<SpaceGroups>().unwrap().try_into().unwrap();
+            let wyckoff = WyckoffSite3::new(&group).unwrap();
+            let images: Vec<Point3<f64>> = wyckoff
+                .symmetries
+                .iter()
+                .map(|s| (s * point).map(|x| x.rem_euclid(1.)))
+                .collect();
+            for (a, b) in iproduct!(wyckoff.symmetries.iter(), wyckoff.symmetries.iter()) {
+                let image = (a * b * point).map(|x| x.rem_euclid(1.));
+                assert!(
+                    images.iter().any(|i| (i - image).norm() < 1e-10),
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//
+// stability.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::traits::*;
+
+/// The outcome of testing the stability of a structure in an unconstrained supercell
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StabilityReport {
+    /// The number of unit cells along the a axis of the supercell
+    pub n: usize,
+    /// The number of unit cells along the b axis of the supercell
+    pub m: usize,
+    /// The root mean squared displacement of the shapes from their symmetry-adapted positions,
+    /// after removing the drift of the whole supercell
+    pub rmsd: f64,
+    /// The root mean squared displacement relative to the mean spacing between shapes, being
+    /// the square root of the area per shape
+    pub relative_rmsd: f64,
+    /// The root mean squared change in the orientation of the shapes in radians
+    pub orientation_rmsd: f64,
+    /// The fraction of the initial density which remains, where the density is the number of
+    /// shapes per unit area
+    pub retained_density: f64,
+    pub initial_score: Option<f64>,
+    pub final_score: Option<f64>,
+    /// Whether the supercell kept the order of the original structure
+    pub stable: bool,
+}
+
+/// The criteria for a structure to have kept its order
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StabilityCriteria {
+    /// The largest relative RMSD of a stable structure, which defaults to the Lindemann
+    /// criterion of 0.1
+    pub max_relative_rmsd: f64,
+    /// The smallest fraction of the initial density retained by a stable structure
+    pub min_retained_density: f64,
+}
+
+impl Default for StabilityCriteria {
+    fn default() -> Self {
+        Self {
+            max_relative_rmsd: 0.1,
+            min_retained_density: 0.95,
+        }
+    }
+}
+
+/// Wrap a value into the range [-period / 2, period / 2)
+fn minimum_image(value: f64, period: f64) -> f64 {
+    value - period * f64::floor(value / period + 0.5)
+}
+
+/// Test whether a structure is stable once the constraints of the wallpaper group are removed
+///
+/// The state is expanded into an `n` by `m` supercell in the wallpaper group p1, where every
+/// shape is free to move independently. The supercell is then passed to `simulate`, which should
+/// be a finite temperature or finite pressure Monte Carlo simulation, like the [`MCOptimiser`]
+/// with a constant temperature, or the [`IsobaricOptimiser`] at a constant pressure. The displacement
+/// of each shape from the initial symmetry-adapted positions, along with the change in the
+/// density indicate whether the structure retained its order. A structure can only lose density
+/// when the simulation allows the cell to expand, so the [`MCOptimiser`] needs
+/// [`MCOptimiser::with_expanding_cell`].
+///
+/// ```
+/// # use std::convert::TryInto;
+/// # use crystal_packing::wallpaper::{WallpaperGroup, WallpaperGroups};
+/// use crystal_packing::{
+///     check_stability, IsobaricOptimiser, LinearPressure, LineShape, MCOptimiser, Optimiser,
+///     PackedState, StabilityCriteria,
+/// };
+/// # let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+/// let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+/// let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+///
+/// let pressure = Box::new(LinearPressure::new(50., 50.));
+/// let optimiser = IsobaricOptimiser::new(pressure, 0.01, 1000, 100, 0);
+/// let simulate = |s| optimiser.optimise_equation_of_state(s, &mut ()).0;
+/// let (report, _) = check_stability(&state, 2, 2, simulate, StabilityCriteria::default());
+/// assert!(report.rmsd >= 0.);
+/// ```
+///
+/// [`MCOptimiser`]: crate::MCOptimiser
+/// [`MCOptimiser::with_expanding_cell`]: crate::MCOptimiser::with_expanding_cell
+/// [`IsobaricOptimiser`]: crate::IsobaricOptimiser
+///
+pub fn check_stability<S, F>(
+    state: &S,
+    n: usize,
+    m: usize,
+    simulate: F,
+    criteria: StabilityCriteria,
+) -> (StabilityReport, S)
+where
+    S: Supercell,
+    F: FnOnce(S) -> S,
+{
+    let supercell = state.supercell(n, m);
+    let initial_positions = supercell.fractional_positions();
+    let initial_area = supercell.cell().area();
+    let initial_score = supercell.score();
+
+    let supercell = simulate(supercell);
+    let final_positions = supercell.fractional_positions();
+    let cell = supercell.cell();
+
+    let displacements: Vec<(f64, f64)> = initial_positions
+        .iter()
+        .zip(final_positions.iter())
+        .map(|(initial, last)| {
+            let (i, f) = (initial.position(), last.position());
+            (minimum_image(f.x - i.x, 1.), minimum_image(f.y - i.y, 1.))
+        })
+        .collect();
+    let shapes = displacements.len() as f64;
+    // The drift of the whole supercell doesn't change the structure
+    let drift = displacements.iter().fold((0., 0.), |acc, d| {
+        (acc.0 + d.0 / shapes, acc.1 + d.1 / shapes)
+    });
+    let rmsd = f64::sqrt(
+        displacements
+            .iter()
+            .map(|&(x, y)| {
+                let (x, y) = cell.to_cartesian(x - drift.0, y - drift.1);
+                x * x + y * y
+            })
+            .sum::<f64>()
+            / shapes,
+    );
+    let orientation_rmsd = f64::sqrt(
+        initial_positions
+            .iter()
+            .zip(final_positions.iter())
+            .map(|(i, f)| minimum_image(f.angle() - i.angle(), 2. * PI).powi(2))
+            .sum::<f64>()
+            / shapes,
+    );
+
+    let spacing = f64::sqrt(cell.area() / shapes);
+    let relative_rmsd = rmsd / spacing;
+    let retained_density = initial_area / cell.area();
+    let final_score = supercell.score();
+
+    let report = StabilityReport {
+        n,
+        m,
+        rmsd,
+        relative_rmsd,
+        orientation_rmsd,
+        retained_density,
+        initial_score,
+        final_score,
+        stable: final_score.is_some()
+            && relative_rmsd <= criteria.max_relative_rmsd
+            && retained_density >= criteria.min_retained_density,
+    };
+    (report, supercell)
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+    use crate::{LJShape2, LineShape, MCOptimiser, PackedState, PotentialState};
+
+    #[test]
+    fn supercell_preserves_structure() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+        let supercell = state.supercell(3, 2);
+
+        assert_eq!(supercell.total_shapes(), 6 * state.total_shapes());
+        assert_eq!(supercell.wallpaper.name, "p1");
+        // The density of the supercell is the same as the original cell
+        assert_abs_diff_eq!(
+            supercell.score().unwrap(),
+            state.score().unwrap(),
+            epsilon = 1e-10
+        );
+        // Every shape is independent, with 3 values for each in addition to the cell
+        assert_eq!(
+            supercell.generate_basis().len(),
+            3 + 3 * supercell.total_shapes()
+        );
+    }
+
+    #[test]
+    fn supercell_potential_energy() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PotentialState::from_group(LJShape2::circle(), &group).unwrap();
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+        let supercell = state.supercell(2, 2);
+        assert_eq!(
+            supercell.fractional_positions().len(),
+            4 * state.total_shapes()
+        );
+    }
+
+    #[test]
+    fn unchanged_is_stable() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+        // Without any steps the supercell is unchanged
+        let optimiser = MCOptimiser::new(0., 1., 0.01, 0, 1, 0, None);
+        let simulate = |s| optimiser.optimise_state(s);
+        let (report, _) = check_stability(&state, 2, 2, simulate, StabilityCriteria::default());
+        assert_abs_diff_eq!(report.rmsd, 0., epsilon = 1e-10);
+        assert_abs_diff_eq!(report.retained_density, 1.);
+        assert!(report.stable);
+    }
+
+    #[test]
+    fn compressed_potential_expands() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PotentialState::from_group(LJShape2::circle(), &group).unwrap();
+        let state = MCOptimiser::new(0., 1., 0.01, 2000, 100, 0, None).optimise_state(state);
+        // Compressing the minimum leaves the shapes overlapping the repulsive core of the potential
+        {
+            let basis = state.generate_basis();
+            basis[0].set_value(0.9 * basis[0].get_value()).unwrap();
+        }
+
+        let optimiser = MCOptimiser::new(0.1, 1., 0.01, 2000, 100, 0, None);
+        let simulate = |s| optimiser.optimise_state(s);
+        let (report, _) = check_stability(&state, 2, 2, simulate, StabilityCriteria::default());
+        // The cell is only able to contract
+        assert!(report.retained_density >= 1.);
+
+        let optimiser = optimiser.with_expanding_cell(true);
+        let simulate = |s| optimiser.optimise_state(s);
+        let (report, _) = check_stability(&state, 2, 2, simulate, StabilityCriteria::default());
+        assert!(report.retained_density < 1.);
+    }
+
+    #[test]
+    fn minimum_image_wraps() {
+        assert_abs_diff_eq!(minimum_image(0.9, 1.), -0.1, epsilon = 1e-12);
+        assert_abs_diff_eq!(minimum_image(-0.7, 1.), 0.3, epsilon = 1e-12);
+    }
+}
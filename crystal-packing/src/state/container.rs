@@ -0,0 +1,391 @@
+//
+// container.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+#![allow(clippy::type_repetition_in_bounds)]
+
+use std::cmp::Ordering;
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+use anyhow::{bail, Error};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::traits::*;
+use crate::{Basis, Container, OccupiedSite, Outline, PointGroup, Transform2};
+
+/// A finite cluster of shapes packed within a container
+///
+/// There is no periodicity, with the shapes confined by the boundary of a circular or polygonal
+/// [`Container`], the size of which is a degree of freedom. The score is the fraction of the
+/// container covered by the shapes.
+///
+/// The cluster can have the symmetry of one of the point groups Cn or Dn about the centre of
+/// the container, where each of the independent shapes has a copy at every operation of the
+/// group. This reduces the number of values to optimise by the order of the group.
+///
+/// ```
+/// use crystal_packing::{
+///     Container, ContainerState, MCOptimiser, MolecularShape2, Optimiser, PointGroup,
+/// };
+/// let container = Container::circle(1.);
+/// let state = ContainerState::new(MolecularShape2::circle(), container, 6, PointGroup::Cyclic(3))
+///     .unwrap();
+/// let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+/// assert_eq!(state.cartesian_positions().count(), 6);
+/// ```
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub struct ContainerState<S>
+where
+    S: Shape + Intersect + Boundary,
+{
+    pub point_group: PointGroup,
+    pub shape: S,
+    pub container: Container,
+    occupied_sites: Vec<OccupiedSite>,
+}
+
+impl<S> Eq for ContainerState<S> where S: Shape + Intersect + Boundary {}
+
+impl<S> PartialEq for ContainerState<S>
+where
+    S: Shape + Intersect + Boundary,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.eq(&o),
+            (_, _) => false,
+        }
+    }
+}
+
+impl<S> PartialOrd for ContainerState<S>
+where
+    S: Shape + Intersect + Boundary,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.partial_cmp(&o),
+            (_, _) => None,
+        }
+    }
+}
+
+impl<S> Ord for ContainerState<S>
+where
+    S: Shape + Intersect + Boundary,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other).unwrap()
+    }
+}
+
+impl<S> State for ContainerState<S>
+where
+    S: Shape + Intersect + Boundary,
+{
+    fn total_shapes(&self) -> usize {
+        self.occupied_sites
+            .iter()
+            .fold(0, |sum, site| sum + site.multiplicity())
+    }
+
+    fn score(&self) -> Option<f64> {
+        if self.check_intersection() {
+            None
+        } else {
+            Some(
+                (self.shape.area() * self.total_shapes() as f64) / self.container.area()
+                    - self.shape.internal_energy(),
+            )
+        }
+    }
+
+    fn generate_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.container.get_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis.append(&mut self.shape.internal_basis());
+        basis
+    }
+
+    fn generate_expanding_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.container.get_expanding_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis.append(&mut self.shape.internal_basis());
+        basis
+    }
+
+    fn basis_blocks(&self) -> Vec<usize> {
+        std::iter::once(self.container.get_degrees_of_freedom().len())
+            .chain(self.occupied_sites.iter().map(|s| s.get_basis().len()))
+            .chain(Some(self.shape.internal_basis().len()).filter(|&n| n > 0))
+            .collect()
+    }
+
+    fn as_positions(&self) -> Result<String, Error> {
+        let mut output = String::new();
+        writeln!(&mut output, "{}", self.container)?;
+        writeln!(&mut output, "Positions")?;
+
+        for transform in self.cartesian_positions() {
+            writeln!(&mut output, "{:?}", transform)?;
+        }
+        Ok(output)
+    }
+}
+
+impl<S> ContainerState<S>
+where
+    S: Shape + Intersect + Boundary,
+{
+    pub fn cartesian_positions(&self) -> impl Iterator<Item = Transform2> + '_ {
+        self.relative_positions()
+            .map(move |position| self.container.to_cartesian_isometry(position))
+    }
+
+    /// The position of every shape in relative coordinates
+    ///
+    /// Unlike the sites of a crystal, the positions aren't wrapped into the range of the
+    /// relative coordinates, since a rotation about the centre needs to remain a rotation.
+    ///
+    pub fn relative_positions(&self) -> impl Iterator<Item = Transform2> + '_ {
+        self.occupied_sites.iter().flat_map(|site| {
+            let transform = site.transform();
+            site.symmetries().map(move |sym| sym * transform)
+        })
+    }
+
+    /// Check for intersections of shapes with each other or with the container
+    ///
+    /// A shape is within a circular container when the furthest point from the centre is
+    /// within the radius. For a polygonal container none of the edges can cross the shape, with
+    /// the position of the shape being inside the container. Since the container doesn't need to
+    /// be convex, none of the vertices can be inside the shape either, which would happen when
+    /// part of the boundary is completely within the shape.
+    ///
+    fn check_intersection(&self) -> bool {
+        let shapes: Vec<(Transform2, S)> = self
+            .cartesian_positions()
+            .map(|p| (p, self.shape.transform(&p)))
+            .collect();
+
+        let edges = self.container.edges();
+        for (position, shape) in shapes.iter() {
+            let outside = match self.container.outline() {
+                Outline::Circle => shape.enclosing_radius() > self.container.size(),
+                Outline::Polygon(_) => {
+                    !self.container.contains(&position.position())
+                        || edges.iter().any(|e| shape.crosses(e))
+                        || edges.iter().any(|e| shape.contains(&e.start))
+                }
+            };
+            if outside {
+                return true;
+            }
+        }
+
+        let diameter = 2. * self.shape.enclosing_radius();
+        for (index, (position1, shape1)) in shapes.iter().enumerate() {
+            for (position2, shape2) in shapes.iter().skip(index + 1) {
+                let distance = (position1.position() - position2.position()).norm();
+                if distance <= diameter && shape1.intersects(shape2) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Create a cluster of `shapes` shapes within the container with the symmetry of `group`
+    ///
+    /// The number of shapes needs to be a multiple of the order of the group. The independent
+    /// shapes are initially spread along a line from the centre, away from any mirror lines,
+    /// within a container large enough that none of the shapes overlap.
+    ///
+    pub fn new(
+        shape: S,
+        container: Container,
+        shapes: usize,
+        group: PointGroup,
+    ) -> Result<Self, Error> {
+        if shapes == 0 || !shapes.is_multiple_of(group.order()) {
+            bail!(
+                "The number of shapes {} is not a multiple of the order of {}",
+                shapes,
+                group
+            );
+        }
+        let wyckoff = group.wyckoff();
+        super::check_sites(&shape, std::slice::from_ref(&wyckoff))?;
+        let sites = shapes / group.order();
+        let rotations = match group {
+            PointGroup::Cyclic(n) | PointGroup::Dihedral(n) => n,
+        };
+        let angle = PI / (2. * rotations as f64);
+
+        let inradius = container.inradius();
+        let occupied_sites = (0..sites)
+            .map(|i| {
+                let distance = 0.45 * inradius * (i + 1) as f64 / (sites + 1) as f64;
+                let position = (distance * angle.cos(), distance * angle.sin());
+                OccupiedSite::from_transform(&wyckoff, Transform2::new(0., position))
+            })
+            .collect();
+
+        let container =
+            container.with_size(4. * shape.enclosing_radius() * shapes as f64 / inradius);
+        debug!("Container: {}", container);
+
+        Ok(ContainerState {
+            point_group: group,
+            shape,
+            container,
+            occupied_sites,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::{LineShape, MCOptimiser, MolecularShape2};
+
+    #[test]
+    fn initially_valid() {
+        let containers = vec![
+            Container::circle(1.),
+            Container::square(1.),
+            Container::regular(3, 1.).unwrap(),
+        ];
+        for container in containers {
+            for group in ["C1", "C2", "C5", "D1", "D3"].iter() {
+                let group: PointGroup = group.parse().unwrap();
+                let state = ContainerState::new(
+                    LineShape::polygon(4).unwrap(),
+                    container.clone(),
+                    group.order() * 2,
+                    group,
+                )
+                .unwrap();
+                assert!(state.score().is_some(), "{} {}", container, group);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetry_reduces_basis() {
+        let container = Container::circle(1.);
+        let free = ContainerState::new(
+            MolecularShape2::circle(),
+            container.clone(),
+            6,
+            PointGroup::Cyclic(1),
+        )
+        .unwrap();
+        let symmetric = ContainerState::new(
+            MolecularShape2::circle(),
+            container,
+            6,
+            PointGroup::Cyclic(6),
+        )
+        .unwrap();
+        assert_eq!(free.generate_basis().len(), 1 + 3 * 6);
+        assert_eq!(symmetric.generate_basis().len(), 1 + 3);
+        assert_eq!(symmetric.total_shapes(), 6);
+    }
+
+    #[test]
+    fn invalid_number_of_shapes() {
+        let state = ContainerState::new(
+            MolecularShape2::circle(),
+            Container::circle(1.),
+            5,
+            PointGroup::Dihedral(2),
+        );
+        assert!(state.is_err());
+    }
+
+    #[test]
+    fn single_circle() {
+        let state = ContainerState::new(
+            MolecularShape2::circle(),
+            Container::circle(1.),
+            1,
+            PointGroup::Cyclic(1),
+        )
+        .unwrap();
+        let basis = state.generate_basis();
+        // Placing the circle at the centre of a container of the same size fills it
+        basis[1].set_value(0.).unwrap();
+        basis[2].set_value(0.).unwrap();
+        basis[0].set_value(1.).unwrap();
+        assert_abs_diff_eq!(state.score().unwrap(), 1., epsilon = 1e-12);
+        basis[0].set_value(0.99).unwrap();
+        assert_eq!(state.score(), None);
+    }
+
+    #[test]
+    fn square_container_boundary() {
+        let state = ContainerState::new(
+            LineShape::polygon(4).unwrap(),
+            Container::square(1.),
+            1,
+            PointGroup::Cyclic(1),
+        )
+        .unwrap();
+        let basis = state.generate_basis();
+        basis[0].set_value(2.).unwrap();
+        // A square at the centre rotated to align with the container
+        basis[1].set_value(0.).unwrap();
+        basis[2].set_value(0.).unwrap();
+        basis[3].set_value(PI / 4.).unwrap();
+        assert!(state.score().is_some());
+        // Moving the square past the edge of the container
+        basis[1].set_value(0.4).unwrap();
+        assert_eq!(state.score(), None);
+    }
+
+    #[test]
+    fn container_within_shape() {
+        let state = ContainerState::new(
+            LineShape::polygon(4).unwrap(),
+            Container::square(1.),
+            1,
+            PointGroup::Cyclic(1),
+        )
+        .unwrap();
+        let basis = state.generate_basis();
+        basis[1].set_value(0.).unwrap();
+        basis[2].set_value(0.).unwrap();
+        // The whole boundary is inside the square, so none of the edges cross it
+        basis[0].set_value(0.2).unwrap();
+        assert_eq!(state.score(), None);
+    }
+
+    #[test]
+    fn optimise_in_container() {
+        let state = ContainerState::new(
+            MolecularShape2::circle(),
+            Container::square(1.),
+            4,
+            PointGroup::Dihedral(2),
+        )
+        .unwrap();
+        let initial = state.score().unwrap();
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+        assert!(state.score().unwrap() > initial);
+    }
+}
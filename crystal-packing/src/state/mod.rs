@@ -4,8 +4,127 @@
 // Distributed under terms of the MIT license.
 //
 
+pub mod container;
 pub mod packed;
+pub mod packed3;
+pub mod polydisperse;
 pub mod potential;
+pub mod strip;
 
+pub use container::*;
 pub use packed::*;
+pub use packed3::*;
+pub use polydisperse::*;
 pub use potential::*;
+pub use strip::*;
+
+use std::f64::consts::PI;
+
+use anyhow::{bail, Error};
+use itertools::iproduct;
+use nalgebra::Point2;
+
+use crate::traits::*;
+use crate::wallpaper::{Wallpaper, WyckoffSite};
+use crate::{Cell2, CrystalFamily, OccupiedSite, Transform2};
+
+/// Check the shape has the symmetry required to occupy each of the Wyckoff sites
+pub(crate) fn check_sites<S: Shape>(shape: &S, isopointal: &[WyckoffSite]) -> Result<(), Error> {
+    if let Some(site) = isopointal.iter().find(|site| !shape.can_occupy(site)) {
+        bail!(
+            "A shape with the point group {} is unable to occupy the Wyckoff site {}, which has the symmetry {}",
+            shape.point_group(),
+            site.letter,
+            site.site_symmetry()
+        );
+    }
+    Ok(())
+}
+
+/// The wallpaper group of a supercell, which has no symmetry
+pub(crate) fn supercell_wallpaper() -> Wallpaper {
+    Wallpaper {
+        name: String::from("p1"),
+        family: CrystalFamily::Monoclinic,
+    }
+}
+
+/// Create a free site for every shape within an `n` by `m` supercell
+///
+/// The positions are the relative positions of the shapes within the original cell. Each site
+/// has a single symmetry operation placing the shape at its position in the supercell, with the
+/// values of the site being relative to that position. The values start in the centre of their
+/// bounds, so every shape is able to move in all directions.
+///
+pub(crate) fn supercell_sites(positions: &[Transform2], n: usize, m: usize) -> Vec<OccupiedSite> {
+    let centre = Transform2::new(PI, (0., 0.));
+    let offset = Transform2::new(-PI, (0., 0.));
+    iproduct!(0..n, 0..m, positions.iter())
+        .map(|(i, j, transform)| {
+            let position = transform.position();
+            let position = Point2::new(
+                (position.x + i as f64 - (n as f64 - 1.) / 2.) / n as f64,
+                (position.y + j as f64 - (m as f64 - 1.) / 2.) / m as f64,
+            );
+            let wyckoff = WyckoffSite {
+                letter: 'a',
+                symmetries: vec![transform.set_position(position) * offset],
+                num_rotations: 1,
+                mirror_primary: false,
+                mirror_secondary: false,
+            };
+            OccupiedSite::from_transform(&wyckoff, centre)
+        })
+        .collect()
+}
+
+/// Check for intersections between shapes placed within a periodic cell
+///
+/// Each shape is paired with its relative position in the cell, allowing every site to have a
+/// different shape. This checks for intersections between any of the shapes within the current
+/// cell and the neighbouring cells. Checking the neighbouring cells ensures there are no
+/// intersections when tiling space.
+///
+pub(crate) fn check_intersection<S>(cell: &Cell2, placed: &[(&S, Transform2)]) -> bool
+where
+    S: Shape + Intersect,
+{
+    let periodic_range = match (cell.a() / cell.b(), cell.angle()) {
+        (p, a) if 0.5 < p && p < 2. && f64::abs(a - PI / 2.) < 0.2 => 1,
+        (p, a) if 0.3 < p && p < 3. && f64::abs(a - PI / 2.) < 0.5 => 2,
+        _ => 3,
+    };
+    let shapes: Vec<(S, Transform2)> = placed
+        .iter()
+        .map(|(shape, position)| {
+            let transform = cell.to_cartesian_isometry(*position);
+            (shape.transform(&transform), transform)
+        })
+        .collect();
+
+    // Compare within the current cell
+    for (index, (shape1, _)) in shapes.iter().enumerate() {
+        for (shape2, _) in shapes.iter().skip(index + 1) {
+            if shape1.intersects(shape2) {
+                return true;
+            }
+        }
+    }
+
+    // Compare in periodic cells
+    for ((template1, _), (shape1, transform1)) in placed.iter().zip(shapes.iter()) {
+        for (template2, position2) in placed.iter() {
+            let radius = template1.enclosing_radius() + template2.enclosing_radius();
+            for transform2 in cell.periodic_images(*position2, periodic_range, false) {
+                let distance = (transform1.position() - transform2.position()).norm_squared();
+                if distance <= radius.powi(2) {
+                    let shape2 = template2.transform(&transform2);
+                    if shape1.intersects(&shape2) {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+    false
+}
@@ -7,9 +7,7 @@
 #![allow(clippy::type_repetition_in_bounds)]
 
 use std::cmp::Ordering;
-use std::f64::consts::PI;
 use std::fmt::Write;
-use std::ops::Mul;
 
 use anyhow::Error;
 use log::debug;
@@ -22,6 +20,7 @@ use crate::{Basis, Cell2, OccupiedSite, Transform2};
 pub type PackedState2<S> = PackedState<S>;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub struct PackedState<S>
 where
     S: Shape + Intersect,
@@ -78,11 +77,8 @@ where
     }
 
     fn score(&self) -> Option<f64> {
-        if self.check_intersection() {
-            None
-        } else {
-            Some((self.shape.area() * self.total_shapes() as f64) / self.cell.area())
-        }
+        self.packing_fraction()
+            .map(|fraction| fraction - Packed::internal_energy(self))
     }
 
     fn generate_basis(&self) -> Vec<Basis> {
@@ -91,9 +87,27 @@ where
         for site in self.occupied_sites.iter() {
             basis.append(&mut site.get_basis());
         }
+        basis.append(&mut self.shape.internal_basis());
+        basis
+    }
+
+    fn generate_expanding_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.cell.get_expanding_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis.append(&mut self.shape.internal_basis());
         basis
     }
 
+    fn basis_blocks(&self) -> Vec<usize> {
+        std::iter::once(self.cell.get_degrees_of_freedom().len())
+            .chain(self.occupied_sites.iter().map(|s| s.get_basis().len()))
+            .chain(Some(self.shape.internal_basis().len()).filter(|&n| n > 0))
+            .collect()
+    }
+
     fn as_positions(&self) -> Result<String, Error> {
         let mut output = String::new();
         writeln!(&mut output, "{}", self.cell)?;
@@ -105,6 +119,45 @@ where
         Ok(output)
     }
 }
+impl<S> Packed for PackedState<S>
+where
+    S: Shape + Intersect,
+{
+    fn packing_fraction(&self) -> Option<f64> {
+        if self.check_intersection() {
+            None
+        } else {
+            Some((self.shape.area() * self.total_shapes() as f64) / self.cell.area())
+        }
+    }
+
+    fn internal_energy(&self) -> f64 {
+        self.shape.internal_energy()
+    }
+}
+
+impl<S> Supercell for PackedState<S>
+where
+    S: Shape + Intersect,
+{
+    fn supercell(&self, n: usize, m: usize) -> Self {
+        PackedState {
+            wallpaper: super::supercell_wallpaper(),
+            shape: self.shape.clone(),
+            cell: self.cell.supercell(n, m),
+            occupied_sites: super::supercell_sites(&self.fractional_positions(), n, m),
+        }
+    }
+
+    fn cell(&self) -> &Cell2 {
+        &self.cell
+    }
+
+    fn fractional_positions(&self) -> Vec<Transform2> {
+        self.relative_positions().collect()
+    }
+}
+
 impl<S> PackedState<S>
 where
     S: Shape + Intersect,
@@ -121,56 +174,26 @@ where
     /// Check for intersections of shapes in the current state.
     ///
     /// This checks for intersections between any shapes, checking all occupied sites and their
-    /// symmetry defined copies for the current cell and the neighbouring cells. Checking the
-    /// neighbouring cells ensures there are no intersections of when tiling space.
+    /// symmetry defined copies for the current cell and the neighbouring cells.
     ///
     fn check_intersection(&self) -> bool {
-        let periodic_range = match (self.cell.a() / self.cell.b(), self.cell.angle()) {
-            (p, a) if 0.5 < p && p < 2. && f64::abs(a - PI / 2.) < 0.2 => 1,
-            (p, a) if 0.3 < p && p < 3. && f64::abs(a - PI / 2.) < 0.5 => 2,
-            _ => 3,
-        };
-        // Compare within the current cell
-        for (index, shape1) in self
-            .cartesian_positions()
-            .map(|p| self.shape.transform(&p))
-            .enumerate()
-        {
-            for shape2 in self
-                .cartesian_positions()
-                .map(|p| self.shape.transform(&p))
-                .skip(index + 1)
-            {
-                if shape1.intersects(&shape2) {
-                    return true;
-                }
-            }
-        }
-
-        let radius_sq = self.shape.enclosing_radius().mul(2.).powi(2);
-        // Compare in periodic cells
-        for transform1 in self.cartesian_positions() {
-            let shape1 = self.shape.transform(&transform1);
-            for position in self.relative_positions() {
-                for transform2 in self.cell.periodic_images(position, periodic_range, false) {
-                    let distance = (transform1.position() - transform2.position()).norm_squared();
-                    if distance <= radius_sq {
-                        let shape2 = self.shape.transform(&transform2);
-                        if shape1.intersects(&shape2) {
-                            return true;
-                        }
-                    }
-                }
-            }
-        }
-        false
+        let placed: Vec<(&S, Transform2)> = self
+            .relative_positions()
+            .map(|position| (&self.shape, position))
+            .collect();
+        super::check_intersection(&self.cell, &placed)
     }
 
+    /// Create a state with the shape on each of the Wyckoff sites
+    ///
+    /// This fails when the shape doesn't have the symmetry required by one of the sites.
+    ///
     pub fn initialise(
         shape: S,
         wallpaper: Wallpaper,
         isopointal: &[WyckoffSite],
-    ) -> PackedState<S> {
+    ) -> Result<PackedState<S>, Error> {
+        super::check_sites(&shape, isopointal)?;
         let num_shapes = isopointal.iter().fold(0, |acc, x| acc + x.multiplicity());
         let max_cell_size = 4. * shape.enclosing_radius() * num_shapes as f64;
 
@@ -178,20 +201,24 @@ where
 
         debug!("Cell: {:?}", cell);
 
-        let occupied_sites: Vec<_> = isopointal.iter().map(OccupiedSite::from_wyckoff).collect();
+        let rotations = shape.rotational_symmetries();
+        let occupied_sites: Vec<_> = isopointal
+            .iter()
+            .map(|w| OccupiedSite::from_wyckoff(w).with_rotations(rotations))
+            .collect();
 
-        PackedState {
+        Ok(PackedState {
             wallpaper,
             shape,
             cell,
             occupied_sites,
-        }
+        })
     }
 
     pub fn from_group(shape: S, group: &WallpaperGroup) -> Result<Self, Error> {
         let wallpaper = Wallpaper::new(group);
         let isopointal = &[WyckoffSite::new(group)?];
-        Ok(Self::initialise(shape, wallpaper, isopointal))
+        Self::initialise(shape, wallpaper, isopointal)
     }
 }
 
@@ -200,6 +227,7 @@ mod packed_state_tests {
     use super::*;
     use crate::{CrystalFamily, LineShape, Transform2};
     use approx::assert_abs_diff_eq;
+    use nalgebra::Point2;
 
     fn create_square() -> LineShape {
         LineShape::from_radial("Square", vec![1., 1., 1., 1.]).unwrap()
@@ -242,6 +270,40 @@ mod packed_state_tests {
         (wallpaper, isopointal)
     }
 
+    /// The special site of p2 on a two-fold rotation axis
+    fn create_wallpaper_p2_special() -> (Wallpaper, Vec<WyckoffSite>) {
+        let wallpaper = Wallpaper {
+            name: String::from("p2"),
+            family: CrystalFamily::Monoclinic,
+        };
+        let isopointal = vec![WyckoffSite {
+            letter: 'a',
+            symmetries: vec![Transform2::from_operations("x,y").unwrap()],
+            num_rotations: 2,
+            mirror_primary: false,
+            mirror_secondary: false,
+        }];
+
+        (wallpaper, isopointal)
+    }
+
+    /// The special site of pm on a mirror line
+    fn create_wallpaper_pm_mirror() -> (Wallpaper, Vec<WyckoffSite>) {
+        let wallpaper = Wallpaper {
+            name: String::from("pm"),
+            family: CrystalFamily::Orthorhombic,
+        };
+        let isopointal = vec![WyckoffSite {
+            letter: 'a',
+            symmetries: vec![Transform2::from_operations("x,y").unwrap()],
+            num_rotations: 1,
+            mirror_primary: true,
+            mirror_secondary: false,
+        }];
+
+        (wallpaper, isopointal)
+    }
+
     fn init_packed_state(group: &str) -> PackedState<LineShape> {
         let square: LineShape = create_square();
 
@@ -251,7 +313,41 @@ mod packed_state_tests {
             _ => None,
         })
         .unwrap();
-        PackedState::initialise(square, wallpaper, &isopointal)
+        PackedState::initialise(square, wallpaper, &isopointal).unwrap()
+    }
+
+    #[test]
+    fn special_site_allowed() {
+        let (wallpaper, isopointal) = create_wallpaper_p2_special();
+        // A square has the two-fold rotation of the site
+        let square = LineShape::polygon(4).unwrap();
+        assert!(PackedState::initialise(square, wallpaper, &isopointal).is_ok());
+    }
+
+    #[test]
+    fn special_site_refused() {
+        let (wallpaper, isopointal) = create_wallpaper_p2_special();
+        // An equilateral triangle doesn't have the two-fold rotation of the site
+        let triangle = LineShape::polygon(3).unwrap();
+        assert!(PackedState::initialise(triangle, wallpaper, &isopointal).is_err());
+    }
+
+    #[test]
+    fn mirror_site_refuses_chiral_shape() {
+        let (wallpaper, isopointal) = create_wallpaper_pm_mirror();
+        // A kite is symmetric about the mirror line
+        let kite = LineShape::from_radial("Kite", vec![1., 0.5, 2., 0.5]).unwrap();
+        assert!(PackedState::initialise(kite, wallpaper.clone(), &isopointal).is_ok());
+        // A scalene triangle is chiral, so can't be its own mirror image
+        let vertices = [
+            Point2::new(-1., -1.),
+            Point2::new(2., -1.),
+            Point2::new(-1., 1.),
+        ];
+        let scalene = LineShape::from_vertices("Scalene", &vertices).unwrap();
+        assert!(scalene.is_chiral());
+        let error = PackedState::initialise(scalene, wallpaper, &isopointal).unwrap_err();
+        assert!(error.to_string().contains("D1"), "{}", error);
     }
 
     #[test]
@@ -277,4 +373,12 @@ mod packed_state_tests {
         let state = init_packed_state("p2mg");
         assert_abs_diff_eq!(state.score().unwrap(), 1. / 32.);
     }
+
+    #[test]
+    fn angle_range_from_symmetry() {
+        let state = init_packed_state("p1");
+        let basis = state.generate_basis();
+        let angle = basis.iter().find(|b| b.is_angle()).unwrap();
+        assert_abs_diff_eq!(angle.bounds().1, std::f64::consts::PI / 2.);
+    }
 }
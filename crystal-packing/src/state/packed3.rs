@@ -0,0 +1,292 @@
+//
+// packed3.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+#![allow(clippy::type_repetition_in_bounds)]
+
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+use anyhow::Error;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::space_group::{Space, SpaceGroup, WyckoffSite3};
+use crate::traits::*;
+use crate::{Basis, Cell3, OccupiedSite3, Transform3};
+
+/// A three dimensional crystal of rigid shapes
+///
+/// This is the three dimensional counterpart of the [`PackedState`], where the shapes occupy
+/// the general position of one of the space groups. The score is the fraction of the volume of
+/// the unit cell filled by the shapes.
+///
+/// ```
+/// use std::convert::TryInto;
+/// use crystal_packing::space_group::SpaceGroups;
+/// use crystal_packing::traits::State;
+/// use crystal_packing::{MolecularShape3, PackedState3, SpaceGroup};
+/// let group: SpaceGroup = SpaceGroups::P21_c.try_into().unwrap();
+/// let state = PackedState3::from_group(MolecularShape3::sphere(), &group).unwrap();
+/// assert_eq!(state.total_shapes(), 4);
+/// assert!(state.score().is_some());
+/// ```
+///
+/// [`PackedState`]: crate::PackedState
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PackedState3<S>
+where
+    S: Shape3 + Intersect3,
+{
+    pub space_group: Space,
+    pub shape: S,
+    pub cell: Cell3,
+    occupied_sites: Vec<OccupiedSite3>,
+}
+
+impl<S> Eq for PackedState3<S> where S: Shape3 + Intersect3 {}
+
+impl<S> PartialEq for PackedState3<S>
+where
+    S: Shape3 + Intersect3,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.eq(&o),
+            (_, _) => false,
+        }
+    }
+}
+
+impl<S> PartialOrd for PackedState3<S>
+where
+    S: Shape3 + Intersect3,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.partial_cmp(&o),
+            (_, _) => None,
+        }
+    }
+}
+
+impl<S> Ord for PackedState3<S>
+where
+    S: Shape3 + Intersect3,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other).unwrap()
+    }
+}
+
+impl<S> State for PackedState3<S>
+where
+    S: Shape3 + Intersect3,
+{
+    fn total_shapes(&self) -> usize {
+        self.occupied_sites
+            .iter()
+            .fold(0, |sum, site| sum + site.multiplicity())
+    }
+
+    fn score(&self) -> Option<f64> {
+        let volume = self.cell.volume();
+        // Angles which don't form a cell give a volume of NaN
+        if volume.is_nan() || volume <= 0. || self.check_intersection() {
+            None
+        } else {
+            Some((self.shape.volume() * self.total_shapes() as f64) / volume)
+        }
+    }
+
+    fn generate_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.cell.get_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis
+    }
+
+    fn generate_expanding_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.cell.get_expanding_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis
+    }
+
+    fn basis_blocks(&self) -> Vec<usize> {
+        std::iter::once(self.cell.get_degrees_of_freedom().len())
+            .chain(self.occupied_sites.iter().map(|s| s.get_basis().len()))
+            .collect()
+    }
+
+    fn as_positions(&self) -> Result<String, Error> {
+        let mut output = String::new();
+        writeln!(&mut output, "{}", self.cell)?;
+        writeln!(&mut output, "Positions")?;
+
+        for transform in self.cartesian_positions() {
+            writeln!(&mut output, "{:?}", transform)?;
+        }
+        Ok(output)
+    }
+}
+
+impl<S> PackedState3<S>
+where
+    S: Shape3 + Intersect3,
+{
+    pub fn cartesian_positions(&self) -> impl Iterator<Item = Transform3> + '_ {
+        self.relative_positions()
+            .map(move |position| self.cell.to_cartesian_isometry(position))
+    }
+
+    pub fn relative_positions(&self) -> impl Iterator<Item = Transform3> + '_ {
+        self.occupied_sites
+            .iter()
+            .flat_map(OccupiedSite3::positions)
+    }
+
+    /// Check for intersections of shapes in the current state.
+    ///
+    /// The number of periodic images to check along each axis is the number of planes of
+    /// lattice points within the diameter of the enclosing sphere of a shape.
+    ///
+    fn check_intersection(&self) -> bool {
+        let diameter = 2. * self.shape.enclosing_radius();
+        let spacing = self.cell.plane_spacing();
+        let shells = |s: f64| f64::max((diameter / s).ceil(), 1.) as i64;
+        let periodic_range = [shells(spacing.x), shells(spacing.y), shells(spacing.z)];
+
+        let shapes: Vec<(Transform3, S)> = self
+            .cartesian_positions()
+            .map(|p| (p, self.shape.transform(&p)))
+            .collect();
+
+        // Compare within the current cell
+        for (index, (_, shape1)) in shapes.iter().enumerate() {
+            for (_, shape2) in shapes.iter().skip(index + 1) {
+                if shape1.intersects(shape2) {
+                    return true;
+                }
+            }
+        }
+
+        let radius_sq = diameter.powi(2);
+        // Compare in periodic cells
+        for (transform1, shape1) in shapes.iter() {
+            for position in self.relative_positions() {
+                for transform2 in self.cell.periodic_images(position, periodic_range, false) {
+                    let distance = (transform1.position() - transform2.position()).norm_squared();
+                    if distance <= radius_sq
+                        && shape1.intersects(&self.shape.transform(&transform2))
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    pub fn initialise(
+        shape: S,
+        space_group: Space,
+        isopointal: &[WyckoffSite3],
+    ) -> PackedState3<S> {
+        let num_shapes = isopointal.iter().fold(0, |acc, x| acc + x.multiplicity());
+        let max_cell_size = 4. * shape.enclosing_radius() * num_shapes as f64;
+
+        let cell = Cell3::from_system(space_group.system, max_cell_size);
+
+        debug!("Cell: {:?}", cell);
+
+        let occupied_sites: Vec<_> = isopointal.iter().map(OccupiedSite3::from_wyckoff).collect();
+
+        PackedState3 {
+            space_group,
+            shape,
+            cell,
+            occupied_sites,
+        }
+    }
+
+    pub fn from_group(shape: S, group: &SpaceGroup) -> Result<Self, Error> {
+        let space_group = Space::new(group);
+        let isopointal = &[WyckoffSite3::new(group)?];
+        Ok(Self::initialise(shape, space_group, isopointal))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::space_group::SpaceGroups;
+    use crate::{MCOptimiser, MolecularShape3, Polyhedron};
+
+    fn create_state(name: &str, shape: MolecularShape3) -> PackedState3<MolecularShape3> {
+        let group: SpaceGroup = name.parse::<SpaceGroups>().unwrap().try_into().unwrap();
+        PackedState3::from_group(shape, &group).unwrap()
+    }
+
+    #[test]
+    fn initially_valid() {
+        for name in SpaceGroups::variants() {
+            let state = create_state(name, MolecularShape3::from_dimer(0.7, 1.2));
+            assert!(state.score().is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn basis_size() {
+        let state = create_state("P21/c", MolecularShape3::sphere());
+        // Monoclinic cell with 4 values, and the position and quaternion of a single site
+        assert_eq!(state.generate_basis().len(), 4 + 7);
+        assert_eq!(state.total_shapes(), 4);
+    }
+
+    #[test]
+    fn simple_cubic() {
+        let state = create_state("P1", MolecularShape3::sphere());
+        let basis = state.generate_basis();
+        basis[0].set_value(2.).unwrap();
+        basis[1].set_value(1.).unwrap();
+        basis[2].set_value(1.).unwrap();
+        // A sphere in a cube with the same width has a packing fraction of π/6
+        assert_abs_diff_eq!(
+            state.score().unwrap(),
+            std::f64::consts::PI / 6.,
+            epsilon = 1e-12
+        );
+        basis[0].set_value(1.99).unwrap();
+        assert_eq!(state.score(), None);
+    }
+
+    #[test]
+    fn optimise_dimers() {
+        let state = create_state("P-1", MolecularShape3::from_dimer(0.8, 1.));
+        let initial = state.score().unwrap();
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+        assert!(state.score().unwrap() > 10. * initial);
+    }
+
+    #[test]
+    fn optimise_polyhedra() {
+        let group: SpaceGroup = SpaceGroups::P_1.try_into().unwrap();
+        let shape = Polyhedron::named("octahedron").unwrap();
+        let state = PackedState3::from_group(shape, &group).unwrap();
+        let initial = state.score().unwrap();
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+        assert!(state.score().unwrap() > 10. * initial);
+    }
+}
@@ -0,0 +1,417 @@
+//
+// polydisperse.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use anyhow::{bail, Error};
+use log::debug;
+use rand::Rng;
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+
+use crate::traits::*;
+use crate::wallpaper::{Wallpaper, WallpaperGroup, WyckoffSite};
+use crate::{Basis, Cell2, OccupiedSite, Transform2};
+
+/// A crystal structure where the shape on each occupied site has a different size
+///
+/// Every shape is a scaled copy of the same template `shape`, with each of the occupied sites
+/// having the scale in the same position of `sizes`. All the symmetry defined copies of a
+/// site have the same size.
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", try_from = "UncheckedSizes<S>")]
+pub struct PolydisperseState<S>
+where
+    S: Shape + Intersect + Scale,
+{
+    pub wallpaper: Wallpaper,
+    pub shape: S,
+    pub cell: Cell2,
+    sizes: Vec<f64>,
+    occupied_sites: Vec<OccupiedSite>,
+}
+
+/// A [`PolydisperseState`] as it is read, before the sizes are checked against the sites
+#[derive(Deserialize)]
+struct UncheckedSizes<S> {
+    wallpaper: Wallpaper,
+    shape: S,
+    cell: Cell2,
+    sizes: Vec<f64>,
+    occupied_sites: Vec<OccupiedSite>,
+}
+
+impl<S> TryFrom<UncheckedSizes<S>> for PolydisperseState<S>
+where
+    S: Shape + Intersect + Scale,
+{
+    type Error = Error;
+
+    fn try_from(value: UncheckedSizes<S>) -> Result<Self, Self::Error> {
+        check_sizes(&value.sizes)?;
+        if value.sizes.len() != value.occupied_sites.len() {
+            bail!(
+                "There needs to be a size for each of the {} occupied sites, got {} sizes",
+                value.occupied_sites.len(),
+                value.sizes.len()
+            );
+        }
+        Ok(Self {
+            wallpaper: value.wallpaper,
+            shape: value.shape,
+            cell: value.cell,
+            sizes: value.sizes,
+            occupied_sites: value.occupied_sites,
+        })
+    }
+}
+
+/// Check there is at least one size, with every size being positive
+fn check_sizes(sizes: &[f64]) -> Result<(), Error> {
+    if sizes.is_empty() {
+        bail!("There needs to be at least one occupied site");
+    }
+    if let Some(size) = sizes.iter().find(|s| !(s.is_finite() && **s > 0.)) {
+        bail!("The size of each site needs to be positive, got {}", size);
+    }
+    Ok(())
+}
+
+impl<S> Eq for PolydisperseState<S> where S: Shape + Intersect + Scale {}
+
+impl<S> PartialEq for PolydisperseState<S>
+where
+    S: Shape + Intersect + Scale,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.eq(&o),
+            (_, _) => false,
+        }
+    }
+}
+
+impl<S> PartialOrd for PolydisperseState<S>
+where
+    S: Shape + Intersect + Scale,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.partial_cmp(&o),
+            (_, _) => None,
+        }
+    }
+}
+
+impl<S> Ord for PolydisperseState<S>
+where
+    S: Shape + Intersect + Scale,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other).unwrap()
+    }
+}
+
+impl<S> State for PolydisperseState<S>
+where
+    S: Shape + Intersect + Scale,
+{
+    fn total_shapes(&self) -> usize {
+        self.occupied_sites
+            .iter()
+            .fold(0, |sum, site| sum + site.multiplicity())
+    }
+
+    fn score(&self) -> Option<f64> {
+        self.packing_fraction()
+            .map(|fraction| fraction - Packed::internal_energy(self))
+    }
+
+    fn generate_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.cell.get_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis.append(&mut self.shape.internal_basis());
+        basis
+    }
+
+    fn generate_expanding_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.cell.get_expanding_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis.append(&mut self.shape.internal_basis());
+        basis
+    }
+
+    fn basis_blocks(&self) -> Vec<usize> {
+        std::iter::once(self.cell.get_degrees_of_freedom().len())
+            .chain(self.occupied_sites.iter().map(|s| s.get_basis().len()))
+            .chain(Some(self.shape.internal_basis().len()).filter(|&n| n > 0))
+            .collect()
+    }
+
+    fn as_positions(&self) -> Result<String, Error> {
+        let mut output = String::new();
+        writeln!(&mut output, "{}", self.cell)?;
+        writeln!(&mut output, "Positions")?;
+
+        for (transform, size) in self.sized_positions() {
+            writeln!(&mut output, "{:?} {}", transform, size)?;
+        }
+        Ok(output)
+    }
+}
+
+impl<S> Packed for PolydisperseState<S>
+where
+    S: Shape + Intersect + Scale,
+{
+    fn packing_fraction(&self) -> Option<f64> {
+        if self.check_intersection() {
+            None
+        } else {
+            let area: f64 = self
+                .shapes()
+                .iter()
+                .zip(self.occupied_sites.iter())
+                .map(|(shape, site)| shape.area() * site.multiplicity() as f64)
+                .sum();
+            Some(area / self.cell.area())
+        }
+    }
+
+    fn internal_energy(&self) -> f64 {
+        self.shape.internal_energy()
+    }
+}
+
+impl<S> PolydisperseState<S>
+where
+    S: Shape + Intersect + Scale,
+{
+    /// The size of the shape on each of the occupied sites
+    pub fn sizes(&self) -> &[f64] {
+        &self.sizes
+    }
+
+    /// The shape on each of the occupied sites, scaled to the size of the site
+    pub fn shapes(&self) -> Vec<S> {
+        self.sizes.iter().map(|&s| self.shape.scale(s)).collect()
+    }
+
+    pub fn cartesian_positions(&self) -> impl Iterator<Item = Transform2> + '_ {
+        self.relative_positions()
+            .map(move |position| self.cell.to_cartesian_isometry(position))
+    }
+
+    pub fn relative_positions(&self) -> impl Iterator<Item = Transform2> + '_ {
+        self.occupied_sites.iter().flat_map(OccupiedSite::positions)
+    }
+
+    /// The relative position of every shape along with the index of its occupied site
+    pub fn site_positions(&self) -> impl Iterator<Item = (usize, Transform2)> + '_ {
+        self.occupied_sites
+            .iter()
+            .enumerate()
+            .flat_map(|(index, site)| site.positions().map(move |p| (index, p)))
+    }
+
+    /// The relative position of every shape along with its size
+    pub fn sized_positions(&self) -> impl Iterator<Item = (Transform2, f64)> + '_ {
+        self.occupied_sites
+            .iter()
+            .zip(self.sizes.iter())
+            .flat_map(|(site, &size)| site.positions().map(move |p| (p, size)))
+    }
+
+    /// Check for intersections of shapes in the current state.
+    ///
+    /// This is the same as for a [`PackedState`](crate::PackedState), with each shape being
+    /// the scaled copy for the site it belongs to.
+    ///
+    fn check_intersection(&self) -> bool {
+        let shapes = self.shapes();
+        let placed: Vec<(&S, Transform2)> = self
+            .site_positions()
+            .map(|(site, position)| (&shapes[site], position))
+            .collect();
+        super::check_intersection(&self.cell, &placed)
+    }
+
+    /// Create a state with a copy of the general Wyckoff site for each of the `sizes`
+    ///
+    /// The sites start spread along the x axis of the cell so none of the shapes overlap.
+    ///
+    pub fn initialise(
+        shape: S,
+        wallpaper: Wallpaper,
+        wyckoff: &WyckoffSite,
+        sizes: &[f64],
+    ) -> Result<PolydisperseState<S>, Error> {
+        check_sizes(sizes)?;
+        let num_shapes = wyckoff.multiplicity() * sizes.len();
+        let largest = sizes.iter().cloned().fold(0., f64::max);
+        let max_cell_size = 4. * largest * shape.enclosing_radius() * num_shapes as f64;
+
+        let cell = Cell2::from_family(wallpaper.family, max_cell_size);
+
+        debug!("Cell: {:?}", cell);
+
+        let rotations = shape.rotational_symmetries();
+        let y = -0.5 + 0.5 / wyckoff.multiplicity() as f64;
+        let occupied_sites = (0..sizes.len())
+            .map(|index| {
+                let x = -0.5 + (index as f64 + 0.5) / num_shapes as f64;
+                OccupiedSite::from_transform(wyckoff, Transform2::new(0., (x, y)))
+                    .with_rotations(rotations)
+            })
+            .collect();
+
+        Ok(PolydisperseState {
+            wallpaper,
+            shape,
+            cell,
+            sizes: sizes.to_vec(),
+            occupied_sites,
+        })
+    }
+
+    pub fn from_group(shape: S, group: &WallpaperGroup, sizes: &[f64]) -> Result<Self, Error> {
+        let wallpaper = Wallpaper::new(group);
+        let wyckoff = WyckoffSite::new(group)?;
+        Self::initialise(shape, wallpaper, &wyckoff, sizes)
+    }
+}
+
+/// The sizes of `sites` shapes drawn from a normal distribution with a mean of 1
+///
+/// The `polydispersity` is the standard deviation of the distribution relative to the mean.
+/// Sizes which are not positive are drawn again, so the distribution is truncated at 0.
+///
+/// ```
+/// use rand::SeedableRng;
+/// use crystal_packing::normal_sizes;
+/// let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(0);
+/// let sizes = normal_sizes(4, 0.05, &mut rng).unwrap();
+/// assert_eq!(sizes.len(), 4);
+/// ```
+///
+pub fn normal_sizes<R: Rng + ?Sized>(
+    sites: usize,
+    polydispersity: f64,
+    rng: &mut R,
+) -> Result<Vec<f64>, Error> {
+    if !(polydispersity.is_finite() && polydispersity >= 0.) {
+        bail!(
+            "The polydispersity needs to be a positive number, got {}",
+            polydispersity
+        );
+    }
+    Ok((0..sites)
+        .map(|_| loop {
+            let size = 1. + polydispersity * rng.sample::<f64, _>(StandardNormal);
+            if size > 0. {
+                break size;
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+    use std::f64::consts::PI;
+
+    use approx::assert_abs_diff_eq;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    use super::*;
+    use crate::wallpaper::WallpaperGroups;
+    use crate::{LineShape, MCOptimiser, MolecularShape2};
+
+    fn p2() -> WallpaperGroup<'static> {
+        WallpaperGroups::p2.try_into().unwrap()
+    }
+
+    #[test]
+    fn packing_fraction_with_sizes() {
+        let state =
+            PolydisperseState::from_group(MolecularShape2::circle(), &p2(), &[1., 0.5]).unwrap();
+        assert_eq!(state.total_shapes(), 4);
+        let area = 2. * PI * (1. + 0.25);
+        assert_abs_diff_eq!(state.score().unwrap(), area / state.cell.area());
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        let shape = MolecularShape2::circle();
+        assert!(PolydisperseState::from_group(shape.clone(), &p2(), &[]).is_err());
+        assert!(PolydisperseState::from_group(shape, &p2(), &[1., -0.5]).is_err());
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        assert!(normal_sizes(4, -0.1, &mut rng).is_err());
+    }
+
+    #[test]
+    fn read_mismatched_sizes() {
+        let state =
+            PolydisperseState::from_group(MolecularShape2::circle(), &p2(), &[1., 0.5]).unwrap();
+        let mut value = serde_json::to_value(&state).unwrap();
+        assert_eq!(value["kind"], "PolydisperseState");
+        let read: PolydisperseState<MolecularShape2> =
+            serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(read.sizes(), state.sizes());
+
+        value["sizes"] = serde_json::json!([1.]);
+        assert!(
+            serde_json::from_value::<PolydisperseState<MolecularShape2>>(value.clone()).is_err()
+        );
+        value["sizes"] = serde_json::json!([1., -0.5]);
+        assert!(serde_json::from_value::<PolydisperseState<MolecularShape2>>(value).is_err());
+    }
+
+    #[test]
+    fn normal_sizes_distribution() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        let sizes = normal_sizes(10000, 0.05, &mut rng).unwrap();
+        let mean = sizes.iter().sum::<f64>() / sizes.len() as f64;
+        let variance = sizes.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / sizes.len() as f64;
+        assert_abs_diff_eq!(mean, 1., epsilon = 2e-3);
+        assert_abs_diff_eq!(variance.sqrt(), 0.05, epsilon = 2e-3);
+        assert_eq!(normal_sizes(3, 0., &mut rng).unwrap(), vec![1.; 3]);
+    }
+
+    #[test]
+    fn intersection_uses_sizes() {
+        let state =
+            PolydisperseState::from_group(MolecularShape2::circle(), &p2(), &[1., 1.]).unwrap();
+        // Shrink the cell until the shapes overlap with the larger size but not the smaller
+        for basis in state.generate_basis().iter().filter(|b| b.is_cell()) {
+            basis.set_value(basis.get_value() / 1.6).unwrap();
+        }
+        assert!(state.score().is_some());
+        let mut larger = state.clone();
+        larger.sizes = vec![1., 3.];
+        assert!(larger.score().is_none());
+    }
+
+    #[test]
+    fn optimise_polydisperse_squares() {
+        let shape = LineShape::polygon(4).unwrap();
+        let state = PolydisperseState::from_group(shape, &p2(), &[1., 0.8]).unwrap();
+        assert!(state.score().is_some());
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 10000, 200, 0, None).optimise_state(state);
+        let score = state.score().unwrap();
+        assert!(score > 0.5, "{}", score);
+    }
+}
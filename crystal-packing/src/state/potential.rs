@@ -14,13 +14,14 @@ use anyhow::Error;
 use log::debug;
 use serde::{Deserialize, Serialize};
 
-use crate::traits::{Potential, Shape, State};
+use crate::traits::{Potential, Shape, State, Supercell};
 use crate::wallpaper::{Wallpaper, WallpaperGroup, WyckoffSite};
 use crate::{Basis, Cell2, OccupiedSite, Transform2};
 
 pub type PotentialState2<S> = PotentialState<S>;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub struct PotentialState<S>
 where
     S: Shape + Potential,
@@ -62,7 +63,7 @@ where
     S: Shape + Potential,
 {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(&other).unwrap()
+        self.partial_cmp(other).unwrap()
     }
 }
 
@@ -76,9 +77,27 @@ where
         for site in self.occupied_sites.iter() {
             basis.append(&mut site.get_basis());
         }
+        basis.append(&mut self.shape.internal_basis());
         basis
     }
 
+    fn generate_expanding_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.cell.get_expanding_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis.append(&mut self.shape.internal_basis());
+        basis
+    }
+
+    fn basis_blocks(&self) -> Vec<usize> {
+        std::iter::once(self.cell.get_degrees_of_freedom().len())
+            .chain(self.occupied_sites.iter().map(|s| s.get_basis().len()))
+            .chain(Some(self.shape.internal_basis().len()).filter(|&n| n > 0))
+            .collect()
+    }
+
     fn score(&self) -> Option<f64> {
         let mut sum = 0.;
 
@@ -111,7 +130,7 @@ where
         }
         // We want to minimize the potential energy, so the score we want to maximize is the
         // negation of the potential energy.
-        Some(-sum / self.total_shapes() as f64)
+        Some(-sum / self.total_shapes() as f64 - self.shape.internal_energy())
     }
 
     fn total_shapes(&self) -> usize {
@@ -132,6 +151,28 @@ where
     }
 }
 
+impl<S> Supercell for PotentialState<S>
+where
+    S: Shape + Potential,
+{
+    fn supercell(&self, n: usize, m: usize) -> Self {
+        PotentialState {
+            wallpaper: super::supercell_wallpaper(),
+            shape: self.shape.clone(),
+            cell: self.cell.supercell(n, m),
+            occupied_sites: super::supercell_sites(&self.fractional_positions(), n, m),
+        }
+    }
+
+    fn cell(&self) -> &Cell2 {
+        &self.cell
+    }
+
+    fn fractional_positions(&self) -> Vec<Transform2> {
+        self.relative_positions().collect()
+    }
+}
+
 impl<S> PotentialState<S>
 where
     S: Shape + Potential,
@@ -145,17 +186,52 @@ where
         self.occupied_sites.iter().flat_map(OccupiedSite::positions)
     }
 
+    /// The potential energy of the crystal per unit cell
+    ///
+    /// Each interaction between a shape and a periodic image is shared equally between the two
+    /// unit cells, so this is the energy of the infinite crystal divided by the number of cells.
+    /// Where the potential isn't symmetric, as for Lennard Jones particles of different sizes,
+    /// each interaction is the mean of the energy in both directions.
+    ///
+    pub fn lattice_energy(&self) -> f64 {
+        let shapes: Vec<S> = self
+            .cartesian_positions()
+            .map(|p| self.shape.transform(&p))
+            .collect();
+        let mut sum = 0.;
+        for (index, shape1) in shapes.iter().enumerate() {
+            for shape2 in shapes.iter().skip(index + 1) {
+                sum += (shape1.energy(shape2) + shape2.energy(shape1)) / 2.;
+            }
+            for position in self.relative_positions() {
+                for shape2 in self
+                    .cell
+                    .periodic_images(position, 3, false)
+                    .map(|p| self.shape.transform(&p))
+                {
+                    sum += shape1.energy(&shape2) / 2.;
+                }
+            }
+        }
+        sum
+    }
+
     pub fn from_group(shape: S, group: &WallpaperGroup) -> Result<Self, Error> {
         let wallpaper = Wallpaper::new(group);
         let isopointal = &[WyckoffSite::new(group)?];
-        Ok(Self::initialise(shape, wallpaper, isopointal))
+        Self::initialise(shape, wallpaper, isopointal)
     }
 
+    /// Create a state with the shape on each of the Wyckoff sites
+    ///
+    /// This fails when the shape doesn't have the symmetry required by one of the sites.
+    ///
     pub fn initialise(
         shape: S,
         wallpaper: Wallpaper,
         isopointal: &[WyckoffSite],
-    ) -> PotentialState<S> {
+    ) -> Result<PotentialState<S>, Error> {
+        super::check_sites(&shape, isopointal)?;
         let num_shapes = isopointal.iter().fold(0, |acc, x| acc + x.multiplicity());
         let max_cell_size = 2. * shape.enclosing_radius() * num_shapes as f64;
 
@@ -163,14 +239,18 @@ where
 
         debug!("Cell: {:?}", cell);
 
-        let occupied_sites: Vec<_> = isopointal.iter().map(OccupiedSite::from_wyckoff).collect();
+        let rotations = shape.rotational_symmetries();
+        let occupied_sites: Vec<_> = isopointal
+            .iter()
+            .map(|w| OccupiedSite::from_wyckoff(w).with_rotations(rotations))
+            .collect();
 
-        PotentialState {
+        Ok(PotentialState {
             wallpaper,
             shape,
             cell,
             occupied_sites,
-        }
+        })
     }
 }
 
@@ -225,7 +305,7 @@ mod packed_state_tests {
             _ => None,
         })
         .unwrap();
-        PotentialState::initialise(circle, wallpaper, &isopointal)
+        PotentialState::initialise(circle, wallpaper, &isopointal).unwrap()
     }
 
     #[test]
@@ -0,0 +1,513 @@
+//
+// strip.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+#![allow(clippy::type_repetition_in_bounds)]
+
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+use anyhow::Error;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::frieze::{Frieze, FriezeGroup};
+use crate::traits::*;
+use crate::wallpaper::WyckoffSite;
+use crate::{Basis, OccupiedSite, Strip, Transform2};
+
+/// Create the occupied sites of a strip, starting a distance `y` from the centre line
+///
+/// The sites are spread along the strip in the same way as for a unit cell, while starting
+/// near the centre line keeps the shapes away from the walls.
+///
+fn strip_sites(isopointal: &[WyckoffSite], y: f64, rotations: u64) -> Vec<OccupiedSite> {
+    isopointal
+        .iter()
+        .map(|wyckoff| {
+            let x = -0.5 + 0.5 / wyckoff.multiplicity() as f64;
+            OccupiedSite::from_transform(wyckoff, Transform2::new(0., (x, y)))
+                .with_rotations(rotations)
+        })
+        .collect()
+}
+
+/// The distance from the centre line of the initial sites
+///
+/// The sites start on the centre line, unless a mirror along the strip places the copies of a
+/// shape on top of each other, in which case they start a quarter of the width from it.
+///
+const INITIAL_OFFSETS: [f64; 2] = [0., 0.25];
+
+/// A packing of hard shapes confined to a strip by a pair of walls
+///
+/// The strip is periodic along its length, which is minimised, with the symmetry of one of the
+/// frieze groups. The walls are hard, so no part of a shape can cross either of them. The score
+/// is the fraction of the strip covered by the shapes.
+///
+/// ```
+/// # use std::convert::TryInto;
+/// use crystal_packing::frieze::{FriezeGroup, FriezeGroups};
+/// use crystal_packing::{LineShape, MCOptimiser, Optimiser, StripState};
+/// let group: FriezeGroup = FriezeGroups::p2.try_into().unwrap();
+/// let state = StripState::from_group(LineShape::polygon(4).unwrap(), 3., &group).unwrap();
+/// let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+/// assert!(state.strip.width() == 3.);
+/// ```
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub struct StripState<S>
+where
+    S: Shape + Intersect + Support,
+{
+    pub frieze: Frieze,
+    pub shape: S,
+    pub strip: Strip,
+    occupied_sites: Vec<OccupiedSite>,
+}
+
+impl<S> Eq for StripState<S> where S: Shape + Intersect + Support {}
+
+impl<S> PartialEq for StripState<S>
+where
+    S: Shape + Intersect + Support,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.eq(&o),
+            (_, _) => false,
+        }
+    }
+}
+
+impl<S> PartialOrd for StripState<S>
+where
+    S: Shape + Intersect + Support,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.partial_cmp(&o),
+            (_, _) => None,
+        }
+    }
+}
+
+impl<S> Ord for StripState<S>
+where
+    S: Shape + Intersect + Support,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other).unwrap()
+    }
+}
+
+impl<S> State for StripState<S>
+where
+    S: Shape + Intersect + Support,
+{
+    fn total_shapes(&self) -> usize {
+        self.occupied_sites
+            .iter()
+            .fold(0, |sum, site| sum + site.multiplicity())
+    }
+
+    fn score(&self) -> Option<f64> {
+        if self.check_intersection() {
+            None
+        } else {
+            Some(
+                (self.shape.area() * self.total_shapes() as f64) / self.strip.area()
+                    - self.shape.internal_energy(),
+            )
+        }
+    }
+
+    fn generate_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.strip.get_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis.append(&mut self.shape.internal_basis());
+        basis
+    }
+
+    fn generate_expanding_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.strip.get_expanding_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis.append(&mut self.shape.internal_basis());
+        basis
+    }
+
+    fn basis_blocks(&self) -> Vec<usize> {
+        std::iter::once(self.strip.get_degrees_of_freedom().len())
+            .chain(self.occupied_sites.iter().map(|s| s.get_basis().len()))
+            .chain(Some(self.shape.internal_basis().len()).filter(|&n| n > 0))
+            .collect()
+    }
+
+    fn as_positions(&self) -> Result<String, Error> {
+        let mut output = String::new();
+        writeln!(&mut output, "{}", self.strip)?;
+        writeln!(&mut output, "Positions")?;
+
+        for transform in self.cartesian_positions() {
+            writeln!(&mut output, "{:?}", transform)?;
+        }
+        Ok(output)
+    }
+}
+
+impl<S> StripState<S>
+where
+    S: Shape + Intersect + Support,
+{
+    pub fn cartesian_positions(&self) -> impl Iterator<Item = Transform2> + '_ {
+        self.relative_positions()
+            .map(move |position| self.strip.to_cartesian_isometry(position))
+    }
+
+    pub fn relative_positions(&self) -> impl Iterator<Item = Transform2> + '_ {
+        self.occupied_sites.iter().flat_map(OccupiedSite::positions)
+    }
+
+    /// Check for intersections of shapes with each other or with the walls
+    ///
+    /// The periodic images along the strip are included up to the distance at which the
+    /// enclosing circles of two shapes can overlap.
+    ///
+    fn check_intersection(&self) -> bool {
+        let shapes: Vec<S> = self
+            .cartesian_positions()
+            .map(|p| self.shape.transform(&p))
+            .collect();
+
+        // The walls are checked first since this is the cheapest check
+        for shape in shapes.iter() {
+            for (normal, offset) in self.strip.walls().iter() {
+                if shape.support(*normal) > *offset {
+                    return true;
+                }
+            }
+        }
+
+        // Compare within the current strip
+        for (index, shape1) in shapes.iter().enumerate() {
+            for shape2 in shapes.iter().skip(index + 1) {
+                if shape1.intersects(shape2) {
+                    return true;
+                }
+            }
+        }
+
+        let diameter = 2. * self.shape.enclosing_radius();
+        let shells = f64::ceil(diameter / self.strip.length()).max(1.) as i64;
+        // Compare with the periodic images along the strip
+        for (transform1, shape1) in self.cartesian_positions().zip(shapes.iter()) {
+            for position in self.relative_positions() {
+                for transform2 in self.strip.periodic_images(position, shells, false) {
+                    let distance = (transform1.position() - transform2.position()).norm();
+                    if distance <= diameter && shape1.intersects(&self.shape.transform(&transform2))
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// Create a state within a strip of the given width
+    ///
+    /// The strip is initially long enough that none of the shapes overlap along its length.
+    ///
+    pub fn initialise(
+        shape: S,
+        width: f64,
+        frieze: Frieze,
+        isopointal: &[WyckoffSite],
+    ) -> Result<Self, Error> {
+        super::check_sites(&shape, isopointal)?;
+        let num_shapes = isopointal.iter().fold(0, |acc, x| acc + x.multiplicity());
+        let length = 4. * shape.enclosing_radius() * num_shapes as f64;
+
+        let rotations = shape.rotational_symmetries();
+        let mut state = StripState {
+            frieze,
+            shape,
+            strip: Strip::new(length, width),
+            occupied_sites: strip_sites(isopointal, INITIAL_OFFSETS[0], rotations),
+        };
+        if state.score().is_none() {
+            state.occupied_sites = strip_sites(isopointal, INITIAL_OFFSETS[1], rotations);
+        }
+        debug!("Strip: {:?}", state.strip);
+        Ok(state)
+    }
+
+    pub fn from_group(shape: S, width: f64, group: &FriezeGroup) -> Result<Self, Error> {
+        let frieze = Frieze::new(group);
+        let isopointal = &[WyckoffSite::from_frieze(group)?];
+        Self::initialise(shape, width, frieze, isopointal)
+    }
+}
+
+/// Shapes interacting through a potential, confined to a strip
+///
+/// As for the [`StripState`] the strip is periodic along its length, with the symmetry of one
+/// of the frieze groups. The shapes interact with the walls through a [`WallPotential`], and
+/// the score is the negative of the potential energy per shape. Each interaction with a
+/// periodic image is shared between the two periods of the strip.
+///
+/// [`WallPotential`]: crate::traits::WallPotential
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub struct PotentialStripState<S>
+where
+    S: Shape + Potential + WallPotential,
+{
+    pub frieze: Frieze,
+    pub shape: S,
+    pub strip: Strip,
+    occupied_sites: Vec<OccupiedSite>,
+}
+
+impl<S> Eq for PotentialStripState<S> where S: Shape + Potential + WallPotential {}
+
+impl<S> PartialEq for PotentialStripState<S>
+where
+    S: Shape + Potential + WallPotential,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.eq(&o),
+            (_, _) => false,
+        }
+    }
+}
+
+impl<S> PartialOrd for PotentialStripState<S>
+where
+    S: Shape + Potential + WallPotential,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.score(), other.score()) {
+            (Some(s), Some(o)) => s.partial_cmp(&o),
+            (_, _) => None,
+        }
+    }
+}
+
+impl<S> Ord for PotentialStripState<S>
+where
+    S: Shape + Potential + WallPotential,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other).unwrap()
+    }
+}
+
+impl<S> State for PotentialStripState<S>
+where
+    S: Shape + Potential + WallPotential,
+{
+    fn total_shapes(&self) -> usize {
+        self.occupied_sites
+            .iter()
+            .fold(0, |sum, site| sum + site.multiplicity())
+    }
+
+    fn score(&self) -> Option<f64> {
+        let shapes: Vec<S> = self
+            .cartesian_positions()
+            .map(|p| self.shape.transform(&p))
+            .collect();
+
+        let mut sum = 0.;
+        for (index, shape1) in shapes.iter().enumerate() {
+            for (normal, offset) in self.strip.walls().iter() {
+                sum += shape1.wall_energy(*normal, *offset);
+            }
+            for shape2 in shapes.iter().skip(index + 1) {
+                sum += (shape1.energy(shape2) + shape2.energy(shape1)) / 2.;
+            }
+            for position in self.relative_positions() {
+                for shape2 in self
+                    .strip
+                    .periodic_images(position, 3, false)
+                    .map(|p| self.shape.transform(&p))
+                {
+                    sum += shape1.energy(&shape2) / 2.;
+                }
+            }
+        }
+        // A shape crossing a wall, or shapes on top of each other are not valid configurations
+        if sum.is_finite() {
+            Some(-sum / self.total_shapes() as f64 - self.shape.internal_energy())
+        } else {
+            None
+        }
+    }
+
+    fn generate_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.strip.get_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis.append(&mut self.shape.internal_basis());
+        basis
+    }
+
+    fn generate_expanding_basis(&self) -> Vec<Basis> {
+        let mut basis: Vec<Basis> = vec![];
+        basis.append(&mut self.strip.get_expanding_degrees_of_freedom());
+        for site in self.occupied_sites.iter() {
+            basis.append(&mut site.get_basis());
+        }
+        basis.append(&mut self.shape.internal_basis());
+        basis
+    }
+
+    fn basis_blocks(&self) -> Vec<usize> {
+        std::iter::once(self.strip.get_degrees_of_freedom().len())
+            .chain(self.occupied_sites.iter().map(|s| s.get_basis().len()))
+            .chain(Some(self.shape.internal_basis().len()).filter(|&n| n > 0))
+            .collect()
+    }
+
+    fn as_positions(&self) -> Result<String, Error> {
+        let mut output = String::new();
+        writeln!(&mut output, "{}", self.strip)?;
+        writeln!(&mut output, "Positions")?;
+
+        for transform in self.cartesian_positions() {
+            writeln!(&mut output, "{:?}", transform)?;
+        }
+        Ok(output)
+    }
+}
+
+impl<S> PotentialStripState<S>
+where
+    S: Shape + Potential + WallPotential,
+{
+    pub fn cartesian_positions(&self) -> impl Iterator<Item = Transform2> + '_ {
+        self.relative_positions()
+            .map(move |position| self.strip.to_cartesian_isometry(position))
+    }
+
+    pub fn relative_positions(&self) -> impl Iterator<Item = Transform2> + '_ {
+        self.occupied_sites.iter().flat_map(OccupiedSite::positions)
+    }
+
+    /// Create a state within a strip of the given width
+    pub fn initialise(
+        shape: S,
+        width: f64,
+        frieze: Frieze,
+        isopointal: &[WyckoffSite],
+    ) -> Result<Self, Error> {
+        super::check_sites(&shape, isopointal)?;
+        let num_shapes = isopointal.iter().fold(0, |acc, x| acc + x.multiplicity());
+        let length = 2. * shape.enclosing_radius() * num_shapes as f64;
+
+        let rotations = shape.rotational_symmetries();
+        let mut state = PotentialStripState {
+            frieze,
+            shape,
+            strip: Strip::new(length, width),
+            occupied_sites: strip_sites(isopointal, INITIAL_OFFSETS[0], rotations),
+        };
+        if state.score().is_none() {
+            state.occupied_sites = strip_sites(isopointal, INITIAL_OFFSETS[1], rotations);
+        }
+        debug!("Strip: {:?}", state.strip);
+        Ok(state)
+    }
+
+    pub fn from_group(shape: S, width: f64, group: &FriezeGroup) -> Result<Self, Error> {
+        let frieze = Frieze::new(group);
+        let isopointal = &[WyckoffSite::from_frieze(group)?];
+        Self::initialise(shape, width, frieze, isopointal)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::frieze::FriezeGroups;
+    use crate::{LJShape2, LineShape, MCOptimiser, MolecularShape2};
+
+    fn square_strip(group: FriezeGroups, width: f64) -> StripState<LineShape> {
+        let group: FriezeGroup = group.try_into().unwrap();
+        StripState::from_group(LineShape::polygon(4).unwrap(), width, &group).unwrap()
+    }
+
+    #[test]
+    fn all_groups_valid() {
+        // The groups with a mirror along the strip need space for two shapes across it
+        for name in FriezeGroups::variants() {
+            let state = square_strip(name.parse().unwrap(), 5.);
+            assert!(state.score().is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn walls_are_hard() {
+        let state = square_strip(FriezeGroups::p1, 4.);
+        let basis = state.generate_basis();
+        // The y position of the site
+        basis[2].set_value(0.4).unwrap();
+        assert_eq!(state.score(), None);
+        basis[2].set_value(0.2).unwrap();
+        assert!(state.score().is_some());
+    }
+
+    #[test]
+    fn width_is_fixed() {
+        let state = square_strip(FriezeGroups::p2, 4.);
+        assert_eq!(state.generate_basis().len(), 1 + 3);
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 1000, 100, 0, None).optimise_state(state);
+        assert_abs_diff_eq!(state.strip.width(), 4.);
+    }
+
+    #[test]
+    fn circles_in_narrow_channel() {
+        // A channel only wide enough for a single row of circles has a packing fraction of
+        // pi / 4 once they are touching
+        let group: FriezeGroup = FriezeGroups::p1.try_into().unwrap();
+        let state = StripState::from_group(MolecularShape2::circle(), 2., &group).unwrap();
+        let basis = state.generate_basis();
+        basis[0].set_value(2.).unwrap();
+        assert_abs_diff_eq!(
+            state.score().unwrap(),
+            std::f64::consts::PI / 4.,
+            epsilon = 1e-12
+        );
+        basis[0].set_value(1.99).unwrap();
+        assert_eq!(state.score(), None);
+    }
+
+    #[test]
+    fn potential_walls() {
+        let group: FriezeGroup = FriezeGroups::p11m.try_into().unwrap();
+        let state = PotentialStripState::from_group(LJShape2::circle(), 4., &group).unwrap();
+        assert!(state.score().is_some());
+        let state = MCOptimiser::new(0.1, 0.9, 0.01, 2000, 100, 0, None).optimise_state(state);
+        for position in state.cartesian_positions() {
+            assert!(position.position().y.abs() < 2.);
+        }
+    }
+}
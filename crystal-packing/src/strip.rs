@@ -0,0 +1,177 @@
+//
+// strip.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use nalgebra::{Point2, Translation2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::{Basis, SharedValue, Transform2};
+
+/// The repeating unit of a strip, which is periodic along x and bounded by walls in y
+///
+/// The length of the strip is the period along x, which is able to change, while the width is
+/// the fixed distance between the walls. The walls are parallel to the x axis, centred about
+/// y = 0. As with the unit cell, positions are stored in fractional coordinates, with both x
+/// and y in the range [-0.5, 0.5].
+///
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Strip {
+    length: SharedValue,
+    width: f64,
+}
+
+impl Clone for Strip {
+    fn clone(&self) -> Self {
+        Strip {
+            length: SharedValue::new(self.length.get_value()),
+            width: self.width,
+        }
+    }
+}
+
+impl std::fmt::Display for Strip {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Strip {{ length: {}, width: {} }}",
+            self.length(),
+            self.width()
+        )
+    }
+}
+
+impl Strip {
+    pub fn new(length: f64, width: f64) -> Strip {
+        Strip {
+            length: SharedValue::new(length),
+            width,
+        }
+    }
+
+    pub fn length(&self) -> f64 {
+        self.length.get_value()
+    }
+
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    /// The area of a single period of the strip
+    pub fn area(&self) -> f64 {
+        self.length() * self.width
+    }
+
+    /// The outward normal and distance from the centre line of each of the walls
+    pub fn walls(&self) -> [(Vector2<f64>, f64); 2] {
+        [
+            (Vector2::new(0., 1.), self.width / 2.),
+            (Vector2::new(0., -1.), self.width / 2.),
+        ]
+    }
+
+    /// Convert two values in relative coordinates to real coordinates
+    ///
+    /// ```
+    /// use crystal_packing::Strip;
+    /// let strip = Strip::new(4., 2.);
+    /// assert_eq!(strip.to_cartesian(0.25, -0.5), (1., -1.));
+    /// ```
+    ///
+    pub fn to_cartesian(&self, x: f64, y: f64) -> (f64, f64) {
+        (x * self.length(), y * self.width)
+    }
+
+    /// Convert a point in relative coordinates to real coordinates
+    pub fn to_cartesian_point(&self, point: Point2<f64>) -> Point2<f64> {
+        let (x, y) = self.to_cartesian(point.x, point.y);
+        Point2::new(x, y)
+    }
+
+    /// Convert a transformation in relative coordinates into Cartesian coordinates
+    pub fn to_cartesian_isometry(&self, transform: Transform2) -> Transform2 {
+        transform.set_position(self.to_cartesian_point(transform.position()))
+    }
+
+    /// The periodic images of a transformation in Cartesian coordinates
+    ///
+    /// This is the transformation translated by up to `shells` periods in either direction along
+    /// the strip, including the untranslated transformation when `zero` is true.
+    ///
+    pub fn periodic_images(
+        &self,
+        transform: Transform2,
+        shells: i64,
+        zero: bool,
+    ) -> impl Iterator<Item = Transform2> + '_ {
+        (-shells..=shells)
+            .filter(move |&x| zero || x != 0)
+            .map(move |x| {
+                let position = Translation2::new(x as f64, 0.) * transform.position();
+                transform.set_position(self.to_cartesian_point(position))
+            })
+    }
+
+    /// The length of the strip is bounded by the current value, so it is only able to contract
+    pub fn get_degrees_of_freedom(&self) -> Vec<Basis> {
+        self.degrees_of_freedom(self.length())
+    }
+
+    /// The length of the strip is able to expand as well as contract
+    pub fn get_expanding_degrees_of_freedom(&self) -> Vec<Basis> {
+        self.degrees_of_freedom(f64::INFINITY)
+    }
+
+    fn degrees_of_freedom(&self, max_length: f64) -> Vec<Basis> {
+        vec![Basis::CellBasis {
+            value: &self.length,
+            min: 0.01,
+            max: max_length,
+        }]
+    }
+
+    /// The corners of a single period of the strip in Cartesian coordinates
+    pub fn get_corners(&self) -> Vec<Point2<f64>> {
+        vec![
+            Point2::new(-0.5, -0.5),
+            Point2::new(-0.5, 0.5),
+            Point2::new(0.5, 0.5),
+            Point2::new(0.5, -0.5),
+        ]
+        .into_iter()
+        .map(|p| self.to_cartesian_point(p))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    #[test]
+    fn periodic_along_length() {
+        let strip = Strip::new(2., 3.);
+        let images: Vec<_> = strip
+            .periodic_images(Transform2::new(0., (0.1, 0.2)), 1, false)
+            .map(|t| t.position())
+            .collect();
+        assert_eq!(images.len(), 2);
+        assert_abs_diff_eq!(images[0].x, -1.8, epsilon = 1e-12);
+        assert_abs_diff_eq!(images[1].x, 2.2, epsilon = 1e-12);
+        for image in images {
+            assert_abs_diff_eq!(image.y, 0.6, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn fixed_width() {
+        let strip = Strip::new(2., 3.);
+        let basis = strip.get_degrees_of_freedom();
+        assert_eq!(basis.len(), 1);
+        basis[0].set_value(1.).unwrap();
+        assert_abs_diff_eq!(strip.area(), 3.);
+    }
+}
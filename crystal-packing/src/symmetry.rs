@@ -0,0 +1,297 @@
+//
+// symmetry.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::f64::consts::PI;
+
+use anyhow::{anyhow, Error};
+use itertools::Itertools;
+use nalgebra::{Matrix2, Matrix3, Point2, Rotation2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::wallpaper::WyckoffSite;
+use crate::Transform2;
+
+/// The tolerance used when finding the point group of a shape
+pub const SYMMETRY_TOLERANCE: f64 = 1e-6;
+
+/// The highest order of rotation which is detected for a point group
+pub const MAX_ORDER: usize = 360;
+
+/// The point group symmetry of a finite cluster, or of a single shape
+///
+/// The cyclic groups Cn have an n-fold rotation about the centre of the container, with the
+/// dihedral groups Dn additionally having n mirror lines through the centre, one of which is
+/// the x axis.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PointGroup {
+    Cyclic(usize),
+    Dihedral(usize),
+}
+
+impl Default for PointGroup {
+    fn default() -> Self {
+        PointGroup::Cyclic(1)
+    }
+}
+
+impl std::str::FromStr for PointGroup {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let order = |n: &str| -> Result<usize, Error> {
+            match n.parse() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(anyhow!("Invalid order of point group {}", s)),
+            }
+        };
+        match s.split_at(s.len().min(1)) {
+            ("C", n) => Ok(PointGroup::Cyclic(order(n)?)),
+            ("D", n) => Ok(PointGroup::Dihedral(order(n)?)),
+            _ => Err(anyhow!("Invalid point group {}, expected Cn or Dn", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for PointGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PointGroup::Cyclic(n) => write!(f, "C{}", n),
+            PointGroup::Dihedral(n) => write!(f, "D{}", n),
+        }
+    }
+}
+
+impl PointGroup {
+    /// The number of symmetry operations in the group
+    pub fn order(&self) -> usize {
+        match self {
+            PointGroup::Cyclic(n) => *n,
+            PointGroup::Dihedral(n) => 2 * n,
+        }
+    }
+
+    /// The symmetry operations of the group
+    ///
+    /// ```
+    /// use crystal_packing::PointGroup;
+    /// let group: PointGroup = "D3".parse().unwrap();
+    /// assert_eq!(group.operations().len(), 6);
+    /// ```
+    ///
+    pub fn operations(&self) -> Vec<Transform2> {
+        let (n, mirror) = match self {
+            PointGroup::Cyclic(n) => (*n, false),
+            PointGroup::Dihedral(n) => (*n, true),
+        };
+        let rotations = (0..n).map(|i| Transform2::new(2. * PI * i as f64 / n as f64, (0., 0.)));
+        if mirror {
+            let reflection: Transform2 = Matrix3::new(1., 0., 0., 0., -1., 0., 0., 0., 1.).into();
+            rotations.flat_map(|r| vec![r, r * reflection]).collect()
+        } else {
+            rotations.collect()
+        }
+    }
+
+    /// The number of rotations in the group, including the identity
+    pub fn rotations(&self) -> usize {
+        match self {
+            PointGroup::Cyclic(n) | PointGroup::Dihedral(n) => *n,
+        }
+    }
+
+    /// Whether a shape with this symmetry is distinct from its mirror image
+    ///
+    /// Only the cyclic groups are without mirror lines, so a shape with a cyclic point group
+    /// can't be superimposed on its mirror image.
+    ///
+    pub fn is_chiral(&self) -> bool {
+        matches!(self, PointGroup::Cyclic(_))
+    }
+
+    /// Whether every operation of `other` is also an operation of this group
+    ///
+    /// This only compares the orders of the groups, assuming the mirror lines of both are
+    /// aligned.
+    ///
+    pub fn contains(&self, other: &PointGroup) -> bool {
+        match (self, other) {
+            (PointGroup::Cyclic(_), PointGroup::Dihedral(_)) => false,
+            _ => self.rotations().is_multiple_of(other.rotations()),
+        }
+    }
+
+    /// Find the point group of a collection of points about the origin
+    ///
+    /// Each point has a list of properties, like the radius of a disc, where a symmetry operation
+    /// has to map every point onto another point with the same properties. Both the positions
+    /// and the properties are compared within `tolerance`, relative to the distance of the
+    /// furthest point from the origin.
+    ///
+    /// Points which are all at the origin have a continuous symmetry, which is approximated
+    /// by the group of order [`MAX_ORDER`].
+    ///
+    /// ```
+    /// use nalgebra::Point2;
+    /// use crystal_packing::PointGroup;
+    /// let points: Vec<_> = [(1., 0.), (0., 1.), (-1., 0.), (0., -1.)]
+    ///     .iter()
+    ///     .map(|&(x, y)| (Point2::new(x, y), vec![]))
+    ///     .collect();
+    /// assert_eq!(PointGroup::detect(&points, 1e-8), PointGroup::Dihedral(4));
+    /// ```
+    ///
+    pub fn detect(points: &[(Point2<f64>, Vec<f64>)], tolerance: f64) -> PointGroup {
+        let scale = points
+            .iter()
+            .map(|(p, _)| p.coords.norm())
+            .fold(0., f64::max);
+        if scale == 0. {
+            return PointGroup::Dihedral(MAX_ORDER);
+        }
+        let tolerance = tolerance * scale;
+        let invariant = |operation: &Matrix2<f64>| {
+            points.iter().all(|(p, props)| {
+                let image = operation * p.coords;
+                points.iter().any(|(q, other)| {
+                    (image - q.coords).norm() < tolerance
+                        && props.len() == other.len()
+                        && props
+                            .iter()
+                            .zip(other)
+                            .all(|(a, b)| (a - b).abs() < tolerance)
+                })
+            })
+        };
+
+        // The largest order of rotation is limited by the number of points away from the centre
+        let outer: Vec<_> = points
+            .iter()
+            .filter(|(p, _)| p.coords.norm() >= tolerance)
+            .map(|(p, _)| p.coords)
+            .collect();
+        let n = (2..=outer.len().min(MAX_ORDER))
+            .rev()
+            .find(|&n| invariant(&Rotation2::new(2. * PI / n as f64).into_inner()))
+            .unwrap_or(1);
+
+        // Any mirror line passes through a point, or bisects a pair of points at the same distance
+        let angle = |v: &Vector2<f64>| f64::atan2(v.y, v.x);
+        let mirror = |phi: f64| {
+            Matrix2::new(
+                f64::cos(2. * phi),
+                f64::sin(2. * phi),
+                f64::sin(2. * phi),
+                -f64::cos(2. * phi),
+            )
+        };
+        let has_mirror = outer
+            .iter()
+            .map(angle)
+            .chain(
+                outer
+                    .iter()
+                    .tuple_combinations()
+                    .filter(|(a, b)| (a.norm() - b.norm()).abs() < tolerance)
+                    .map(|(a, b)| (angle(a) + angle(b)) / 2.),
+            )
+            .any(|phi| invariant(&mirror(phi)));
+
+        if has_mirror {
+            PointGroup::Dihedral(n)
+        } else {
+            PointGroup::Cyclic(n)
+        }
+    }
+
+    /// The site with a copy of a shape at every symmetry operation of the group
+    pub fn wyckoff(&self) -> WyckoffSite {
+        WyckoffSite {
+            letter: 'a',
+            symmetries: self.operations(),
+            num_rotations: 1,
+            mirror_primary: false,
+            mirror_secondary: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn point_group_parse() {
+        assert_eq!("C4".parse::<PointGroup>().unwrap(), PointGroup::Cyclic(4));
+        assert_eq!("D2".parse::<PointGroup>().unwrap(), PointGroup::Dihedral(2));
+        assert!("C0".parse::<PointGroup>().is_err());
+        assert!("T".parse::<PointGroup>().is_err());
+    }
+
+    #[test]
+    fn dihedral_has_mirrors() {
+        let group = PointGroup::Dihedral(2);
+        let point = Point2::new(0.3, 0.1);
+        let images: Vec<_> = group.operations().iter().map(|t| t * point).collect();
+        assert_eq!(images.len(), 4);
+        for image in [(0.3, -0.1), (-0.3, -0.1), (-0.3, 0.1)].iter() {
+            assert!(images
+                .iter()
+                .any(|p| (p - Point2::new(image.0, image.1)).norm() < 1e-12));
+        }
+    }
+
+    #[test]
+    fn detect_point_groups() {
+        let points = |coords: &[(f64, f64)]| -> Vec<_> {
+            coords
+                .iter()
+                .map(|&(x, y)| (Point2::new(x, y), vec![1.]))
+                .collect()
+        };
+        let square = points(&[(1., 1.), (-1., 1.), (-1., -1.), (1., -1.)]);
+        assert_eq!(PointGroup::detect(&square, 1e-8), PointGroup::Dihedral(4));
+        let rectangle = points(&[(2., 1.), (-2., 1.), (-2., -1.), (2., -1.)]);
+        assert_eq!(
+            PointGroup::detect(&rectangle, 1e-8),
+            PointGroup::Dihedral(2)
+        );
+        // A pinwheel has rotations without any mirror lines
+        let mut pinwheel = points(&[(1., 0.), (0., 1.), (-1., 0.), (0., -1.)]);
+        pinwheel.extend(
+            [(1., 0.3), (-0.3, 1.), (-1., -0.3), (0.3, -1.)]
+                .iter()
+                .map(|&(x, y)| (Point2::new(x, y), vec![0.5])),
+        );
+        assert_eq!(PointGroup::detect(&pinwheel, 1e-8), PointGroup::Cyclic(4));
+        let scalene = points(&[(1., 0.), (0., 2.), (-3., -1.)]);
+        assert_eq!(PointGroup::detect(&scalene, 1e-8), PointGroup::Cyclic(1));
+        let centre = points(&[(0., 0.)]);
+        assert_eq!(
+            PointGroup::detect(&centre, 1e-8),
+            PointGroup::Dihedral(MAX_ORDER)
+        );
+    }
+
+    #[test]
+    fn detect_with_properties() {
+        // Changing the property of a single point removes the rotations
+        let mut points: Vec<_> = [(1., 0.), (0., 1.), (-1., 0.), (0., -1.)]
+            .iter()
+            .map(|&(x, y)| (Point2::new(x, y), vec![1.]))
+            .collect();
+        points[0].1 = vec![0.5];
+        assert_eq!(PointGroup::detect(&points, 1e-8), PointGroup::Dihedral(1));
+    }
+
+    #[test]
+    fn group_contains() {
+        assert!(PointGroup::Dihedral(4).contains(&PointGroup::Cyclic(2)));
+        assert!(PointGroup::Dihedral(4).contains(&PointGroup::Dihedral(2)));
+        assert!(!PointGroup::Cyclic(4).contains(&PointGroup::Dihedral(1)));
+        assert!(!PointGroup::Dihedral(3).contains(&PointGroup::Cyclic(2)));
+    }
+}
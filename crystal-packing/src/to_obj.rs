@@ -0,0 +1,79 @@
+//
+// to_obj.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fmt::Write;
+
+use crate::state::PackedState3;
+use crate::traits::*;
+use crate::*;
+
+/// Write the vertices and faces of a polyhedron as a named object
+///
+/// The vertices of the faces in an OBJ file are numbered from 1, counting every vertex in the
+/// file, so the `offset` is the number of vertices already written.
+///
+fn obj_object(output: &mut String, name: &str, shape: &Polyhedron, offset: usize) {
+    writeln!(output, "o {}", name).unwrap();
+    for v in shape.vertices.iter() {
+        writeln!(output, "v {} {} {}", v.x, v.y, v.z).unwrap();
+    }
+    for face in shape.faces.iter() {
+        let indices: Vec<String> = face.iter().map(|i| (i + offset + 1).to_string()).collect();
+        writeln!(output, "f {}", indices.join(" ")).unwrap();
+    }
+}
+
+impl ToOBJ for Polyhedron {
+    fn as_obj(&self) -> String {
+        let mut output = String::new();
+        obj_object(&mut output, &self.name, self, 0);
+        output
+    }
+}
+
+/// Each of the shapes within the unit cell as a separate object
+impl ToOBJ for PackedState3<Polyhedron> {
+    fn as_obj(&self) -> String {
+        let mut output = String::new();
+        let mut offset = 0;
+        for (index, position) in self.cartesian_positions().enumerate() {
+            let shape = self.shape.transform(&position);
+            obj_object(
+                &mut output,
+                &format!("{}_{}", self.shape.name(), index),
+                &shape,
+                offset,
+            );
+            offset += shape.vertices.len();
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::space_group::SpaceGroups;
+
+    #[test]
+    fn packed_obj() {
+        let group: SpaceGroup = SpaceGroups::P_1.try_into().unwrap();
+        let shape = Polyhedron::named("tetrahedron").unwrap();
+        let state = PackedState3::from_group(shape, &group).unwrap();
+        let obj = state.as_obj();
+
+        assert_eq!(obj.lines().filter(|l| l.starts_with("o ")).count(), 2);
+        assert_eq!(obj.lines().filter(|l| l.starts_with("v ")).count(), 8);
+        // The faces of the second shape refer to the vertices after the first shape
+        let last = obj.lines().last().unwrap();
+        assert!(last
+            .split_whitespace()
+            .skip(1)
+            .all(|i| (5..=8).contains(&i.parse::<usize>().unwrap())));
+    }
+}
@@ -4,11 +4,14 @@
 // Distributed under terms of the MIT license.
 //
 
-use nalgebra::Matrix3;
+use itertools::iproduct;
+use nalgebra::{Matrix3, Point2, Vector2};
 use svg::node::element;
 use svg::Document;
 
-use crate::state::PackedState;
+use crate::state::{
+    ContainerState, PackedState, PackedState3, PolydisperseState, PotentialStripState, StripState,
+};
 use crate::traits::*;
 use crate::*;
 
@@ -60,6 +63,68 @@ impl ToSVG for LineShape {
     }
 }
 
+impl ToSVG for ParametricShape {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let start = self.items[0].start;
+        let mut data = element::path::Data::new().move_to((start.x, start.y));
+
+        for item in self {
+            data = data.line_to((item.end.x, item.end.y));
+        }
+        element::Group::new().add(element::Path::new().set("d", data.close()))
+    }
+}
+
+impl ToSVG for Primitive {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let group = element::Group::new();
+        match self {
+            Primitive::Disc(a) => group.add(a.as_svg()),
+            Primitive::Segment(l) => group.add(l.as_svg()),
+            Primitive::Capsule(c) => group.add(c.as_svg()),
+            Primitive::Polygon(s) => group.add(s.as_svg()),
+        }
+    }
+}
+
+impl ToSVG for MixedShape {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let mut group = element::Group::new();
+        for item in self {
+            group = group.add(item.as_svg())
+        }
+        group
+    }
+}
+
+impl ToSVG for RoundedShape {
+    type Value = element::Group;
+
+    /// The edges of the polygon moved outwards, joined by circular arcs around each corner
+    fn as_svg(&self) -> Self::Value {
+        let radius = self.radius();
+        let normals = self.outward_normals();
+        // The arcs turn in the same direction as the vertices of the polygon
+        let sweep = if self.signed_area() > 0. { 1 } else { 0 };
+        let first = self.items[0].segment.start + normals[0] * radius;
+        let mut data = element::path::Data::new().move_to((first.x, first.y));
+        for (index, (item, normal)) in self.items.iter().zip(normals.iter()).enumerate() {
+            let end = item.segment.end + normal * radius;
+            let next = item.segment.end + normals[(index + 1) % normals.len()] * radius;
+            data = data
+                .line_to((end.x, end.y))
+                .elliptical_arc_to((radius, radius, 0, 0, sweep, next.x, next.y));
+        }
+        element::Group::new().add(element::Path::new().set("d", data.close()))
+    }
+}
+
 impl ToSVG for Cell2 {
     type Value = element::Group;
 
@@ -93,6 +158,77 @@ impl ToSVG for Atom2 {
     }
 }
 
+impl ToSVG for Ellipse2 {
+    type Value = element::Ellipse;
+
+    fn as_svg(&self) -> Self::Value {
+        element::Ellipse::new()
+            .set("rx", self.semi_major)
+            .set("ry", self.semi_minor)
+            .set("cx", self.position.x)
+            .set("cy", self.position.y)
+            .set(
+                "transform",
+                format!(
+                    "rotate({} {} {})",
+                    self.angle.to_degrees(),
+                    self.position.x,
+                    self.position.y
+                ),
+            )
+    }
+}
+
+impl ToSVG for Capsule2 {
+    type Value = element::Path;
+
+    /// The straight sides of the capsule joined by semicircular arcs at each end
+    fn as_svg(&self) -> Self::Value {
+        let (start, end) = (self.segment.start, self.segment.end);
+        let length = self.segment.length();
+        // A capsule of zero length is a disc, where any direction will do
+        let direction = if length > 0. {
+            (end - start) / length
+        } else {
+            Vector2::x()
+        };
+        let normal = Vector2::new(-direction.y, direction.x) * self.radius;
+        let arc = |point: Point2<f64>| (self.radius, self.radius, 0, 0, 0, point.x, point.y);
+        let data = element::path::Data::new()
+            .move_to(((start + normal).x, (start + normal).y))
+            .line_to(((end + normal).x, (end + normal).y))
+            .elliptical_arc_to(arc(end - normal))
+            .line_to(((start - normal).x, (start - normal).y))
+            .elliptical_arc_to(arc(start + normal))
+            .close();
+        element::Path::new().set("d", data)
+    }
+}
+
+impl ToSVG for CapsuleShape {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let mut group = element::Group::new();
+        for item in self {
+            group = group.add(item.as_svg())
+        }
+        group
+    }
+}
+
+impl ToSVG for EllipseShape {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let mut group = element::Group::new();
+        for item in self {
+            group = group.add(item.as_svg())
+        }
+        group
+    }
+}
+
 impl ToSVG for MolecularShape2 {
     type Value = element::Group;
 
@@ -105,6 +241,96 @@ impl ToSVG for MolecularShape2 {
     }
 }
 
+impl ToSVG for FlexibleTrimer {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        self.conformation().as_svg()
+    }
+}
+
+/// The projection of the edges of the cell onto the xy plane
+impl ToSVG for Cell3 {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let corners = self.get_corners();
+        let mut cell_data = element::path::Data::new();
+        // The corners are ordered by the bits of the index, with an edge between each pair of
+        // corners which differ by a single bit.
+        for (i, j) in iproduct!(0..corners.len(), 0..3).map(|(i, b)| (i, i | 1 << b)) {
+            if i != j {
+                cell_data = cell_data
+                    .move_to((corners[i].x, corners[i].y))
+                    .line_to((corners[j].x, corners[j].y));
+            }
+        }
+
+        element::Group::new().add(
+            element::Path::new()
+                .set("fill", "None")
+                .set("stroke", "grey")
+                .set("stroke-width", 0.1)
+                .set("d", cell_data),
+        )
+    }
+}
+
+impl ToSVG for Atom3 {
+    type Value = element::Circle;
+
+    fn as_svg(&self) -> Self::Value {
+        element::Circle::new()
+            .set("r", self.radius)
+            .set("cx", self.position.x)
+            .set("cy", self.position.y)
+    }
+}
+
+/// The projection of the shape onto the xy plane
+///
+/// The spheres are drawn in order of increasing z, so the spheres closest to the viewer are on
+/// top.
+///
+impl ToSVG for MolecularShape3 {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let mut items: Vec<&Atom3> = self.items.iter().collect();
+        items.sort_by(|a, b| a.position.z.total_cmp(&b.position.z));
+        let mut smol = element::Group::new();
+        for item in items {
+            smol = smol.add(item.as_svg())
+        }
+        smol
+    }
+}
+
+/// The faces of the polyhedron which are visible when looking down the z axis
+impl ToSVG for Polyhedron {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let mut group = element::Group::new();
+        for face in self.faces.iter() {
+            if self.face_normal(face).z > 0. {
+                let points = face
+                    .iter()
+                    .map(|&i| format!("{},{}", self.vertices[i].x, self.vertices[i].y))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                group = group.add(
+                    element::Polygon::new()
+                        .set("points", points)
+                        .set("stroke", "black")
+                        .set("stroke-width", 0.02),
+                );
+            }
+        }
+        group
+    }
+}
+
 impl ToSVG for Transform2 {
     type Value = element::Use;
 
@@ -127,6 +353,19 @@ impl ToSVG for Transform2 {
     }
 }
 
+/// The colours of a shape at `position`, and of the periodic images of the shape
+///
+/// The mirror image of a chiral shape is a different species, so it is drawn in different
+/// colours to the shape in the asymmetric unit.
+///
+fn fill(position: &Transform2, chiral: bool) -> (&'static str, &'static str) {
+    if chiral && position.is_improper() {
+        ("red", "orange")
+    } else {
+        ("blue", "green")
+    }
+}
+
 impl<S> ToSVG for PotentialState<S>
 where
     S: Shape + Potential,
@@ -157,11 +396,13 @@ where
             doc = doc.add(transform.as_svg().set("href", "#cell"));
         }
 
+        let chiral = self.shape.is_chiral();
         for position in self.relative_positions() {
             let transform = self.cell.to_cartesian_isometry(position);
-            doc = doc.add(transform.as_svg().set("href", "#mol").set("fill", "blue"));
+            let (central, image) = fill(&position, chiral);
+            doc = doc.add(transform.as_svg().set("href", "#mol").set("fill", central));
             for periodic in self.cell.periodic_images(position, 1, false) {
-                doc = doc.add(periodic.as_svg().set("href", "#mol").set("fill", "green"));
+                doc = doc.add(periodic.as_svg().set("href", "#mol").set("fill", image));
             }
         }
         doc
@@ -197,12 +438,271 @@ where
         for transform in self.cell.periodic_images(Transform2::identity(), 1, true) {
             doc = doc.add(transform.as_svg().set("href", "#cell"));
         }
+        let chiral = self.shape.is_chiral();
         for position in self.relative_positions() {
             let matrix = self.cell.to_cartesian_isometry(position);
-            doc = doc.add(matrix.as_svg().set("href", "#mol").set("fill", "blue"));
+            let (central, image) = fill(&position, chiral);
+            doc = doc.add(matrix.as_svg().set("href", "#mol").set("fill", central));
+            for transform in self.cell.periodic_images(position, 1, false) {
+                doc = doc.add(transform.as_svg().set("href", "#mol").set("fill", image));
+            }
+        }
+        doc
+    }
+}
+
+impl<S> ToSVG for PolydisperseState<S>
+where
+    S: Shape + Intersect + Scale,
+{
+    type Value = Document;
+
+    fn as_svg(&self) -> Self::Value {
+        let padding =
+            self.sizes().iter().cloned().fold(0., f64::max) * self.shape.enclosing_radius();
+        let viewbox =
+            self.cell
+                .get_corners()
+                .iter()
+                .map(|p| p * 3.)
+                .fold((0., 0., 0., 0.), |acc, p| {
+                    (
+                        f64::min(p.x - padding, acc.0),
+                        f64::min(p.y - padding, acc.1),
+                        f64::max(2. * (p.x + padding), acc.2),
+                        f64::max(2. * (p.y + padding), acc.3),
+                    )
+                });
+        // Each of the sizes of the shape is defined separately
+        let definitions = self.shapes().iter().enumerate().fold(
+            element::Definitions::new().add(self.cell.as_svg().set("id", "cell")),
+            |defs, (index, shape)| defs.add(shape.as_svg().set("id", format!("mol{}", index))),
+        );
+        let mut doc = Document::new().set("viewBox", viewbox).add(definitions);
+        for transform in self.cell.periodic_images(Transform2::identity(), 1, true) {
+            doc = doc.add(transform.as_svg().set("href", "#cell"));
+        }
+        let chiral = self.shape.is_chiral();
+        for (site, position) in self.site_positions() {
+            let href = format!("#mol{}", site);
+            let matrix = self.cell.to_cartesian_isometry(position);
+            let (central, image) = fill(&position, chiral);
+            doc = doc.add(
+                matrix
+                    .as_svg()
+                    .set("href", href.clone())
+                    .set("fill", central),
+            );
             for transform in self.cell.periodic_images(position, 1, false) {
-                doc = doc.add(transform.as_svg().set("href", "#mol").set("fill", "green"));
+                doc = doc.add(
+                    transform
+                        .as_svg()
+                        .set("href", href.clone())
+                        .set("fill", image),
+                );
+            }
+        }
+        doc
+    }
+}
+
+/// The projection of the crystal along the z axis
+///
+/// Unlike the two dimensional states, each shape is drawn in Cartesian coordinates, since the
+/// three dimensional transforms can't be expressed as an SVG transform.
+///
+impl<S> ToSVG for PackedState3<S>
+where
+    S: Shape3 + Intersect3,
+{
+    type Value = Document;
+
+    fn as_svg(&self) -> Self::Value {
+        let padding = self.shape.enclosing_radius();
+        let viewbox =
+            self.cell
+                .get_corners()
+                .iter()
+                .map(|p| p * 3.)
+                .fold((0., 0., 0., 0.), |acc, p| {
+                    (
+                        f64::min(p.x - padding, acc.0),
+                        f64::min(p.y - padding, acc.1),
+                        f64::max(2. * (p.x + padding), acc.2),
+                        f64::max(2. * (p.y + padding), acc.3),
+                    )
+                });
+        let mut doc = Document::new()
+            .set("viewBox", viewbox)
+            .add(self.cell.as_svg());
+
+        let mut shapes: Vec<(Transform3, &str)> = vec![];
+        for position in self.relative_positions() {
+            shapes.push((self.cell.to_cartesian_isometry(position), "blue"));
+            for transform in self.cell.periodic_images(position, [1, 1, 0], false) {
+                shapes.push((transform, "green"));
+            }
+        }
+        shapes.sort_by(|a, b| a.0.position().z.total_cmp(&b.0.position().z));
+        for (transform, colour) in shapes {
+            doc = doc.add(
+                self.shape
+                    .transform(&transform)
+                    .as_svg()
+                    .set("fill", colour),
+            );
+        }
+        doc
+    }
+}
+
+impl ToSVG for Strip {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let corners = self.get_corners();
+        let walls = element::path::Data::new()
+            .move_to((corners[0].x, corners[0].y))
+            .line_to((corners[3].x, corners[3].y))
+            .move_to((corners[1].x, corners[1].y))
+            .line_to((corners[2].x, corners[2].y));
+        let period = element::path::Data::new()
+            .move_to((corners[0].x, corners[0].y))
+            .line_to((corners[1].x, corners[1].y));
+
+        element::Group::new()
+            .add(
+                element::Path::new()
+                    .set("fill", "None")
+                    .set("stroke", "black")
+                    .set("stroke-width", 0.2)
+                    .set("d", walls),
+            )
+            .add(
+                element::Path::new()
+                    .set("fill", "None")
+                    .set("stroke", "grey")
+                    .set("stroke-width", 0.1)
+                    .set("stroke-dasharray", "0.2,0.2")
+                    .set("d", period),
+            )
+    }
+}
+
+/// Draw three periods of a strip, with the shapes in the central period
+fn strip_document<'a>(
+    strip: &'a Strip,
+    shape: element::Group,
+    padding: f64,
+    chiral: bool,
+    positions: impl Iterator<Item = Transform2> + 'a,
+) -> Document {
+    let (length, width) = (strip.length(), strip.width());
+    let viewbox = (
+        -1.5 * length - padding,
+        -width / 2. - padding,
+        3. * length + 2. * padding,
+        width + 2. * padding,
+    );
+    let mut doc = Document::new().set("viewBox", viewbox).add(
+        element::Definitions::new()
+            .add(strip.as_svg().set("id", "strip"))
+            .add(shape.set("id", "mol")),
+    );
+    for transform in strip.periodic_images(Transform2::identity(), 1, true) {
+        doc = doc.add(transform.as_svg().set("href", "#strip"));
+    }
+    for position in positions {
+        let transform = strip.to_cartesian_isometry(position);
+        let (central, image) = fill(&position, chiral);
+        doc = doc.add(transform.as_svg().set("href", "#mol").set("fill", central));
+        for periodic in strip.periodic_images(position, 1, false) {
+            doc = doc.add(periodic.as_svg().set("href", "#mol").set("fill", image));
+        }
+    }
+    doc
+}
+
+impl<S> ToSVG for StripState<S>
+where
+    S: Shape + Intersect + Support,
+{
+    type Value = Document;
+
+    fn as_svg(&self) -> Self::Value {
+        strip_document(
+            &self.strip,
+            self.shape.as_svg(),
+            self.shape.enclosing_radius(),
+            self.shape.is_chiral(),
+            self.relative_positions(),
+        )
+    }
+}
+
+impl<S> ToSVG for PotentialStripState<S>
+where
+    S: Shape + Potential + WallPotential,
+{
+    type Value = Document;
+
+    fn as_svg(&self) -> Self::Value {
+        strip_document(
+            &self.strip,
+            self.shape.as_svg(),
+            self.shape.enclosing_radius(),
+            self.shape.is_chiral(),
+            self.relative_positions(),
+        )
+    }
+}
+
+impl ToSVG for Container {
+    type Value = element::Group;
+
+    fn as_svg(&self) -> Self::Value {
+        let outline = match self.outline() {
+            Outline::Circle => element::Group::new().add(
+                element::Circle::new()
+                    .set("r", self.size())
+                    .set("cx", 0.)
+                    .set("cy", 0.),
+            ),
+            Outline::Polygon(_) => {
+                let edges = self.edges();
+                let mut data =
+                    element::path::Data::new().move_to((edges[0].start.x, edges[0].start.y));
+                for edge in edges.iter() {
+                    data = data.line_to((edge.end.x, edge.end.y));
+                }
+                element::Group::new().add(element::Path::new().set("d", data.close()))
             }
+        };
+        outline
+            .set("fill", "None")
+            .set("stroke", "black")
+            .set("stroke-width", 0.2)
+    }
+}
+
+impl<S> ToSVG for ContainerState<S>
+where
+    S: Shape + Intersect + Boundary,
+{
+    type Value = Document;
+
+    fn as_svg(&self) -> Self::Value {
+        // The outline of every container lies within the square of half length size
+        let extent = self.container.size() + self.shape.enclosing_radius();
+        let viewbox = (-extent, -extent, 2. * extent, 2. * extent);
+        let mut doc = Document::new()
+            .set("viewBox", viewbox)
+            .add(element::Definitions::new().add(self.shape.as_svg().set("id", "mol")))
+            .add(self.container.as_svg());
+        let chiral = self.shape.is_chiral();
+        for transform in self.cartesian_positions() {
+            let (central, _) = fill(&transform, chiral);
+            doc = doc.add(transform.as_svg().set("href", "#mol").set("fill", central));
         }
         doc
     }
@@ -0,0 +1,305 @@
+//
+// to_xyz.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::fmt::Write;
+
+use crate::state::{
+    ContainerState, PackedState, PackedState3, PolydisperseState, PotentialStripState, StripState,
+};
+use crate::traits::*;
+use crate::*;
+
+/// The Lattice property of the comment line of an extended XYZ frame
+///
+/// The third lattice vector is a unit vector along the z axis, so the 2D crystal is a single
+/// layer in 3D space.
+///
+fn xyz_lattice(cell: &Cell2) -> String {
+    let a = cell.to_cartesian(1., 0.);
+    let b = cell.to_cartesian(0., 1.);
+    format!(
+        "Lattice=\"{} {} 0 {} {} 0 0 0 1\" pbc=\"T T F\"",
+        a.0, a.1, b.0, b.1
+    )
+}
+
+/// The Lattice property of a strip, which is only periodic along the first lattice vector
+///
+/// The second lattice vector spans the width of the strip, with the walls at half the width
+/// either side of the x axis.
+///
+fn xyz_strip_lattice(strip: &Strip) -> String {
+    format!(
+        "Lattice=\"{} 0 0 0 {} 0 0 0 1\" pbc=\"T F F\"",
+        strip.length(),
+        strip.width()
+    )
+}
+
+/// Create a frame from the positions of each shape
+///
+/// Each shape is represented by a single row, with the species being the name of the shape,
+/// the position of the shape, and the orientation as a rotation about the z axis.
+///
+fn xyz_frame(
+    lattice: String,
+    name: &str,
+    score: Option<f64>,
+    positions: impl Iterator<Item = Transform2>,
+) -> String {
+    let species: String = name.split_whitespace().collect::<Vec<_>>().join("_");
+    let positions: Vec<Transform2> = positions.collect();
+
+    let mut frame = String::new();
+    writeln!(frame, "{}", positions.len()).unwrap();
+    write!(
+        frame,
+        "{} Properties=species:S:1:pos:R:3:orientation:R:1",
+        lattice
+    )
+    .unwrap();
+    if let Some(score) = score {
+        write!(frame, " score={}", score).unwrap();
+    }
+    writeln!(frame).unwrap();
+    for position in positions {
+        let point = position.position();
+        writeln!(
+            frame,
+            "{} {} {} 0 {}",
+            species,
+            point.x,
+            point.y,
+            position.angle()
+        )
+        .unwrap();
+    }
+    frame
+}
+
+/// Create a frame from the positions of shapes which have different sizes
+///
+/// This is the same as [`xyz_frame`], with an additional column for the size of each shape.
+///
+fn xyz_sized_frame(
+    lattice: String,
+    name: &str,
+    score: Option<f64>,
+    positions: impl Iterator<Item = (Transform2, f64)>,
+) -> String {
+    let species: String = name.split_whitespace().collect::<Vec<_>>().join("_");
+    let positions: Vec<(Transform2, f64)> = positions.collect();
+
+    let mut frame = String::new();
+    writeln!(frame, "{}", positions.len()).unwrap();
+    write!(
+        frame,
+        "{} Properties=species:S:1:pos:R:3:orientation:R:1:size:R:1",
+        lattice
+    )
+    .unwrap();
+    if let Some(score) = score {
+        write!(frame, " score={}", score).unwrap();
+    }
+    writeln!(frame).unwrap();
+    for (position, size) in positions {
+        let point = position.position();
+        writeln!(
+            frame,
+            "{} {} {} 0 {} {}",
+            species,
+            point.x,
+            point.y,
+            position.angle(),
+            size
+        )
+        .unwrap();
+    }
+    frame
+}
+
+/// A frame of a three dimensional crystal
+///
+/// The orientation of each shape is the quaternion (w, i, j, k) rotating the shape from the
+/// reference orientation.
+///
+fn xyz_frame3(
+    cell: &Cell3,
+    name: &str,
+    score: Option<f64>,
+    positions: impl Iterator<Item = Transform3>,
+) -> String {
+    let species: String = name.split_whitespace().collect::<Vec<_>>().join("_");
+    let positions: Vec<Transform3> = positions.collect();
+    let lattice = cell.lattice();
+
+    let mut frame = String::new();
+    writeln!(frame, "{}", positions.len()).unwrap();
+    write!(frame, "Lattice=\"").unwrap();
+    for (index, value) in lattice.iter().enumerate() {
+        if index > 0 {
+            write!(frame, " ").unwrap();
+        }
+        write!(frame, "{}", value).unwrap();
+    }
+    write!(
+        frame,
+        "\" pbc=\"T T T\" Properties=species:S:1:pos:R:3:orientation:R:4"
+    )
+    .unwrap();
+    if let Some(score) = score {
+        write!(frame, " score={}", score).unwrap();
+    }
+    writeln!(frame).unwrap();
+    for position in positions {
+        let point = position.position();
+        let q = position.orientation();
+        writeln!(
+            frame,
+            "{} {} {} {} {} {} {} {}",
+            species, point.x, point.y, point.z, q.w, q.i, q.j, q.k
+        )
+        .unwrap();
+    }
+    frame
+}
+
+impl<S> ToXYZ for PotentialState<S>
+where
+    S: Shape + Potential,
+{
+    fn as_xyz(&self) -> String {
+        xyz_frame(
+            xyz_lattice(&self.cell),
+            self.shape.name(),
+            self.score(),
+            self.cartesian_positions(),
+        )
+    }
+}
+
+impl<S> ToXYZ for PackedState<S>
+where
+    S: Shape + Intersect,
+{
+    fn as_xyz(&self) -> String {
+        xyz_frame(
+            xyz_lattice(&self.cell),
+            self.shape.name(),
+            self.score(),
+            self.cartesian_positions(),
+        )
+    }
+}
+
+impl<S> ToXYZ for PolydisperseState<S>
+where
+    S: Shape + Intersect + Scale,
+{
+    fn as_xyz(&self) -> String {
+        xyz_sized_frame(
+            xyz_lattice(&self.cell),
+            self.shape.name(),
+            self.score(),
+            self.sized_positions()
+                .map(|(p, size)| (self.cell.to_cartesian_isometry(p), size)),
+        )
+    }
+}
+
+impl<S> ToXYZ for StripState<S>
+where
+    S: Shape + Intersect + Support,
+{
+    fn as_xyz(&self) -> String {
+        xyz_frame(
+            xyz_strip_lattice(&self.strip),
+            self.shape.name(),
+            self.score(),
+            self.cartesian_positions(),
+        )
+    }
+}
+
+impl<S> ToXYZ for PotentialStripState<S>
+where
+    S: Shape + Potential + WallPotential,
+{
+    fn as_xyz(&self) -> String {
+        xyz_frame(
+            xyz_strip_lattice(&self.strip),
+            self.shape.name(),
+            self.score(),
+            self.cartesian_positions(),
+        )
+    }
+}
+
+impl<S> ToXYZ for PackedState3<S>
+where
+    S: Shape3 + Intersect3,
+{
+    fn as_xyz(&self) -> String {
+        xyz_frame3(
+            &self.cell,
+            self.shape.name(),
+            self.score(),
+            self.cartesian_positions(),
+        )
+    }
+}
+
+/// A finite cluster has no periodicity, so there is no lattice
+impl<S> ToXYZ for ContainerState<S>
+where
+    S: Shape + Intersect + Boundary,
+{
+    fn as_xyz(&self) -> String {
+        xyz_frame(
+            String::from("pbc=\"F F F\""),
+            self.shape.name(),
+            self.score(),
+            self.cartesian_positions(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::wallpaper::{WallpaperGroup, WallpaperGroups};
+
+    #[test]
+    fn packed_frame() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        let state = PackedState::from_group(LineShape::polygon(4).unwrap(), &group).unwrap();
+        let frame = state.as_xyz();
+        let lines: Vec<_> = frame.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "2");
+        assert!(lines[1].starts_with("Lattice=\"8 0 0 "));
+        assert!(lines[2].starts_with("Polygon "));
+    }
+
+    #[test]
+    fn packed3_frame() {
+        let group: SpaceGroup = space_group::SpaceGroups::P_1.try_into().unwrap();
+        let state = PackedState3::from_group(MolecularShape3::sphere(), &group).unwrap();
+        let frame = state.as_xyz();
+        let lines: Vec<_> = frame.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        let lattice = lines[1].split('"').nth(1).unwrap();
+        assert_eq!(lattice.split_whitespace().count(), 9);
+        assert!(lattice.starts_with("8 0 0 "));
+        assert!(lines[1].contains("pbc=\"T T T\""));
+        // The species, position, and the four components of the quaternion
+        assert_eq!(lines[2].split_whitespace().count(), 8);
+    }
+}
@@ -7,12 +7,13 @@
 use std::{fmt, ops, slice};
 
 use anyhow::Error;
-use nalgebra::SVector;
+use nalgebra::{Matrix3, Point2, SVector, Vector2};
 use serde::Serialize;
 use svg::node::element::Group;
 use svg::Document;
 
-use crate::{Basis, Transform2};
+use crate::wallpaper::WyckoffSite;
+use crate::{Basis, Cell2, Line2, Observer, PointGroup, Transform2, Transform3};
 
 pub trait Transformer {
     fn as_simple(&self) -> String;
@@ -38,10 +39,46 @@ pub trait Intersect {
     fn area(&self) -> f64;
 }
 
+/// The three dimensional counterpart of [`Intersect`], where the shapes fill a volume
+pub trait Intersect3 {
+    fn intersects(&self, other: &Self) -> bool;
+    fn volume(&self) -> f64;
+}
+
 pub trait Potential {
     fn energy(&self, other: &Self) -> f64;
 }
 
+/// The extent of a shape, which allows it to be confined by walls
+pub trait Support {
+    /// The largest projection of any point within the shape onto the unit vector `direction`
+    fn support(&self, direction: Vector2<f64>) -> f64;
+}
+
+/// Whether a shape crosses the edges of a boundary, which allows it to be confined by a polygon
+pub trait Boundary {
+    /// Whether any part of the shape is on both sides of, or overlaps the line segment
+    fn crosses(&self, edge: &Line2) -> bool;
+    /// Whether the point is inside the shape
+    fn contains(&self, point: &Point2<f64>) -> bool;
+}
+
+/// The interaction of a shape with a flat wall
+pub trait WallPotential {
+    /// The energy of the shape with a wall a distance `offset` from the origin along the unit
+    /// vector `normal`, where the shape is on the side of the wall containing the origin
+    ///
+    /// This is infinite when any part of the shape is beyond the wall.
+    ///
+    fn wall_energy(&self, normal: Vector2<f64>, offset: f64) -> f64;
+}
+
+/// The distribution of mass within a shape, which determines how the shape vibrates
+pub trait Inertia {
+    /// The position and mass of each of the point masses making up the shape
+    fn point_masses(&self) -> Vec<(Point2<f64>, f64)>;
+}
+
 pub trait Shape:
     Clone + Send + Sync + Serialize + fmt::Debug + fmt::Display + ToSVG<Value = Group>
 {
@@ -54,16 +91,86 @@ pub trait Shape:
         + ops::Mul<Transform2, Output = Self::Component>
         + ToSVG;
 
+    fn name(&self) -> &str;
     fn score(&self, other: &Self) -> Option<f64>;
     fn enclosing_radius(&self) -> f64;
     fn get_items(&self) -> Vec<Self::Component>;
+    /// The symmetry of the shape about the origin
+    fn point_group(&self) -> PointGroup {
+        PointGroup::default()
+    }
+    /// The number of orientations of the shape which are indistinguishable
     fn rotational_symmetries(&self) -> u64 {
-        1
+        self.point_group().rotations() as u64
+    }
+    /// The mirror image of the shape, reflected through the y axis
+    ///
+    /// This is the enantiomer of a chiral shape, which is the shape placed by the improper
+    /// operations of a wallpaper group, up to a rotation.
+    ///
+    fn mirror(&self) -> Self {
+        let reflection: Transform2 = Matrix3::new(-1., 0., 0., 0., 1., 0., 0., 0., 1.).into();
+        self.transform(&reflection)
+    }
+    /// Whether the shape is distinct from its mirror image
+    ///
+    /// Shapes which don't find their point group are assumed to be chiral.
+    ///
+    fn is_chiral(&self) -> bool {
+        self.point_group().is_chiral()
+    }
+    /// The internal degrees of freedom of the shape, like the angle of a flexible molecule
+    ///
+    /// These are optimised along with the positions of the shapes, with every copy of the shape
+    /// in a structure having the same conformation.
+    ///
+    fn internal_basis(&self) -> Vec<Basis> {
+        vec![]
+    }
+    /// The energy of the current conformation of the shape
+    ///
+    /// This is a penalty on the score of a structure, being subtracted from the packing fraction,
+    /// or added to the potential energy of each shape.
+    ///
+    fn internal_energy(&self) -> f64 {
+        0.
+    }
+    /// Whether the shape has the symmetry required to occupy a special Wyckoff site
+    fn can_occupy(&self, site: &WyckoffSite) -> bool {
+        self.point_group().contains(&site.site_symmetry())
     }
     fn iter(&self) -> slice::Iter<'_, Self::Component>;
     fn transform(&self, transform: &Transform2) -> Self;
 }
 
+/// A shape which is able to be resized
+pub trait Scale {
+    /// A copy of the shape with every length multiplied by `factor`, scaling about the origin
+    fn scale(&self, factor: f64) -> Self;
+}
+
+/// A rigid shape in three dimensions
+///
+/// The SVG representation is the projection of the shape onto the xy plane.
+///
+pub trait Shape3:
+    Clone + Send + Sync + Serialize + fmt::Debug + fmt::Display + ToSVG<Value = Group>
+{
+    type Component: Clone
+        + Send
+        + Sync
+        + Serialize
+        + fmt::Debug
+        + fmt::Display
+        + ops::Mul<Transform3, Output = Self::Component>;
+
+    fn name(&self) -> &str;
+    fn enclosing_radius(&self) -> f64;
+    fn get_items(&self) -> Vec<Self::Component>;
+    fn iter(&self) -> slice::Iter<'_, Self::Component>;
+    fn transform(&self, transform: &Transform3) -> Self;
+}
+
 pub trait FromSymmetry: Sized {
     fn from_operations(ops: &str) -> Result<Self, Error>;
 }
@@ -79,14 +186,85 @@ pub trait State:
     + Serialize
     + fmt::Debug
     + ToSVG<Value = Document>
+    + ToXYZ
 {
     fn score(&self) -> Option<f64>;
     fn generate_basis(&self) -> Vec<Basis>;
+    /// The basis where the unit cell is able to expand as well as contract
+    ///
+    /// By default this is the same as [`State::generate_basis`].
+    ///
+    fn generate_expanding_basis(&self) -> Vec<Basis> {
+        self.generate_basis()
+    }
+    /// The number of values in each independent block of the basis
+    ///
+    /// The blocks are contiguous ranges of the basis which describe a single part of the state,
+    /// like the unit cell or one of the occupied sites. By default the whole basis is one block.
+    ///
+    fn basis_blocks(&self) -> Vec<usize> {
+        vec![self.generate_basis().len()]
+    }
     fn total_shapes(&self) -> usize;
     fn as_positions(&self) -> Result<String, Error>;
 }
 
+/// A state which can be expanded into a supercell where every shape is independent
+pub trait Supercell: State {
+    /// Expand into `n` by `m` copies of the unit cell in the wallpaper group p1
+    fn supercell(&self, n: usize, m: usize) -> Self;
+    /// The unit cell of the state
+    fn cell(&self) -> &Cell2;
+    /// The position of every shape in fractional coordinates
+    fn fractional_positions(&self) -> Vec<Transform2>;
+}
+
+/// A state of hard shapes where the score is based on the packing fraction
+///
+/// The area of the cell follows from the packing fraction, which is required to simulate the
+/// state at a constant pressure. The score is the packing fraction less the internal energy of
+/// the shape, so the packing fraction is kept separately for shapes with an internal energy.
+pub trait Packed: State {
+    /// The fraction of the cell covered by the shapes, which is None when any intersect
+    fn packing_fraction(&self) -> Option<f64>;
+    /// The internal energy of each shape, which is subtracted from the packing fraction
+    fn internal_energy(&self) -> f64;
+}
+
+/// An algorithm which finds a state with the best possible score
+///
+/// Implementations modify the values of the basis of the state, returning the state once the
+/// optimisation is complete.
+///
+pub trait Optimiser {
+    fn optimise_state<S: State>(&self, state: S) -> S {
+        self.optimise_observed(state, &mut ())
+    }
+
+    /// Optimise the state, passing the progress of the optimisation to the observer
+    fn optimise_observed<S: State>(&self, state: S, observer: &mut dyn Observer<S>) -> S;
+}
+
 pub trait ToSVG {
     type Value: svg::Node;
     fn as_svg(&self) -> Self::Value;
 }
+
+/// Output in the extended XYZ format
+///
+/// This is a text format for a single frame of a trajectory, which can be read by visualisation
+/// tools like [OVITO](https://www.ovito.org/) and [ASE](https://wiki.fysik.dtu.dk/ase/).
+///
+pub trait ToXYZ {
+    fn as_xyz(&self) -> String;
+}
+
+/// Output as a Wavefront OBJ file
+///
+/// This is a text format for polygonal meshes, which allows visualising three dimensional
+/// shapes with tools like [Blender](https://www.blender.org/) and
+/// [Meshlab](https://www.meshlab.net/).
+///
+pub trait ToOBJ {
+    fn as_obj(&self) -> String;
+}
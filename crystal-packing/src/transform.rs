@@ -9,7 +9,7 @@ use std::ops::Mul;
 
 #[cfg(test)]
 use approx::AbsDiffEq;
-use nalgebra::{Matrix3, Point2, Translation2};
+use nalgebra::{DMatrix, Matrix3, Point2, Translation2};
 use serde::{Deserialize, Serialize};
 
 /// Perform coordinate tranforms on a point in space
@@ -98,6 +98,32 @@ impl Transform2 {
         Translation2::new(self.0.matrix()[(0, 2)], self.0.matrix()[(1, 2)])
     }
 
+    /// The angle of rotation of the transform in radians
+    ///
+    /// ```
+    /// use crystal_packing::Transform2;
+    /// let t = Transform2::new(1., (0., 0.));
+    /// assert!((t.angle() - 1.).abs() < 1e-12);
+    /// ```
+    ///
+    pub fn angle(&self) -> f64 {
+        let matrix = self.0.matrix();
+        f64::atan2(matrix[(1, 0)], matrix[(0, 0)])
+    }
+
+    /// Whether the transform includes a reflection, turning a chiral shape into its enantiomer
+    ///
+    /// ```
+    /// use crystal_packing::Transform2;
+    /// assert!(Transform2::from_operations("-x,y").unwrap().is_improper());
+    /// assert!(!Transform2::from_operations("-x,-y").unwrap().is_improper());
+    /// ```
+    ///
+    pub fn is_improper(&self) -> bool {
+        let matrix = self.0.matrix();
+        matrix[(0, 0)] * matrix[(1, 1)] - matrix[(0, 1)] * matrix[(1, 0)] < 0.
+    }
+
     pub fn set_position(mut self, position: Point2<f64>) -> Transform2 {
         self.0[(0, 2)] = position.x;
         self.0[(1, 2)] = position.y;
@@ -122,65 +148,83 @@ impl Transform2 {
     /// ```
     ///
     pub fn from_operations(sym_ops: &str) -> Result<Transform2, Error> {
-        let braces: &[_] = &['(', ')'];
-        let operations: Vec<&str> = sym_ops
-            // Remove braces from front and back
-            .trim_matches(braces)
-            // Split at the comma
-            .split_terminator(',')
-            .collect();
-
-        match operations.len() {
-            x if x < 2 => bail!("Not enough dimensions in input"),
-            x if x > 2 => bail!("Too many dimensions in input"),
-            _ => (),
-        }
-
+        let operations = parse_operations(sym_ops, 2)?;
         let mut transform: Matrix3<f64> = Matrix3::zeros();
+        transform
+            .fixed_slice_mut::<2, 3>(0, 0)
+            .copy_from(&operations);
+        Ok(Transform2::from(transform))
+    }
+}
 
-        for (index, op) in operations.iter().enumerate() {
-            let mut sign = 1.;
-            let mut constant = 0.;
-            let mut operator: Option<char> = None;
-            for c in op.chars() {
-                match c {
-                    'x' => {
-                        transform[(index, 0)] = sign;
-                        sign = 1.;
-                    }
-                    'y' => {
-                        transform[(index, 1)] = sign;
-                        sign = 1.;
-                    }
-                    '*' | '/' => {
-                        operator = Some(c);
-                    }
-                    '-' => {
-                        sign = -1.;
-                    }
-                    // This matches all digits from 0 to 9
-                    '0'..='9' => {
-                        let val = c.to_string().parse::<u64>()? as f64;
-                        // Is there an operator defined, i.e. is this the first digit
-                        constant = match operator {
-                            Some(op) if op == '/' => sign * constant / val,
-                            Some(op) if op == '*' => sign * constant / val,
-                            Some(_) => 0.,
-                            None => sign * val,
-                        };
-                        // Reset values
-                        operator = None;
-                        sign = 1.
+/// Convert the string representation of a symmetry operation to a matrix
+///
+/// Each of the comma separated operations is a row of the matrix, with the columns being the
+/// coefficients of x, y, and z up to the number of dimensions, followed by the constant term.
+///
+pub(crate) fn parse_operations(sym_ops: &str, dimensions: usize) -> Result<DMatrix<f64>, Error> {
+    let braces: &[_] = &['(', ')'];
+    let operations: Vec<&str> = sym_ops
+        // Remove braces from front and back
+        .trim_matches(braces)
+        // Split at the comma
+        .split_terminator(',')
+        .collect();
+
+    match operations.len() {
+        x if x < dimensions => bail!("Not enough dimensions in input"),
+        x if x > dimensions => bail!("Too many dimensions in input"),
+        _ => (),
+    }
+
+    let mut transform: DMatrix<f64> = DMatrix::zeros(dimensions, dimensions + 1);
+
+    for (index, op) in operations.iter().enumerate() {
+        let mut sign = 1.;
+        let mut constant = 0.;
+        let mut operator: Option<char> = None;
+        for c in op.chars() {
+            match c {
+                'x' | 'y' | 'z' => {
+                    let axis = match c {
+                        'x' => 0,
+                        'y' => 1,
+                        _ => 2,
+                    };
+                    if axis >= dimensions {
+                        bail!("Found invalid value: '{}'", c);
                     }
-                    ' ' | '+' => (),
-                    // Default is do nothing (shouldn't encounter this at all)
-                    x => bail!("Found invalid value: '{}'", x),
-                };
-            }
-            transform[(index, 2)] = constant;
+                    transform[(index, axis)] = sign;
+                    sign = 1.;
+                }
+                '*' | '/' => {
+                    operator = Some(c);
+                }
+                '-' => {
+                    sign = -1.;
+                }
+                // This matches all digits from 0 to 9
+                '0'..='9' => {
+                    let val = c.to_string().parse::<u64>()? as f64;
+                    // Is there an operator defined, i.e. is this the first digit
+                    constant = match operator {
+                        Some(op) if op == '/' => sign * constant / val,
+                        Some(op) if op == '*' => sign * constant / val,
+                        Some(_) => 0.,
+                        None => sign * val,
+                    };
+                    // Reset values
+                    operator = None;
+                    sign = 1.
+                }
+                ' ' | '+' => (),
+                // Default is do nothing (shouldn't encounter this at all)
+                x => bail!("Found invalid value: '{}'", x),
+            };
         }
-        Ok(Transform2::from(transform))
+        transform[(index, dimensions)] = constant;
     }
+    Ok(transform)
 }
 
 #[cfg(test)]
@@ -0,0 +1,216 @@
+//
+// transform3.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+use std::ops::Mul;
+
+use anyhow::Error;
+#[cfg(test)]
+use approx::AbsDiffEq;
+use nalgebra::{Matrix3, Matrix4, Point3, Rotation3, Translation3, UnitQuaternion};
+use serde::{Deserialize, Serialize};
+
+use crate::transform::parse_operations;
+
+/// Perform coordinate transforms on a point in three dimensional space
+///
+/// This is the three dimensional counterpart of the [`Transform2`], allowing for translations,
+/// rotations, mirror planes, inversion, and any combination of these. The orientation of a rigid
+/// body is a rotation described by a unit quaternion.
+///
+/// ```
+/// use crystal_packing::Transform3;
+/// use nalgebra::{Point3, UnitQuaternion, Vector3};
+/// let rotation = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), std::f64::consts::PI);
+/// let t = Transform3::new(rotation, (0., 0., 1.));
+/// let point = t * Point3::new(1., 0., 0.);
+/// assert!((point - Point3::new(-1., 0., 1.)).norm() < 1e-12);
+/// ```
+///
+/// [`Transform2`]: crate::Transform2
+///
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Transform3(nalgebra::Transform3<f64>);
+
+impl From<Matrix4<f64>> for Transform3 {
+    fn from(matrix: Matrix4<f64>) -> Self {
+        Self(nalgebra::Transform3::from_matrix_unchecked(matrix))
+    }
+}
+
+// Since Matrix4 is a type defined in another crate, we run into the orphan rule where we can't
+// define a trait for a type that we don't define.
+#[allow(clippy::from_over_into)]
+impl Into<Matrix4<f64>> for Transform3 {
+    fn into(self) -> Matrix4<f64> {
+        *self.0.matrix()
+    }
+}
+
+#[cfg(test)]
+impl AbsDiffEq for Transform3 {
+    type Epsilon = f64;
+
+    fn default_epsilon() -> Self::Epsilon {
+        f64::default_epsilon()
+    }
+
+    fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
+        self.0.abs_diff_eq(&other.0, epsilon)
+    }
+}
+
+binop_impl_all!(
+    Mul, mul;
+    self: Transform3, rhs: Point3<f64>, Output = Point3<f64>;
+    [ref ref] => {
+        self.0 * rhs
+    };
+);
+
+binop_impl_all!(
+    Mul, mul;
+    self: Point3<f64>, rhs: Transform3, Output = Point3<f64>;
+    [ref ref] => {
+        rhs.0 * self
+    };
+);
+
+binop_impl_all!(
+    Mul, mul;
+    self: Transform3, rhs: Transform3, Output = Transform3;
+    [ref ref] => {
+        Transform3(self.0 * rhs.0)
+    };
+);
+
+impl Transform3 {
+    pub fn new(rotation: UnitQuaternion<f64>, translation: (f64, f64, f64)) -> Transform3 {
+        let translation = Translation3::new(translation.0, translation.1, translation.2);
+        Transform3(nalgebra::Transform3::from_matrix_unchecked(
+            nalgebra::Isometry3::from_parts(translation, rotation).to_homogeneous(),
+        ))
+    }
+
+    pub fn identity() -> Self {
+        Self(nalgebra::Transform3::identity())
+    }
+
+    pub fn position(&self) -> Point3<f64> {
+        self.0 * Point3::origin()
+    }
+
+    pub fn set_position(mut self, position: Point3<f64>) -> Transform3 {
+        self.0[(0, 3)] = position.x;
+        self.0[(1, 3)] = position.y;
+        self.0[(2, 3)] = position.z;
+        self
+    }
+
+    /// The linear part of the transform, being the rotation and any mirror or inversion
+    pub fn linear(&self) -> Matrix3<f64> {
+        self.0.matrix().fixed_slice::<3, 3>(0, 0).into()
+    }
+
+    /// The orientation of the transform as a unit quaternion
+    ///
+    /// Where the transform is improper, containing a mirror or inversion, this is the rotation
+    /// remaining after removing an inversion.
+    ///
+    pub fn orientation(&self) -> UnitQuaternion<f64> {
+        let linear = self.linear();
+        let proper = if linear.determinant() < 0. {
+            -linear
+        } else {
+            linear
+        };
+        UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(proper))
+    }
+
+    /// Wrap the position into the range [offset, offset + period) along each axis
+    pub fn periodic(&self, period: f64, offset: f64) -> Transform3 {
+        let wrap = |x: f64| (((x - offset) % period) + period) % period + offset;
+        let position = self.position();
+        self.set_position(Point3::new(
+            wrap(position.x),
+            wrap(position.y),
+            wrap(position.z),
+        ))
+    }
+
+    /// Convert the string representation of a symmetry operation to a Transform
+    ///
+    /// This is the same notation as the International Tables of Crystallography, with each of
+    /// the three comma separated values giving the new x, y, and z coordinates.
+    ///
+    /// ```
+    /// use crystal_packing::Transform3;
+    /// use nalgebra::Point3;
+    /// let t = Transform3::from_operations("-x, y+1/2, -z+1/2").unwrap();
+    /// assert_eq!(t * Point3::new(0.1, 0.2, 0.3), Point3::new(-0.1, 0.7, 0.2));
+    /// ```
+    ///
+    pub fn from_operations(sym_ops: &str) -> Result<Transform3, Error> {
+        let operations = parse_operations(sym_ops, 3)?;
+        let mut transform: Matrix4<f64> = Matrix4::identity();
+        transform
+            .fixed_slice_mut::<3, 4>(0, 0)
+            .copy_from(&operations);
+        Ok(Transform3::from(transform))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use approx::assert_abs_diff_eq;
+    use nalgebra::Vector3;
+
+    use super::*;
+
+    #[test]
+    fn parse_identity() {
+        let t = Transform3::from_operations("(x, y, z)").unwrap();
+        assert_abs_diff_eq!(t, Transform3::identity());
+    }
+
+    #[test]
+    fn parse_inversion() {
+        let t = Transform3::from_operations("-x,-y,-z").unwrap();
+        let point = Point3::new(0.1, 0.2, 0.3);
+        assert_abs_diff_eq!(t * point, Point3::new(-0.1, -0.2, -0.3));
+        assert!(t.linear().determinant() < 0.);
+    }
+
+    #[test]
+    fn parse_wrong_dimensions() {
+        assert!(Transform3::from_operations("x,y").is_err());
+        assert!(Transform3::from_operations("x,y,z,x").is_err());
+        assert!(Transform3::from_operations("x,y,w").is_err());
+    }
+
+    #[test]
+    fn orientation_of_rotation() {
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 0.3);
+        let t = Transform3::new(rotation, (1., 2., 3.));
+        assert_abs_diff_eq!(t.orientation().angle_to(&rotation), 0., epsilon = 1e-12);
+        assert_abs_diff_eq!(t.position(), Point3::new(1., 2., 3.));
+    }
+
+    #[test]
+    fn periodic_wraps() {
+        let t = Transform3::new(UnitQuaternion::identity(), (0.7, -0.6, 0.2));
+        let wrapped = t.periodic(1., -0.5).position();
+        assert_abs_diff_eq!(wrapped, Point3::new(-0.3, 0.4, 0.2), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn compose() {
+        let screw = Transform3::from_operations("-x,y+1/2,-z").unwrap();
+        // Applying a two-fold screw axis twice is a translation along the axis
+        let twice = screw * screw;
+        let point = Point3::new(0.1, 0.2, 0.3);
+        assert_abs_diff_eq!(twice * point, Point3::new(0.1, 1.2, 0.3), epsilon = 1e-12);
+    }
+}
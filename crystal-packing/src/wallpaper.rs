@@ -8,7 +8,7 @@ use anyhow::{anyhow, Error};
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 
-use crate::{CrystalFamily, Transform2};
+use crate::{CrystalFamily, PointGroup, Transform2};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct WallpaperGroup<'a> {
@@ -73,6 +73,29 @@ impl WyckoffSite {
         self.symmetries.len()
     }
 
+    /// Whether any of the symmetry operations reflect the shape
+    ///
+    /// The shapes at the positions of these operations are the mirror images of the shape in the
+    /// asymmetric unit, so a packing of a chiral shape in a group with these operations is
+    /// racemic.
+    ///
+    pub fn has_improper(&self) -> bool {
+        self.symmetries.iter().any(Transform2::is_improper)
+    }
+
+    /// The point group of the symmetry operations which leave the site in place
+    ///
+    /// A shape is only able to occupy the site when it has all of these symmetries.
+    ///
+    pub fn site_symmetry(&self) -> PointGroup {
+        let rotations = self.num_rotations.max(1) as usize;
+        if self.mirror_primary || self.mirror_secondary {
+            PointGroup::Dihedral(rotations)
+        } else {
+            PointGroup::Cyclic(rotations)
+        }
+    }
+
     pub fn degrees_of_freedom(&self) -> &[bool] {
         // TODO implement -> This is only required for the non-general Wyckoff sites since all the
         // general sites have 3 degrees-of-freedom.
@@ -83,7 +106,7 @@ impl WyckoffSite {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum WallpaperGroups {
     p1,
     p2,
@@ -177,6 +200,8 @@ impl<'a> TryFrom<WallpaperGroups> for WallpaperGroup<'a> {
 
 #[cfg(test)]
 mod wyckoff_site_tests {
+    use std::convert::TryInto;
+
     use super::*;
 
     pub fn create_wyckoff() -> WyckoffSite {
@@ -194,4 +219,12 @@ mod wyckoff_site_tests {
         let wyckoff = create_wyckoff();
         assert_eq!(wyckoff.multiplicity(), 1);
     }
+
+    #[test]
+    fn improper_operations() {
+        let group: WallpaperGroup = WallpaperGroups::p2.try_into().unwrap();
+        assert!(!WyckoffSite::new(&group).unwrap().has_improper());
+        let group: WallpaperGroup = WallpaperGroups::p2mg.try_into().unwrap();
+        assert!(WyckoffSite::new(&group).unwrap().has_improper());
+    }
 }
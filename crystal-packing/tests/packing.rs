@@ -31,7 +31,7 @@ fn test_packing_improves() -> Result<(), Error> {
         mirror_secondary: false,
     }];
 
-    let state = PackedState::<LineShape>::initialise(square, wallpaper, isopointal);
+    let state = PackedState::<LineShape>::initialise(square, wallpaper, isopointal).unwrap();
 
     let init_packing = state
         .score()
@@ -31,7 +31,7 @@ fn test_score_improves() -> Result<(), Error> {
         mirror_secondary: false,
     }];
 
-    let state = PotentialState::<LJShape2>::initialise(square, wallpaper, isopointal);
+    let state = PotentialState::<LJShape2>::initialise(square, wallpaper, isopointal).unwrap();
 
     let init_score = state
         .score()